@@ -0,0 +1,114 @@
+//! push a large `FUSE_BATCH_FORGET` through [`TestSession`], to compare the default
+//! `batch_forget`, which calls `forget` for each inode, with a filesystem which handles the whole
+//! batch at once.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fuse3::raw::prelude::*;
+use fuse3::test::{request, TestSession};
+use fuse3::{MountOptions, Result};
+use futures_util::stream::Empty;
+use tokio::runtime::{Builder, Runtime};
+
+/// the lookup count of each inode, large enough to never reach 0 in the benchmark.
+const NLOOKUP: u64 = u64::MAX / 2;
+
+/// the lookup count table of the inodes, locked once for each forget.
+#[derive(Debug)]
+struct PerInodeFs {
+    nlookups: Mutex<HashMap<u64, u64>>,
+}
+
+/// the same table as [`PerInodeFs`], but locked once for the whole batch.
+#[derive(Debug)]
+struct BatchFs {
+    nlookups: Mutex<HashMap<u64, u64>>,
+}
+
+fn nlookups(count: u64) -> Mutex<HashMap<u64, u64>> {
+    Mutex::new((2..count + 2).map(|inode| (inode, NLOOKUP)).collect())
+}
+
+fn forget_one(nlookups: &mut HashMap<u64, u64>, inode: u64, nlookup: u64) {
+    if let Some(count) = nlookups.get_mut(&inode) {
+        *count = count.saturating_sub(nlookup);
+    }
+}
+
+impl Filesystem for PerInodeFs {
+    type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+
+    async fn init(&self, _req: Request) -> Result<ReplyInit> {
+        Ok(ReplyInit {
+            max_write: 4096.try_into().unwrap(),
+        })
+    }
+
+    async fn destroy(&self, _req: Request) {}
+
+    async fn forget(&self, _req: Request, inode: u64, nlookup: u64) {
+        forget_one(&mut self.nlookups.lock().unwrap(), inode, nlookup);
+    }
+}
+
+impl Filesystem for BatchFs {
+    type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+
+    async fn init(&self, _req: Request) -> Result<ReplyInit> {
+        Ok(ReplyInit {
+            max_write: 4096.try_into().unwrap(),
+        })
+    }
+
+    async fn destroy(&self, _req: Request) {}
+
+    async fn forget(&self, _req: Request, inode: u64, nlookup: u64) {
+        forget_one(&mut self.nlookups.lock().unwrap(), inode, nlookup);
+    }
+
+    async fn batch_forget(&self, _req: Request, inodes: &[(u64, u64)]) {
+        let mut nlookups = self.nlookups.lock().unwrap();
+
+        for &(inode, nlookup) in inodes {
+            forget_one(&mut nlookups, inode, nlookup);
+        }
+    }
+}
+
+fn bench_batch<FS>(c: &mut Criterion, runtime: &Runtime, name: &str, fs: impl Fn(u64) -> FS)
+where
+    FS: Filesystem + Send + Sync + 'static,
+{
+    let mut group = c.benchmark_group("batch_forget");
+
+    for count in [1024, 65536] {
+        let inodes = (2..count + 2).map(|inode| (inode, 1)).collect::<Vec<_>>();
+        let mut session = TestSession::new(fs(count), MountOptions::default());
+        runtime.block_on(session.init(request(0, 0, 0))).unwrap();
+
+        group.throughput(Throughput::Elements(count));
+        group.bench_function(BenchmarkId::new(name, count), |b| {
+            b.iter(|| runtime.block_on(session.batch_forget(request(0, 0, 0), &inodes)))
+        });
+    }
+
+    group.finish();
+}
+
+fn batch_forget(c: &mut Criterion) {
+    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+
+    bench_batch(c, &runtime, "per_inode", |count| PerInodeFs {
+        nlookups: nlookups(count),
+    });
+    bench_batch(c, &runtime, "batch", |count| BatchFs {
+        nlookups: nlookups(count),
+    });
+}
+
+criterion_group!(benches, batch_forget);
+criterion_main!(benches);
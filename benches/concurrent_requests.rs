@@ -0,0 +1,97 @@
+//! send a burst of CPU-bound reads through [`TestSession`] at once, to compare the unlimited
+//! concurrency with a [`max_concurrent_requests`][MountOptions::max_concurrent_requests] of the
+//! available parallelism.
+
+use std::thread;
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fuse3::raw::prelude::*;
+use fuse3::test::{request, TestSession};
+use fuse3::{MountOptions, Result};
+use futures_util::stream::Empty;
+use tokio::runtime::{Builder, Runtime};
+
+/// the reads sent at once.
+const FAN_OUT: u64 = 256;
+
+/// the size of each read, the data is generated by a CPU-bound loop like a decompression.
+const READ_SIZE: u32 = 64 * 1024;
+
+#[derive(Debug)]
+struct CpuBoundFs;
+
+impl Filesystem for CpuBoundFs {
+    type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+
+    async fn init(&self, _req: Request) -> Result<ReplyInit> {
+        Ok(ReplyInit {
+            max_write: 4096.try_into().unwrap(),
+        })
+    }
+
+    async fn destroy(&self, _req: Request) {}
+
+    async fn read(
+        &self,
+        _req: Request,
+        _inode: u64,
+        _fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<ReplyData> {
+        let mut state = offset | 1;
+        let data = (0..size)
+            .map(|_| {
+                // xorshift
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+
+                state as u8
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Bytes::from(data).into())
+    }
+}
+
+fn bench_fan_out(
+    c: &mut Criterion,
+    runtime: &Runtime,
+    name: &str,
+    max_concurrent_requests: Option<usize>,
+) {
+    let mut group = c.benchmark_group("concurrent_requests");
+
+    let mut mount_options = MountOptions::default();
+    if let Some(max_concurrent_requests) = max_concurrent_requests {
+        mount_options.max_concurrent_requests(max_concurrent_requests);
+    }
+
+    let mut session = TestSession::new(CpuBoundFs, mount_options);
+    runtime.block_on(session.init(request(0, 0, 0))).unwrap();
+
+    let reads = (0..FAN_OUT)
+        .map(|i| (i * READ_SIZE as u64, READ_SIZE))
+        .collect::<Vec<_>>();
+
+    group.throughput(Throughput::Bytes(FAN_OUT * READ_SIZE as u64));
+    group.bench_function(BenchmarkId::new(name, FAN_OUT), |b| {
+        b.iter(|| runtime.block_on(session.read_many(request(0, 0, 0), 2, 0, &reads)))
+    });
+
+    group.finish();
+}
+
+fn concurrent_requests(c: &mut Criterion) {
+    let runtime = Builder::new_multi_thread().enable_all().build().unwrap();
+    let parallelism = thread::available_parallelism().map_or(1, |n| n.get());
+
+    bench_fan_out(c, &runtime, "unlimited", None);
+    bench_fan_out(c, &runtime, "limited", Some(parallelism));
+}
+
+criterion_group!(benches, concurrent_requests);
+criterion_main!(benches);
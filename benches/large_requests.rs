@@ -0,0 +1,122 @@
+//! transfer a file through [`TestSession`] by read and write requests of 128 KiB, the size of a
+//! kernel without `FUSE_MAX_PAGES`, and 1 MiB, which needs 256 pages negotiated by
+//! [`MountOptions::max_pages`], to compare the cost of the request count.
+//!
+//! [`TestSession`] copies each request and reply like kernel does, so the copies are counted in
+//! both, the difference of them is the overhead of each request.
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fuse3::raw::prelude::*;
+use fuse3::test::{request, TestSession};
+use fuse3::{MountOptions, Result};
+use futures_util::stream::Empty;
+use tokio::runtime::Builder;
+
+/// the size of the file.
+const FILE_SIZE: usize = 16 * 1024 * 1024;
+
+/// the largest request size.
+const MAX_WRITE: u32 = 1024 * 1024;
+
+#[derive(Debug)]
+struct BytesFs {
+    data: Bytes,
+}
+
+impl Filesystem for BytesFs {
+    type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+
+    async fn init(&self, _req: Request) -> Result<ReplyInit> {
+        Ok(ReplyInit {
+            max_write: MAX_WRITE.try_into().unwrap(),
+        })
+    }
+
+    async fn destroy(&self, _req: Request) {}
+
+    async fn read(
+        &self,
+        _req: Request,
+        _inode: u64,
+        _fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<ReplyData> {
+        let start = (offset as usize).min(self.data.len());
+        let end = (start + size as usize).min(self.data.len());
+
+        Ok(self.data.slice(start..end).into())
+    }
+
+    async fn write(
+        &self,
+        _req: Request,
+        _inode: u64,
+        _fh: u64,
+        _offset: u64,
+        data: &[u8],
+        _write_flags: WriteFlags,
+        _flags: u32,
+    ) -> Result<ReplyWrite> {
+        Ok(ReplyWrite {
+            written: data.len() as u32,
+        })
+    }
+}
+
+fn large_requests(c: &mut Criterion) {
+    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+    let mut group = c.benchmark_group("large_requests");
+    let data = vec![1; FILE_SIZE];
+
+    for max_pages in [32, 256] {
+        let mut mount_options = MountOptions::default();
+        mount_options.max_pages(max_pages);
+
+        let fs = BytesFs {
+            data: Bytes::from(data.clone()),
+        };
+        let mut session = TestSession::new(fs, mount_options);
+        let connection_info = runtime.block_on(session.init(request(0, 0, 0))).unwrap();
+        let size = connection_info.max_write();
+
+        println!(
+            "large_requests: max_pages {} max_write {size}",
+            connection_info.max_pages()
+        );
+
+        group.throughput(Throughput::Bytes(FILE_SIZE as u64));
+        group.bench_function(BenchmarkId::new("read", size), |b| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    for offset in (0..FILE_SIZE as u64).step_by(size as usize) {
+                        session
+                            .read(request(0, 0, 0), 2, 0, offset, size)
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+        });
+        group.bench_function(BenchmarkId::new("write", size), |b| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    for (i, chunk) in data.chunks(size as usize).enumerate() {
+                        let offset = (i * size as usize) as u64;
+                        session
+                            .write(request(0, 0, 0), 2, 0, offset, chunk, 0)
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, large_requests);
+criterion_main!(benches);
@@ -0,0 +1,127 @@
+//! read a file kept in a reference counted [`Bytes`] buffer through [`TestSession`], to compare
+//! replying a [`Bytes::slice`] of the buffer with replying a copy of the data, the allocated
+//! bytes of each read are counted by the global allocator and printed before the benchmark.
+//!
+//! [`TestSession`] copies each reply to decode it like kernel does, the copy is counted in both,
+//! so the difference of them is the allocation saved by the slice.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fuse3::raw::prelude::*;
+use fuse3::test::{request, TestSession};
+use fuse3::{MountOptions, Result};
+use futures_util::stream::Empty;
+use tokio::runtime::{Builder, Runtime};
+
+/// the size of the file.
+const FILE_SIZE: usize = 4 * 1024 * 1024;
+
+/// count the allocated bytes.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[derive(Debug)]
+struct BytesFs {
+    data: Bytes,
+    /// reply a copy of the data instead of a slice of the buffer.
+    copy: bool,
+}
+
+impl Filesystem for BytesFs {
+    type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+
+    async fn init(&self, _req: Request) -> Result<ReplyInit> {
+        Ok(ReplyInit {
+            max_write: 4096.try_into().unwrap(),
+        })
+    }
+
+    async fn destroy(&self, _req: Request) {}
+
+    async fn read(
+        &self,
+        _req: Request,
+        _inode: u64,
+        _fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<ReplyData> {
+        let start = (offset as usize).min(self.data.len());
+        let end = (start + size as usize).min(self.data.len());
+
+        if self.copy {
+            Ok(ReplyData::from_slice(&self.data[start..end]))
+        } else {
+            Ok(self.data.slice(start..end).into())
+        }
+    }
+}
+
+fn bench_read(c: &mut Criterion, runtime: &Runtime, name: &str, copy: bool) {
+    let mut group = c.benchmark_group("read_reply");
+
+    let fs = BytesFs {
+        data: Bytes::from(vec![1; FILE_SIZE]),
+        copy,
+    };
+    let mut session = TestSession::new(fs, MountOptions::default());
+    runtime.block_on(session.init(request(0, 0, 0))).unwrap();
+
+    for size in [4096, 128 * 1024] {
+        let read_file = |session: &mut TestSession<BytesFs>| {
+            runtime.block_on(async {
+                for offset in (0..FILE_SIZE as u64).step_by(size as usize) {
+                    session
+                        .read(request(0, 0, 0), 2, 0, offset, size)
+                        .await
+                        .unwrap();
+                }
+            })
+        };
+
+        let allocated = ALLOCATED.load(Ordering::Relaxed);
+        read_file(&mut session);
+        let allocated = ALLOCATED.load(Ordering::Relaxed) - allocated;
+        println!(
+            "read_reply/{name}/{size}: {} bytes allocated per read",
+            allocated / (FILE_SIZE / size as usize)
+        );
+
+        group.throughput(Throughput::Bytes(FILE_SIZE as u64));
+        group.bench_function(BenchmarkId::new(name, size), |b| {
+            b.iter(|| read_file(&mut session))
+        });
+    }
+
+    group.finish();
+}
+
+fn read_reply(c: &mut Criterion) {
+    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+
+    bench_read(c, &runtime, "slice", false);
+    bench_read(c, &runtime, "copy", true);
+}
+
+criterion_group!(benches, read_reply);
+criterion_main!(benches);
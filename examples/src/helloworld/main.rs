@@ -60,6 +60,7 @@ impl Filesystem for HelloWorld {
                 atime: SystemTime::now().into(),
                 mtime: SystemTime::now().into(),
                 ctime: SystemTime::now().into(),
+                crtime: None,
                 kind: FileType::RegularFile,
                 perm: FILE_MODE,
                 nlink: 0,
@@ -89,6 +90,7 @@ impl Filesystem for HelloWorld {
                     atime: SystemTime::now().into(),
                     mtime: SystemTime::now().into(),
                     ctime: SystemTime::now().into(),
+                    crtime: None,
                     kind: FileType::Directory,
                     perm: PARENT_MODE,
                     nlink: 0,
@@ -108,6 +110,7 @@ impl Filesystem for HelloWorld {
                     atime: SystemTime::now().into(),
                     mtime: SystemTime::now().into(),
                     ctime: SystemTime::now().into(),
+                    crtime: None,
                     kind: FileType::RegularFile,
                     perm: FILE_MODE,
                     nlink: 0,
@@ -122,12 +125,12 @@ impl Filesystem for HelloWorld {
         }
     }
 
-    async fn open(&self, _req: Request, inode: u64, flags: u32) -> Result<ReplyOpen> {
+    async fn open(&self, _req: Request, inode: u64, _flags: OpenFlags) -> Result<ReplyOpen> {
         if inode != PARENT_INODE && inode != FILE_INODE {
             return Err(libc::ENOENT.into());
         }
 
-        Ok(ReplyOpen { fh: 0, flags })
+        Ok(ReplyOpen::new(0))
     }
 
     async fn read(
@@ -240,6 +243,7 @@ impl Filesystem for HelloWorld {
                     atime: SystemTime::now().into(),
                     mtime: SystemTime::now().into(),
                     ctime: SystemTime::now().into(),
+                    crtime: None,
                     kind: FileType::Directory,
                     perm: PARENT_MODE,
                     nlink: 0,
@@ -264,6 +268,7 @@ impl Filesystem for HelloWorld {
                     atime: SystemTime::now().into(),
                     mtime: SystemTime::now().into(),
                     ctime: SystemTime::now().into(),
+                    crtime: None,
                     kind: FileType::Directory,
                     perm: PARENT_MODE,
                     nlink: 0,
@@ -288,6 +293,7 @@ impl Filesystem for HelloWorld {
                     atime: SystemTime::now().into(),
                     mtime: SystemTime::now().into(),
                     ctime: SystemTime::now().into(),
+                    crtime: None,
                     kind: FileType::RegularFile,
                     perm: FILE_MODE,
                     nlink: 0,
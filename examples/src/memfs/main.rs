@@ -46,6 +46,7 @@ impl Entry {
                     atime: SystemTime::UNIX_EPOCH.into(),
                     mtime: SystemTime::UNIX_EPOCH.into(),
                     ctime: SystemTime::UNIX_EPOCH.into(),
+                    crtime: None,
                     kind: FileType::Directory,
                     perm: fuse3::perm_from_mode_and_kind(FileType::Directory, dir.mode),
                     nlink: nlink as _,
@@ -60,13 +61,14 @@ impl Entry {
                 let nlink = Arc::strong_count(file) - 1;
                 let file = file.read().await;
 
-                FileAttr {
+                let mut attr = FileAttr {
                     ino: file.inode,
-                    size: file.content.len() as _,
-                    blocks: (file.content.len() as f64 / BLOCK_SIZE).ceil() as _,
+                    size: 0,
+                    blocks: 0,
                     atime: SystemTime::UNIX_EPOCH.into(),
                     mtime: SystemTime::UNIX_EPOCH.into(),
                     ctime: SystemTime::UNIX_EPOCH.into(),
+                    crtime: None,
                     kind: FileType::RegularFile,
                     perm: fuse3::perm_from_mode_and_kind(FileType::RegularFile, file.mode),
                     nlink: nlink as _,
@@ -74,7 +76,10 @@ impl Entry {
                     gid: 0,
                     rdev: 0,
                     blksize: BLOCK_SIZE as _,
-                }
+                };
+                attr.set_size(file.content.len() as _);
+
+                attr
             }
         }
     }
@@ -153,6 +158,7 @@ struct Dir {
 #[derive(Debug)]
 struct File {
     inode: u64,
+    #[allow(dead_code)]
     parent: u64,
     name: OsString,
     content: Vec<u8>,
@@ -281,6 +287,7 @@ impl Filesystem for Fs {
         name: &OsStr,
         mode: u32,
         _umask: u32,
+        _security_context: Option<&SecurityContext>,
     ) -> Result<ReplyEntry> {
         let mut inner = self.0.write().await;
 
@@ -292,7 +299,7 @@ impl Filesystem for Fs {
         if let Entry::Dir(dir) = entry {
             let mut dir = dir.write().await;
 
-            if dir.children.get(name).is_some() {
+            if dir.children.contains_key(name) {
                 return Err(libc::EEXIST.into());
             }
 
@@ -496,7 +503,7 @@ impl Filesystem for Fs {
         })
     }
 
-    async fn open(&self, _req: Request, inode: u64, _flags: u32) -> Result<ReplyOpen> {
+    async fn open(&self, _req: Request, inode: u64, _flags: OpenFlags) -> Result<ReplyOpen> {
         let inner = self.0.read().await;
 
         let entry = inner
@@ -555,7 +562,7 @@ impl Filesystem for Fs {
         _fh: u64,
         offset: u64,
         mut data: &[u8],
-        _write_flags: u32,
+        _write_flags: WriteFlags,
         _flags: u32,
     ) -> Result<ReplyWrite> {
         let inner = self.0.read().await;
@@ -630,7 +637,9 @@ impl Filesystem for Fs {
         parent: u64,
         name: &OsStr,
         mode: u32,
-        flags: u32,
+        _flags: OpenFlags,
+        _umask: u32,
+        _security_context: Option<&SecurityContext>,
     ) -> Result<ReplyCreated> {
         let mut inner = self.0.write().await;
 
@@ -642,7 +651,7 @@ impl Filesystem for Fs {
         if let Entry::Dir(dir) = entry {
             let mut dir = dir.write().await;
 
-            if dir.children.get(name).is_some() {
+            if dir.children.contains_key(name) {
                 return Err(libc::EEXIST.into());
             }
 
@@ -669,7 +678,7 @@ impl Filesystem for Fs {
                 attr,
                 generation: 0,
                 fh: 0,
-                flags,
+                flags: 0,
             })
         } else {
             Err(libc::ENOTDIR.into())
@@ -687,7 +696,7 @@ impl Filesystem for Fs {
         _fh: u64,
         offset: u64,
         length: u64,
-        _mode: u32,
+        _mode: FallocateMode,
     ) -> Result<()> {
         let inner = self.0.read().await;
 
@@ -748,19 +757,16 @@ impl Filesystem for Fs {
                     .await
             };
 
-            let pre_children = stream::iter(
-                vec![
-                    (dir.inode, FileType::Directory, OsString::from("."), attr, 1),
-                    (
-                        dir.parent,
-                        FileType::Directory,
-                        OsString::from(".."),
-                        parent_attr,
-                        2,
-                    ),
-                ]
-                .into_iter(),
-            );
+            let pre_children = stream::iter(vec![
+                (dir.inode, FileType::Directory, OsString::from("."), attr, 1),
+                (
+                    dir.parent,
+                    FileType::Directory,
+                    OsString::from(".."),
+                    parent_attr,
+                    2,
+                ),
+            ]);
 
             let children = pre_children
                 .chain(stream::iter(dir.children.iter()).enumerate().filter_map(
@@ -794,18 +800,6 @@ impl Filesystem for Fs {
         }
     }
 
-    async fn rename2(
-        &self,
-        req: Request,
-        parent: u64,
-        name: &OsStr,
-        new_parent: u64,
-        new_name: &OsStr,
-        _flags: u32,
-    ) -> Result<()> {
-        self.rename(req, parent, name, new_parent, new_name).await
-    }
-
     async fn lseek(
         &self,
         _req: Request,
@@ -861,7 +855,15 @@ impl Filesystem for Fs {
         let data = data.data.as_ref();
 
         let ReplyWrite { written } = self
-            .write(req, inode_out, fh_out, off_out, data, 0, flags as _)
+            .write(
+                req,
+                inode_out,
+                fh_out,
+                off_out,
+                data,
+                WriteFlags::empty(),
+                flags as _,
+            )
             .await?;
 
         Ok(ReplyCopyFileRange {
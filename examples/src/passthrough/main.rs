@@ -0,0 +1,532 @@
+//! mirror a backing directory to the mount point.
+//!
+//! usage: passthrough <backing dir> <mount point>
+//!
+//! it runs on tokio by default, build it with `--no-default-features --features async-io-runtime`
+//! to run on async-io.
+//!
+//! the backing files are accessed by the blocking `std::fs` calls, so the filesystem doesn't
+//! depend on the runtime, a real filesystem should run them in a blocking thread pool.
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{CString, OsStr, OsString};
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io;
+use std::num::NonZeroU32;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{DirBuilderExt, FileExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::Bytes;
+use fuse3::path::prelude::*;
+use fuse3::path::ReaddirCacheStream;
+use fuse3::{Errno, MountOptions, Result};
+use tracing::{debug, Level};
+
+const TTL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+struct Passthrough {
+    root: PathBuf,
+    files: Mutex<HashMap<u64, Arc<File>>>,
+    next_fh: AtomicU64,
+    dirs: ReaddirCache<DirectoryEntry>,
+    dirs_plus: ReaddirCache<DirectoryEntryPlus>,
+}
+
+impl Passthrough {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            files: Default::default(),
+            next_fh: AtomicU64::new(1),
+            dirs: ReaddirCache::new(),
+            dirs_plus: ReaddirCache::new(),
+        }
+    }
+
+    /// the backing path of a filesystem path, which is always absolute.
+    fn backing_path(&self, path: &OsStr) -> PathBuf {
+        let path = Path::new(path);
+
+        self.root.join(path.strip_prefix("/").unwrap_or(path))
+    }
+
+    fn backing_child_path(&self, parent: &OsStr, name: &OsStr) -> PathBuf {
+        self.backing_path(parent).join(name)
+    }
+
+    fn attr(path: &Path) -> Result<FileAttr> {
+        Ok(FileAttr::from_metadata(&fs::symlink_metadata(path)?))
+    }
+
+    fn insert_file(&self, file: File) -> u64 {
+        let fh = self.next_fh.fetch_add(1, Ordering::Relaxed);
+
+        self.files.lock().unwrap().insert(fh, Arc::new(file));
+
+        fh
+    }
+
+    fn get_file(&self, fh: u64) -> Result<Arc<File>> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(&fh)
+            .cloned()
+            .ok_or_else(|| libc::EBADF.into())
+    }
+
+    /// list the directory with `.` and `..`.
+    fn list(&self, path: &OsStr) -> Result<Vec<(OsString, PathBuf)>> {
+        let backing_path = self.backing_path(path);
+        let parent = if backing_path == self.root {
+            backing_path.clone()
+        } else {
+            backing_path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| backing_path.clone())
+        };
+
+        let mut entries = vec![
+            (OsString::from("."), backing_path.clone()),
+            (OsString::from(".."), parent),
+        ];
+
+        for entry in fs::read_dir(&backing_path)? {
+            let entry = entry?;
+
+            entries.push((entry.file_name(), entry.path()));
+        }
+
+        Ok(entries)
+    }
+}
+
+fn open_options(flags: OpenFlags) -> OpenOptions {
+    let mut options = OpenOptions::new();
+
+    options.read(flags.is_read()).write(flags.is_write());
+
+    // kernel gives the offset of the append write, so the backing file is written by offset.
+    // The direct io isn't aligned for a backing O_DIRECT file, and kernel bypasses the cache
+    // for it already
+    options
+        .custom_flags(flags.bits() as i32 & !(libc::O_ACCMODE | libc::O_APPEND | libc::O_DIRECT));
+    options
+}
+
+/// label the new backing file with the security context which kernel computes for the caller, it
+/// is only sent when `MountOptions::security_context` is enabled.
+fn set_security_context(path: &Path, security_context: Option<&SecurityContext>) -> Result<()> {
+    let security_context = match security_context {
+        None => return Ok(()),
+        Some(security_context) => security_context,
+    };
+
+    let path = CString::new(path.as_os_str().as_bytes()).map_err(|_| Errno::from(libc::EINVAL))?;
+    let name =
+        CString::new(security_context.name.as_bytes()).map_err(|_| Errno::from(libc::EINVAL))?;
+    let value = &security_context.value;
+
+    // Safety: path and name are null terminated, value is valid for its length
+    let res = unsafe {
+        libc::lsetxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(())
+}
+
+impl PathFilesystem for Passthrough {
+    async fn init(&self, _req: Request) -> Result<ReplyInit> {
+        Ok(ReplyInit {
+            max_write: NonZeroU32::new(128 * 1024).unwrap(),
+        })
+    }
+
+    async fn destroy(&self, _req: Request) {}
+
+    async fn lookup(&self, _req: Request, parent: &OsStr, name: &OsStr) -> Result<ReplyEntry> {
+        let attr = Self::attr(&self.backing_child_path(parent, name))?;
+
+        Ok(ReplyEntry { ttl: TTL, attr })
+    }
+
+    async fn getattr(
+        &self,
+        _req: Request,
+        path: Option<&OsStr>,
+        fh: Option<u64>,
+        _flags: u32,
+    ) -> Result<ReplyAttr> {
+        let attr = match (fh, path) {
+            (Some(fh), _) => FileAttr::from_metadata(&self.get_file(fh)?.metadata()?),
+            (None, Some(path)) => Self::attr(&self.backing_path(path))?,
+            (None, None) => return Err(Errno::new_not_exist()),
+        };
+
+        Ok(ReplyAttr { ttl: TTL, attr })
+    }
+
+    async fn setattr(
+        &self,
+        _req: Request,
+        path: Option<&OsStr>,
+        fh: Option<u64>,
+        set_attr: SetAttr,
+    ) -> Result<ReplyAttr> {
+        let path = path.ok_or_else(Errno::new_not_exist)?;
+        let backing_path = self.backing_path(path);
+
+        if let Some(mode) = set_attr.mode {
+            fs::set_permissions(&backing_path, fs::Permissions::from_mode(mode))?;
+        }
+
+        if let Some(size) = set_attr.size {
+            match fh {
+                Some(fh) => self.get_file(fh)?.set_len(size)?,
+                None => OpenOptions::new()
+                    .write(true)
+                    .open(&backing_path)?
+                    .set_len(size)?,
+            }
+        }
+
+        let attr = Self::attr(&backing_path)?;
+
+        Ok(ReplyAttr { ttl: TTL, attr })
+    }
+
+    async fn readlink(&self, _req: Request, path: &OsStr) -> Result<ReplyData> {
+        let target = fs::read_link(self.backing_path(path))?;
+
+        Ok(Bytes::from(target.into_os_string().into_vec()).into())
+    }
+
+    async fn mkdir(
+        &self,
+        _req: Request,
+        parent: &OsStr,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        security_context: Option<&SecurityContext>,
+    ) -> Result<ReplyEntry> {
+        let path = self.backing_child_path(parent, name);
+
+        DirBuilder::new().mode(mode & !umask).create(&path)?;
+
+        if let Err(err) = set_security_context(&path, security_context) {
+            let _ = fs::remove_dir(&path);
+
+            return Err(err);
+        }
+
+        let attr = Self::attr(&path)?;
+
+        Ok(ReplyEntry { ttl: TTL, attr })
+    }
+
+    async fn unlink(&self, _req: Request, parent: &OsStr, name: &OsStr) -> Result<()> {
+        Ok(fs::remove_file(self.backing_child_path(parent, name))?)
+    }
+
+    async fn rmdir(&self, _req: Request, parent: &OsStr, name: &OsStr) -> Result<()> {
+        Ok(fs::remove_dir(self.backing_child_path(parent, name))?)
+    }
+
+    async fn rename(
+        &self,
+        _req: Request,
+        origin_parent: &OsStr,
+        origin_name: &OsStr,
+        parent: &OsStr,
+        name: &OsStr,
+    ) -> Result<()> {
+        debug!(
+            "rename {:?} {:?} to {:?} {:?}",
+            origin_parent, origin_name, parent, name
+        );
+
+        Ok(fs::rename(
+            self.backing_child_path(origin_parent, origin_name),
+            self.backing_child_path(parent, name),
+        )?)
+    }
+
+    async fn open(&self, _req: Request, path: &OsStr, flags: OpenFlags) -> Result<ReplyOpen> {
+        let file = open_options(flags).open(self.backing_path(path))?;
+
+        Ok(ReplyOpen::new(self.insert_file(file)))
+    }
+
+    async fn read_buf(
+        &self,
+        _req: Request,
+        _path: Option<&OsStr>,
+        fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<ReplyReadBuf> {
+        // the data is spliced from the backing file when splice is enabled
+        Ok(ReplyReadBuf::File {
+            fd: self.get_file(fh)?,
+            offset,
+            len: size,
+        })
+    }
+
+    async fn write(
+        &self,
+        _req: Request,
+        _path: Option<&OsStr>,
+        fh: u64,
+        offset: u64,
+        data: &[u8],
+        _write_flags: WriteFlags,
+        _flags: u32,
+    ) -> Result<ReplyWrite> {
+        self.get_file(fh)?.write_all_at(data, offset)?;
+
+        Ok(ReplyWrite {
+            written: data.len() as u32,
+        })
+    }
+
+    async fn release(
+        &self,
+        _req: Request,
+        _path: Option<&OsStr>,
+        fh: u64,
+        _flags: u32,
+        _lock_owner: u64,
+        _flush: bool,
+    ) -> Result<()> {
+        self.files.lock().unwrap().remove(&fh);
+
+        Ok(())
+    }
+
+    async fn fsync(
+        &self,
+        _req: Request,
+        _path: Option<&OsStr>,
+        fh: u64,
+        datasync: bool,
+    ) -> Result<()> {
+        let file = self.get_file(fh)?;
+
+        if datasync {
+            file.sync_data()?;
+        } else {
+            file.sync_all()?;
+        }
+
+        Ok(())
+    }
+
+    async fn flush(
+        &self,
+        _req: Request,
+        _path: Option<&OsStr>,
+        _fh: u64,
+        _lock_owner: u64,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn opendir(&self, _req: Request, path: &OsStr, _flags: OpenFlags) -> Result<ReplyOpen> {
+        if !fs::metadata(self.backing_path(path))?.is_dir() {
+            return Err(Errno::new_is_not_dir());
+        }
+
+        Ok(ReplyOpen::new(self.dirs.open()))
+    }
+
+    type DirEntryStream<'a>
+        = ReaddirCacheStream<DirectoryEntry>
+    where
+        Self: 'a;
+
+    async fn readdir<'a>(
+        &'a self,
+        _req: Request,
+        path: &'a OsStr,
+        fh: u64,
+        offset: i64,
+    ) -> Result<ReplyDirectory<Self::DirEntryStream<'a>>> {
+        let entries = self
+            .dirs
+            .readdir(fh, offset, || async {
+                self.list(path)?
+                    .into_iter()
+                    .map(|(name, path)| {
+                        Ok(DirectoryEntry {
+                            kind: Self::attr(&path)?.kind,
+                            name,
+                            offset: 0,
+                        })
+                    })
+                    .collect()
+            })
+            .await?;
+
+        Ok(ReplyDirectory { entries })
+    }
+
+    async fn releasedir(&self, _req: Request, _path: &OsStr, fh: u64, _flags: u32) -> Result<()> {
+        self.dirs.release(fh);
+        self.dirs_plus.release(fh);
+
+        Ok(())
+    }
+
+    async fn create(
+        &self,
+        _req: Request,
+        parent: &OsStr,
+        name: &OsStr,
+        mode: u32,
+        flags: OpenFlags,
+        umask: u32,
+        security_context: Option<&SecurityContext>,
+    ) -> Result<ReplyCreated> {
+        let path = self.backing_child_path(parent, name);
+        // kernel sends create for an existing file as well, such as the negative lookup entry is
+        // cached or another process creates it at the same time, so only the file created by
+        // this call is labeled and removed on failure
+        let file = match open_options(flags)
+            .create_new(true)
+            .mode(mode & !umask)
+            .open(&path)
+        {
+            // the caller's O_EXCL is kept in the custom flags, so this open still fails with it
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => open_options(flags)
+                .create(true)
+                .mode(mode & !umask)
+                .open(&path)?,
+
+            res => {
+                let file = res?;
+                if let Err(err) = set_security_context(&path, security_context) {
+                    let _ = fs::remove_file(&path);
+
+                    return Err(err);
+                }
+
+                file
+            }
+        };
+        let attr = FileAttr::from_metadata(&file.metadata()?);
+
+        Ok(ReplyCreated {
+            ttl: TTL,
+            attr,
+            generation: 0,
+            fh: self.insert_file(file),
+            flags: 0,
+        })
+    }
+
+    type DirEntryPlusStream<'a>
+        = ReaddirCacheStream<DirectoryEntryPlus>
+    where
+        Self: 'a;
+
+    async fn readdirplus<'a>(
+        &'a self,
+        _req: Request,
+        parent: &'a OsStr,
+        fh: u64,
+        offset: u64,
+        _lock_owner: u64,
+    ) -> Result<ReplyDirectoryPlus<Self::DirEntryPlusStream<'a>>> {
+        let entries = self
+            .dirs_plus
+            .readdir(fh, offset as i64, || async {
+                self.list(parent)?
+                    .into_iter()
+                    .map(|(name, path)| {
+                        let attr = Self::attr(&path)?;
+
+                        Ok(DirectoryEntryPlus {
+                            kind: attr.kind,
+                            name,
+                            offset: 0,
+                            attr,
+                            entry_ttl: TTL,
+                            attr_ttl: TTL,
+                        })
+                    })
+                    .collect()
+            })
+            .await?;
+
+        Ok(ReplyDirectoryPlus { entries })
+    }
+
+    async fn statfs(&self, _req: Request, path: &OsStr) -> Result<ReplyStatFs> {
+        ReplyStatFs::from_statvfs(&self.backing_path(path))
+    }
+}
+
+fn log_init() {
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(Level::DEBUG)
+        .finish();
+    tracing::subscriber::set_global_default(subscriber).unwrap();
+}
+
+#[cfg(feature = "tokio-runtime")]
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    run().await
+}
+
+#[cfg(feature = "async-io-runtime")]
+fn main() {
+    async_global_executor::block_on(run())
+}
+
+async fn run() {
+    log_init();
+
+    let args = env::args_os().skip(1).take(2).collect::<Vec<_>>();
+
+    let backing_path = args.first().expect("no backing directory specified");
+    let mount_path = args.get(1).expect("no mount point specified");
+
+    let backing_path = fs::canonicalize(backing_path).expect("invalid backing directory");
+
+    let uid = unsafe { libc::getuid() };
+    let gid = unsafe { libc::getgid() };
+
+    let mut mount_options = MountOptions::default();
+    mount_options
+        .fs_name("passthrough")
+        .uid(uid)
+        .gid(gid)
+        .security_context(true)
+        .use_splice(true);
+
+    Session::new(mount_options)
+        .mount_with_unprivileged(Passthrough::new(backing_path), mount_path)
+        .await
+        .unwrap()
+        .await
+        .unwrap();
+}
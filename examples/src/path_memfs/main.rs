@@ -33,6 +33,7 @@ impl Entry {
                 atime: SystemTime::UNIX_EPOCH,
                 mtime: SystemTime::UNIX_EPOCH,
                 ctime: SystemTime::UNIX_EPOCH,
+                crtime: None,
                 kind: FileType::Directory,
                 perm: fuse3::perm_from_mode_and_kind(FileType::Directory, dir.mode),
                 nlink: 0,
@@ -42,20 +43,26 @@ impl Entry {
                 blksize: 0,
             },
 
-            Entry::File(file) => FileAttr {
-                size: file.content.len() as _,
-                blocks: 0,
-                atime: SystemTime::UNIX_EPOCH,
-                mtime: SystemTime::UNIX_EPOCH,
-                ctime: SystemTime::UNIX_EPOCH,
-                kind: FileType::RegularFile,
-                perm: fuse3::perm_from_mode_and_kind(FileType::RegularFile, file.mode),
-                nlink: 0,
-                uid: 0,
-                gid: 0,
-                rdev: 0,
-                blksize: 0,
-            },
+            Entry::File(file) => {
+                let mut attr = FileAttr {
+                    size: 0,
+                    blocks: 0,
+                    atime: SystemTime::UNIX_EPOCH,
+                    mtime: SystemTime::UNIX_EPOCH,
+                    ctime: SystemTime::UNIX_EPOCH,
+                    crtime: None,
+                    kind: FileType::RegularFile,
+                    perm: fuse3::perm_from_mode_and_kind(FileType::RegularFile, file.mode),
+                    nlink: 0,
+                    uid: 0,
+                    gid: 0,
+                    rdev: 0,
+                    blksize: 0,
+                };
+                attr.set_size(file.content.len() as _);
+
+                attr
+            }
         }
     }
 
@@ -100,6 +107,7 @@ impl Entry {
 
 #[derive(Debug)]
 struct Dir {
+    #[allow(dead_code)]
     name: OsString,
     children: BTreeMap<OsString, Entry>,
     mode: mode_t,
@@ -107,6 +115,7 @@ struct Dir {
 
 #[derive(Debug)]
 struct File {
+    #[allow(dead_code)]
     name: OsString,
     content: BytesMut,
     mode: mode_t,
@@ -244,6 +253,7 @@ impl PathFilesystem for Fs {
         name: &OsStr,
         mode: u32,
         _umask: u32,
+        _security_context: Option<&SecurityContext>,
     ) -> Result<ReplyEntry> {
         let path = parent.to_string_lossy();
         let paths = split_path(&path);
@@ -437,7 +447,7 @@ impl PathFilesystem for Fs {
         Ok(())
     }
 
-    async fn open(&self, _req: Request, path: &OsStr, flags: u32) -> Result<ReplyOpen> {
+    async fn open(&self, _req: Request, path: &OsStr, _flags: OpenFlags) -> Result<ReplyOpen> {
         let path = path.to_string_lossy();
         let paths = split_path(&path);
 
@@ -459,7 +469,7 @@ impl PathFilesystem for Fs {
         if entry.is_dir() {
             Err(Errno::new_is_dir())
         } else {
-            Ok(ReplyOpen { fh: 0, flags })
+            Ok(ReplyOpen::new(0))
         }
     }
 
@@ -518,7 +528,7 @@ impl PathFilesystem for Fs {
         _fh: u64,
         offset: u64,
         data: &[u8],
-        _write_flags: u32,
+        _write_flags: WriteFlags,
         _flags: u32,
     ) -> Result<ReplyWrite> {
         let path = path.ok_or_else(Errno::new_not_exist)?.to_string_lossy();
@@ -610,7 +620,9 @@ impl PathFilesystem for Fs {
         parent: &OsStr,
         name: &OsStr,
         mode: u32,
-        flags: u32,
+        _flags: OpenFlags,
+        _umask: u32,
+        _security_context: Option<&SecurityContext>,
     ) -> Result<ReplyCreated> {
         let path = parent.to_string_lossy();
         let paths = split_path(&path);
@@ -649,7 +661,7 @@ impl PathFilesystem for Fs {
                 attr,
                 generation: 0,
                 fh: 0,
-                flags,
+                flags: 0,
             })
         } else {
             Err(Errno::new_is_not_dir())
@@ -667,10 +679,8 @@ impl PathFilesystem for Fs {
         _fh: u64,
         offset: u64,
         length: u64,
-        mode: u32,
+        mode: FallocateMode,
     ) -> Result<()> {
-        use std::os::raw::c_int;
-
         let path = path.ok_or_else(Errno::new_not_exist)?.to_string_lossy();
         let paths = split_path(&path);
 
@@ -696,8 +706,8 @@ impl PathFilesystem for Fs {
         let offset = offset as usize;
         let length = length as usize;
 
-        match mode as c_int {
-            0 => {
+        match mode {
+            mode if mode.is_empty() => {
                 if offset + length > file.content.len() {
                     file.content.resize(offset + length, 0);
                 }
@@ -705,7 +715,7 @@ impl PathFilesystem for Fs {
                 Ok(())
             }
 
-            libc::FALLOC_FL_KEEP_SIZE => {
+            FallocateMode::KEEP_SIZE => {
                 if offset + length > file.content.len() {
                     file.content.reserve(offset + length - file.content.len());
                 }
@@ -786,19 +796,6 @@ impl PathFilesystem for Fs {
         }
     }
 
-    async fn rename2(
-        &self,
-        req: Request,
-        origin_parent: &OsStr,
-        origin_name: &OsStr,
-        parent: &OsStr,
-        name: &OsStr,
-        _flags: u32,
-    ) -> Result<()> {
-        self.rename(req, origin_parent, origin_name, parent, name)
-            .await
-    }
-
     async fn lseek(
         &self,
         _req: Request,
@@ -864,9 +861,17 @@ impl PathFilesystem for Fs {
             .read(req, from_path, fh_in, offset_in, length as _)
             .await?;
 
-        // write_flags set to 0 because we don't care it in this example implement
+        // write_flags set to empty because we don't care it in this example implement
         let ReplyWrite { written } = self
-            .write(req, to_path, fh_out, offset_out, &data.data, 0, flags as _)
+            .write(
+                req,
+                to_path,
+                fh_out,
+                offset_out,
+                &data.data,
+                WriteFlags::empty(),
+                flags as _,
+            )
             .await?;
 
         Ok(ReplyCopyFileRange {
@@ -59,6 +59,7 @@ impl Filesystem for Poll {
                 atime: SystemTime::now().into(),
                 mtime: SystemTime::now().into(),
                 ctime: SystemTime::now().into(),
+                crtime: None,
                 kind: FileType::RegularFile,
                 perm: FILE_MODE,
                 nlink: 0,
@@ -88,6 +89,7 @@ impl Filesystem for Poll {
                     atime: SystemTime::now().into(),
                     mtime: SystemTime::now().into(),
                     ctime: SystemTime::now().into(),
+                    crtime: None,
                     kind: FileType::Directory,
                     perm: PARENT_MODE,
                     nlink: 0,
@@ -107,6 +109,7 @@ impl Filesystem for Poll {
                     atime: SystemTime::now().into(),
                     mtime: SystemTime::now().into(),
                     ctime: SystemTime::now().into(),
+                    crtime: None,
                     kind: FileType::RegularFile,
                     perm: FILE_MODE,
                     nlink: 0,
@@ -121,12 +124,12 @@ impl Filesystem for Poll {
         }
     }
 
-    async fn open(&self, _req: Request, inode: u64, flags: u32) -> Result<ReplyOpen> {
+    async fn open(&self, _req: Request, inode: u64, _flags: OpenFlags) -> Result<ReplyOpen> {
         if inode != PARENT_INODE && inode != FILE_INODE {
             return Err(libc::ENOENT.into());
         }
 
-        Ok(ReplyOpen { fh: 1, flags })
+        Ok(ReplyOpen::new(1))
     }
 
     async fn read(
@@ -239,6 +242,7 @@ impl Filesystem for Poll {
                     atime: SystemTime::now().into(),
                     mtime: SystemTime::now().into(),
                     ctime: SystemTime::now().into(),
+                    crtime: None,
                     kind: FileType::Directory,
                     perm: PARENT_MODE,
                     nlink: 0,
@@ -263,6 +267,7 @@ impl Filesystem for Poll {
                     atime: SystemTime::now().into(),
                     mtime: SystemTime::now().into(),
                     ctime: SystemTime::now().into(),
+                    crtime: None,
                     kind: FileType::Directory,
                     perm: PARENT_MODE,
                     nlink: 0,
@@ -287,6 +292,7 @@ impl Filesystem for Poll {
                     atime: SystemTime::now().into(),
                     mtime: SystemTime::now().into(),
                     ctime: SystemTime::now().into(),
+                    crtime: None,
                     kind: FileType::RegularFile,
                     perm: FILE_MODE,
                     nlink: 0,
@@ -312,20 +318,20 @@ impl Filesystem for Poll {
         _fh: u64,
         kh: Option<u64>,
         flags: u32,
-        events: u32,
+        events: PollEvents,
         notify: &Notify,
     ) -> Result<ReplyPoll> {
         if inode != PARENT_INODE && inode != FILE_INODE {
             return Err(libc::ENOENT.into());
         }
 
-        debug!("poll flags {} events {}", flags, events);
+        debug!("poll flags {} events {:?}", flags, events);
 
         if let Some(kh) = kh {
             let ready = self.ready.clone();
 
             if ready.load(Ordering::SeqCst) {
-                return Ok(ReplyPoll { revents: events });
+                return Ok(ReplyPoll::new(events));
             }
 
             let notify = notify.clone();
@@ -343,7 +349,7 @@ impl Filesystem for Poll {
             });
         }
 
-        Ok(ReplyPoll { revents: 0 })
+        Ok(ReplyPoll::pending())
     }
 }
 
@@ -1,12 +1,16 @@
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
-use std::io::Error as IoError;
+use std::io::{Error as IoError, ErrorKind};
 use std::os::raw::c_int;
 
 use nix::Error as NixError;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 /// linux errno wrap.
+///
+/// the common errnos are the associated constants, such as [`Errno::ENOENT`], their values are
+/// from the target platform. Any other errno can be created from its number, such as
+/// `Errno::from(libc::EOVERFLOW)`.
 pub struct Errno(c_int);
 
 impl From<Errno> for c_int {
@@ -21,14 +25,47 @@ impl From<c_int> for Errno {
     }
 }
 
-/// When raw os error is undefined, will return Errno(libc::EIO)
+/// use the raw os error if it is defined, otherwise map the common [`ErrorKind`] to the errno,
+/// such as [`ErrorKind::NotFound`] to `ENOENT`, the other kinds will return `Errno(libc::EIO)`.
+///
+/// # Examples:
+///
+/// ```
+/// # use std::io::{Error, ErrorKind};
+/// # use fuse3::Errno;
+/// let errno = Errno::from(Error::new(ErrorKind::NotFound, "no such file"));
+/// assert!(errno.is_not_exist());
+///
+/// let errno = Errno::from(Error::from_raw_os_error(libc::ENOTEMPTY));
+/// assert_eq!(errno, libc::ENOTEMPTY.into());
+/// ```
 impl From<IoError> for Errno {
     fn from(err: IoError) -> Self {
         if let Some(errno) = err.raw_os_error() {
-            Self(errno)
-        } else {
-            Self(libc::EIO)
+            return Self(errno);
         }
+
+        let errno = match err.kind() {
+            ErrorKind::NotFound => libc::ENOENT,
+            ErrorKind::PermissionDenied => libc::EACCES,
+            ErrorKind::AlreadyExists => libc::EEXIST,
+            ErrorKind::WouldBlock => libc::EAGAIN,
+            ErrorKind::InvalidInput => libc::EINVAL,
+            ErrorKind::TimedOut => libc::ETIMEDOUT,
+            ErrorKind::Interrupted => libc::EINTR,
+            ErrorKind::Unsupported => libc::ENOTSUP,
+            ErrorKind::OutOfMemory => libc::ENOMEM,
+            ErrorKind::BrokenPipe => libc::EPIPE,
+            ErrorKind::ConnectionRefused => libc::ECONNREFUSED,
+            ErrorKind::ConnectionReset => libc::ECONNRESET,
+            ErrorKind::ConnectionAborted => libc::ECONNABORTED,
+            ErrorKind::NotConnected => libc::ENOTCONN,
+            ErrorKind::AddrInUse => libc::EADDRINUSE,
+            ErrorKind::AddrNotAvailable => libc::EADDRNOTAVAIL,
+            _ => libc::EIO,
+        };
+
+        Self(errno)
     }
 }
 
@@ -57,6 +94,86 @@ impl Display for Errno {
 }
 
 impl Errno {
+    /// operation not permitted.
+    pub const EPERM: Self = Self(libc::EPERM);
+
+    /// no such file or directory.
+    pub const ENOENT: Self = Self(libc::ENOENT);
+
+    /// interrupted system call.
+    pub const EINTR: Self = Self(libc::EINTR);
+
+    /// input/output error.
+    pub const EIO: Self = Self(libc::EIO);
+
+    /// bad file descriptor.
+    pub const EBADF: Self = Self(libc::EBADF);
+
+    /// resource temporarily unavailable, the same as `EWOULDBLOCK`.
+    pub const EAGAIN: Self = Self(libc::EAGAIN);
+
+    /// cannot allocate memory.
+    pub const ENOMEM: Self = Self(libc::ENOMEM);
+
+    /// permission denied.
+    pub const EACCES: Self = Self(libc::EACCES);
+
+    /// device or resource busy.
+    pub const EBUSY: Self = Self(libc::EBUSY);
+
+    /// file exists.
+    pub const EEXIST: Self = Self(libc::EEXIST);
+
+    /// invalid cross-device link.
+    pub const EXDEV: Self = Self(libc::EXDEV);
+
+    /// not a directory.
+    pub const ENOTDIR: Self = Self(libc::ENOTDIR);
+
+    /// is a directory.
+    pub const EISDIR: Self = Self(libc::EISDIR);
+
+    /// invalid argument.
+    pub const EINVAL: Self = Self(libc::EINVAL);
+
+    /// file too large.
+    pub const EFBIG: Self = Self(libc::EFBIG);
+
+    /// no space left on device.
+    pub const ENOSPC: Self = Self(libc::ENOSPC);
+
+    /// read-only file system.
+    pub const EROFS: Self = Self(libc::EROFS);
+
+    /// too many links.
+    pub const EMLINK: Self = Self(libc::EMLINK);
+
+    /// result too large, such as the xattr buffer is too small.
+    pub const ERANGE: Self = Self(libc::ERANGE);
+
+    /// file name too long.
+    pub const ENAMETOOLONG: Self = Self(libc::ENAMETOOLONG);
+
+    /// function not implemented.
+    pub const ENOSYS: Self = Self(libc::ENOSYS);
+
+    /// directory not empty.
+    pub const ENOTEMPTY: Self = Self(libc::ENOTEMPTY);
+
+    /// too many levels of symbolic links.
+    pub const ELOOP: Self = Self(libc::ELOOP);
+
+    /// operation not supported.
+    pub const ENOTSUP: Self = Self(libc::ENOTSUP);
+
+    /// no data available, such as the xattr doesn't exist. It is `ENOATTR` on BSD.
+    #[cfg(target_os = "linux")]
+    pub const ENODATA: Self = Self(libc::ENODATA);
+
+    /// no data available, such as the xattr doesn't exist. It is `ENOATTR` on BSD.
+    #[cfg(not(target_os = "linux"))]
+    pub const ENODATA: Self = Self(libc::ENOATTR);
+
     pub fn new_not_exist() -> Self {
         Self(libc::ENOENT)
     }
@@ -33,7 +33,7 @@ pub const fn mode_from_kind_and_perm(kind: FileType, perm: u16) -> u32 {
 
 // Some platforms like Linux x86_64 have mode_t = u32, and lint warns of a trivial_numeric_casts.
 // But others like macOS x86_64 have mode_t = u16, requiring a typecast. So, just silence lint.
-#[cfg(all(not(target_os = "linux"), target_os = "freebsd"))]
+#[cfg(not(target_os = "linux"))]
 #[allow(trivial_numeric_casts)]
 /// returns the mode for a given file kind and permission
 pub const fn mode_from_kind_and_perm(kind: FileType, perm: u16) -> u32 {
@@ -46,6 +46,11 @@ pub const fn perm_from_mode_and_kind(kind: FileType, mode: mode_t) -> u16 {
     (mode ^ kind.const_into_mode_t()) as u16
 }
 
+/// returns the file kind for a given mode
+pub const fn kind_from_mode(mode: u32) -> Option<FileType> {
+    FileType::from_mode(mode)
+}
+
 #[inline]
 pub const fn get_padding_size(dir_entry_size: usize) -> usize {
     // 64bit align
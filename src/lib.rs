@@ -10,18 +10,61 @@
 //!
 //! - `file-lock`: enable POSIX file lock feature.
 //! - `async-io-runtime`: use [async_io](https://docs.rs/async-io) and
-//! [async-global-executor](https://docs.rs/async-global-executor) to drive async io and task.
+//!   [async-global-executor](https://docs.rs/async-global-executor) to drive async io and task.
 //! - `tokio-runtime`: use [tokio](https://docs.rs/tokio) runtime to drive async io and task.
+//! - `async-std-runtime`: use [async-std](https://docs.rs/async-std) to spawn the tasks, run the
+//!   blocking calls and timers, async-std runs on async_io, so this feature enables
+//!   `async-io-runtime` and the `/dev/fuse` fd is registered in the same async_io reactor.
 //! - `unprivileged`: allow mount filesystem without root permission by using `fusermount3`.
+//! - `request-span`: wrap each request in a `fuse_request` span with the `opcode`, `unique`,
+//!   `nodeid`, `uid` and `pid` fields, the span is closed after the reply is written and records
+//!   the replied `error` if any. The library always logs by [tracing](https://docs.rs/tracing),
+//!   this feature only adds the spans, it doesn't change whether `tracing` is linked.
+//! - `wire-debug`: log the hex dump of each request read from and each reply or notification
+//!   written to `/dev/fuse` at trace level, with the decoded opcode, `unique` and `error`. The
+//!   logging is compiled out without this feature.
+//!
+//! # Runtimes:
+//!
+//! | feature             | task spawn            | `/dev/fuse` io | timer     | lock       |
+//! |---------------------|-----------------------|----------------|-----------|------------|
+//! | `tokio-runtime`     | tokio                 | tokio          | tokio     | tokio      |
+//! | `async-io-runtime`  | async-global-executor | async-io       | async-io  | async-lock |
+//! | `async-std-runtime` | async-std             | async-io       | async-std | async-lock |
+//!
+//! The [`Filesystem`][raw::Filesystem] and [`PathFilesystem`][path::PathFilesystem] methods
+//! are plain futures, they can await the io and timer of any runtime which is running.
+//!
+//! They are native `async fn` in traits, the returned futures are required to be `Send` and are
+//! not boxed, so a request doesn't allocate for the future of the filesystem method. That needs
+//! Rust 1.75 or later, and the crate requires Rust 1.77 or later, there is no `async_trait`
+//! version of the traits.
+//!
+//! [`Runtime::enabled`] tells which runtime is in use, [`raw::SessionBuilder::runtime`] can
+//! require a runtime explicitly and fail to build the session if it is not enabled.
 //!
 //! # Notes:
 //!
-//! You must enable `async-io-runtime` or `tokio-runtime` feature.
+//! You must enable one of `async-io-runtime`, `async-std-runtime` or `tokio-runtime` feature,
+//! `tokio-runtime` can't be enabled with the other two.
+//!
+//! Linux, FreeBSD and macOS are supported, macOS mounts the filesystem by the `mount_macfuse`
+//! helper of macFUSE 4 or later, the macOS support is unstable.
 
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+#[cfg(all(feature = "tokio-runtime", feature = "async-io-runtime"))]
+compile_error!(
+    "`tokio-runtime` can't be enabled with `async-io-runtime` or `async-std-runtime` feature"
+);
+
+#[cfg(not(any(feature = "tokio-runtime", feature = "async-io-runtime")))]
+compile_error!(
+    "one of `tokio-runtime`, `async-io-runtime` or `async-std-runtime` feature is required"
+);
+
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
-use std::io::{self, ErrorKind};
+use std::io;
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
 use std::path::PathBuf;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
@@ -31,9 +74,13 @@ pub use helper::{mode_from_kind_and_perm, perm_from_mode_and_kind};
 pub use mount_options::MountOptions;
 use nix::sys::stat::mode_t;
 use raw::abi::{
-    fuse_setattr_in, FATTR_ATIME, FATTR_ATIME_NOW, FATTR_CTIME, FATTR_GID, FATTR_LOCKOWNER,
-    FATTR_MODE, FATTR_MTIME, FATTR_MTIME_NOW, FATTR_SIZE, FATTR_UID,
+    fuse_setattr_in, FATTR_ATIME, FATTR_ATIME_NOW, FATTR_CTIME, FATTR_FH, FATTR_GID,
+    FATTR_LOCKOWNER, FATTR_MODE, FATTR_MTIME, FATTR_MTIME_NOW, FATTR_SIZE, FATTR_UID,
 };
+#[cfg(target_os = "macos")]
+use raw::abi::{FATTR_BKUPTIME, FATTR_CHGTIME, FATTR_CRTIME, FATTR_FLAGS};
+use raw::flags::SetAttrValid;
+pub use runtime::Runtime;
 
 mod errno;
 mod helper;
@@ -41,6 +88,10 @@ mod mount_options;
 pub mod notify;
 pub mod path;
 pub mod raw;
+mod runtime;
+#[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
+pub mod test;
+pub mod util;
 
 /// Filesystem Inode.
 pub type Inode = u64;
@@ -68,6 +119,34 @@ pub enum FileType {
 }
 
 impl FileType {
+    /// get the file type from the `S_IFMT` bits of `mode`, such as the `st_mode` of `stat(2)` or
+    /// the `mode` of mknod, the permission bits are ignored. Return `None` if the file type bits
+    /// are missing or unknown.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use fuse3::FileType;
+    /// assert_eq!(
+    ///     FileType::from_mode(libc::S_IFREG as u32 | 0o644),
+    ///     Some(FileType::RegularFile)
+    /// );
+    /// assert_eq!(FileType::from_mode(0o644), None);
+    /// ```
+    #[allow(clippy::unnecessary_cast)] // Not unnecessary on all platforms.
+    pub const fn from_mode(mode: u32) -> Option<Self> {
+        match mode as mode_t & libc::S_IFMT {
+            libc::S_IFIFO => Some(FileType::NamedPipe),
+            libc::S_IFCHR => Some(FileType::CharDevice),
+            libc::S_IFBLK => Some(FileType::BlockDevice),
+            libc::S_IFDIR => Some(FileType::Directory),
+            libc::S_IFREG => Some(FileType::RegularFile),
+            libc::S_IFLNK => Some(FileType::Symlink),
+            libc::S_IFSOCK => Some(FileType::Socket),
+            _ => None,
+        }
+    }
+
     /// convert [`FileType`] into [`mode_t`]
     pub const fn const_into_mode_t(self) -> mode_t {
         match self {
@@ -89,8 +168,17 @@ impl From<FileType> for mode_t {
 }
 
 /// the setattr argument.
+///
+/// when kernel asks to set atime or mtime to the current time (`UTIME_NOW`), `atime` or `mtime`
+/// is set to the current time of the FUSE server, and `atime_now` or `mtime_now` is true, so
+/// filesystem can distinguish it from an explicit timestamp. ctime is never set to now by kernel.
+///
+/// [`valid`][SetAttr::valid] tells which attributes are requested by kernel, so an attribute set
+/// to 0 can be told apart from an unchanged one.
 #[derive(Debug, Clone, Default, Eq, PartialEq)]
 pub struct SetAttr {
+    /// the file handle, same as the `fh` argument of setattr.
+    pub fh: Option<u64>,
     /// set file or directory mode.
     pub mode: Option<mode_t>,
     /// set file or directory uid.
@@ -103,18 +191,30 @@ pub struct SetAttr {
     pub lock_owner: Option<u64>,
     /// set file or directory atime.
     pub atime: Option<Timestamp>,
+    /// atime should be set to the current time.
+    pub atime_now: bool,
     /// set file or directory mtime.
     pub mtime: Option<Timestamp>,
+    /// mtime should be set to the current time.
+    pub mtime_now: bool,
     /// set file or directory ctime.
     pub ctime: Option<Timestamp>,
+    /// set file or directory creation time, macOS only.
     #[cfg(target_os = "macos")]
     pub crtime: Option<Timestamp>,
+    /// set file or directory change time, macOS only.
     #[cfg(target_os = "macos")]
     pub chgtime: Option<Timestamp>,
+    /// set file or directory backup time, macOS only.
     #[cfg(target_os = "macos")]
     pub bkuptime: Option<Timestamp>,
+    /// set file or directory flags, see `chflags(2)`, macOS only.
     #[cfg(target_os = "macos")]
     pub flags: Option<u32>,
+    /// the attributes which are requested to set by kernel, even the value is 0. It is empty when
+    /// the struct is built with `..Default::default()`, set it with the fields if the filesystem
+    /// checks it.
+    pub valid: SetAttrValid,
 }
 
 /// Helper for constructing Timestamps from fuse_setattr_in, which sign-casts
@@ -127,7 +227,14 @@ macro_rules! fsai2ts {
 
 impl From<&fuse_setattr_in> for SetAttr {
     fn from(setattr_in: &fuse_setattr_in) -> Self {
-        let mut set_attr = Self::default();
+        let mut set_attr = Self {
+            valid: SetAttrValid::from_bits_retain(setattr_in.valid),
+            ..Default::default()
+        };
+
+        if setattr_in.valid & FATTR_FH > 0 {
+            set_attr.fh = Some(setattr_in.fh);
+        }
 
         if setattr_in.valid & FATTR_MODE > 0 {
             set_attr.mode = Some(setattr_in.mode as mode_t);
@@ -151,6 +258,7 @@ impl From<&fuse_setattr_in> for SetAttr {
 
         if setattr_in.valid & FATTR_ATIME_NOW > 0 {
             set_attr.atime = Some(SystemTime::now().into());
+            set_attr.atime_now = true;
         }
 
         if setattr_in.valid & FATTR_MTIME > 0 {
@@ -159,6 +267,7 @@ impl From<&fuse_setattr_in> for SetAttr {
 
         if setattr_in.valid & FATTR_MTIME_NOW > 0 {
             set_attr.mtime = Some(SystemTime::now().into());
+            set_attr.mtime_now = true;
         }
 
         if setattr_in.valid & FATTR_LOCKOWNER > 0 {
@@ -169,6 +278,25 @@ impl From<&fuse_setattr_in> for SetAttr {
             set_attr.ctime = fsai2ts!(setattr_in.ctime, setattr_in.ctimensec);
         }
 
+        #[cfg(target_os = "macos")]
+        {
+            if setattr_in.valid & FATTR_CRTIME > 0 {
+                set_attr.crtime = fsai2ts!(setattr_in.crtime, setattr_in.crtimensec);
+            }
+
+            if setattr_in.valid & FATTR_CHGTIME > 0 {
+                set_attr.chgtime = fsai2ts!(setattr_in.chgtime, setattr_in.chgtimensec);
+            }
+
+            if setattr_in.valid & FATTR_BKUPTIME > 0 {
+                set_attr.bkuptime = fsai2ts!(setattr_in.bkuptime, setattr_in.bkuptimensec);
+            }
+
+            if setattr_in.valid & FATTR_FLAGS > 0 {
+                set_attr.flags = Some(setattr_in.flags);
+            }
+        }
+
         set_attr
     }
 }
@@ -207,10 +335,6 @@ impl From<SystemTime> for Timestamp {
 
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
 fn find_fusermount3() -> io::Result<PathBuf> {
-    which::which("fusermount3").map_err(|err| {
-        io::Error::new(
-            ErrorKind::Other,
-            format!("find fusermount3 binary failed {err:?}"),
-        )
-    })
+    which::which("fusermount3")
+        .map_err(|err| io::Error::other(format!("find fusermount3 binary failed {err:?}")))
 }
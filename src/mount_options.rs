@@ -1,11 +1,21 @@
 use std::ffi::OsString;
 #[cfg(target_os = "linux")]
 use std::os::unix::io::RawFd;
+use std::time::Duration;
 
 #[cfg(target_os = "freebsd")]
 use nix::mount::Nmount;
 #[cfg(target_os = "linux")]
 use nix::unistd;
+use tracing::warn;
+
+use crate::notify::NotifyQueuePolicy;
+use crate::raw::abi::FUSE_MAX_REQUEST_SIZE;
+use crate::raw::flags::{InitFlags, OptionalOps};
+
+/// the max daemon timeout in seconds FreeBSD fusefs and macFUSE accept.
+#[cfg(any(target_os = "freebsd", target_os = "macos"))]
+const MAX_DAEMON_TIMEOUT: u64 = 600;
 
 /// mount options.
 #[derive(Debug, Clone, Default, Eq, PartialEq)]
@@ -16,12 +26,16 @@ pub struct MountOptions {
     // mount options
     pub(crate) allow_other: bool,
     pub(crate) allow_root: bool,
+    #[cfg(target_os = "linux")]
+    pub(crate) auto_unmount: bool,
     pub(crate) custom_options: Option<OsString>,
     #[cfg(target_os = "linux")]
     pub(crate) dirsync: bool,
     pub(crate) default_permissions: bool,
     pub(crate) fs_name: Option<String>,
     pub(crate) gid: Option<u32>,
+    #[cfg(any(target_os = "freebsd", target_os = "macos"))]
+    pub(crate) daemon_timeout: Option<u64>,
     #[cfg(target_os = "freebsd")]
     pub(crate) intr: bool,
     #[cfg(target_os = "linux")]
@@ -31,19 +45,76 @@ pub struct MountOptions {
     pub(crate) nodev: bool,
     pub(crate) noexec: bool,
     pub(crate) nosuid: bool,
+    #[cfg(target_os = "freebsd")]
+    pub(crate) push_symlinks_in: bool,
     pub(crate) read_only: bool,
     #[cfg(target_os = "freebsd")]
     pub(crate) suiddir: bool,
     pub(crate) sync: bool,
     pub(crate) uid: Option<u32>,
+    pub(crate) volume_name: Option<String>,
 
     // Optional FUSE features
     pub(crate) dont_mask: bool,
+    // None means follow default_permissions
+    pub(crate) posix_acl: Option<bool>,
     pub(crate) no_open_support: bool,
     pub(crate) no_open_dir_support: bool,
     pub(crate) handle_killpriv: bool,
+    pub(crate) security_context: bool,
+    pub(crate) create_supp_group: bool,
+    #[cfg(feature = "file-lock")]
+    pub(crate) flock_locks: bool,
     pub(crate) write_back: bool,
     pub(crate) force_readdir_plus: bool,
+    pub(crate) use_splice: bool,
+    // None means all optional operations are supported
+    pub(crate) supported_ops: Option<OptionalOps>,
+    // None means the init flags are derived from the options above
+    pub(crate) init_flags: Option<InitFlags>,
+
+    // Write the multi-segment replies with writev, None means enable
+    pub(crate) reply_writev: Option<bool>,
+
+    // Request size limits, 0 means use default
+    pub(crate) max_read: u32,
+    pub(crate) max_write: u32,
+
+    // Max pages of a request, None means cover the max_write and max_read
+    pub(crate) max_pages: Option<u16>,
+
+    // Readahead size in bytes, None means use the kernel offer
+    pub(crate) max_readahead: Option<u32>,
+
+    // Background requests limits
+    pub(crate) max_background: Option<u16>,
+    pub(crate) congestion_threshold: Option<u16>,
+
+    // Timestamp granularity in nanoseconds
+    pub(crate) time_gran: Option<u32>,
+
+    // Timeout of a filesystem request, None means no timeout
+    pub(crate) request_timeout: Option<Duration>,
+
+    // Pass FUSE_INTERRUPT to the filesystem instead of cancelling the request
+    pub(crate) forward_interrupt: bool,
+
+    // Pass FUSE_ACCESS to the filesystem even if default_permissions is set
+    pub(crate) forward_access: bool,
+
+    // Max number of the concurrent filesystem requests, None means unlimited
+    pub(crate) max_concurrent_requests: Option<usize>,
+
+    // Max memory of the read buffers, None means unbounded and the buffers are not reused
+    pub(crate) max_buffer_pool_bytes: Option<usize>,
+
+    // Bounded queue of the notifications, None means unbounded
+    pub(crate) notify_queue_size: Option<usize>,
+    pub(crate) notify_queue_policy: NotifyQueuePolicy,
+
+    // Retry policy when open /dev/fuse failed with EPERM
+    pub(crate) open_device_retries: u32,
+    pub(crate) open_device_retry_delay: Option<Duration>,
 
     // Other FUSE mount options
     // default 40000
@@ -73,6 +144,25 @@ impl MountOptions {
         self
     }
 
+    /// set the volume name which is shown to the user, default is not set.
+    ///
+    /// # Notes:
+    ///
+    /// Linux doesn't have a volume name, it is used as the filesystem subtype as a best effort,
+    /// so the filesystem type is `fuse.<volume_name>`, which is shown by `df -T` and `mount(8)`,
+    /// the source shown by `df` is still [`fs_name`][MountOptions::fs_name].
+    ///
+    /// FreeBSD uses it as the `subtype` mount option instead of
+    /// [`fs_name`][MountOptions::fs_name].
+    ///
+    /// macOS (macFUSE) shows it in Finder as the `volname` mount option, but macOS mount is not
+    /// implemented yet, so it is ignored now.
+    pub fn volume_name(&mut self, volume_name: impl Into<String>) -> &mut Self {
+        self.volume_name.replace(volume_name.into());
+
+        self
+    }
+
     /// set fuse filesystem `rootmode`, default is 40000.
     #[cfg(target_os = "linux")]
     pub fn rootmode(&mut self, rootmode: u32) -> &mut Self {
@@ -95,6 +185,31 @@ impl MountOptions {
         self
     }
 
+    /// unmount the filesystem automatically when the process exits, even if it crashes, default
+    /// is disable.
+    ///
+    /// # Notes:
+    ///
+    /// with [`mount_with_unprivileged`], `fusermount3` keeps running to watch the process and
+    /// unmounts when the process exits in any way. Some `fusermount3` versions only accept
+    /// `auto_unmount` with [`allow_other`][MountOptions::allow_other] or
+    /// [`allow_root`][MountOptions::allow_root], which needs `user_allow_other` in
+    /// `/etc/fuse.conf` for non-root users.
+    ///
+    /// with [`mount`], the filesystem is only unmounted lazily when the process exits normally,
+    /// such as returning from `main` or calling [`std::process::exit`], it is not unmounted when
+    /// the process is killed by a signal or aborted, use [`mount_with_unprivileged`] as root to
+    /// cover them.
+    ///
+    /// [`mount_with_unprivileged`]: crate::raw::Session::mount_with_unprivileged
+    /// [`mount`]: crate::raw::Session::mount
+    #[cfg(target_os = "linux")]
+    pub fn auto_unmount(&mut self, auto_unmount: bool) -> &mut Self {
+        self.auto_unmount = auto_unmount;
+
+        self
+    }
+
     /// set fuse filesystem `ro` mount option, default is disable.
     pub fn read_only(&mut self, read_only: bool) -> &mut Self {
         self.read_only = read_only;
@@ -102,6 +217,93 @@ impl MountOptions {
         self
     }
 
+    /// set fuse filesystem `nosuid` mount option, which ignores the set-user-ID and set-group-ID
+    /// bits of the files, default is disable.
+    ///
+    /// # Notes:
+    ///
+    /// `fusermount3` always mounts with `nosuid` for non-root users.
+    pub fn nosuid(&mut self, nosuid: bool) -> &mut Self {
+        self.nosuid = nosuid;
+
+        self
+    }
+
+    /// set fuse filesystem `nodev` mount option, which disallows to access the device files,
+    /// default is disable.
+    ///
+    /// # Notes:
+    ///
+    /// `fusermount3` always mounts with `nodev` for non-root users.
+    #[cfg(target_os = "linux")]
+    pub fn nodev(&mut self, nodev: bool) -> &mut Self {
+        self.nodev = nodev;
+
+        self
+    }
+
+    /// set fuse filesystem `noexec` mount option, which disallows to execute the files, default
+    /// is disable.
+    pub fn noexec(&mut self, noexec: bool) -> &mut Self {
+        self.noexec = noexec;
+
+        self
+    }
+
+    /// set fuse filesystem `noatime` mount option, which doesn't update the access time of the
+    /// files, default is disable.
+    pub fn noatime(&mut self, noatime: bool) -> &mut Self {
+        self.noatime = noatime;
+
+        self
+    }
+
+    /// set fusefs `intr` mount option, which allows the blocked filesystem requests to be
+    /// interrupted by signals, default is disable.
+    #[cfg(target_os = "freebsd")]
+    pub fn intr(&mut self, intr: bool) -> &mut Self {
+        self.intr = intr;
+
+        self
+    }
+
+    /// set fusefs `push_symlinks_in` mount option, which prefixes the absolute symlinks with the
+    /// mount point, default is disable.
+    #[cfg(target_os = "freebsd")]
+    pub fn push_symlinks(&mut self, push_symlinks: bool) -> &mut Self {
+        self.push_symlinks_in = push_symlinks;
+
+        self
+    }
+
+    /// set fusefs `timeout` mount option on FreeBSD, or macFUSE `daemon_timeout` mount option on
+    /// macOS, how long kernel waits a filesystem reply before it considers the filesystem dead and
+    /// fails the requests, default is 60 seconds.
+    ///
+    /// # Notes:
+    ///
+    /// the timeout is in seconds, the sub-second part is ignored, FreeBSD and macFUSE accept 0 to
+    /// 600 seconds, other value will be clamped to 600 seconds. A filesystem which may take long
+    /// to reply, such as a network filesystem, should set a larger timeout. macFUSE unmounts the
+    /// filesystem when the timeout fires, instead of only failing the request.
+    #[cfg(any(target_os = "freebsd", target_os = "macos"))]
+    pub fn daemon_timeout(&mut self, daemon_timeout: Duration) -> &mut Self {
+        let mut timeout = daemon_timeout.as_secs();
+        if timeout > MAX_DAEMON_TIMEOUT {
+            warn!(
+                timeout,
+                max = MAX_DAEMON_TIMEOUT,
+                "daemon_timeout is larger than kernel accepts, clamp it"
+            );
+
+            timeout = MAX_DAEMON_TIMEOUT;
+        }
+
+        self.daemon_timeout.replace(timeout);
+
+        self
+    }
+
     /// allow fuse filesystem mount on a non-empty directory, default is not allowed.
     pub fn nonempty(&mut self, nonempty: bool) -> &mut Self {
         self.nonempty = nonempty;
@@ -111,7 +313,11 @@ impl MountOptions {
 
     /// set fuse filesystem `default_permissions` mount option, default is disable.
     ///
-    /// When `default_permissions` is set, the [`raw::access`] and [`path::access`] is useless.
+    /// When `default_permissions` is set, kernel checks the permissions by the mode, uid and gid
+    /// of the file attributes itself, and the [`raw::access`] and [`path::access`] are useless.
+    /// Linux kernel doesn't send access requests at all, if a kernel sends one anyway, fuse3
+    /// replies success without calling the filesystem, unless
+    /// [`forward_access`][MountOptions::forward_access] is enabled.
     ///
     /// [`raw::access`]: crate::raw::Filesystem::access
     /// [`path::access`]: crate::path::PathFilesystem::access
@@ -122,13 +328,95 @@ impl MountOptions {
     }
 
     /// don't apply umask to file mode on create operations, default is disable.
+    ///
+    /// # Notes:
+    ///
+    /// when enabled, the `mode` of mknod, mkdir and create is passed as the caller gives, the
+    /// filesystem should apply the `umask` itself, which is needed to inherit the default ACL of
+    /// the parent directory, see [`posix_acl`][MountOptions::posix_acl].
     pub fn dont_mask(&mut self, dont_mask: bool) -> &mut Self {
         self.dont_mask = dont_mask;
 
         self
     }
 
+    /// try to set the `FUSE_POSIX_ACL` to let kernel enforce the POSIX ACLs, default follows
+    /// [`default_permissions`][MountOptions::default_permissions].
+    ///
+    /// # Notes:
+    ///
+    /// when negotiated, kernel checks the permissions by the mode and the ACLs itself, even if
+    /// `default_permissions` is not set, so the access requests are not sent. The ACLs are
+    /// stored as the `system.posix_acl_access` and `system.posix_acl_default` xattrs by the
+    /// filesystem, kernel caches them, and forgets the cache when they are set or the mode is
+    /// changed. The filesystem should keep the mode bits and the access ACL in sync on
+    /// setxattr and on the setattr of mode.
+    ///
+    /// kernel still applies the umask to the `mode` of mknod, mkdir and create unless
+    /// [`dont_mask`][MountOptions::dont_mask] is enabled, which breaks the default ACL
+    /// inheritance. To handle the default ACL of the parent directory, enable `dont_mask` too,
+    /// then the filesystem copies the default ACL to the new node and masks its mode by the
+    /// ACL if the parent has one, or applies the `umask` otherwise, like a local filesystem.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use fuse3::raw::prelude::*;
+    /// # use fuse3::raw::flags::InitFlags;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// # struct Fs;
+    /// #
+    /// # impl Filesystem for Fs {
+    /// #     type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #     type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #     async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #         Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #     }
+    /// #
+    /// #     async fn destroy(&self, _req: Request) {}
+    /// # }
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut mount_options = MountOptions::default();
+    /// mount_options.posix_acl(true).dont_mask(true);
+    ///
+    /// let mut session = TestSession::new(Fs, mount_options);
+    /// let connection_info = session.init(request(0, 0, 0)).await.unwrap();
+    ///
+    /// assert!(connection_info.capabilities().contains(InitFlags::POSIX_ACL));
+    /// assert!(connection_info.capabilities().contains(InitFlags::DONT_MASK));
+    /// # }
+    /// ```
+    pub fn posix_acl(&mut self, posix_acl: bool) -> &mut Self {
+        self.posix_acl.replace(posix_acl);
+
+        self
+    }
+
     /// make kernel support zero-message opens, default is disable
+    ///
+    /// # Notes:
+    ///
+    /// the zero-message open is a stateless open mode. When the filesystem replies `ENOSYS` to
+    /// [`open`][crate::raw::Filesystem::open], or doesn't implement it, kernel which supports
+    /// `FUSE_NO_OPEN_SUPPORT` treats the open as succeeded and stops sending the open and
+    /// release requests of the files. After that:
+    ///
+    /// - `fh` of read, write, flush, fsync, lseek and the other file operations is always 0, it
+    ///   carries no state, the filesystem must locate the file by the inode, or the path of the
+    ///   path filesystem.
+    /// - [`release`][crate::raw::Filesystem::release] is never called, don't keep any per-open
+    ///   state which needs to be cleaned up.
+    /// - the open flags are not sent, a direct io write still carries them in the `flags` of
+    ///   write.
+    ///
+    /// when kernel doesn't support it, replying `ENOSYS` fails the `open(2)` of the caller,
+    /// check [`InitFlags::NO_OPEN_SUPPORT`][crate::raw::flags::InitFlags::NO_OPEN_SUPPORT] in
+    /// [`ConnectionInfo::capabilities`][crate::raw::ConnectionInfo::capabilities].
     pub fn no_open_support(&mut self, no_open_support: bool) -> &mut Self {
         self.no_open_support = no_open_support;
 
@@ -143,18 +431,75 @@ impl MountOptions {
     }
 
     /// fs handle killing `suid`/`sgid`/`cap` on `write`/`chown`/`trunc`, default is disable.
+    ///
+    /// # Notes:
+    ///
+    /// use [`KillPriv`][crate::util::KillPriv] to clear them the same as kernel.
     pub fn handle_killpriv(&mut self, handle_killpriv: bool) -> &mut Self {
         self.handle_killpriv = handle_killpriv;
 
         self
     }
 
+    /// try to set the `FUSE_SECURITY_CTX` to let kernel send the security context of the new
+    /// file with create, mkdir, mknod and symlink, default is disable.
+    ///
+    /// # Notes:
+    ///
+    /// it is only negotiated with Linux 6.3 and later, which sends the security context as a
+    /// request extension. Check
+    /// [`InitFlags2::SECURITY_CTX`][crate::raw::flags::InitFlags2::SECURITY_CTX] in
+    /// [`ConnectionInfo::capabilities2`][crate::raw::ConnectionInfo::capabilities2] to know
+    /// whether kernel supports it. When it is enabled, the filesystem is responsible to set the
+    /// passed [`SecurityContext`][crate::raw::SecurityContext] on the new file.
+    pub fn security_context(&mut self, security_context: bool) -> &mut Self {
+        self.security_context = security_context;
+
+        self
+    }
+
+    /// try to set the `FUSE_CREATE_SUPP_GROUP` to let kernel send the group of the parent
+    /// directory as [`Request::supplementary_group`][crate::raw::Request::supplementary_group]
+    /// with create, mkdir, mknod and symlink, default is disable.
+    ///
+    /// # Notes:
+    ///
+    /// it is supported on Linux 6.5 and later. Check
+    /// [`InitFlags2::CREATE_SUPP_GROUP`][crate::raw::flags::InitFlags2::CREATE_SUPP_GROUP] in
+    /// [`ConnectionInfo::capabilities2`][crate::raw::ConnectionInfo::capabilities2] to know
+    /// whether kernel supports it.
+    pub fn create_supp_group(&mut self, create_supp_group: bool) -> &mut Self {
+        self.create_supp_group = create_supp_group;
+
+        self
+    }
+
+    /// try to set the `FUSE_FLOCK_LOCKS` to let the filesystem handle the BSD style `flock(2)`
+    /// locks by [`Filesystem::flock`][crate::raw::Filesystem::flock], default is disable, kernel
+    /// handles the `flock(2)` locks locally.
+    ///
+    /// # Notes:
+    ///
+    /// this is supported on enable **`file-lock`** feature. Check
+    /// [`InitFlags::FLOCK_LOCKS`][crate::raw::flags::InitFlags::FLOCK_LOCKS] in
+    /// [`ConnectionInfo::capabilities`][crate::raw::ConnectionInfo::capabilities] to know whether
+    /// kernel supports it.
+    #[cfg(feature = "file-lock")]
+    pub fn flock_locks(&mut self, flock_locks: bool) -> &mut Self {
+        self.flock_locks = flock_locks;
+
+        self
+    }
+
     /// try to set the `FUSE_WRITEBACK_CACHE` enable write back cache for buffered writes, default
     /// is disable.
     ///
     /// # Notes:
     ///
-    /// if enable this feature, when write flags has `FUSE_WRITE_CACHE`, file handle is guessed.
+    /// if enable this feature, when the write flags contain
+    /// [`WriteFlags::CACHE`][crate::raw::flags::WriteFlags::CACHE], the file handle is picked by
+    /// kernel from any writable open of the file, see
+    /// [`Filesystem::write`][crate::raw::Filesystem::write].
     pub fn write_back(&mut self, write_back: bool) -> &mut Self {
         self.write_back = write_back;
 
@@ -172,6 +517,415 @@ impl MountOptions {
         self
     }
 
+    /// declare the optional operations which the filesystem implements, default is all.
+    ///
+    /// # Notes:
+    ///
+    /// an unsupported operation is replied with `ENOSYS` without calling the filesystem, kernel
+    /// falls back like the filesystem returns `ENOSYS`, see [`OptionalOps`] for each operation.
+    /// The file locks and readdirplus are not advertised in `FUSE_INIT` when unsupported, so
+    /// kernel never sends them, instead of finding out by the first `ENOSYS`.
+    pub fn supported_ops(&mut self, supported_ops: OptionalOps) -> &mut Self {
+        self.supported_ops.replace(supported_ops);
+
+        self
+    }
+
+    /// set the `FUSE_INIT` flags requested from kernel explicitly, instead of the ones derived
+    /// from the other options, default is derived.
+    ///
+    /// # Notes:
+    ///
+    /// this is an escape hatch for the capabilities which the other options don't cover, such as
+    /// disabling [`ASYNC_READ`][InitFlags::ASYNC_READ] or requesting
+    /// [`HAS_IOCTL_DIR`][InitFlags::HAS_IOCTL_DIR] for the ioctl on directories. The options which
+    /// request the init flags, such as [`write_back`][MountOptions::write_back],
+    /// [`dont_mask`][MountOptions::dont_mask] and [`supported_ops`][MountOptions::supported_ops],
+    /// are ignored, see [`InitFlags`] for each flag.
+    ///
+    /// the flags are still AND-ed with the flags kernel supports, the negotiated ones are
+    /// reported by [`ConnectionInfo::capabilities`]. [`INIT_EXT`][InitFlags::INIT_EXT] is always
+    /// requested when kernel supports it, because the `flags2` are requested by
+    /// [`security_context`][MountOptions::security_context] and
+    /// [`create_supp_group`][MountOptions::create_supp_group]. The filesystem must implement the
+    /// operations the requested flags imply, such as `readdirplus` for
+    /// [`DO_READDIRPLUS`][InitFlags::DO_READDIRPLUS] and the lock operations for
+    /// [`POSIX_LOCKS`][InitFlags::POSIX_LOCKS].
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use fuse3::raw::prelude::*;
+    /// # use fuse3::raw::flags::InitFlags;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// # struct Fs;
+    /// #
+    /// # impl Filesystem for Fs {
+    /// #     type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #     type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #     async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #         Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #     }
+    /// #
+    /// #     async fn destroy(&self, _req: Request) {}
+    /// # }
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut mount_options = MountOptions::default();
+    /// // serialize the reads, and allow the ioctl on directories
+    /// mount_options.init_flags(InitFlags::ATOMIC_O_TRUNC | InitFlags::HAS_IOCTL_DIR);
+    ///
+    /// let mut session = TestSession::new(Fs, mount_options);
+    /// let connection_info = session.init(request(0, 0, 0)).await.unwrap();
+    ///
+    /// assert!(!connection_info.capabilities().contains(InitFlags::ASYNC_READ));
+    /// assert!(connection_info.capabilities().contains(InitFlags::HAS_IOCTL_DIR));
+    /// # }
+    /// ```
+    ///
+    /// [`ConnectionInfo::capabilities`]: crate::raw::ConnectionInfo::capabilities
+    pub fn init_flags(&mut self, init_flags: InitFlags) -> &mut Self {
+        self.init_flags.replace(init_flags);
+
+        self
+    }
+
+    /// write the read replies from a file to kernel with `splice(2)` if kernel supports, default
+    /// is disable.
+    ///
+    /// # Notes:
+    ///
+    /// only Linux supports this feature. When [`read_buf`][crate::raw::Filesystem::read_buf]
+    /// replies [`ReplyReadBuf::File`][crate::raw::reply::ReplyReadBuf::File], the data is spliced
+    /// from the file into a pipe after the reply header, and then the pipe is spliced to
+    /// `/dev/fuse`, so the data is never copied into user space. Other replies are written by
+    /// `writev(2)`. If the pipe can't be created, the file doesn't support splice, or the reply is
+    /// larger than the pipe capacity, which an unprivileged user may not be able to grow over
+    /// `/proc/sys/fs/pipe-max-size`, the data is read by `pread(2)` and written by `writev(2)`.
+    pub fn use_splice(&mut self, use_splice: bool) -> &mut Self {
+        self.use_splice = use_splice;
+
+        self
+    }
+
+    /// write the multi-segment replies, such as `read`, `readdir` and `getxattr` replies, with
+    /// `writev(2)` in a single syscall, the header and the data are written from their own
+    /// buffers, default is enable.
+    ///
+    /// # Notes:
+    ///
+    /// when disabled, the header and the data are concatenated into one buffer before written,
+    /// which copies the data but only passes one segment to kernel. Every reply is always written
+    /// in a single syscall, kernel doesn't allow coalescing multiple replies into one write.
+    pub fn reply_writev(&mut self, reply_writev: bool) -> &mut Self {
+        self.reply_writev.replace(reply_writev);
+
+        self
+    }
+
+    /// set fuse filesystem `max_read` mount option, the max size of a single read request, 0
+    /// means use the kernel default.
+    ///
+    /// # Notes:
+    ///
+    /// the value will be clamped to the max request size kernel supports, which is 1 MiB.
+    pub fn max_read(&mut self, max_read: u32) -> &mut Self {
+        self.max_read = max_read.min(FUSE_MAX_REQUEST_SIZE);
+
+        self
+    }
+
+    /// set the max size of a single write request, 0 means use the [`ReplyInit::max_write`]
+    /// which returned by [`init`].
+    ///
+    /// # Notes:
+    ///
+    /// the value will be clamped to the max request size kernel supports, which is 1 MiB, and
+    /// the `max_write` returned by [`init`] can't be larger than this value.
+    ///
+    /// [`ReplyInit::max_write`]: crate::raw::reply::ReplyInit::max_write
+    /// [`init`]: crate::raw::Filesystem::init
+    pub fn max_write(&mut self, max_write: u32) -> &mut Self {
+        self.max_write = max_write.min(FUSE_MAX_REQUEST_SIZE);
+
+        self
+    }
+
+    /// set the max pages of a single read or write request, default is the pages which cover the
+    /// larger one of [`max_write`][MountOptions::max_write] and
+    /// [`max_read`][MountOptions::max_read], 0 `max_read` counts as 1 MiB. A larger request
+    /// means less requests for the large sequential read and write.
+    ///
+    /// # Notes:
+    ///
+    /// kernel without `FUSE_MAX_PAGES` support, which is added in Linux 4.20, uses 32 pages, 128
+    /// KiB with 4 KiB pages. The value is clamped to `/proc/sys/fs/fuse/max_pages_limit`, which
+    /// is 256 by default, the older kernel without it has the fixed limit 256. The negotiated
+    /// `max_write` is clamped to the max pages too, so the read buffers are not larger than the
+    /// largest write, the negotiated values can be got from [`ConnectionInfo::max_pages`] and
+    /// [`ConnectionInfo::max_write`].
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use fuse3::raw::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// struct Fs;
+    ///
+    /// impl Filesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    ///     async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    ///         Ok(ReplyInit { max_write: (1024 * 1024).try_into().unwrap() })
+    ///     }
+    ///
+    ///     async fn destroy(&self, _req: Request) {}
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut mount_options = MountOptions::default();
+    /// mount_options.max_pages(16);
+    ///
+    /// let mut session = TestSession::new(Fs, mount_options);
+    /// let connection_info = session.init(request(0, 0, 0)).await.unwrap();
+    /// let page_size = connection_info.max_write() / 16;
+    ///
+    /// assert_eq!(connection_info.max_pages(), 16);
+    /// assert!(page_size >= 4096 && page_size.is_power_of_two());
+    /// # }
+    /// ```
+    ///
+    /// [`ConnectionInfo::max_pages`]: crate::raw::ConnectionInfo::max_pages
+    /// [`ConnectionInfo::max_write`]: crate::raw::ConnectionInfo::max_write
+    pub fn max_pages(&mut self, max_pages: u16) -> &mut Self {
+        self.max_pages.replace(max_pages.max(1));
+
+        self
+    }
+
+    /// set the max readahead size in bytes, default is the max readahead kernel offers in
+    /// `FUSE_INIT`. Filesystems serving large sequential reads can benefit from a larger
+    /// readahead, while a smaller one saves the useless reads of random access workloads.
+    ///
+    /// # Notes:
+    ///
+    /// the value will be clamped to the max readahead kernel offers, which usually comes from
+    /// the bdi `read_ahead_kb` of the mount, the negotiated value can be got from
+    /// [`ConnectionInfo::max_readahead`].
+    ///
+    /// [`ConnectionInfo::max_readahead`]: crate::raw::ConnectionInfo::max_readahead
+    pub fn max_readahead(&mut self, max_readahead: u32) -> &mut Self {
+        self.max_readahead.replace(max_readahead);
+
+        self
+    }
+
+    /// set the max number of pending background requests kernel allows, such as readahead and
+    /// async direct io, default is 12.
+    pub fn max_background(&mut self, max_background: u16) -> &mut Self {
+        self.max_background.replace(max_background);
+
+        self
+    }
+
+    /// set the number of pending background requests when kernel starts to mark the filesystem
+    /// as congested, default is 3/4 of `max_background`.
+    ///
+    /// # Notes:
+    ///
+    /// if `congestion_threshold` is larger than `max_background`, it will be clamped to
+    /// `max_background`.
+    pub fn congestion_threshold(&mut self, congestion_threshold: u16) -> &mut Self {
+        self.congestion_threshold.replace(congestion_threshold);
+
+        self
+    }
+
+    /// set the timestamp granularity in nanoseconds which the filesystem supports, default is 1.
+    ///
+    /// # Notes:
+    ///
+    /// the valid value is power of ten from 1 to 1_000_000_000, other value will be clamped to
+    /// the nearest valid value.
+    pub fn time_gran(&mut self, time_gran: u32) -> &mut Self {
+        let mut valid_time_gran = 1;
+        while valid_time_gran < 1_000_000_000 && valid_time_gran * 10 <= time_gran {
+            valid_time_gran *= 10;
+        }
+        if valid_time_gran < 1_000_000_000
+            && time_gran > valid_time_gran
+            && time_gran - valid_time_gran > valid_time_gran * 10 - time_gran
+        {
+            valid_time_gran *= 10;
+        }
+
+        if valid_time_gran != time_gran {
+            warn!(
+                time_gran,
+                valid_time_gran, "time_gran is not a power of ten, clamp it"
+            );
+        }
+
+        self.time_gran.replace(valid_time_gran);
+
+        self
+    }
+
+    /// set the timeout of a filesystem request, default is no timeout.
+    ///
+    /// # Notes:
+    ///
+    /// when a filesystem method doesn't return in time, its future will be dropped and `EIO`
+    /// will be replied to kernel, so the filesystem must be cancel safe. The requests which kernel
+    /// doesn't wait the reply, such as `forget` and `interrupt`, and the blocking `setlk` which
+    /// may wait the lock for long, won't be cancelled.
+    pub fn request_timeout(&mut self, request_timeout: Duration) -> &mut Self {
+        self.request_timeout.replace(request_timeout);
+
+        self
+    }
+
+    /// pass the interrupt requests to [`interrupt`][crate::raw::Filesystem::interrupt] instead of
+    /// cancelling the interrupted requests, default is disable.
+    ///
+    /// # Notes:
+    ///
+    /// by default, when kernel sends an interrupt request, such as the calling process receives a
+    /// signal, the future of the interrupted filesystem request will be dropped and `EINTR` will
+    /// be replied, so the filesystem must be cancel safe. When enabled, the interrupted request
+    /// keeps running, and the filesystem should make it return `EINTR` early in `interrupt`.
+    pub fn forward_interrupt(&mut self, forward_interrupt: bool) -> &mut Self {
+        self.forward_interrupt = forward_interrupt;
+
+        self
+    }
+
+    /// pass the access requests to [`access`][crate::raw::Filesystem::access] even if
+    /// [`default_permissions`][MountOptions::default_permissions] is set, default is disable.
+    ///
+    /// # Notes:
+    ///
+    /// by default, the access requests are replied with success directly when
+    /// `default_permissions` is set, because kernel has checked the permissions already. Enable
+    /// it when the filesystem checks more than the file mode in `access`, such as ACL or a
+    /// remote policy.
+    pub fn forward_access(&mut self, forward_access: bool) -> &mut Self {
+        self.forward_access = forward_access;
+
+        self
+    }
+
+    /// set the max number of the filesystem requests running concurrently, default is unlimited,
+    /// 0 is treated as 1.
+    ///
+    /// # Notes:
+    ///
+    /// each request is handled by a spawned task, when the limit is reached, the new requests
+    /// wait for a running one to be done before calling the filesystem, so a burst of requests
+    /// doesn't run the filesystem methods unbounded, which is useful for the CPU-bound
+    /// filesystems. The waiting requests are queued in order and only hold the request data.
+    ///
+    /// the session keeps reading `/dev/fuse` when the limit is reached, the requests which
+    /// kernel doesn't wait the reply, such as `FUSE_INTERRUPT` and `FUSE_FORGET`, don't take a
+    /// slot and are handled at once. A running or waiting request can be interrupted as usual,
+    /// the waiting one replies `EINTR` without calling the filesystem. With
+    /// [`forward_interrupt`][MountOptions::forward_interrupt], the waiting request is not
+    /// cancelled by the library, it runs after it gets a slot. The blocking `setlkw` takes a
+    /// slot while it waits for the lock, the `setlk` which releases the lock waits for a slot
+    /// too, so set the limit larger than the expected blocked `setlkw`.
+    pub fn max_concurrent_requests(&mut self, max_concurrent_requests: usize) -> &mut Self {
+        self.max_concurrent_requests
+            .replace(max_concurrent_requests.max(1));
+
+        self
+    }
+
+    /// reuse the read buffers in a pool and bound their total memory to `max_buffer_pool_bytes`,
+    /// default is unbounded and the buffers are not reused.
+    ///
+    /// # Notes:
+    ///
+    /// the requests are read into a buffer of `max_write` plus the request header, a large
+    /// write takes its buffer until the write request is done, so a burst of large writes may
+    /// allocate many buffers. With the pool, the buffer is returned to the pool and reused when
+    /// the write request is done, at least one buffer is allocated even if
+    /// `max_buffer_pool_bytes` is less than the buffer size.
+    ///
+    /// when all buffers are in use, the session stops reading the requests until a write request
+    /// is done and returns its buffer, the requests wait in kernel, including `FUSE_INTERRUPT`,
+    /// so a large write which waits for another request may wait forever, use it with
+    /// [`request_timeout`][MountOptions::request_timeout]. If the filesystem still holds the
+    /// write data after the write is done, the data is copied so the buffer can be reused, the
+    /// memory held by the filesystem isn't counted.
+    pub fn max_buffer_pool_bytes(&mut self, max_buffer_pool_bytes: usize) -> &mut Self {
+        self.max_buffer_pool_bytes.replace(max_buffer_pool_bytes);
+
+        self
+    }
+
+    /// queue the notifications sent by [`Notify`][crate::notify::Notify] in a bounded queue of
+    /// `notify_queue_size`, default is unbounded, 0 is treated as 1.
+    ///
+    /// # Notes:
+    ///
+    /// the notifications are written to `/dev/fuse` by the same task which writes the replies,
+    /// by default they are queued with the replies without limit, so a burst of notifications
+    /// won't be lost, but may use a lot of memory if they are sent faster than written. When the
+    /// bounded queue is full, [`notify_queue_policy`][MountOptions::notify_queue_policy] decides
+    /// to wait, drop the oldest notify or fail.
+    pub fn notify_queue_size(&mut self, notify_queue_size: usize) -> &mut Self {
+        self.notify_queue_size.replace(notify_queue_size.max(1));
+
+        self
+    }
+
+    /// set what to do when the notify queue is full, default is
+    /// [`Block`][NotifyQueuePolicy::Block], it only takes effect when
+    /// [`notify_queue_size`][MountOptions::notify_queue_size] is set.
+    ///
+    /// # Notes:
+    ///
+    /// a filesystem which relies on the invalidations for correctness shouldn't use
+    /// [`DropOldest`][NotifyQueuePolicy::DropOldest], or should check
+    /// [`Notify::dropped`][crate::notify::Notify::dropped] and invalidate more widely when
+    /// notifications are dropped.
+    pub fn notify_queue_policy(&mut self, notify_queue_policy: NotifyQueuePolicy) -> &mut Self {
+        self.notify_queue_policy = notify_queue_policy;
+
+        self
+    }
+
+    /// set how many times to retry when open `/dev/fuse` failed with `EPERM`, default is 0.
+    ///
+    /// # Notes:
+    ///
+    /// in some sandboxed environments, `/dev/fuse` may return `EPERM` for a while after the device
+    /// node is created. Other errors won't be retried, and if all retries failed, the last error
+    /// will be returned. It is not used by unprivileged mount, which `/dev/fuse` is opened by
+    /// `fusermount3`.
+    pub fn open_device_retries(&mut self, open_device_retries: u32) -> &mut Self {
+        self.open_device_retries = open_device_retries;
+
+        self
+    }
+
+    /// set the delay before the first retry of opening `/dev/fuse`, the delay will be doubled
+    /// after each retry, default is 10ms.
+    pub fn open_device_retry_delay(&mut self, open_device_retry_delay: Duration) -> &mut Self {
+        self.open_device_retry_delay
+            .replace(open_device_retry_delay);
+
+        self
+    }
+
     /// set custom options for fuse filesystem, the custom options will be used in mount
     pub fn custom_options(&mut self, custom_options: impl Into<OsString>) -> &mut Self {
         self.custom_options = Some(custom_options.into());
@@ -179,32 +933,68 @@ impl MountOptions {
         self
     }
 
+    /// whether the filesystem implements the optional operation.
+    pub(crate) fn is_supported(&self, op: OptionalOps) -> bool {
+        self.supported_ops
+            .map_or(true, |supported_ops| supported_ops.contains(op))
+    }
+
+    /// build the fusefs nmount options, the mount flags such as `ro` are also set by
+    /// [`flags`][MountOptions::flags].
+    ///
+    /// `from` is only the name shown by `mount(8)`, the fuse device instance of the mount is
+    /// the `fd` option set by the session, so multiple mounts don't share a connection.
     #[cfg(target_os = "freebsd")]
-    pub(crate) fn build(&self) -> Nmount {
+    pub(crate) fn build(&self) -> Nmount<'static> {
         let mut nmount = Nmount::new();
         nmount
             .str_opt(c"fstype", c"fusefs")
             .str_opt(c"from", c"/dev/fuse");
+        for (name, value) in self.nmount_options() {
+            match value {
+                None => nmount.null_opt_owned(name.as_os_str()),
+                Some(value) => nmount.str_opt_owned(name.as_os_str(), value.as_os_str()),
+            };
+        }
+        nmount
+    }
+
+    /// the fusefs options added by [`build`][MountOptions::build] after `fstype` and `from`, the
+    /// value is `None` if the option has no value.
+    #[cfg(target_os = "freebsd")]
+    fn nmount_options(&self) -> Vec<(OsString, Option<OsString>)> {
+        let mut options = vec![];
         if self.allow_other {
-            nmount.null_opt(c"allow_other");
+            options.push(("allow_other".into(), None));
         }
         if self.allow_root {
-            nmount.null_opt(c"allow_root");
+            options.push(("allow_root".into(), None));
+        }
+        if self.read_only {
+            options.push(("ro".into(), None));
         }
         if self.default_permissions {
-            nmount.null_opt(c"default_permissions");
+            options.push(("default_permissions".into(), None));
         }
-        if let Some(fs_name) = &self.fs_name {
-            nmount.str_opt_owned(c"subtype=", fs_name.as_str());
+        if let Some(subtype) = self.volume_name.as_ref().or(self.fs_name.as_ref()) {
+            options.push(("subtype=".into(), Some(subtype.into())));
         }
         if self.intr {
-            nmount.null_opt(c"intr");
+            options.push(("intr".into(), None));
+        }
+        if self.push_symlinks_in {
+            options.push(("push_symlinks_in".into(), None));
+        }
+        if let Some(daemon_timeout) = self.daemon_timeout {
+            options.push(("timeout=".into(), Some(daemon_timeout.to_string().into())));
+        }
+        if self.max_read > 0 {
+            options.push(("max_read=".into(), Some(self.max_read.to_string().into())));
         }
         if let Some(custom_options) = self.custom_options.as_ref() {
-            nmount.null_opt_owned(custom_options.as_os_str());
+            options.push((custom_options.clone(), None));
         }
-        // TODO: additional options: push_symlinks_in, max_read=, timeout=
-        nmount
+        options
     }
 
     #[cfg(target_os = "linux")]
@@ -234,6 +1024,10 @@ impl MountOptions {
             opts.push("default_permissions".to_string());
         }
 
+        if self.max_read > 0 {
+            opts.push(format!("max_read={}", self.max_read));
+        }
+
         let mut options = OsString::from(opts.join(","));
 
         if let Some(custom_options) = &self.custom_options {
@@ -262,6 +1056,13 @@ impl MountOptions {
             ),
         ];
 
+        if let Some(volume_name) = &self.volume_name {
+            // fusermount3 splits the options by `,`, and unescapes `\,` and `\\`
+            let volume_name = volume_name.replace('\\', "\\\\").replace(',', "\\,");
+
+            opts.push(format!("subtype={volume_name}"));
+        }
+
         if self.allow_root {
             opts.push("allow_root".to_string());
         }
@@ -274,10 +1075,96 @@ impl MountOptions {
             opts.push("ro".to_string());
         }
 
+        if self.auto_unmount {
+            opts.push("auto_unmount".to_string());
+        }
+
+        if self.nosuid {
+            opts.push("nosuid".to_string());
+        }
+
+        if self.nodev {
+            opts.push("nodev".to_string());
+        }
+
+        if self.noexec {
+            opts.push("noexec".to_string());
+        }
+
+        if self.noatime {
+            opts.push("noatime".to_string());
+        }
+
+        if self.default_permissions {
+            opts.push("default_permissions".to_string());
+        }
+
+        if self.max_read > 0 {
+            opts.push(format!("max_read={}", self.max_read));
+        }
+
+        let mut options = OsString::from(opts.join(","));
+
+        if let Some(custom_options) = &self.custom_options {
+            options.push(",");
+            options.push(custom_options);
+        }
+
+        options
+    }
+
+    /// build the `-o` options of `mount_macfuse`.
+    ///
+    /// `fs_name` is the `fsname` shown by `mount(8)`, and `volume_name` is the `volname` shown by
+    /// Finder. macFUSE checks `allow_root` and `allow_other` itself, `allow_other` may require
+    /// the `allow_other` permission of macFUSE granted by the administrator.
+    #[cfg(target_os = "macos")]
+    pub(crate) fn build_with_macfuse(&self) -> OsString {
+        let mut opts = vec![format!(
+            "fsname={}",
+            self.fs_name.as_deref().unwrap_or("fuse")
+        )];
+
+        if let Some(volume_name) = &self.volume_name {
+            opts.push(format!("volname={volume_name}"));
+        }
+
+        if self.allow_root {
+            opts.push("allow_root".to_string());
+        }
+
+        if self.allow_other {
+            opts.push("allow_other".to_string());
+        }
+
+        if self.read_only {
+            opts.push("rdonly".to_string());
+        }
+
+        if self.nosuid {
+            opts.push("nosuid".to_string());
+        }
+
+        if self.noexec {
+            opts.push("noexec".to_string());
+        }
+
+        if self.noatime {
+            opts.push("noatime".to_string());
+        }
+
+        if self.sync {
+            opts.push("sync".to_string());
+        }
+
         if self.default_permissions {
             opts.push("default_permissions".to_string());
         }
 
+        if let Some(daemon_timeout) = self.daemon_timeout {
+            opts.push(format!("daemon_timeout={daemon_timeout}"));
+        }
+
         let mut options = OsString::from(opts.join(","));
 
         if let Some(custom_options) = &self.custom_options {
@@ -346,3 +1233,53 @@ impl MountOptions {
         flags
     }
 }
+
+#[cfg(all(test, target_os = "freebsd"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nmount_options() {
+        let mut mount_options = MountOptions::default();
+        mount_options
+            .allow_root(true)
+            .read_only(true)
+            .max_read(64 * 1024)
+            .fs_name("test");
+
+        let options = mount_options.nmount_options();
+
+        for (name, value) in [
+            ("allow_root", None),
+            ("ro", None),
+            ("max_read=", Some("65536")),
+            ("subtype=", Some("test")),
+        ] {
+            let option = (OsString::from(name), value.map(OsString::from));
+
+            assert!(options.contains(&option), "{option:?} not in {options:?}");
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "macos"))]
+mod macos_tests {
+    use super::*;
+
+    #[test]
+    fn macfuse_options() {
+        let mut mount_options = MountOptions::default();
+        mount_options
+            .allow_root(true)
+            .read_only(true)
+            .daemon_timeout(Duration::from_secs(1000))
+            .volume_name("test");
+
+        let options = mount_options.build_with_macfuse();
+
+        assert_eq!(
+            options,
+            "fsname=fuse,volname=test,allow_root,rdonly,daemon_timeout=600"
+        );
+    }
+}
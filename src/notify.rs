@@ -1,13 +1,23 @@
 //! notify kernel.
 
+use std::collections::{HashMap, VecDeque};
 use std::ffi::OsString;
+use std::future::Future;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
 use std::os::unix::ffi::OsStrExt;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
 
+use async_notify::Notify as AsyncNotify;
 use bincode::Options;
 use bytes::{Buf, Bytes};
 use futures_channel::mpsc::UnboundedSender;
+use futures_channel::oneshot::{self, Receiver, Sender};
 use futures_util::future::Either;
 use futures_util::sink::SinkExt;
+use tracing::warn;
 
 use crate::helper::get_bincode_config;
 use crate::raw::abi::{
@@ -18,22 +28,276 @@ use crate::raw::abi::{
     FUSE_NOTIFY_POLL_WAKEUP_OUT_SIZE, FUSE_NOTIFY_RETRIEVE_OUT_SIZE, FUSE_NOTIFY_STORE_OUT_SIZE,
     FUSE_OUT_HEADER_SIZE,
 };
+use crate::raw::connection_info::ConnectionInfoReceiver;
+use crate::raw::session::get_notify_retrieve_unique;
 use crate::raw::FuseData;
 
+/// the min protocol minor version which supports `FUSE_NOTIFY_INVAL_INODE` and
+/// `FUSE_NOTIFY_INVAL_ENTRY`.
+const NOTIFY_INVAL_MINOR_VERSION: u32 = 12;
+
+/// the min protocol minor version which supports `FUSE_NOTIFY_STORE` and
+/// `FUSE_NOTIFY_RETRIEVE`.
+const NOTIFY_STORE_MINOR_VERSION: u32 = 15;
+
+/// the min protocol minor version which supports `FUSE_NOTIFY_DELETE`.
+const NOTIFY_DELETE_MINOR_VERSION: u32 = 18;
+
+/// the retrieve requests which are waiting for the `FUSE_NOTIFY_REPLY` from kernel.
+#[derive(Debug, Default)]
+pub(crate) struct PendingRetrieves {
+    next_notify_unique: AtomicU64,
+    senders: Mutex<HashMap<u64, Sender<Bytes>>>,
+}
+
+impl PendingRetrieves {
+    fn register(&self) -> (u64, Receiver<Bytes>) {
+        let notify_unique = self.next_notify_unique.fetch_add(1, Ordering::Relaxed) + 1;
+        let (sender, receiver) = oneshot::channel();
+
+        self.senders.lock().unwrap().insert(notify_unique, sender);
+
+        (notify_unique, receiver)
+    }
+
+    /// take the waiting retrieve sender by the `notify_unique`.
+    pub(crate) fn take(&self, notify_unique: u64) -> Option<Sender<Bytes>> {
+        self.senders.lock().unwrap().remove(&notify_unique)
+    }
+
+    /// stop all waiting retrieve requests, they will get an error.
+    pub(crate) fn clear(&self) {
+        self.senders.lock().unwrap().clear();
+    }
+}
+
+/// a handle of the retrieve notify, it resolves to the data kernel replied.
+///
+/// # Notes:
+///
+/// if the filesystem is unmounted, or kernel rejects the retrieve notify, for example the inode
+/// is not cached by kernel, the handle will resolve to an error. Dropping the handle won't cancel
+/// the retrieve notify, the data replied by kernel will be discarded.
+#[derive(Debug)]
+pub struct RetrieveHandle {
+    notify_unique: u64,
+    receiver: Receiver<Bytes>,
+}
+
+impl RetrieveHandle {
+    /// the `notify_unique` of this retrieve notify.
+    pub fn notify_unique(&self) -> u64 {
+        self.notify_unique
+    }
+}
+
+impl Future for RetrieveHandle {
+    type Output = IoResult<Bytes>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver).poll(cx).map(|result| {
+            result.map_err(|_| {
+                IoError::new(
+                    ErrorKind::BrokenPipe,
+                    "fuse filesystem is unmounted or kernel rejects the retrieve",
+                )
+            })
+        })
+    }
+}
+
+/// what to do when the notify queue set by
+/// [`MountOptions::notify_queue_size`][crate::MountOptions::notify_queue_size] is full.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum NotifyQueuePolicy {
+    /// wait until a queued notify is written to kernel, it is the default.
+    #[default]
+    Block,
+    /// drop the oldest queued notify to make room, the dropped notifications are counted by
+    /// [`Notify::dropped`]. A dropped retrieve notify makes its [`RetrieveHandle`] resolve to an
+    /// error.
+    DropOldest,
+    /// fail the notify with an [`ErrorKind::WouldBlock`] error.
+    Error,
+}
+
+/// the bounded queue of the notifications, it is drained by the task which writes the replies.
+#[derive(Debug)]
+pub(crate) struct NotifyQueue {
+    capacity: usize,
+    policy: NotifyQueuePolicy,
+    queue: Mutex<VecDeque<FuseData>>,
+    /// notified when a notify is pushed.
+    pushed: AsyncNotify,
+    /// notified when a notify is popped or the queue is closed.
+    popped: AsyncNotify,
+    closed: AtomicBool,
+    dropped: AtomicU64,
+}
+
+impl NotifyQueue {
+    pub(crate) fn new(capacity: usize, policy: NotifyQueuePolicy) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            policy,
+            queue: Default::default(),
+            pushed: AsyncNotify::new(),
+            popped: AsyncNotify::new(),
+            closed: AtomicBool::new(false),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// push a notify, handle the full queue by the policy.
+    async fn push(&self, data: FuseData, pending_retrieves: &PendingRetrieves) -> IoResult<()> {
+        loop {
+            if self.closed.load(Ordering::Acquire) {
+                // wake the next blocked notify, it will find the queue closed too
+                self.popped.notify();
+
+                return Err(unmounted_error());
+            }
+
+            {
+                let mut queue = self.lock();
+
+                if queue.len() < self.capacity {
+                    queue.push_back(data);
+                    self.pushed.notify();
+
+                    return Ok(());
+                }
+
+                match self.policy {
+                    NotifyQueuePolicy::Block => {}
+
+                    NotifyQueuePolicy::DropOldest => {
+                        let dropped = queue.pop_front().expect("notify queue is full");
+                        queue.push_back(data);
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+
+                        warn!("notify queue is full, drop the oldest notify");
+
+                        // kernel won't reply the dropped retrieve notify
+                        let header = match &dropped {
+                            Either::Left(data) => data,
+                            Either::Right((data, _)) => data,
+                        };
+                        if let Some(notify_unique) = get_notify_retrieve_unique(header) {
+                            pending_retrieves.take(notify_unique);
+                        }
+
+                        return Ok(());
+                    }
+
+                    NotifyQueuePolicy::Error => {
+                        return Err(IoError::new(
+                            ErrorKind::WouldBlock,
+                            "fuse notify queue is full",
+                        ));
+                    }
+                }
+            }
+
+            self.popped.notified().await;
+        }
+    }
+
+    /// wait and pop the oldest notify, it is cancel safe.
+    pub(crate) async fn pop(&self) -> FuseData {
+        loop {
+            let data = self.lock().pop_front();
+            if let Some(data) = data {
+                self.popped.notify();
+
+                return data;
+            }
+
+            self.pushed.notified().await;
+        }
+    }
+
+    /// close the queue when the filesystem is unmounted, the queued notifications are dropped
+    /// and the blocked notifications fail.
+    pub(crate) fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.lock().clear();
+        self.popped.notify();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<FuseData>> {
+        self.queue.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
 #[derive(Debug, Clone)]
 /// notify kernel there are something need to handle.
 pub struct Notify {
     sender: UnboundedSender<FuseData>,
+    queue: Option<Arc<NotifyQueue>>,
+    connection_info: ConnectionInfoReceiver,
+    pending_retrieves: Arc<PendingRetrieves>,
 }
 
 impl Notify {
-    pub(crate) fn new(sender: UnboundedSender<FuseData>) -> Self {
-        Self { sender }
+    pub(crate) fn new(
+        sender: UnboundedSender<FuseData>,
+        queue: Option<Arc<NotifyQueue>>,
+        connection_info: ConnectionInfoReceiver,
+        pending_retrieves: Arc<PendingRetrieves>,
+    ) -> Self {
+        Self {
+            sender,
+            queue,
+            connection_info,
+            pending_retrieves,
+        }
+    }
+
+    /// the number of notifications dropped by [`NotifyQueuePolicy::DropOldest`] since mount, it
+    /// is shared by all [`Notify`] of the session, and always 0 if the notify queue is not set.
+    pub fn dropped(&self) -> u64 {
+        self.queue
+            .as_ref()
+            .map_or(0, |queue| queue.dropped.load(Ordering::Relaxed))
     }
 
-    /// notify kernel there are something need to handle. If notify failed, the `kind` will be
-    /// return in `Err`.
-    async fn notify(&mut self, kind: NotifyKind) -> Result<(), NotifyKind> {
+    /// check the negotiated protocol minor version supports the notify or not, it will wait
+    /// until the `FUSE_INIT` is done.
+    async fn is_supported(&self, minor: u32, kind: &str) -> IoResult<bool> {
+        let connection_info = self.connection_info.wait().await?;
+        let (_, negotiated_minor) = connection_info.protocol_version();
+
+        if negotiated_minor < minor {
+            warn!(
+                negotiated_minor,
+                minor, "{} notify is not supported by kernel, ignore it", kind
+            );
+
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// like [`is_supported`](Notify::is_supported), but return an error if not supported.
+    async fn check_supported(&self, minor: u32, kind: &str) -> IoResult<()> {
+        let connection_info = self.connection_info.wait().await?;
+        let (_, negotiated_minor) = connection_info.protocol_version();
+
+        if negotiated_minor < minor {
+            return Err(IoError::new(
+                ErrorKind::Unsupported,
+                format!("{kind} notify is not supported by kernel"),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// notify kernel there are something need to handle, return an error if the filesystem is
+    /// unmounted or the notify queue is full with [`NotifyQueuePolicy::Error`].
+    async fn notify(&mut self, kind: NotifyKind) -> IoResult<()> {
         let data = match &kind {
             NotifyKind::Wakeup { kh } => {
                 let out_header = fuse_out_header {
@@ -84,8 +348,10 @@ impl Notify {
             }
 
             NotifyKind::InvalidEntry { parent, name } => {
+                // kernel requires the name ends with null
                 let out_header = fuse_out_header {
-                    len: (FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_INVAL_ENTRY_OUT_SIZE) as u32,
+                    len: (FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_INVAL_ENTRY_OUT_SIZE + name.len() + 1)
+                        as u32,
                     error: fuse_notify_code::FUSE_NOTIFY_INVAL_ENTRY as i32,
                     unique: 0,
                 };
@@ -106,9 +372,11 @@ impl Notify {
                     .serialize_into(&mut data, &invalid_entry_out)
                     .expect("vec size is not enough");
 
-                // TODO should I add null at the end?
+                let mut name_data = Vec::with_capacity(name.len() + 1);
+                name_data.extend_from_slice(name.as_bytes());
+                name_data.push(0);
 
-                Either::Right((data, Bytes::copy_from_slice(name.as_bytes())))
+                Either::Right((data, Bytes::from(name_data).into()))
             }
 
             NotifyKind::Delete {
@@ -116,8 +384,10 @@ impl Notify {
                 child,
                 name,
             } => {
+                // kernel requires the name ends with null
                 let out_header = fuse_out_header {
-                    len: (FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_DELETE_OUT_SIZE) as u32,
+                    len: (FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_DELETE_OUT_SIZE + name.len() + 1)
+                        as u32,
                     error: fuse_notify_code::FUSE_NOTIFY_DELETE as i32,
                     unique: 0,
                 };
@@ -139,9 +409,11 @@ impl Notify {
                     .serialize_into(&mut data, &delete_out)
                     .expect("vec size is not enough");
 
-                // TODO should I add null at the end?
+                let mut name_data = Vec::with_capacity(name.len() + 1);
+                name_data.extend_from_slice(name.as_bytes());
+                name_data.push(0);
 
-                Either::Right((data, Bytes::copy_from_slice(name.as_bytes())))
+                Either::Right((data, Bytes::from(name_data).into()))
             }
 
             NotifyKind::Store {
@@ -150,7 +422,7 @@ impl Notify {
                 data,
             } => {
                 let out_header = fuse_out_header {
-                    len: (FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_STORE_OUT_SIZE) as u32,
+                    len: (FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_STORE_OUT_SIZE + data.len()) as u32,
                     error: fuse_notify_code::FUSE_NOTIFY_STORE as i32,
                     unique: 0,
                 };
@@ -172,7 +444,7 @@ impl Notify {
                     .serialize_into(&mut data_buf, &store_out)
                     .expect("vec size is not enough");
 
-                Either::Right((data_buf, data.clone()))
+                Either::Right((data_buf, data.clone().into()))
             }
 
             NotifyKind::Retrieve {
@@ -209,68 +481,172 @@ impl Notify {
             }
         };
 
-        self.sender.send(data).await.or(Err(kind))
+        match &self.queue {
+            None => self.sender.send(data).await.map_err(|_| unmounted_error()),
+            Some(queue) => queue.push(data, &self.pending_retrieves).await,
+        }
     }
 
     /// try to notify kernel the IO is ready, kernel can wakeup the waiting program.
+    ///
+    /// the `kh` is the kernel poll handle passed to [`poll`] when kernel requests to be
+    /// notified.
+    ///
+    /// [`poll`]: crate::raw::Filesystem::poll
     pub async fn wakeup(mut self, kh: u64) {
         let _ = self.notify(NotifyKind::Wakeup { kh }).await;
     }
 
-    /// try to notify the cache invalidation about an inode.
-    pub async fn invalid_inode(mut self, inode: u64, offset: i64, len: i64) {
-        let _ = self
-            .notify(NotifyKind::InvalidInode { inode, offset, len })
-            .await;
+    /// notify the cache invalidation about an inode.
+    ///
+    /// kernel always invalidates the cached attributes of the inode. When `offset` is negative,
+    /// only the attributes are invalidated. Otherwise the page cache from `offset` is invalidated
+    /// too, `len` zero or negative means to the end of file, note that `len` 0 is not an empty
+    /// range. See [`invalidate_attr`][Notify::invalidate_attr] and
+    /// [`invalidate_data`][Notify::invalidate_data].
+    ///
+    /// return an error if the filesystem is unmounted or the notify queue is full. If kernel
+    /// doesn't support this notify, it will be ignored with a warning log.
+    pub async fn invalid_inode(mut self, inode: u64, offset: i64, len: i64) -> IoResult<()> {
+        if !self
+            .is_supported(NOTIFY_INVAL_MINOR_VERSION, "invalid inode")
+            .await?
+        {
+            return Ok(());
+        }
+
+        self.notify(NotifyKind::InvalidInode { inode, offset, len })
+            .await
     }
 
-    /// try to notify the invalidation about a directory entry.
-    pub async fn invalid_entry(mut self, parent: u64, name: OsString) {
-        let _ = self.notify(NotifyKind::InvalidEntry { parent, name }).await;
+    /// invalidate the cached attributes of an inode, such as the size is changed on the backing
+    /// store, the page cache is kept. Kernel gets the attributes again by getattr next time.
+    ///
+    /// return an error if the filesystem is unmounted or the notify queue is full. If kernel
+    /// doesn't support this notify, it will be ignored with a warning log.
+    pub async fn invalidate_attr(self, inode: u64) -> IoResult<()> {
+        self.invalid_inode(inode, -1, 0).await
     }
 
-    /// try to notify a directory entry has been deleted.
-    pub async fn delete(mut self, parent: u64, child: u64, name: OsString) {
-        let _ = self
-            .notify(NotifyKind::Delete {
-                parent,
-                child,
-                name,
-            })
-            .await;
+    /// invalidate the page cache of an inode in `offset..offset + len`, a `len` of 0 means to the
+    /// end of file, use `(0, 0)` to drop the whole page cache. The cached attributes are always
+    /// invalidated with the data.
+    ///
+    /// the page cache is kept across opens when the open replies
+    /// [`keep_cache`][crate::raw::reply::ReplyOpen::keep_cache], invalidate it when the file data
+    /// is changed on the backing store, or the callers keep reading the stale data.
+    ///
+    /// return an error if the filesystem is unmounted or the notify queue is full. If kernel
+    /// doesn't support this notify, it will be ignored with a warning log.
+    pub async fn invalidate_data(self, inode: u64, offset: u64, len: u64) -> IoResult<()> {
+        // the range beyond i64::MAX is past the max file size, just invalidate to the end
+        let offset = offset.min(i64::MAX as u64) as i64;
+        let len = len.try_into().unwrap_or(0);
+
+        self.invalid_inode(inode, offset, len).await
     }
 
-    /// try to push the data in an inode for updating the kernel cache.
-    pub async fn store(mut self, inode: u64, offset: u64, mut data: impl Buf) {
-        let _ = self
-            .notify(NotifyKind::Store {
-                inode,
-                offset,
-                data: data.copy_to_bytes(data.remaining()),
-            })
-            .await;
+    /// notify the invalidation about a directory entry.
+    ///
+    /// return an error if the filesystem is unmounted or the notify queue is full. If kernel
+    /// doesn't support this notify, it will be ignored with a warning log.
+    pub async fn invalid_entry(mut self, parent: u64, name: OsString) -> IoResult<()> {
+        if !self
+            .is_supported(NOTIFY_INVAL_MINOR_VERSION, "invalid entry")
+            .await?
+        {
+            return Ok(());
+        }
+
+        self.notify(NotifyKind::InvalidEntry { parent, name }).await
+    }
+
+    /// notify a directory entry has been deleted, unlike [`invalid_entry`], kernel will also
+    /// drop the dentry even if it is in use.
+    ///
+    /// return an error if the filesystem is unmounted, the notify queue is full or kernel doesn't
+    /// support this notify.
+    ///
+    /// [`invalid_entry`]: Notify::invalid_entry
+    pub async fn delete(mut self, parent: u64, child: u64, name: OsString) -> IoResult<()> {
+        self.check_supported(NOTIFY_DELETE_MINOR_VERSION, "delete")
+            .await?;
+
+        self.notify(NotifyKind::Delete {
+            parent,
+            child,
+            name,
+        })
+        .await
+    }
+
+    /// push the data in an inode for updating the kernel cache.
+    ///
+    /// return an error if the filesystem is unmounted or the notify queue is full. If kernel
+    /// doesn't support this notify, it will be ignored with a warning log.
+    pub async fn store(mut self, inode: u64, offset: u64, mut data: impl Buf) -> IoResult<()> {
+        if !self
+            .is_supported(NOTIFY_STORE_MINOR_VERSION, "store")
+            .await?
+        {
+            return Ok(());
+        }
+
+        self.notify(NotifyKind::Store {
+            inode,
+            offset,
+            data: data.copy_to_bytes(data.remaining()),
+        })
+        .await
     }
 
-    /// try to retrieve data in an inode from the kernel cache.
-    pub async fn retrieve(mut self, notify_unique: u64, inode: u64, offset: u64, size: u32) {
-        let _ = self
+    /// retrieve data in an inode from the kernel cache, the returned [`RetrieveHandle`] will
+    /// resolve to the data when kernel replies.
+    ///
+    /// return an error if the filesystem is unmounted, the notify queue is full or kernel doesn't
+    /// support this notify.
+    pub async fn retrieve(
+        mut self,
+        inode: u64,
+        offset: u64,
+        size: u32,
+    ) -> IoResult<RetrieveHandle> {
+        self.check_supported(NOTIFY_STORE_MINOR_VERSION, "retrieve")
+            .await?;
+
+        let (notify_unique, receiver) = self.pending_retrieves.register();
+
+        if let Err(err) = self
             .notify(NotifyKind::Retrieve {
                 notify_unique,
                 inode,
                 offset,
                 size,
             })
-            .await;
+            .await
+        {
+            self.pending_retrieves.take(notify_unique);
+
+            return Err(err);
+        }
+
+        Ok(RetrieveHandle {
+            notify_unique,
+            receiver,
+        })
     }
 }
 
+fn unmounted_error() -> IoError {
+    IoError::new(ErrorKind::NotConnected, "fuse filesystem is unmounted")
+}
+
 #[derive(Debug)]
 /// the kind of notify.
 enum NotifyKind {
     /// notify the IO is ready.
     Wakeup { kh: u64 },
 
-    // TODO need check is right or not
     /// notify the cache invalidation about an inode.
     InvalidInode { inode: u64, offset: i64, len: i64 },
 
@@ -4,20 +4,29 @@ use crate::Inode;
 
 #[derive(Debug)]
 pub struct InodeGenerator {
-    slab: Slab<()>,
+    // the value is the inode generation
+    slab: Slab<u64>,
+    next_generation: u64,
 }
 
 impl InodeGenerator {
     pub fn new() -> Self {
         let mut slab = Slab::new();
         // drop 0 key
-        slab.insert(());
+        slab.insert(0);
 
-        Self { slab }
+        Self {
+            slab,
+            next_generation: 0,
+        }
     }
 
+    /// allocate an inode, a released inode may be reused with a new generation.
     pub fn allocate_inode(&mut self) -> Inode {
-        self.slab.insert(()) as _
+        let generation = self.next_generation;
+        self.next_generation += 1;
+
+        self.slab.insert(generation) as _
     }
 
     pub fn release_inode(&mut self, inode: Inode) {
@@ -25,4 +34,8 @@ impl InodeGenerator {
             self.slab.remove(inode as _);
         }
     }
+
+    pub fn generation(&self, inode: Inode) -> u64 {
+        self.slab.get(inode as _).copied().unwrap_or(0)
+    }
 }
@@ -2,12 +2,13 @@ use std::collections::{HashMap, HashSet};
 use std::ffi::{OsStr, OsString};
 use std::fmt::{self, Debug, Formatter};
 use std::path::PathBuf;
+use std::time::Duration;
 use std::vec::IntoIter;
 
 #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
 use async_lock::RwLock;
 use bytes::Bytes;
-use futures_util::stream::{self, Iter, Stream, StreamExt};
+use futures_util::stream::{self, BoxStream, Iter, Stream, StreamExt, TryStreamExt};
 use slab::Slab;
 #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
 use tokio::sync::RwLock;
@@ -16,8 +17,15 @@ use super::inode_generator::InodeGenerator;
 use super::path_filesystem::PathFilesystem;
 use crate::helper::Apply;
 use crate::notify::Notify;
+use crate::raw::flags::{
+    FallocateMode, OpenFlags, PollEvents, RenameFlags, SetXattrFlags, WriteFlags,
+};
 use crate::raw::reply::*;
-use crate::raw::{Filesystem, Request};
+#[cfg(feature = "file-lock")]
+use crate::raw::{FileLock, LockType};
+use crate::raw::{Filesystem, Request, SecurityContext};
+#[cfg(target_os = "macos")]
+use crate::Timestamp;
 use crate::{Errno, SetAttr};
 use crate::{Inode, Result};
 
@@ -39,22 +47,39 @@ impl Name {
 struct InodeNameManager {
     inode_to_names: HashMap<Inode, HashSet<Name>>,
     name_to_inode: HashMap<Name, Inode>,
+    // the lookup count of the inodes which kernel references
+    nlookup: HashMap<Inode, u64>,
     inode_generator: InodeGenerator,
+    // deliver the paths relative to the mount root instead of rooted at `/`
+    relative_path: bool,
 }
 
 impl InodeNameManager {
-    fn get_absolute_path(&self, inode: Inode) -> Option<PathBuf> {
-        let names = self.inode_to_names.get(&inode)?;
-        let name = names.iter().next().unwrap();
+    /// get the path of the inode, it is joined from the names which kernel passes, so symlinks
+    /// are never resolved and `.` or `..` never appears except the relative root `.`.
+    fn get_path(&self, mut inode: Inode) -> Option<PathBuf> {
+        // walk up instead of recursing, the directory tree may be deeper than the stack allows
+        let mut names = Vec::new();
+        while inode != ROOT_INODE {
+            let name = self.inode_to_names.get(&inode)?.iter().next().unwrap();
+
+            names.push(&name.name);
+            inode = name.parent;
+        }
 
-        if name.parent == ROOT_INODE {
-            Some(PathBuf::from("/").apply(|path| path.push(&name.name)))
-        } else {
-            Some(
-                self.get_absolute_path(name.parent)?
-                    .apply(|path| path.push(&name.name)),
-            )
+        if names.is_empty() {
+            let root = if self.relative_path { "." } else { "/" };
+
+            return Some(PathBuf::from(root));
         }
+
+        let path = if self.relative_path {
+            PathBuf::new()
+        } else {
+            PathBuf::from("/")
+        };
+
+        Some(path.apply(|path| names.into_iter().rev().for_each(|name| path.push(name))))
     }
 
     fn remove_name(&mut self, name: &Name) {
@@ -64,7 +89,11 @@ impl InodeNameManager {
 
                 if names.is_empty() {
                     self.inode_to_names.remove(&inode);
-                    self.inode_generator.release_inode(inode);
+
+                    // kernel still references the inode, release it when it is forgotten
+                    if !self.nlookup.contains_key(&inode) {
+                        self.inode_generator.release_inode(inode);
+                    }
                 }
             }
         }
@@ -97,18 +126,80 @@ impl InodeNameManager {
         inode
     }
 
+    /// swap the inodes of two names after they are exchanged, the children of an exchanged
+    /// directory are linked to its inode, so their paths follow it.
+    fn exchange_names(&mut self, name: Name, other: Name) {
+        let inode = self.name_to_inode.remove(&name);
+        let other_inode = self.name_to_inode.remove(&other);
+
+        // remove both names before inserting, the two names may be hard links of one inode
+        for (inode, name) in [(inode, &name), (other_inode, &other)] {
+            if let Some(names) = inode.and_then(|inode| self.inode_to_names.get_mut(&inode)) {
+                names.remove(name);
+            }
+        }
+
+        for (inode, name) in [(inode, other), (other_inode, name)] {
+            if let Some(inode) = inode {
+                self.inode_to_names
+                    .entry(inode)
+                    .or_default()
+                    .insert(name.clone());
+                self.name_to_inode.insert(name, inode);
+            }
+        }
+    }
+
     fn get_name_inode(&self, name: &Name) -> Option<Inode> {
         self.name_to_inode.get(name).copied()
     }
+
+    /// get or allocate the inode of the name, and count one lookup because the inode is replied
+    /// to kernel.
+    fn lookup_name(&mut self, name: Name) -> Inode {
+        let inode = self
+            .get_name_inode(&name)
+            .unwrap_or_else(|| self.insert_name(name));
+
+        *self.nlookup.entry(inode).or_default() += 1;
+
+        inode
+    }
+
+    /// decrease the lookup count of the inode, the inode is removed when kernel doesn't
+    /// reference it anymore. The root inode is never removed.
+    fn forget(&mut self, inode: Inode, nlookup: u64) {
+        if inode == ROOT_INODE {
+            return;
+        }
+
+        let Some(count) = self.nlookup.get_mut(&inode) else {
+            return;
+        };
+
+        *count = count.saturating_sub(nlookup);
+        if *count > 0 {
+            return;
+        }
+
+        self.nlookup.remove(&inode);
+        self.remove_inode(inode);
+    }
 }
 
 pub struct InodePathBridge<FS> {
     path_filesystem: FS,
     inode_name_manager: RwLock<InodeNameManager>,
+    // reply the missing names as the negative entries with this TTL
+    negative_timeout: Option<Duration>,
 }
 
 impl<FS> InodePathBridge<FS> {
-    pub fn new(path_filesystem: FS) -> Self {
+    pub fn new(
+        path_filesystem: FS,
+        relative_path: bool,
+        negative_timeout: Option<Duration>,
+    ) -> Self {
         let mut slab = Slab::new();
         // drop 0 key
         slab.insert(());
@@ -116,7 +207,9 @@ impl<FS> InodePathBridge<FS> {
         let mut inode_name_manager = InodeNameManager {
             inode_to_names: Default::default(),
             name_to_inode: Default::default(),
+            nlookup: Default::default(),
             inode_generator: InodeGenerator::new(),
+            relative_path,
         };
 
         let root_inode = inode_name_manager.inode_generator.allocate_inode();
@@ -132,6 +225,7 @@ impl<FS> InodePathBridge<FS> {
         Self {
             path_filesystem,
             inode_name_manager: RwLock::new(inode_name_manager),
+            negative_timeout,
         }
     }
 }
@@ -162,7 +256,7 @@ where
         let mut inode_name_manager = self.inode_name_manager.write().await;
 
         let parent_path = inode_name_manager
-            .get_absolute_path(parent)
+            .get_path(parent)
             .ok_or_else(Errno::new_not_exist)?;
 
         match self
@@ -171,24 +265,27 @@ where
             .await
         {
             Err(err) => {
-                if err.is_not_exist() {
-                    inode_name_manager.remove_name(&Name::new(parent, name.to_owned()));
+                if !err.is_not_exist() {
+                    return Err(err);
                 }
 
-                Err(err)
+                inode_name_manager.remove_name(&Name::new(parent, name.to_owned()));
+
+                match self.negative_timeout {
+                    None => Err(err),
+                    Some(ttl) => Ok(ReplyEntry::negative(ttl)),
+                }
             }
 
             Ok(entry) => {
                 let name = Name::new(parent, name.to_owned());
 
-                let inode = inode_name_manager
-                    .get_name_inode(&name)
-                    .unwrap_or_else(|| inode_name_manager.insert_name(name));
+                let inode = inode_name_manager.lookup_name(name);
 
                 Ok(ReplyEntry {
                     ttl: entry.ttl,
                     attr: (inode, entry.attr).into(),
-                    generation: 0,
+                    generation: inode_name_manager.inode_generator.generation(inode),
                 })
             }
         }
@@ -199,19 +296,13 @@ where
 
         let mut inode_name_manager = self.inode_name_manager.write().await;
 
-        if let Some(path) = inode_name_manager.get_absolute_path(inode) {
+        if let Some(path) = inode_name_manager.get_path(inode) {
             self.path_filesystem
                 .forget(req, path.as_ref(), nlookup)
                 .await;
-
-            if let Some(names) = inode_name_manager.inode_to_names.remove(&inode) {
-                for name in names {
-                    inode_name_manager.name_to_inode.remove(&name);
-                }
-
-                inode_name_manager.inode_generator.release_inode(inode);
-            }
         }
+
+        inode_name_manager.forget(inode, nlookup);
     }
 
     async fn getattr(
@@ -222,7 +313,7 @@ where
         flags: u32,
     ) -> Result<ReplyAttr> {
         let inode_name_manager = self.inode_name_manager.read().await;
-        let path = inode_name_manager.get_absolute_path(inode);
+        let path = inode_name_manager.get_path(inode);
 
         let attr = self
             .path_filesystem
@@ -243,7 +334,7 @@ where
         set_attr: SetAttr,
     ) -> Result<ReplyAttr> {
         let inode_name_manager = self.inode_name_manager.read().await;
-        let path = inode_name_manager.get_absolute_path(inode);
+        let path = inode_name_manager.get_path(inode);
 
         let attr = self
             .path_filesystem
@@ -259,7 +350,7 @@ where
     async fn readlink(&self, req: Request, inode: u64) -> Result<ReplyData> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager
-            .get_absolute_path(inode)
+            .get_path(inode)
             .ok_or_else(Errno::new_not_exist)?;
 
         self.path_filesystem.readlink(req, path.as_ref()).await
@@ -271,15 +362,16 @@ where
         parent: u64,
         name: &OsStr,
         link: &OsStr,
+        security_context: Option<&SecurityContext>,
     ) -> Result<ReplyEntry> {
         let mut inode_name_manager = self.inode_name_manager.write().await;
         let parent_path = inode_name_manager
-            .get_absolute_path(parent)
+            .get_path(parent)
             .ok_or_else(Errno::new_not_exist)?;
 
         match self
             .path_filesystem
-            .symlink(req, parent_path.as_ref(), name, link)
+            .symlink(req, parent_path.as_ref(), name, link, security_context)
             .await
         {
             Err(err) => {
@@ -294,19 +386,18 @@ where
             Ok(entry) => {
                 let name = Name::new(parent, name.to_owned());
 
-                let inode = inode_name_manager
-                    .get_name_inode(&name)
-                    .unwrap_or_else(|| inode_name_manager.insert_name(name));
+                let inode = inode_name_manager.lookup_name(name);
 
                 Ok(ReplyEntry {
                     ttl: entry.ttl,
                     attr: (inode, entry.attr).into(),
-                    generation: 0,
+                    generation: inode_name_manager.inode_generator.generation(inode),
                 })
             }
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn mknod(
         &self,
         req: Request,
@@ -314,15 +405,25 @@ where
         name: &OsStr,
         mode: u32,
         rdev: u32,
+        umask: u32,
+        security_context: Option<&SecurityContext>,
     ) -> Result<ReplyEntry> {
         let mut inode_name_manager = self.inode_name_manager.write().await;
         let parent_path = inode_name_manager
-            .get_absolute_path(parent)
+            .get_path(parent)
             .ok_or_else(Errno::new_not_exist)?;
 
         match self
             .path_filesystem
-            .mknod(req, parent_path.as_ref(), name, mode, rdev)
+            .mknod(
+                req,
+                parent_path.as_ref(),
+                name,
+                mode,
+                rdev,
+                umask,
+                security_context,
+            )
             .await
         {
             Err(err) => {
@@ -337,14 +438,12 @@ where
             Ok(entry) => {
                 let name = Name::new(parent, name.to_owned());
 
-                let inode = inode_name_manager
-                    .get_name_inode(&name)
-                    .unwrap_or_else(|| inode_name_manager.insert_name(name));
+                let inode = inode_name_manager.lookup_name(name);
 
                 Ok(ReplyEntry {
                     ttl: entry.ttl,
                     attr: (inode, entry.attr).into(),
-                    generation: 0,
+                    generation: inode_name_manager.inode_generator.generation(inode),
                 })
             }
         }
@@ -357,15 +456,16 @@ where
         name: &OsStr,
         mode: u32,
         umask: u32,
+        security_context: Option<&SecurityContext>,
     ) -> Result<ReplyEntry> {
         let mut inode_name_manager = self.inode_name_manager.write().await;
         let parent_path = inode_name_manager
-            .get_absolute_path(parent)
+            .get_path(parent)
             .ok_or_else(Errno::new_not_exist)?;
 
         match self
             .path_filesystem
-            .mkdir(req, parent_path.as_ref(), name, mode, umask)
+            .mkdir(req, parent_path.as_ref(), name, mode, umask, security_context)
             .await
         {
             Err(err) => {
@@ -380,14 +480,12 @@ where
             Ok(entry) => {
                 let name = Name::new(parent, name.to_owned());
 
-                let inode = inode_name_manager
-                    .get_name_inode(&name)
-                    .unwrap_or_else(|| inode_name_manager.insert_name(name));
+                let inode = inode_name_manager.lookup_name(name);
 
                 Ok(ReplyEntry {
                     ttl: entry.ttl,
                     attr: (inode, entry.attr).into(),
-                    generation: 0,
+                    generation: inode_name_manager.inode_generator.generation(inode),
                 })
             }
         }
@@ -396,7 +494,7 @@ where
     async fn unlink(&self, req: Request, parent: u64, name: &OsStr) -> Result<()> {
         let mut inode_name_manager = self.inode_name_manager.write().await;
         let parent_path = inode_name_manager
-            .get_absolute_path(parent)
+            .get_path(parent)
             .ok_or_else(Errno::new_not_exist)?;
 
         if let Err(err) = self
@@ -426,7 +524,7 @@ where
     async fn rmdir(&self, req: Request, parent: u64, name: &OsStr) -> Result<()> {
         let mut inode_name_manager = self.inode_name_manager.write().await;
         let parent_path = inode_name_manager
-            .get_absolute_path(parent)
+            .get_path(parent)
             .ok_or_else(Errno::new_not_exist)?;
 
         if let Err(err) = self
@@ -464,10 +562,10 @@ where
         let mut inode_name_manager = self.inode_name_manager.write().await;
 
         let origin_parent_path = inode_name_manager
-            .get_absolute_path(parent)
+            .get_path(parent)
             .ok_or_else(Errno::new_not_exist)?;
         let new_parent_path = inode_name_manager
-            .get_absolute_path(new_parent)
+            .get_path(new_parent)
             .ok_or_else(Errno::new_not_exist)?;
 
         // here is very complex so don't modify the inode_name_manager when error
@@ -501,10 +599,10 @@ where
     ) -> Result<ReplyEntry> {
         let mut inode_name_manager = self.inode_name_manager.write().await;
         let parent_path = inode_name_manager
-            .get_absolute_path(inode)
+            .get_path(inode)
             .ok_or_else(Errno::new_not_exist)?;
         let new_parent_path = inode_name_manager
-            .get_absolute_path(new_parent)
+            .get_path(new_parent)
             .ok_or_else(Errno::new_not_exist)?;
 
         // here is very complex so don't modify the inode_name_manager when error
@@ -520,21 +618,19 @@ where
 
         let name = Name::new(new_parent, new_name.to_owned());
 
-        let inode = inode_name_manager
-            .get_name_inode(&name)
-            .unwrap_or_else(|| inode_name_manager.insert_name(name));
+        let inode = inode_name_manager.lookup_name(name);
 
         Ok(ReplyEntry {
             ttl: entry.ttl,
             attr: (inode, entry.attr).into(),
-            generation: 0,
+            generation: inode_name_manager.inode_generator.generation(inode),
         })
     }
 
-    async fn open(&self, req: Request, inode: u64, flags: u32) -> Result<ReplyOpen> {
+    async fn open(&self, req: Request, inode: u64, flags: OpenFlags) -> Result<ReplyOpen> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager
-            .get_absolute_path(inode)
+            .get_path(inode)
             .ok_or_else(Errno::new_not_exist)?;
 
         self.path_filesystem.open(req, path.as_ref(), flags).await
@@ -548,11 +644,7 @@ where
         offset: u64,
         size: u32,
     ) -> Result<ReplyData> {
-        let path = self
-            .inode_name_manager
-            .read()
-            .await
-            .get_absolute_path(inode);
+        let path = self.inode_name_manager.read().await.get_path(inode);
 
         self.path_filesystem
             .read(
@@ -565,6 +657,27 @@ where
             .await
     }
 
+    async fn read_buf(
+        &self,
+        req: Request,
+        inode: u64,
+        fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<ReplyReadBuf> {
+        let path = self.inode_name_manager.read().await.get_path(inode);
+
+        self.path_filesystem
+            .read_buf(
+                req,
+                path.as_ref().map(|path| path.as_ref()),
+                fh,
+                offset,
+                size,
+            )
+            .await
+    }
+
     async fn write(
         &self,
         req: Request,
@@ -572,14 +685,10 @@ where
         fh: u64,
         offset: u64,
         data: &[u8],
-        write_flags: u32,
+        write_flags: WriteFlags,
         flags: u32,
     ) -> Result<ReplyWrite> {
-        let path = self
-            .inode_name_manager
-            .read()
-            .await
-            .get_absolute_path(inode);
+        let path = self.inode_name_manager.read().await.get_path(inode);
 
         self.path_filesystem
             .write(
@@ -594,10 +703,35 @@ where
             .await
     }
 
+    async fn write_buf(
+        &self,
+        req: Request,
+        inode: u64,
+        fh: u64,
+        offset: u64,
+        data: Bytes,
+        write_flags: WriteFlags,
+        flags: u32,
+    ) -> Result<ReplyWrite> {
+        let path = self.inode_name_manager.read().await.get_path(inode);
+
+        self.path_filesystem
+            .write_buf(
+                req,
+                path.as_ref().map(|path| path.as_ref()),
+                fh,
+                offset,
+                data,
+                write_flags,
+                flags,
+            )
+            .await
+    }
+
     async fn statfs(&self, req: Request, inode: u64) -> Result<ReplyStatFs> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager
-            .get_absolute_path(inode)
+            .get_path(inode)
             .ok_or_else(Errno::new_not_exist)?;
 
         self.path_filesystem.statfs(req, path.as_ref()).await
@@ -612,11 +746,7 @@ where
         lock_owner: u64,
         flush: bool,
     ) -> Result<()> {
-        let path = self
-            .inode_name_manager
-            .read()
-            .await
-            .get_absolute_path(inode);
+        let path = self.inode_name_manager.read().await.get_path(inode);
 
         self.path_filesystem
             .release(
@@ -631,11 +761,7 @@ where
     }
 
     async fn fsync(&self, req: Request, inode: u64, fh: u64, datasync: bool) -> Result<()> {
-        let path = self
-            .inode_name_manager
-            .read()
-            .await
-            .get_absolute_path(inode);
+        let path = self.inode_name_manager.read().await.get_path(inode);
 
         self.path_filesystem
             .fsync(req, path.as_ref().map(|path| path.as_ref()), fh, datasync)
@@ -648,12 +774,12 @@ where
         inode: u64,
         name: &OsStr,
         value: &[u8],
-        flags: u32,
+        flags: SetXattrFlags,
         position: u32,
     ) -> Result<()> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager
-            .get_absolute_path(inode)
+            .get_path(inode)
             .ok_or_else(Errno::new_not_exist)?;
 
         self.path_filesystem
@@ -670,7 +796,7 @@ where
     ) -> Result<ReplyXAttr> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager
-            .get_absolute_path(inode)
+            .get_path(inode)
             .ok_or_else(Errno::new_not_exist)?;
 
         self.path_filesystem
@@ -681,7 +807,7 @@ where
     async fn listxattr(&self, req: Request, inode: u64, size: u32) -> Result<ReplyXAttr> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager
-            .get_absolute_path(inode)
+            .get_path(inode)
             .ok_or_else(Errno::new_not_exist)?;
 
         self.path_filesystem
@@ -692,7 +818,7 @@ where
     async fn removexattr(&self, req: Request, inode: u64, name: &OsStr) -> Result<()> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager
-            .get_absolute_path(inode)
+            .get_path(inode)
             .ok_or_else(Errno::new_not_exist)?;
 
         self.path_filesystem
@@ -701,21 +827,17 @@ where
     }
 
     async fn flush(&self, req: Request, inode: u64, fh: u64, lock_owner: u64) -> Result<()> {
-        let path = self
-            .inode_name_manager
-            .read()
-            .await
-            .get_absolute_path(inode);
+        let path = self.inode_name_manager.read().await.get_path(inode);
 
         self.path_filesystem
             .flush(req, path.as_ref().map(|path| path.as_ref()), fh, lock_owner)
             .await
     }
 
-    async fn opendir(&self, req: Request, inode: u64, flags: u32) -> Result<ReplyOpen> {
+    async fn opendir(&self, req: Request, inode: u64, flags: OpenFlags) -> Result<ReplyOpen> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager
-            .get_absolute_path(inode)
+            .get_path(inode)
             .ok_or_else(Errno::new_not_exist)?;
 
         self.path_filesystem
@@ -723,7 +845,10 @@ where
             .await
     }
 
-    type DirEntryStream<'a> = Iter<IntoIter<Result<DirectoryEntry>>> where Self: 'a;
+    type DirEntryStream<'a>
+        = Iter<IntoIter<Result<DirectoryEntry>>>
+    where
+        Self: 'a;
 
     async fn readdir(
         &self,
@@ -734,7 +859,7 @@ where
     ) -> Result<ReplyDirectory<Self::DirEntryStream<'_>>> {
         let mut inode_name_manager = self.inode_name_manager.write().await;
         let parent_path = inode_name_manager
-            .get_absolute_path(parent)
+            .get_path(parent)
             .ok_or_else(Errno::new_not_exist)?;
 
         let children = self
@@ -786,7 +911,7 @@ where
     async fn releasedir(&self, req: Request, inode: u64, fh: u64, flags: u32) -> Result<()> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager
-            .get_absolute_path(inode)
+            .get_path(inode)
             .ok_or_else(Errno::new_not_exist)?;
 
         self.path_filesystem
@@ -797,7 +922,7 @@ where
     async fn fsyncdir(&self, req: Request, inode: u64, fh: u64, datasync: bool) -> Result<()> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager
-            .get_absolute_path(inode)
+            .get_path(inode)
             .ok_or_else(Errno::new_not_exist)?;
 
         self.path_filesystem
@@ -805,69 +930,52 @@ where
             .await
     }
 
-    #[allow(clippy::too_many_arguments)]
     #[cfg(feature = "file-lock")]
-    async fn getlk(
-        &self,
-        req: Request,
-        inode: u64,
-        fh: u64,
-        lock_owner: u64,
-        start: u64,
-        end: u64,
-        r#type: u32,
-        pid: u32,
-    ) -> Result<ReplyLock> {
-        let path = self
-            .inode_name_manager
-            .read()
+    async fn getlk(&self, req: Request, inode: u64, fh: u64, lock: FileLock) -> Result<ReplyLock> {
+        let path = self.inode_name_manager.read().await.get_path(inode);
+
+        self.path_filesystem
+            .getlk(req, path.as_ref().map(|path| path.as_ref()), fh, lock)
             .await
-            .get_absolute_path(inode);
+    }
+
+    #[cfg(feature = "file-lock")]
+    async fn setlk(&self, req: Request, inode: u64, fh: u64, lock: FileLock) -> Result<()> {
+        let path = self.inode_name_manager.read().await.get_path(inode);
 
         self.path_filesystem
-            .getlk(
-                req,
-                path.as_ref().map(|path| path.as_ref()),
-                fh,
-                lock_owner,
-                start,
-                end,
-                r#type,
-                pid,
-            )
+            .setlk(req, path.as_ref().map(|path| path.as_ref()), fh, lock)
+            .await
+    }
+
+    #[cfg(feature = "file-lock")]
+    async fn setlkw(&self, req: Request, inode: u64, fh: u64, lock: FileLock) -> Result<()> {
+        let path = self.inode_name_manager.read().await.get_path(inode);
+
+        self.path_filesystem
+            .setlkw(req, path.as_ref().map(|path| path.as_ref()), fh, lock)
             .await
     }
 
-    #[allow(clippy::too_many_arguments)]
     #[cfg(feature = "file-lock")]
-    async fn setlk(
+    async fn flock(
         &self,
         req: Request,
         inode: u64,
         fh: u64,
-        lock_owner: u64,
-        start: u64,
-        end: u64,
-        r#type: u32,
-        pid: u32,
+        owner: u64,
+        r#type: LockType,
         block: bool,
     ) -> Result<()> {
-        let path = self
-            .inode_name_manager
-            .read()
-            .await
-            .get_absolute_path(inode);
+        let path = self.inode_name_manager.read().await.get_path(inode);
 
         self.path_filesystem
-            .setlk(
+            .flock(
                 req,
                 path.as_ref().map(|path| path.as_ref()),
                 fh,
-                lock_owner,
-                start,
-                end,
+                owner,
                 r#type,
-                pid,
                 block,
             )
             .await
@@ -876,28 +984,39 @@ where
     async fn access(&self, req: Request, inode: u64, mask: u32) -> Result<()> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager
-            .get_absolute_path(inode)
+            .get_path(inode)
             .ok_or_else(Errno::new_not_exist)?;
 
         self.path_filesystem.access(req, path.as_ref(), mask).await
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn create(
         &self,
         req: Request,
         parent: u64,
         name: &OsStr,
         mode: u32,
-        flags: u32,
+        flags: OpenFlags,
+        umask: u32,
+        security_context: Option<&SecurityContext>,
     ) -> Result<ReplyCreated> {
         let mut inode_name_manager = self.inode_name_manager.write().await;
         let parent_path = inode_name_manager
-            .get_absolute_path(parent)
+            .get_path(parent)
             .ok_or_else(Errno::new_not_exist)?;
 
         match self
             .path_filesystem
-            .create(req, parent_path.as_ref(), name, mode, flags)
+            .create(
+                req,
+                parent_path.as_ref(),
+                name,
+                mode,
+                flags,
+                umask,
+                security_context,
+            )
             .await
         {
             Err(err) => {
@@ -915,14 +1034,12 @@ where
             Ok(created) => {
                 let name = Name::new(parent, name.to_owned());
 
-                let inode = inode_name_manager
-                    .get_name_inode(&name)
-                    .unwrap_or_else(|| inode_name_manager.insert_name(name));
+                let inode = inode_name_manager.lookup_name(name);
 
                 Ok(ReplyCreated {
                     ttl: created.ttl,
                     attr: (inode, created.attr).into(),
-                    generation: 0,
+                    generation: inode_name_manager.inode_generator.generation(inode),
                     fh: created.fh,
                     flags: created.flags,
                 })
@@ -938,7 +1055,7 @@ where
     async fn bmap(&self, req: Request, inode: u64, block_size: u32, idx: u64) -> Result<ReplyBmap> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager
-            .get_absolute_path(inode)
+            .get_path(inode)
             .ok_or_else(Errno::new_not_exist)?;
 
         self.path_filesystem
@@ -946,6 +1063,34 @@ where
             .await
     }
 
+    #[allow(clippy::too_many_arguments)]
+    async fn ioctl(
+        &self,
+        req: Request,
+        inode: u64,
+        fh: u64,
+        flags: u32,
+        cmd: u32,
+        arg: u64,
+        in_data: &[u8],
+        out_size: u32,
+    ) -> Result<ReplyIoctl> {
+        let path = self.inode_name_manager.read().await.get_path(inode);
+
+        self.path_filesystem
+            .ioctl(
+                req,
+                path.as_ref().map(|path| path.as_ref()),
+                fh,
+                flags,
+                cmd,
+                arg,
+                in_data,
+                out_size,
+            )
+            .await
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn poll(
         &self,
@@ -954,14 +1099,10 @@ where
         fh: u64,
         kh: Option<u64>,
         flags: u32,
-        events: u32,
+        events: PollEvents,
         notify: &Notify,
     ) -> Result<ReplyPoll> {
-        let path = self
-            .inode_name_manager
-            .read()
-            .await
-            .get_absolute_path(inode);
+        let path = self.inode_name_manager.read().await.get_path(inode);
 
         self.path_filesystem
             .poll(
@@ -979,7 +1120,7 @@ where
     async fn notify_reply(&self, req: Request, inode: u64, offset: u64, data: Bytes) -> Result<()> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager
-            .get_absolute_path(inode)
+            .get_path(inode)
             .ok_or_else(Errno::new_not_exist)?;
 
         self.path_filesystem
@@ -987,15 +1128,14 @@ where
             .await
     }
 
-    async fn batch_forget(&self, req: Request, inodes: &[u64]) {
+    async fn batch_forget(&self, req: Request, inodes: &[(u64, u64)]) {
         // TODO if kernel forget a dir which has children, it may break
 
         let mut inode_name_manager = self.inode_name_manager.write().await;
 
         let paths = inodes
             .iter()
-            .copied()
-            .filter_map(|inode| inode_name_manager.get_absolute_path(inode))
+            .filter_map(|&(inode, _)| inode_name_manager.get_path(inode))
             .collect::<Vec<_>>();
         let paths = paths.iter().map(|path| path.as_ref()).collect::<Vec<_>>();
 
@@ -1003,8 +1143,7 @@ where
 
         inodes
             .iter()
-            .copied()
-            .for_each(|inode| inode_name_manager.remove_inode(inode));
+            .for_each(|&(inode, nlookup)| inode_name_manager.forget(inode, nlookup));
     }
 
     async fn fallocate(
@@ -1014,13 +1153,9 @@ where
         fh: u64,
         offset: u64,
         length: u64,
-        mode: u32,
+        mode: FallocateMode,
     ) -> Result<()> {
-        let path = self
-            .inode_name_manager
-            .read()
-            .await
-            .get_absolute_path(inode);
+        let path = self.inode_name_manager.read().await.get_path(inode);
 
         self.path_filesystem
             .fallocate(
@@ -1034,7 +1169,10 @@ where
             .await
     }
 
-    type DirEntryPlusStream<'a> = Iter<IntoIter<Result<DirectoryEntryPlus>>> where Self: 'a;
+    type DirEntryPlusStream<'a>
+        = BoxStream<'a, Result<DirectoryEntryPlus>>
+    where
+        Self: 'a;
 
     async fn readdirplus(
         &self,
@@ -1044,9 +1182,11 @@ where
         offset: u64,
         lock_owner: u64,
     ) -> Result<ReplyDirectoryPlus<Self::DirEntryPlusStream<'_>>> {
-        let mut inode_name_manager = self.inode_name_manager.write().await;
-        let parent_path = inode_name_manager
-            .get_absolute_path(parent)
+        let parent_path = self
+            .inode_name_manager
+            .read()
+            .await
+            .get_path(parent)
             .ok_or_else(Errno::new_not_exist)?;
 
         let children = self
@@ -1054,49 +1194,41 @@ where
             .readdirplus(req, parent_path.as_ref(), fh, offset, lock_owner)
             .await?;
 
-        let entries = children.entries;
-        futures_util::pin_mut!(entries);
+        let entries = children.entries.try_collect::<Vec<_>>().await?;
 
-        let entries_size = entries.size_hint().1.unwrap_or(0);
-        let mut entry_list = Vec::with_capacity(entries_size);
-
-        while let Some(entry) = entries.next().await {
-            let entry = entry?;
+        // count the lookup when the entry is pulled, the entries which are not pulled won't be
+        // replied to kernel
+        let entries = stream::iter(entries)
+            .then(move |entry| async move {
+                let mut inode_name_manager = self.inode_name_manager.write().await;
 
-            let inode = if entry.name == OsStr::new(".") {
-                parent
-            } else if entry.name == OsStr::new("..") {
-                inode_name_manager
-                    .inode_to_names
-                    .get(&parent)
-                    .unwrap()
-                    .iter()
-                    .next()
-                    .unwrap()
-                    .parent
-            } else {
-                let name = Name::new(parent, entry.name.clone());
-
-                inode_name_manager
-                    .get_name_inode(&name)
-                    .unwrap_or_else(|| inode_name_manager.insert_name(name))
-            };
-
-            entry_list.push(Ok(DirectoryEntryPlus {
-                inode,
-                generation: 0,
-                kind: entry.kind,
-                name: entry.name,
-                offset: entry.offset,
-                attr: (inode, entry.attr).into(),
-                entry_ttl: entry.entry_ttl,
-                attr_ttl: entry.attr_ttl,
-            }));
-        }
+                let inode = if entry.name == OsStr::new(".") {
+                    parent
+                } else if entry.name == OsStr::new("..") {
+                    inode_name_manager
+                        .inode_to_names
+                        .get(&parent)
+                        .and_then(|names| names.iter().next())
+                        .ok_or_else(Errno::new_not_exist)?
+                        .parent
+                } else {
+                    inode_name_manager.lookup_name(Name::new(parent, entry.name.clone()))
+                };
+
+                Ok(DirectoryEntryPlus {
+                    inode,
+                    generation: inode_name_manager.inode_generator.generation(inode),
+                    kind: entry.kind,
+                    name: entry.name,
+                    offset: entry.offset,
+                    attr: (inode, entry.attr).into(),
+                    entry_ttl: entry.entry_ttl,
+                    attr_ttl: entry.attr_ttl,
+                })
+            })
+            .boxed();
 
-        Ok(ReplyDirectoryPlus {
-            entries: stream::iter(entry_list),
-        })
+        Ok(ReplyDirectoryPlus { entries })
     }
 
     async fn rename2(
@@ -1106,15 +1238,15 @@ where
         name: &OsStr,
         new_parent: u64,
         new_name: &OsStr,
-        flags: u32,
+        flags: RenameFlags,
     ) -> Result<()> {
         let mut inode_name_manager = self.inode_name_manager.write().await;
 
         let origin_parent_path = inode_name_manager
-            .get_absolute_path(parent)
+            .get_path(parent)
             .ok_or_else(Errno::new_not_exist)?;
         let new_parent_path = inode_name_manager
-            .get_absolute_path(new_parent)
+            .get_path(new_parent)
             .ok_or_else(Errno::new_not_exist)?;
 
         // here is very complex so don't modify the inode_name_manager when error
@@ -1129,6 +1261,16 @@ where
             )
             .await?;
 
+        // both names still exist after exchange, but each one refers to the other inode
+        if flags.contains(RenameFlags::EXCHANGE) {
+            inode_name_manager.exchange_names(
+                Name::new(parent, name.to_owned()),
+                Name::new(new_parent, new_name.to_owned()),
+            );
+
+            return Ok(());
+        }
+
         inode_name_manager.remove_name(&Name::new(parent, name.to_owned()));
 
         let new_name = Name::new(new_parent, new_name.to_owned());
@@ -1148,11 +1290,7 @@ where
         offset: u64,
         whence: u32,
     ) -> Result<ReplyLSeek> {
-        let path = self
-            .inode_name_manager
-            .read()
-            .await
-            .get_absolute_path(inode);
+        let path = self.inode_name_manager.read().await.get_path(inode);
 
         self.path_filesystem
             .lseek(
@@ -1179,8 +1317,8 @@ where
         flags: u64,
     ) -> Result<ReplyCopyFileRange> {
         let inode_name_manager = self.inode_name_manager.read().await;
-        let path_in = inode_name_manager.get_absolute_path(inode);
-        let path_out = inode_name_manager.get_absolute_path(inode_out);
+        let path_in = inode_name_manager.get_path(inode);
+        let path_out = inode_name_manager.get_path(inode_out);
 
         drop(inode_name_manager);
 
@@ -1198,4 +1336,47 @@ where
             )
             .await
     }
+
+    async fn syncfs(&self, req: Request) -> Result<()> {
+        self.path_filesystem.syncfs(req).await
+    }
+
+    async fn canonical_path(&self, req: Request, inode: Inode) -> Result<ReplyData> {
+        let inode_name_manager = self.inode_name_manager.read().await;
+        let path = inode_name_manager
+            .get_path(inode)
+            .ok_or_else(Errno::new_not_exist)?;
+
+        self.path_filesystem
+            .canonical_path(req, path.as_ref())
+            .await
+    }
+
+    #[cfg(target_os = "macos")]
+    async fn getxtimes(&self, req: Request, inode: Inode) -> Result<ReplyXTimes> {
+        let inode_name_manager = self.inode_name_manager.read().await;
+        let path = inode_name_manager
+            .get_path(inode)
+            .ok_or_else(Errno::new_not_exist)?;
+
+        self.path_filesystem.getxtimes(req, path.as_ref()).await
+    }
+
+    #[cfg(target_os = "macos")]
+    async fn setxtimes(
+        &self,
+        req: Request,
+        inode: Inode,
+        bkuptime: Option<Timestamp>,
+        crtime: Option<Timestamp>,
+    ) -> Result<()> {
+        let inode_name_manager = self.inode_name_manager.read().await;
+        let path = inode_name_manager
+            .get_path(inode)
+            .ok_or_else(Errno::new_not_exist)?;
+
+        self.path_filesystem
+            .setxtimes(req, path.as_ref(), bkuptime, crtime)
+            .await
+    }
 }
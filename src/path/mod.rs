@@ -4,7 +4,9 @@
 //! than inode based [`Filesystem`][crate::raw::Filesystem]. However if you want to control the
 //! inode or do the path<->inode map on yourself, use [`Filesystem`][crate::raw::Filesystem].
 
+pub(crate) use inode_path_bridge::InodePathBridge;
 pub use path_filesystem::PathFilesystem;
+pub use readdir_cache::{ReaddirCache, ReaddirCacheStream, ReaddirEntry};
 pub use session::Session;
 
 pub use crate::raw::Request;
@@ -12,6 +14,7 @@ pub use crate::raw::Request;
 mod inode_generator;
 mod inode_path_bridge;
 mod path_filesystem;
+mod readdir_cache;
 pub mod reply;
 mod session;
 
@@ -19,9 +22,17 @@ pub mod prelude {
     pub use super::reply::FileAttr;
     pub use super::reply::*;
     pub use super::PathFilesystem;
+    pub use super::ReaddirCache;
     pub use super::Request;
     pub use super::Session;
     pub use crate::notify::Notify;
+    pub use crate::raw::flags::{
+        AccessMode, FallocateMode, OpenFlags, OptionalOps, PollEvents, RenameFlags, SetAttrValid,
+        SetXattrFlags, WriteFlags,
+    };
+    pub use crate::raw::{SecurityContext, XattrNamespace};
+    #[cfg(feature = "file-lock")]
+    pub use crate::raw::{FileLock, LockType};
     pub use crate::FileType;
     pub use crate::SetAttr;
 }
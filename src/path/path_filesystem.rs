@@ -6,11 +6,24 @@ use futures_util::stream::Stream;
 use super::reply::*;
 use super::Request;
 use crate::notify::Notify;
+use crate::raw::flags::{
+    FallocateMode, OpenFlags, PollEvents, RenameFlags, SetXattrFlags, WriteFlags,
+};
+use crate::raw::SecurityContext;
+#[cfg(feature = "file-lock")]
+use crate::raw::{FileLock, LockType};
+#[cfg(target_os = "macos")]
+use crate::Timestamp;
 use crate::{Result, SetAttr};
 
 #[allow(unused_variables)]
 #[trait_make::make(Send)]
 /// Path based filesystem trait.
+///
+/// the paths passed to the methods are rooted at `/`, which is the mount root, such as
+/// `/dir/file`, or relative to the mount root when
+/// [`Session::relative_path`][super::Session::relative_path] is enabled. They are joined from the
+/// literal names without resolving symlinks, see it for the details.
 pub trait PathFilesystem {
     /// initialize filesystem. Called before any other filesystem method.
     async fn init(&self, req: Request) -> Result<ReplyInit>;
@@ -21,7 +34,9 @@ pub trait PathFilesystem {
     /// <https://sourceforge.net/p/fuse/mailman/message/31995737/>
     async fn destroy(&self, req: Request);
 
-    /// look up a directory entry by name and get its attributes.
+    /// look up a directory entry by name and get its attributes. The returned `ENOENT` is not
+    /// cached by kernel unless [`negative_timeout`][crate::path::Session::negative_timeout] is
+    /// set.
     async fn lookup(&self, req: Request, parent: &OsStr, name: &OsStr) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
@@ -52,6 +67,9 @@ pub trait PathFilesystem {
 
     /// set file attributes. If `fh` is None, means `fh` is not set. If `path` is None, means the
     /// path may be deleted.
+    ///
+    /// use [`SetAttr::valid`] to know which attributes are requested. The filesystem should
+    /// update ctime when mode, uid, gid or size is changed, and reply the updated attributes.
     async fn setattr(
         &self,
         req: Request,
@@ -68,12 +86,17 @@ pub trait PathFilesystem {
     }
 
     /// create a symbolic link.
+    ///
+    /// `security_context` is the security context of the new symbolic link, it is only sent when
+    /// [`security_context`](crate::MountOptions::security_context) is enabled, see
+    /// [`SecurityContext`].
     async fn symlink(
         &self,
         req: Request,
         parent: &OsStr,
         name: &OsStr,
         link_path: &OsStr,
+        security_context: Option<&SecurityContext>,
     ) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
@@ -81,6 +104,15 @@ pub trait PathFilesystem {
     /// create file node. Create a regular file, character device, block device, fifo or socket
     /// node. When creating file, most cases user only need to implement
     /// [`create`][PathFilesystem::create].
+    ///
+    /// `umask` is the umask of the calling process, unless
+    /// [`dont_mask`](crate::MountOptions::dont_mask) is set, kernel has applied it to `mode`
+    /// already. It is 0 if kernel doesn't send it.
+    ///
+    /// `security_context` is the security context of the new node, it is only sent when
+    /// [`security_context`](crate::MountOptions::security_context) is enabled, see
+    /// [`SecurityContext`].
+    #[allow(clippy::too_many_arguments)]
     async fn mknod(
         &self,
         req: Request,
@@ -88,11 +120,21 @@ pub trait PathFilesystem {
         name: &OsStr,
         mode: u32,
         rdev: u32,
+        umask: u32,
+        security_context: Option<&SecurityContext>,
     ) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
 
     /// create a directory.
+    ///
+    /// `umask` is the umask of the calling process, unless
+    /// [`dont_mask`](crate::MountOptions::dont_mask) is set, kernel has applied it to `mode`
+    /// already. It is 0 if kernel doesn't send it.
+    ///
+    /// `security_context` is the security context of the new directory, it is only sent when
+    /// [`security_context`](crate::MountOptions::security_context) is enabled, see
+    /// [`SecurityContext`].
     async fn mkdir(
         &self,
         req: Request,
@@ -100,6 +142,7 @@ pub trait PathFilesystem {
         name: &OsStr,
         mode: u32,
         umask: u32,
+        security_context: Option<&SecurityContext>,
     ) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
@@ -114,7 +157,8 @@ pub trait PathFilesystem {
         Err(libc::ENOSYS.into())
     }
 
-    /// rename a file or directory.
+    /// rename a file or directory, it is called by the default
+    /// [`rename2`][PathFilesystem::rename2] when the rename has no flags.
     async fn rename(
         &self,
         req: Request,
@@ -144,14 +188,22 @@ pub trait PathFilesystem {
     /// also some flags (`direct_io`, `keep_cache`) which the filesystem may set, to change the way
     /// the file is opened.  A file system need not implement this method if it
     /// sets [`MountOptions::no_open_support`][crate::MountOptions::no_open_support] and if the
-    /// kernel supports `FUSE_NO_OPEN_SUPPORT`.
+    /// kernel supports `FUSE_NO_OPEN_SUPPORT`, the files are opened without sending open and
+    /// release requests then, and `fh` of the other file operations is always 0, see
+    /// [`MountOptions::no_open_support`][crate::MountOptions::no_open_support] for the stateless
+    /// open mode.
     ///
     /// # Notes:
     ///
     /// See `fuse_file_info` structure in
     /// [fuse_common.h](https://libfuse.github.io/doxygen/include_2fuse__common_8h_source.html) for
     /// more details.
-    async fn open(&self, req: Request, path: &OsStr, flags: u32) -> Result<ReplyOpen> {
+    ///
+    /// when the caller uses direct io, [`OpenFlags::is_direct`] is true, read doesn't carry the
+    /// open flags, so record it in the state of `fh` if the filesystem needs it in read. Before
+    /// passing the direct io to a backing file opened with `O_DIRECT`, see
+    /// [`DirectIoAlignment`][crate::util::DirectIoAlignment] for the alignment.
+    async fn open(&self, req: Request, path: &OsStr, flags: OpenFlags) -> Result<ReplyOpen> {
         Err(libc::ENOSYS.into())
     }
 
@@ -160,7 +212,11 @@ pub trait PathFilesystem {
     /// when the file has been opened in `direct_io` mode, in which case the return value of the
     /// read system call will reflect the return value of this operation. `fh` will contain the
     /// value set by the open method, or will be undefined if the open method didn't set any value.
+    /// It is 0 with the zero-message open, see [`open`][Self::open].
     /// when `path` is None, it means the path may be deleted.
+    ///
+    /// the `offset` and `size` of a direct io read may not be aligned, see
+    /// [`DirectIoAlignment`][crate::util::DirectIoAlignment].
     async fn read(
         &self,
         req: Request,
@@ -172,13 +228,41 @@ pub trait PathFilesystem {
         Err(libc::ENOSYS.into())
     }
 
+    /// read data like [`read`][PathFilesystem::read], but the data can be replied from a file
+    /// without reading it into memory, see [`ReplyReadBuf::File`]. When it returns `ENOSYS`,
+    /// which is the default implementation, [`read`][PathFilesystem::read] is called instead,
+    /// and it won't be called again. When `path` is None, it means the path may be deleted.
+    async fn read_buf(
+        &self,
+        req: Request,
+        path: Option<&OsStr>,
+        fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<ReplyReadBuf> {
+        Err(libc::ENOSYS.into())
+    }
+
     /// write data. Write should return exactly the number of bytes requested except on error. An
     /// exception to this is when the file has been opened in `direct_io` mode, in which case the
     /// return value of the write system call will reflect the return value of this operation. `fh`
     /// will contain the value set by the open method, or will be undefined if the open method
-    /// didn't set any value. When `path` is None, it means the path may be deleted. When
-    /// `write_flags` contains [`FUSE_WRITE_CACHE`](crate::raw::flags::FUSE_WRITE_CACHE), means the
-    /// write operation is a delay write.
+    /// didn't set any value. It is 0 with the zero-message open. When `path` is None, it means
+    /// the path may be deleted.
+    ///
+    /// # Notes:
+    ///
+    /// when `write_flags` contains [`WriteFlags::CACHE`], the data is written back from the page
+    /// cache by kernel, which only happens when
+    /// [`MountOptions::write_back`][crate::MountOptions::write_back] is enabled. In this case
+    /// `fh` is picked by kernel from the opens of the file which have write access, it may not be
+    /// the open which the data was written through, and `flags` are the flags of that open. The
+    /// filesystem should locate the file by `path` and not rely on the per-open state of `fh`,
+    /// such as the open flags or the lock owner. `fh` is still valid because kernel delays its
+    /// [`release`][PathFilesystem::release] until the cached data is written back.
+    ///
+    /// `flags` are the open flags, [`OpenFlags::is_direct`] tells a direct io write, its `offset`,
+    /// size and data may not be aligned, see [`DirectIoAlignment`][crate::util::DirectIoAlignment].
     #[allow(clippy::too_many_arguments)]
     async fn write(
         &self,
@@ -187,12 +271,34 @@ pub trait PathFilesystem {
         fh: u64,
         offset: u64,
         data: &[u8],
-        write_flags: u32,
+        write_flags: WriteFlags,
         flags: u32,
     ) -> Result<ReplyWrite> {
         Err(libc::ENOSYS.into())
     }
 
+    /// write data in a [`Bytes`] buffer, it is called by fuse3 instead of
+    /// [`write`][PathFilesystem::write]. The default implementation calls
+    /// [`write`][PathFilesystem::write] with the contiguous data, see
+    /// [`Filesystem::write_buf`][crate::raw::Filesystem::write_buf].
+    #[allow(clippy::too_many_arguments)]
+    async fn write_buf(
+        &self,
+        req: Request,
+        path: Option<&OsStr>,
+        fh: u64,
+        offset: u64,
+        data: Bytes,
+        write_flags: WriteFlags,
+        flags: u32,
+    ) -> Result<ReplyWrite>
+    where
+        Self: Sync,
+    {
+        self.write(req, path, fh, offset, &data, write_flags, flags)
+            .await
+    }
+
     /// get filesystem statistics.
     async fn statfs(&self, req: Request, path: &OsStr) -> Result<ReplyStatFs> {
         Err(libc::ENOSYS.into())
@@ -200,11 +306,21 @@ pub trait PathFilesystem {
 
     /// release an open file. Release is called when there are no more references to an open file:
     /// all file descriptors are closed and all memory mappings are unmapped. For every open call
-    /// there will be exactly one release call. The filesystem may reply with an error, but error
-    /// values are not returned to `close()` or `munmap()` which triggered the release. `fh` will
-    /// contain the value set by the open method, or will be undefined if the open method didn't
-    /// set any value. `flags` will contain the same flags as for open. `flush` means flush the
-    /// data or not when closing file. when `path` is None, it means the path may be deleted.
+    /// there will be exactly one release call, except the zero-message open which has no release
+    /// call. The filesystem may reply with an error, but error values are not returned to
+    /// `close()` or `munmap()` which triggered the release. `fh` will contain the value set by the
+    /// open method, or will be undefined if the open method didn't set any value. `flags` will
+    /// contain the same flags as for open. when `path` is None, it means the path may be deleted.
+    ///
+    /// # Notes:
+    ///
+    /// release is sent after the [`flush`][PathFilesystem::flush] of the last `close()`, so the
+    /// buffered writes should be flushed and the POSIX locks of the closing process should be
+    /// removed there, because kernel passes their `lock_owner` to flush only. When the file was
+    /// locked with `flock(2)`, `lock_owner` is the owner of the flock locks, and the `flock`
+    /// unlock of it is called before release when the `file-lock` feature is enabled, otherwise
+    /// `lock_owner` is 0. `flush` is true when kernel asks to flush the data on release, Linux
+    /// kernel doesn't ask it now.
     async fn release(
         &self,
         req: Request,
@@ -229,21 +345,23 @@ pub trait PathFilesystem {
         Ok(())
     }
 
-    /// set an extended attribute.
+    /// set an extended attribute. Use [`SetXattrFlags::check`] to handle `XATTR_CREATE` and
+    /// `XATTR_REPLACE` in `flags`.
     async fn setxattr(
         &self,
         req: Request,
         path: &OsStr,
         name: &OsStr,
         value: &[u8],
-        flags: u32,
+        flags: SetXattrFlags,
         position: u32,
     ) -> Result<()> {
         Err(libc::ENOSYS.into())
     }
 
-    /// get an extended attribute. If size is too small, use [`ReplyXAttr::Size`] to return correct
-    /// size. If size is enough, use [`ReplyXAttr::Data`] to send it, or return error.
+    /// get an extended attribute. If size is 0, use [`ReplyXAttr::Size`] to return correct size.
+    /// If size is too small, return `ERANGE`. If size is enough, use [`ReplyXAttr::Data`] to
+    /// send it, or return error. [`ReplyXAttr::new`] handles the size for you.
     async fn getxattr(
         &self,
         req: Request,
@@ -254,8 +372,9 @@ pub trait PathFilesystem {
         Err(libc::ENOSYS.into())
     }
 
-    /// list extended attribute names. If size is too small, use [`ReplyXAttr::Size`] to return
-    /// correct size. If size is enough, use [`ReplyXAttr::Data`] to send it, or return error.
+    /// list extended attribute names. If size is 0, use [`ReplyXAttr::Size`] to return correct
+    /// size. If size is too small, return `ERANGE`. If size is enough, use [`ReplyXAttr::Data`]
+    /// to send it, or return error. [`ReplyXAttr::from_names`] handles the size for you.
     async fn listxattr(&self, req: Request, path: &OsStr, size: u32) -> Result<ReplyXAttr> {
         Err(libc::ENOSYS.into())
     }
@@ -279,6 +398,10 @@ pub trait PathFilesystem {
     /// errors. If the filesystem supports file locking operations (
     /// [`setlk`][PathFilesystem::setlk], [`getlk`][PathFilesystem::getlk]) it should remove all
     /// locks belonging to `lock_owner`.
+    /// `lock_owner` identifies the file table of the closing process, which is the owner of its
+    /// POSIX locks. The `flock(2)` locks belong to the open file instead, they are kept until
+    /// [`release`][PathFilesystem::release]. If flush replies `ENOSYS`, kernel will not send
+    /// flush any more and the locks can't be removed on `close()`.
     async fn flush(
         &self,
         req: Request,
@@ -296,7 +419,7 @@ pub trait PathFilesystem {
     /// I/O and not store anything in `fh`.  A file system need not implement this method if it
     /// sets [`MountOptions::no_open_dir_support`][crate::MountOptions::no_open_dir_support] and if
     /// the kernel supports `FUSE_NO_OPENDIR_SUPPORT`.
-    async fn opendir(&self, req: Request, path: &OsStr, flags: u32) -> Result<ReplyOpen> {
+    async fn opendir(&self, req: Request, path: &OsStr, flags: OpenFlags) -> Result<ReplyOpen> {
         Err(libc::ENOSYS.into())
     }
 
@@ -308,6 +431,7 @@ pub trait PathFilesystem {
     /// read directory. `offset` is used to track the offset of the directory entries. `fh` will
     /// contain the value set by the [`opendir`][PathFilesystem::opendir] method, or will be
     /// undefined if the [`opendir`][PathFilesystem::opendir] method didn't set any value.
+    /// [`ReaddirCache`][crate::path::ReaddirCache] can track the offsets for you.
     async fn readdir<'a>(
         &'a self,
         req: Request,
@@ -326,56 +450,115 @@ pub trait PathFilesystem {
         Ok(())
     }
 
-    /// synchronize directory contents. If the `datasync` is true, then only the directory contents
-    /// should be flushed, not the metadata. `fh` will contain the value set by the
+    /// synchronize directory contents, it is called when `fsync(2)` or `fdatasync(2)` is called
+    /// on an open directory, such as making the entries created in it durable. If the `datasync`
+    /// is true, then only the directory contents should be flushed, not the metadata of the
+    /// directory itself, such as the times. `fh` will contain the value set by the
     /// [`opendir`][PathFilesystem::opendir] method, or will be undefined if the
-    /// [`opendir`][PathFilesystem::opendir] method didn't set any value.
+    /// [`opendir`][PathFilesystem::opendir] method didn't set any value. The default implementation
+    /// does nothing and returns success.
+    ///
+    /// # Notes:
+    ///
+    /// when this method returns `ENOSYS`, kernel treats it as success and never sends fsyncdir
+    /// again, a filesystem which has nothing to flush can return `ENOSYS` to avoid the later
+    /// requests.
     async fn fsyncdir(&self, req: Request, path: &OsStr, fh: u64, datasync: bool) -> Result<()> {
-        Err(libc::ENOSYS.into())
+        Ok(())
     }
 
     #[cfg(feature = "file-lock")]
-    /// test for a POSIX file lock.
+    /// test for a POSIX file lock. Reply the first lock of another owner which conflicts with
+    /// `lock` by [`ReplyLock::conflict`], or reply [`ReplyLock::unlocked`] if `lock` can be
+    /// placed. The locks of `lock.owner` never conflict with `lock`.
     ///
     /// # Notes:
     ///
     /// this is supported on enable **`file-lock`** feature.
-    #[allow(clippy::too_many_arguments)]
     async fn getlk(
         &self,
         req: Request,
         path: Option<&OsStr>,
         fh: u64,
-        lock_owner: u64,
-        start: u64,
-        end: u64,
-        r#type: u32,
-        pid: u32,
-    ) -> Result<ReplyLock>;
+        lock: FileLock,
+    ) -> Result<ReplyLock> {
+        Err(libc::ENOSYS.into())
+    }
 
     #[cfg(feature = "file-lock")]
-    /// acquire, modify or release a POSIX file lock.
+    /// acquire, modify or release a POSIX file lock without blocking, return `EAGAIN` if `lock`
+    /// conflicts with a lock of another owner.
     ///
     /// # Notes:
     ///
     /// this is supported on enable **`file-lock`** feature.
-    #[allow(clippy::too_many_arguments)]
     async fn setlk(
         &self,
         req: Request,
         path: Option<&OsStr>,
         fh: u64,
-        lock_owner: u64,
-        start: u64,
-        end: u64,
-        r#type: u32,
-        pid: u32,
+        lock: FileLock,
+    ) -> Result<()> {
+        Err(libc::ENOSYS.into())
+    }
+
+    #[cfg(feature = "file-lock")]
+    /// acquire a POSIX file lock, wait until the conflicting locks are released.
+    ///
+    /// # Notes:
+    ///
+    /// this is supported on enable **`file-lock`** feature.
+    ///
+    /// the reply is deferred until the returned future is done, the calling process is blocked
+    /// in kernel until then, and [`MountOptions::request_timeout`] doesn't apply to it. When the
+    /// calling process is interrupted by a signal, the future is dropped and `EINTR` is replied.
+    /// If [`MountOptions::forward_interrupt`] is enabled,
+    /// [`interrupt`][PathFilesystem::interrupt] is called with the unique of this request instead, the
+    /// filesystem should stop waiting and return `EINTR`.
+    ///
+    /// [`MountOptions::request_timeout`]: crate::MountOptions::request_timeout
+    /// [`MountOptions::forward_interrupt`]: crate::MountOptions::forward_interrupt
+    async fn setlkw(
+        &self,
+        req: Request,
+        path: Option<&OsStr>,
+        fh: u64,
+        lock: FileLock,
+    ) -> Result<()> {
+        Err(libc::ENOSYS.into())
+    }
+
+    #[cfg(feature = "file-lock")]
+    /// acquire, convert or release a BSD style whole file lock of `flock(2)`, it is called only
+    /// when [`MountOptions::flock_locks`] is enabled. `type` is [`LockType::Read`] for
+    /// `LOCK_SH`, [`LockType::Write`] for `LOCK_EX` and [`LockType::Unlock`] for `LOCK_UN`.
+    /// `owner` identifies the open file description, the flock locks are independent of the POSIX
+    /// locks of [`setlk`][PathFilesystem::setlk]. If `block` is false (`LOCK_NB`), return
+    /// `EWOULDBLOCK` if `type` conflicts with a lock of another owner, otherwise wait until the
+    /// lock can be placed. When `path` is None, it means the path may be deleted.
+    ///
+    /// # Notes:
+    ///
+    /// this is supported on enable **`file-lock`** feature.
+    ///
+    /// [`MountOptions::flock_locks`]: crate::MountOptions::flock_locks
+    async fn flock(
+        &self,
+        req: Request,
+        path: Option<&OsStr>,
+        fh: u64,
+        owner: u64,
+        r#type: LockType,
         block: bool,
-    ) -> Result<()>;
+    ) -> Result<()> {
+        Err(libc::ENOSYS.into())
+    }
 
-    /// check file access permissions. This will be called for the `access()` system call. If the
-    /// `default_permissions` mount option is given, this method is not be called. This method is
-    /// not called under Linux kernel versions 2.4.x.
+    /// check file access permissions. This will be called for the `access()` system call and
+    /// `chdir(2)`. If the [`default_permissions`][crate::MountOptions::default_permissions] mount
+    /// option is given, kernel checks the permissions itself and this method is not called,
+    /// unless [`forward_access`][crate::MountOptions::forward_access] is enabled and kernel
+    /// still sends the request. This method is not called under Linux kernel versions 2.4.x.
     async fn access(&self, req: Request, path: &OsStr, mask: u32) -> Result<()> {
         Err(libc::ENOSYS.into())
     }
@@ -391,33 +574,67 @@ pub trait PathFilesystem {
     /// than 2.6.15, the [`mknod`][PathFilesystem::mknod] and [`open`][PathFilesystem::open]
     /// methods will be called instead.
     ///
+    /// `umask` is the umask of the calling process, unless
+    /// [`dont_mask`](crate::MountOptions::dont_mask) is set, kernel has applied it to `mode`
+    /// already. It is 0 if kernel doesn't send it.
+    ///
+    /// `security_context` is the security context of the new file, it is only sent when
+    /// [`security_context`](crate::MountOptions::security_context) is enabled, see
+    /// [`SecurityContext`].
+    ///
+    /// # Fallback:
+    ///
+    /// when this method returns `ENOSYS`, kernel remembers it and never sends create again for
+    /// this mount, each following `open(2)` with `O_CREAT` of a non-existent file becomes a
+    /// [`mknod`][PathFilesystem::mknod] with a regular file `mode` and `rdev` 0, followed by
+    /// an [`open`][PathFilesystem::open] without `O_CREAT` and `O_EXCL`, so the two steps are
+    /// not atomic.
+    /// Kernel before 2.6.15 (protocol minor version 1) also uses this fallback. A filesystem
+    /// which doesn't implement create detects the fallback by a `S_IFREG` mknod, and must
+    /// implement both mknod and open.
+    ///
     /// # Notes:
     ///
     /// See `fuse_file_info` structure in
     /// [fuse_common.h](https://libfuse.github.io/doxygen/include_2fuse__common_8h_source.html) for
     /// more details.
+    #[allow(clippy::too_many_arguments)]
     async fn create(
         &self,
         req: Request,
         parent: &OsStr,
         name: &OsStr,
         mode: u32,
-        flags: u32,
+        flags: OpenFlags,
+        umask: u32,
+        security_context: Option<&SecurityContext>,
     ) -> Result<ReplyCreated> {
         Err(libc::ENOSYS.into())
     }
 
     /// handle interrupt. When a operation is interrupted, an interrupt request will send to fuse
     /// server with the unique id of the operation.
+    ///
+    /// # Notes:
+    ///
+    /// this is only called when [`MountOptions::forward_interrupt`] is enabled, otherwise the
+    /// interrupted operation is cancelled by dropping its future.
+    ///
+    /// [`MountOptions::forward_interrupt`]: crate::MountOptions::forward_interrupt
     async fn interrupt(&self, req: Request, unique: u64) -> Result<()> {
         Err(libc::ENOSYS.into())
     }
 
-    /// map block index within file to block index within device.
+    /// map block index within file to block index within device. `block_size` is the block size
+    /// of the device, `idx` is the block index within the file in `block_size` units, the block
+    /// index within device should be replied in the same units. It is used by `ioctl(FIBMAP)` and
+    /// some bootloaders.
     ///
     /// # Notes:
     ///
-    /// This may not works because currently this crate doesn't support fuseblk mode yet.
+    /// kernel only sends bmap for a fuseblk mount, which is backed by a real block device, so it
+    /// is meaningful only for a block device backed filesystem and rarely used, currently this
+    /// crate doesn't support fuseblk mode yet. If ENOSYS is replied, kernel won't send bmap again.
     async fn bmap(
         &self,
         req: Request,
@@ -428,36 +645,68 @@ pub trait PathFilesystem {
         Err(libc::ENOSYS.into())
     }
 
-    /*async fn ioctl(
+    /// control device. `cmd` and `arg` are the `ioctl(2)` arguments, `in_data` is the input data
+    /// copied from the caller, and `out_size` is the max size of the output data, `flags` is the
+    /// `FUSE_IOCTL_*` flags in [`flags`][crate::raw::flags].
+    ///
+    /// # Notes:
+    ///
+    /// for a normal fuse filesystem kernel sends restricted ioctl, `in_data` and `out_size` are
+    /// decided by the size and direction encoded in `cmd`, and only [`ReplyIoctl::Done`] is
+    /// allowed. When [`FUSE_IOCTL_UNRESTRICTED`] is set, `arg` may be a pointer which kernel
+    /// can't understand, reply [`ReplyIoctl::Retry`] with the caller memory areas to read and
+    /// write, then kernel will send the ioctl again with the `in_data` read from the `in_iovs`
+    /// and `out_size` is the total length of the `out_iovs`. `FUSE_IOCTL_DIR` is set when the
+    /// ioctl is on a directory, which needs [`InitFlags::HAS_IOCTL_DIR`] supported by kernel.
+    ///
+    /// [`FUSE_IOCTL_UNRESTRICTED`]: crate::raw::flags::FUSE_IOCTL_UNRESTRICTED
+    /// [`InitFlags::HAS_IOCTL_DIR`]: crate::raw::flags::InitFlags::HAS_IOCTL_DIR
+    #[allow(clippy::too_many_arguments)]
+    async fn ioctl(
         &self,
         req: Request,
-        inode: u64,
+        path: Option<&OsStr>,
         fh: u64,
         flags: u32,
         cmd: u32,
         arg: u64,
-        in_size: u32,
+        in_data: &[u8],
         out_size: u32,
     ) -> Result<ReplyIoctl> {
         Err(libc::ENOSYS.into())
-    }*/
+    }
 
-    /// poll for IO readiness events.
+    /// poll for IO readiness events, return the events which are ready now.
+    ///
+    /// # Notes:
+    ///
+    /// `kh` is `Some` only when kernel sets the `FUSE_POLL_SCHEDULE_NOTIFY` flag, it means
+    /// kernel wants to be notified when the events are ready, keep the `kh` and a clone of
+    /// `notify`, and call [`Notify::wakeup`] with the `kh` when the IO is ready. When `kh` is
+    /// `None`, kernel doesn't want to be notified, just return the ready events. When no
+    /// requested event is ready, reply [`ReplyPoll::pending`], see [`ReplyPoll`] for an example.
     #[allow(clippy::too_many_arguments)]
     async fn poll(
         &self,
         req: Request,
         path: Option<&OsStr>,
         fh: u64,
-        kn: Option<u64>,
+        kh: Option<u64>,
         flags: u32,
-        envents: u32,
+        events: PollEvents,
         notify: &Notify,
     ) -> Result<ReplyPoll> {
         Err(libc::ENOSYS.into())
     }
 
     /// receive notify reply from kernel.
+    ///
+    /// # Notes:
+    ///
+    /// the reply of a retrieve notify sent by [`Notify::retrieve`] is delivered to the returned
+    /// [`RetrieveHandle`], only the reply which can't be matched will be passed to this method.
+    ///
+    /// [`RetrieveHandle`]: crate::notify::RetrieveHandle
     async fn notify_reply(
         &self,
         req: Request,
@@ -476,7 +725,8 @@ pub trait PathFilesystem {
     ///
     /// # Notes:
     ///
-    /// more information about `fallocate`, please see **`man 2 fallocate`**
+    /// more information about `fallocate`, please see **`man 2 fallocate`**, the raw mode is still
+    /// available by [`FallocateMode::bits`].
     async fn fallocate(
         &self,
         req: Request,
@@ -484,7 +734,7 @@ pub trait PathFilesystem {
         fh: u64,
         offset: u64,
         length: u64,
-        mode: u32,
+        mode: FallocateMode,
     ) -> Result<()> {
         Err(libc::ENOSYS.into())
     }
@@ -495,7 +745,14 @@ pub trait PathFilesystem {
         Self: 'a;
 
     /// read directory entries, but with their attribute, like [`readdir`][PathFilesystem::readdir]
-    /// + [`lookup`][PathFilesystem::lookup] at the same time.
+    /// + [`lookup`][PathFilesystem::lookup] at the same time, so kernel doesn't need to lookup
+    /// the entries after listing the directory.
+    ///
+    /// # Notes:
+    ///
+    /// kernel counts one lookup for each replied entry except `.` and `..`, fuse3 tracks the
+    /// lookup count of the inodes it allocates for the paths, so each replied entry will receive a
+    /// [`forget`][PathFilesystem::forget] like a successful [`lookup`][PathFilesystem::lookup].
     async fn readdirplus<'a>(
         &'a self,
         req: Request,
@@ -507,7 +764,17 @@ pub trait PathFilesystem {
         Err(libc::ENOSYS.into())
     }
 
-    /// rename a file or directory with flags.
+    /// rename a file or directory with flags. Kernel sends the legacy rename without flags here
+    /// too, with empty `flags`. The default implementation calls [`rename`][PathFilesystem::rename]
+    /// when `flags` is empty, or returns ENOSYS.
+    ///
+    /// # Notes:
+    ///
+    /// with [`RenameFlags::EXCHANGE`], the two names should be swapped atomically; with
+    /// [`RenameFlags::NOREPLACE`], return EEXIST if the new name exists. If return ENOSYS for a
+    /// flagged rename, kernel won't send rename with flags anymore, and the `renameat2(2)` with
+    /// flags fails with EINVAL, kernel can't fall back because the flags semantics can't be done
+    /// by a plain rename.
     async fn rename2(
         &self,
         req: Request,
@@ -515,12 +782,26 @@ pub trait PathFilesystem {
         origin_name: &OsStr,
         parent: &OsStr,
         name: &OsStr,
-        flags: u32,
-    ) -> Result<()> {
-        Err(libc::ENOSYS.into())
+        flags: RenameFlags,
+    ) -> Result<()>
+    where
+        Self: Sync,
+    {
+        if !flags.is_empty() {
+            return Err(libc::ENOSYS.into());
+        }
+
+        self.rename(req, origin_parent, origin_name, parent, name).await
     }
 
     /// find next data or hole after the specified offset.
+    ///
+    /// `whence` is the raw `SEEK_*` value passed to `lseek(2)`, kernel only sends `libc::SEEK_DATA`
+    /// and `libc::SEEK_HOLE` to FUSE server, other `whence` are handled by kernel itself. When
+    /// `whence` is `SEEK_DATA`, reply the start of the next data region at or after `offset`; when
+    /// `whence` is `SEEK_HOLE`, reply the start of the next hole at or after `offset`, the end of
+    /// file is treated as a hole. If there is no more data after `offset`, return ENXIO. If return
+    /// ENOSYS, kernel will treat the whole file as data and never send lseek again.
     async fn lseek(
         &self,
         req: Request,
@@ -537,6 +818,13 @@ pub trait PathFilesystem {
     /// then to kernel, finally send back to FUSE server. By implement this method, data will only
     /// copy in FUSE server internal.  when `from_path` or `to_path` is None, it means the path may
     /// be deleted.
+    ///
+    /// # Notes:
+    ///
+    /// because the FUSE server sees both source and destination, it can avoid copying data at all,
+    /// for example share the extents by reflink (`FICLONERANGE`) or deduplicate the blocks in its
+    /// backend storage, then reply the `length` as copied. If return ENOSYS, kernel will fall back
+    /// to read and write.
     #[allow(clippy::too_many_arguments)]
     async fn copy_file_range(
         &self,
@@ -552,4 +840,64 @@ pub trait PathFilesystem {
     ) -> Result<ReplyCopyFileRange> {
         Err(libc::ENOSYS.into())
     }
+
+    /// synchronize the whole filesystem, it is called when `syncfs(2)` is called on the mount,
+    /// the filesystem should flush all its dirty state. The default implementation does nothing
+    /// and returns success.
+    ///
+    /// # Notes:
+    ///
+    /// there is no capability to negotiate in init, kernel sends it only when the connection
+    /// enables `sync_fs`, which is only virtiofs now, so a filesystem mounted through
+    /// `/dev/fuse` can't rely on it for durability yet, it should flush in
+    /// [`fsync`][PathFilesystem::fsync] and [`flush`][PathFilesystem::flush] too.
+    async fn syncfs(&self, req: Request) -> Result<()> {
+        Ok(())
+    }
+
+    /// get the canonical path of a file, which kernel resolves to the real file behind it, such
+    /// as the file of the lower filesystem which a stacked filesystem passes through. The default
+    /// implementation returns `ENOSYS`, then kernel uses the fuse file itself.
+    ///
+    /// the returned path should be absolute, without the trailing null byte, see
+    /// [`Filesystem::canonical_path`][crate::raw::Filesystem::canonical_path].
+    ///
+    /// # Notes:
+    ///
+    /// `FUSE_CANONICAL_PATH` is not in the mainline Linux kernel, only the Android kernels send
+    /// it, when inotify watches a fuse file. There is no capability to negotiate in init.
+    async fn canonical_path(&self, req: Request, path: &OsStr) -> Result<ReplyData> {
+        Err(libc::ENOSYS.into())
+    }
+
+    /// get the macOS extended times, the backup time and the creation time, of a file.
+    ///
+    /// # Notes:
+    ///
+    /// macFUSE sends it only when the [`XTIMES`][crate::raw::flags::InitFlags::XTIMES] init
+    /// flag is enabled.
+    #[cfg(target_os = "macos")]
+    async fn getxtimes(&self, req: Request, path: &OsStr) -> Result<ReplyXTimes> {
+        Err(libc::ENOSYS.into())
+    }
+
+    /// set the macOS extended times of a file, `None` means the time is not changed.
+    ///
+    /// # Notes:
+    ///
+    /// there is no setxtimes request, macFUSE sends the times in setattr, it is called before
+    /// [`setattr`][PathFilesystem::setattr] when the setattr request sets the backup time or the
+    /// creation time, and the setattr request fails if it fails with an error other than
+    /// `ENOSYS`, so the default implementation doesn't fail the setattr. The times are still in
+    /// the [`SetAttr`] of setattr.
+    #[cfg(target_os = "macos")]
+    async fn setxtimes(
+        &self,
+        req: Request,
+        path: &OsStr,
+        bkuptime: Option<Timestamp>,
+        crtime: Option<Timestamp>,
+    ) -> Result<()> {
+        Err(libc::ENOSYS.into())
+    }
 }
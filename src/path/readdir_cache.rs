@@ -0,0 +1,210 @@
+//! keep the directory listing of an open directory, so readdir offsets are stable.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures_util::stream::Stream;
+
+use super::reply::{DirectoryEntry, DirectoryEntryPlus};
+use crate::Result;
+
+/// a directory entry which offset can be assigned by [`ReaddirCache`].
+pub trait ReaddirEntry: Clone {
+    /// set the offset of the _next_ entry.
+    fn set_offset(&mut self, offset: i64);
+}
+
+impl ReaddirEntry for DirectoryEntry {
+    fn set_offset(&mut self, offset: i64) {
+        self.offset = offset;
+    }
+}
+
+impl ReaddirEntry for DirectoryEntryPlus {
+    fn set_offset(&mut self, offset: i64) {
+        self.offset = offset;
+    }
+}
+
+/// the entries stream returned by [`ReaddirCache::readdir`].
+///
+/// it shares the cached listing and clones an entry only when it is polled, the session stops
+/// polling when the reply is full, so a read only clones the entries in its reply.
+#[derive(Debug)]
+pub struct ReaddirCacheStream<E> {
+    listing: Arc<[E]>,
+    position: usize,
+}
+
+impl<E: Clone> Stream for ReaddirCacheStream<E> {
+    type Item = Result<E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let entry = self.listing.get(self.position).cloned();
+        if entry.is_some() {
+            self.position += 1;
+        }
+
+        Poll::Ready(entry.map(Ok))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.listing.len().saturating_sub(self.position);
+
+        (len, Some(len))
+    }
+}
+
+/// keep the full directory listing of each open directory handle, and serve
+/// [`readdir`][crate::path::PathFilesystem::readdir] or
+/// [`readdirplus`][crate::path::PathFilesystem::readdirplus] from it.
+///
+/// the listing is loaded once when kernel reads from offset 0, the offset of each entry is its
+/// position in the listing, so the following reads of the same `fh` won't duplicate or miss
+/// entries even if the directory is modified concurrently. When kernel reads from offset 0 again,
+/// such as `rewinddir(3)`, the listing is loaded again. The entries added or removed after the
+/// listing is loaded are visible after rewind.
+///
+/// # Notes:
+///
+/// the `fh` must be unique for each open directory, use [`open`][ReaddirCache::open] to allocate
+/// one in [`opendir`][crate::path::PathFilesystem::opendir] if the filesystem doesn't have its
+/// own, and call [`release`][ReaddirCache::release] in
+/// [`releasedir`][crate::path::PathFilesystem::releasedir], or the listing is leaked.
+///
+/// # Examples:
+///
+/// ```no_run
+/// # use std::ffi::OsStr;
+/// # use fuse3::path::prelude::*;
+/// # use fuse3::path::{ReaddirCache, ReaddirCacheStream};
+/// # use fuse3::Result;
+/// # async fn list(path: &OsStr) -> Result<Vec<DirectoryEntry>> { unimplemented!() }
+/// # async fn example(cache: &ReaddirCache<DirectoryEntry>, path: &OsStr, fh: u64, offset: i64)
+/// # -> Result<ReplyDirectory<ReaddirCacheStream<DirectoryEntry>>> {
+/// // in readdir
+/// let entries = cache.readdir(fh, offset, || list(path)).await?;
+///
+/// Ok(ReplyDirectory { entries })
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ReaddirCache<E> {
+    listings: Mutex<HashMap<u64, Arc<[E]>>>,
+    next_fh: AtomicU64,
+}
+
+impl<E> Default for ReaddirCache<E> {
+    fn default() -> Self {
+        Self {
+            listings: Default::default(),
+            next_fh: AtomicU64::new(1),
+        }
+    }
+}
+
+impl<E: ReaddirEntry> ReaddirCache<E> {
+    /// create an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// allocate a unique `fh` for an open directory.
+    pub fn open(&self) -> u64 {
+        self.next_fh.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// get the entries after `offset` of the `fh` listing. `list` is called to load the whole
+    /// listing when `offset` is 0 or the `fh` listing is not loaded yet, the `offset` of the
+    /// entries returned by `list` is ignored.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use fuse3::path::prelude::*;
+    /// # use futures_util::stream::TryStreamExt;
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let cache = ReaddirCache::new();
+    /// let fh = cache.open();
+    ///
+    /// let list = || async {
+    ///     Ok(["a", "b", "c"]
+    ///         .map(|name| DirectoryEntry {
+    ///             kind: FileType::RegularFile,
+    ///             name: name.into(),
+    ///             offset: 0,
+    ///         })
+    ///         .to_vec())
+    /// };
+    ///
+    /// let entries = cache.readdir(fh, 0, list).await.unwrap();
+    /// assert_eq!(entries.try_collect::<Vec<_>>().await.unwrap().len(), 3);
+    ///
+    /// // the listing is kept, the following read starts after the offset of the last entry
+    /// let entries = cache
+    ///     .readdir(fh, 2, || async { unreachable!("the listing is loaded again") })
+    ///     .await
+    ///     .unwrap()
+    ///     .try_collect::<Vec<_>>()
+    ///     .await
+    ///     .unwrap();
+    /// assert_eq!(entries[0].name, "c");
+    /// assert_eq!(entries[0].offset, 3);
+    /// # }
+    /// ```
+    pub async fn readdir<F, Fut>(
+        &self,
+        fh: u64,
+        offset: i64,
+        list: F,
+    ) -> Result<ReaddirCacheStream<E>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<E>>>,
+    {
+        let listing = match self.get(fh) {
+            Some(listing) if offset != 0 => listing,
+
+            _ => {
+                let mut entries = list().await?;
+                for (index, entry) in entries.iter_mut().enumerate() {
+                    entry.set_offset(index as i64 + 1);
+                }
+
+                let listing = Arc::<[E]>::from(entries);
+
+                self.listings
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .insert(fh, listing.clone());
+
+                listing
+            }
+        };
+
+        let position = (offset.max(0) as usize).min(listing.len());
+
+        Ok(ReaddirCacheStream { listing, position })
+    }
+
+    /// drop the `fh` listing.
+    pub fn release(&self, fh: u64) {
+        self.listings
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(&fh);
+    }
+
+    fn get(&self, fh: u64) -> Option<Arc<[E]>> {
+        self.listings
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .get(&fh)
+            .cloned()
+    }
+}
@@ -1,5 +1,6 @@
 //! reply structures.
 use std::ffi::OsString;
+use std::fs::Metadata;
 use std::num::NonZeroU32;
 use std::time::{Duration, SystemTime};
 
@@ -7,18 +8,23 @@ use futures_util::stream::Stream;
 
 #[cfg(feature = "file-lock")]
 pub use crate::raw::reply::ReplyLock;
+#[cfg(target_os = "macos")]
+pub use crate::raw::reply::ReplyXTimes;
+use crate::raw::reply::BLOCK_UNIT_SIZE;
 pub use crate::raw::reply::{
-    ReplyBmap, ReplyCopyFileRange, ReplyData, ReplyLSeek, ReplyOpen, ReplyPoll, ReplyStatFs,
-    ReplyWrite, ReplyXAttr,
+    IoctlIovec, ReplyBmap, ReplyCopyFileRange, ReplyData, ReplyIoctl, ReplyLSeek, ReplyOpen,
+    ReplyPoll, ReplyReadBuf, ReplyStatFs, ReplyWrite, ReplyXAttr, MAX_TTL,
 };
-use crate::{FileType, Inode, Result};
+use crate::{mode_from_kind_and_perm, FileType, Inode, Result, Timestamp};
 
 /// file attributes
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub struct FileAttr {
     /// Size in bytes
     pub size: u64,
-    /// Size in blocks
+    /// Size in 512-byte blocks, it is what `st_blocks` of `stat(2)` reports and what `du` uses,
+    /// no matter what the [`blksize`][Self::blksize] is. Use [`set_size`][Self::set_size] to
+    /// update it with the size.
     pub blocks: u64,
     /// Time of last access
     pub atime: SystemTime,
@@ -26,9 +32,9 @@ pub struct FileAttr {
     pub mtime: SystemTime,
     /// Time of last change
     pub ctime: SystemTime,
-    #[cfg(target_os = "macos")]
-    /// Time of creation (macOS only)
-    pub crtime: SystemTime,
+    /// Time of creation, the birth time, `None` means unknown. It is only replied on macOS, the
+    /// attribute reply of Linux and FreeBSD doesn't carry it, so it is ignored there.
+    pub crtime: Option<SystemTime>,
     /// Kind of file (directory, file, pipe, etc)
     pub kind: FileType,
     /// Permissions
@@ -47,6 +53,88 @@ pub struct FileAttr {
     pub blksize: u32,
 }
 
+impl FileAttr {
+    /// set the size in bytes, and set [`blocks`][Self::blocks] to the number of 512-byte blocks
+    /// which cover the size, that is `size.div_ceil(512)`.
+    ///
+    /// # Notes:
+    ///
+    /// the blocks is the allocated space, not the filesystem blocks of
+    /// [`blksize`][Self::blksize]. When the allocated space differs from the size, such as a
+    /// sparse or compressed file, set `blocks` after calling it.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use fuse3::path::reply::FileAttr;
+    /// let mut attr = FileAttr::from_metadata(&std::fs::metadata(".").unwrap());
+    /// attr.set_size(512);
+    ///
+    /// assert_eq!(attr.size, 512);
+    /// assert_eq!(attr.blocks, 1);
+    /// ```
+    pub fn set_size(&mut self, size: u64) -> &mut Self {
+        self.size = size;
+        self.blocks = size.div_ceil(BLOCK_UNIT_SIZE);
+
+        self
+    }
+
+    /// the mode of the file, which combines the file type bits of [`kind`][Self::kind] and the
+    /// permission bits of [`perm`][Self::perm], like the `st_mode` of `stat(2)`.
+    pub const fn mode(&self) -> u32 {
+        mode_from_kind_and_perm(self.kind, self.perm)
+    }
+
+    /// set [`kind`][Self::kind] and [`perm`][Self::perm] from a mode, the permission bits include
+    /// the suid, sgid and sticky bits. If `mode` doesn't carry a known file type, such as the mode
+    /// of chmod, `kind` is kept.
+    pub fn set_mode(&mut self, mode: u32) -> &mut Self {
+        if let Some(kind) = FileType::from_mode(mode) {
+            self.kind = kind;
+        }
+        self.perm = (mode & 0o7777) as u16;
+
+        self
+    }
+
+    /// build the attributes from the [`Metadata`] of a file, such as the backing file of a
+    /// passthrough filesystem, see
+    /// [`raw::reply::FileAttr::from_metadata`][crate::raw::reply::FileAttr::from_metadata]. The
+    /// inode is allocated by fuse3, the inode number of the backing file is not used.
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        let attr = crate::raw::reply::FileAttr::from_metadata(0, metadata);
+
+        Self {
+            size: attr.size,
+            blocks: attr.blocks,
+            atime: system_time(attr.atime),
+            mtime: system_time(attr.mtime),
+            ctime: system_time(attr.ctime),
+            crtime: attr.crtime.map(system_time),
+            kind: attr.kind,
+            perm: attr.perm,
+            nlink: attr.nlink,
+            uid: attr.uid,
+            gid: attr.gid,
+            rdev: attr.rdev,
+            #[cfg(target_os = "macos")]
+            flags: attr.flags,
+            blksize: attr.blksize,
+        }
+    }
+}
+
+fn system_time(timestamp: Timestamp) -> SystemTime {
+    let nsec = Duration::from_nanos(timestamp.nsec.into());
+
+    if timestamp.sec >= 0 {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp.sec as u64) + nsec
+    } else {
+        SystemTime::UNIX_EPOCH - Duration::from_secs(timestamp.sec.unsigned_abs()) + nsec
+    }
+}
+
 impl From<(Inode, FileAttr)> for crate::raw::reply::FileAttr {
     fn from((inode, attr): (u64, FileAttr)) -> Self {
         crate::raw::reply::FileAttr {
@@ -56,12 +144,15 @@ impl From<(Inode, FileAttr)> for crate::raw::reply::FileAttr {
             atime: attr.atime.into(),
             mtime: attr.mtime.into(),
             ctime: attr.ctime.into(),
+            crtime: attr.crtime.map(Into::into),
             kind: attr.kind,
             perm: attr.perm,
             nlink: attr.nlink,
             uid: attr.uid,
             gid: attr.gid,
             rdev: attr.rdev,
+            #[cfg(target_os = "macos")]
+            flags: attr.flags,
             blksize: attr.blksize,
         }
     }
@@ -77,36 +168,75 @@ pub struct ReplyInit {
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// entry reply.
 pub struct ReplyEntry {
-    /// the attribute TTL.
+    /// the entry and attribute TTL, it is clamped to [`MAX_TTL`]. A zero TTL makes kernel lookup
+    /// the path again every time, which is the correct choice for volatile filesystems whose
+    /// entries may change without notifying kernel.
     pub ttl: Duration,
     /// the attribute.
     pub attr: FileAttr,
 }
 
+impl ReplyEntry {
+    /// create an entry reply, `ttl` is used as both the entry and attribute TTL, and is clamped
+    /// to [`MAX_TTL`].
+    pub fn new(attr: FileAttr, ttl: Duration) -> Self {
+        Self {
+            ttl: ttl.min(MAX_TTL),
+            attr,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// reply attr.
 pub struct ReplyAttr {
-    /// the attribute TTL.
+    /// the attribute TTL, it is clamped to [`MAX_TTL`]. A zero TTL makes kernel getattr again
+    /// every time, which is the correct choice for volatile filesystems.
     pub ttl: Duration,
     /// the attribute.
     pub attr: FileAttr,
 }
 
+impl ReplyAttr {
+    /// create an attribute reply, `ttl` is clamped to [`MAX_TTL`].
+    pub fn new(attr: FileAttr, ttl: Duration) -> Self {
+        Self {
+            ttl: ttl.min(MAX_TTL),
+            attr,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
-/// crate reply.
+/// create reply, it carries both the new entry and the open file handle, kernel gets them in
+/// one reply, so the file is created and opened atomically.
 pub struct ReplyCreated {
-    /// the attribute TTL.
+    /// the entry and attribute TTL, it is clamped to [`MAX_TTL`].
     pub ttl: Duration,
     /// the attribute of file.
     pub attr: FileAttr,
-    /// the generation of file.
+    /// the generation of file, it is ignored, fuse3 allocates the inode and its generation.
     pub generation: u64,
     /// the file handle.
     pub fh: u64,
-    /// the flags.
+    /// the `FOPEN_*` flags, see [`FopenFlags`][crate::raw::flags::FopenFlags].
     pub flags: u32,
 }
 
+impl ReplyCreated {
+    /// create a create reply from the `entry` of the new file and the `open` reply of it, the
+    /// `direct_io` and `keep_cache` flags of `open` are kept.
+    pub fn new(entry: ReplyEntry, open: ReplyOpen) -> Self {
+        Self {
+            ttl: entry.ttl.min(MAX_TTL),
+            attr: entry.attr,
+            generation: 0,
+            fh: open.fh,
+            flags: open.flags,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// directory entry.
 pub struct DirectoryEntry {
@@ -119,6 +249,14 @@ pub struct DirectoryEntry {
 }
 
 /// readdir reply.
+///
+/// # Notes:
+///
+/// the path layer collects all entries of `entries` into memory to assign their inodes, so the
+/// stream doesn't need to be lazy, for directories with millions of entries, return only a part
+/// of the entries after `offset` in each readdir, or use the raw
+/// [`ReplyDirectory`][crate::raw::reply::ReplyDirectory] which only pulls the entries fit in the
+/// reply buffer.
 pub struct ReplyDirectory<S: Stream<Item = Result<DirectoryEntry>>> {
     pub entries: S,
 }
@@ -142,13 +280,25 @@ pub struct DirectoryEntryPlus {
     pub offset: i64,
     /// the entry attribute.
     pub attr: FileAttr,
-    /// the entry TTL.
+    /// the entry TTL, it is clamped to [`MAX_TTL`].
     pub entry_ttl: Duration,
-    /// the attribute TTL.
+    /// the attribute TTL, it is clamped to [`MAX_TTL`].
     pub attr_ttl: Duration,
 }
 
+impl DirectoryEntryPlus {
+    /// set the entry and attribute TTL together, `ttl` is clamped to [`MAX_TTL`].
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.entry_ttl = ttl.min(MAX_TTL);
+        self.attr_ttl = self.entry_ttl;
+
+        self
+    }
+}
+
 /// the readdirplus reply.
+///
+/// `entries` is collected into memory like [`ReplyDirectory::entries`].
 pub struct ReplyDirectoryPlus<S: Stream<Item = Result<DirectoryEntryPlus>>> {
     pub entries: S,
 }
@@ -1,23 +1,234 @@
+use std::fmt::{self, Debug, Formatter};
 use std::io;
+use std::os::fd::OwnedFd;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::path::inode_path_bridge::InodePathBridge;
 use crate::path::path_filesystem::PathFilesystem;
 use crate::raw;
+use crate::raw::SessionObserver;
 use crate::MountOptions;
 
 #[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
-#[derive(Debug)]
 /// fuse filesystem session, path based.
 pub struct Session {
     mount_options: MountOptions,
+    observer: Option<Arc<dyn SessionObserver>>,
+    relative_path: bool,
+    negative_timeout: Option<Duration>,
+}
+
+#[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
+impl Debug for Session {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("mount_options", &self.mount_options)
+            .field("relative_path", &self.relative_path)
+            .field("negative_timeout", &self.negative_timeout)
+            .finish_non_exhaustive()
+    }
 }
 
 #[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
 impl Session {
     /// new a fuse filesystem session.
     pub fn new(mount_options: MountOptions) -> Self {
-        Self { mount_options }
+        Self {
+            mount_options,
+            observer: None,
+            relative_path: false,
+            negative_timeout: None,
+        }
+    }
+
+    /// set the [`SessionObserver`] which observes the requests handled by the filesystem.
+    pub fn observer(mut self, observer: Arc<dyn SessionObserver>) -> Self {
+        self.observer.replace(observer);
+
+        self
+    }
+
+    /// pass the paths relative to the mount root to the [`PathFilesystem`], such as `dir/file`
+    /// and `.` for the root, instead of the default `/dir/file` and `/`. The relative paths can
+    /// be passed to `openat(2)` with a fd of the backing directory directly.
+    ///
+    /// # Notes:
+    ///
+    /// both kinds of path are joined from the names which kernel passes in lookup, create and
+    /// other requests, or which the filesystem replies in readdir, no other normalization is
+    /// applied:
+    ///
+    /// - symlinks are never resolved, a symlink component is passed as is and it is up to the
+    ///   filesystem to follow it or not.
+    /// - the root is `/`, or `.` with relative paths. Other paths never contain `.` or `..`
+    ///   components, repeated or trailing `/`, a relative path doesn't start with `./` either.
+    /// - the `parent` and `name` arguments are passed separately, joining a name to the relative
+    ///   root by [`Path::join`] gives `./name`, which is the same file as `name` for `openat(2)`.
+    /// - a name never contains `/` or a null byte, kernel rejects such names before sending the
+    ///   request, and the names replied in readdir must follow the same rule.
+    /// - the path length is not limited by `PATH_MAX`, a deeply nested file may have a path
+    ///   longer than it.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use std::ffi::{OsStr, OsString};
+    /// # use std::sync::{Arc, Mutex};
+    /// # use std::time::Duration;
+    /// # use fuse3::path::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// #[derive(Default)]
+    /// struct Fs {
+    ///     parents: Arc<Mutex<Vec<OsString>>>,
+    /// }
+    ///
+    /// impl PathFilesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #   }
+    /// #
+    /// #   async fn destroy(&self, _req: Request) {}
+    /// #
+    ///     async fn lookup(&self, _req: Request, parent: &OsStr, _name: &OsStr)
+    ///         -> Result<ReplyEntry> {
+    ///         self.parents.lock().unwrap().push(parent.to_owned());
+    ///
+    ///         let attr = FileAttr::from_metadata(&std::fs::metadata(".").unwrap());
+    ///
+    ///         Ok(ReplyEntry::new(attr, Duration::from_secs(1)))
+    ///     }
+    /// }
+    ///
+    /// /// look up `a/b/c` and return the parents passed to the filesystem.
+    /// async fn lookup_parents(relative_path: bool) -> Vec<OsString> {
+    ///     let fs = Fs::default();
+    ///     let parents = fs.parents.clone();
+    ///
+    ///     let session = Session::new(MountOptions::default()).relative_path(relative_path);
+    ///     let mut session = TestSession::new_path(fs, session);
+    ///     session.init(request(0, 0, 0)).await.unwrap();
+    ///
+    ///     let mut inode = 1;
+    ///     for name in ["a", "b", "c"] {
+    ///         let entry = session.lookup(request(0, 0, 0), inode, name.as_ref()).await.unwrap();
+    ///         inode = entry.attr.ino;
+    ///     }
+    ///
+    ///     let parents = parents.lock().unwrap().clone();
+    ///     parents
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// assert_eq!(lookup_parents(false).await, ["/", "/a", "/a/b"]);
+    /// assert_eq!(lookup_parents(true).await, [".", "a", "a/b"]);
+    /// # }
+    /// ```
+    pub fn relative_path(mut self, relative_path: bool) -> Self {
+        self.relative_path = relative_path;
+
+        self
+    }
+
+    /// let kernel cache the missing names for `ttl`, like the `negative_timeout` option of
+    /// libfuse. When [`lookup`][PathFilesystem::lookup] returns `ENOENT`, a negative entry with
+    /// the `ttl` is replied instead, and kernel won't look up the same name again until the `ttl`
+    /// expires or the name is created through the filesystem. Default is disable, every lookup
+    /// of a missing name reaches the filesystem.
+    ///
+    /// # Notes:
+    ///
+    /// the filesystem whose names may be created without going through kernel, such as a
+    /// network filesystem, should keep the `ttl` short or disable it.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use std::ffi::OsStr;
+    /// # use std::time::Duration;
+    /// # use fuse3::path::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// struct Fs;
+    ///
+    /// impl PathFilesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #   }
+    /// #
+    /// #   async fn destroy(&self, _req: Request) {}
+    /// #
+    ///     async fn lookup(&self, _req: Request, _parent: &OsStr, _name: &OsStr)
+    ///         -> Result<ReplyEntry> {
+    ///         Err(libc::ENOENT.into())
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let session = Session::new(MountOptions::default());
+    /// let session = session.negative_timeout(Duration::from_secs(1));
+    /// let mut session = TestSession::new_path(Fs, session);
+    /// session.init(request(0, 0, 0)).await.unwrap();
+    ///
+    /// let mut message = Vec::new();
+    /// message.extend_from_slice(&48u32.to_ne_bytes()); // len
+    /// message.extend_from_slice(&1u32.to_ne_bytes()); // FUSE_LOOKUP
+    /// message.extend_from_slice(&100u64.to_ne_bytes()); // unique
+    /// message.extend_from_slice(&1u64.to_ne_bytes()); // nodeid
+    /// message.extend_from_slice(&[0; 16]); // uid, gid, pid and padding
+    /// message.extend_from_slice(b"missing\0");
+    ///
+    /// // fuse_entry_out starts with nodeid, generation, entry_valid and attr_valid
+    /// let entry_out = session.send_message(&message).await.unwrap();
+    /// let field = |i: usize| u64::from_ne_bytes(entry_out[i * 8..][..8].try_into().unwrap());
+    /// assert_eq!(field(0), 0);
+    /// assert_eq!(field(2), 1);
+    /// # }
+    /// ```
+    pub fn negative_timeout(mut self, ttl: Duration) -> Self {
+        self.negative_timeout.replace(ttl);
+
+        self
+    }
+
+    /// split the session into the mount options, the observer and the bridge of `fs`, used by
+    /// [`TestSession::new_path`][crate::test::TestSession::new_path].
+    pub(crate) fn into_bridge<FS>(
+        self,
+        fs: FS,
+    ) -> (
+        MountOptions,
+        Option<Arc<dyn SessionObserver>>,
+        InodePathBridge<FS>,
+    ) {
+        let bridge = self.bridge(fs);
+
+        (self.mount_options, self.observer, bridge)
+    }
+
+    fn bridge<FS>(&self, fs: FS) -> InodePathBridge<FS> {
+        InodePathBridge::new(fs, self.relative_path, self.negative_timeout)
+    }
+
+    fn raw_session<FS>(self) -> raw::Session<FS> {
+        let session = raw::Session::new(self.mount_options);
+
+        match self.observer {
+            None => session,
+            Some(observer) => session.observer(observer),
+        }
     }
 
     #[cfg(feature = "unprivileged")]
@@ -32,9 +243,9 @@ impl Session {
         P: AsRef<Path>,
         FS: PathFilesystem + Send + Sync + 'static,
     {
-        let bridge = InodePathBridge::new(fs);
+        let bridge = self.bridge(fs);
 
-        raw::Session::new(self.mount_options)
+        self.raw_session()
             .mount_with_unprivileged(bridge, mount_path)
             .await
     }
@@ -46,10 +257,19 @@ impl Session {
         P: AsRef<Path>,
         FS: PathFilesystem + Send + Sync + 'static,
     {
-        let bridge = InodePathBridge::new(fs);
+        let bridge = self.bridge(fs);
 
-        raw::Session::new(self.mount_options)
-            .mount(bridge, mount_path)
-            .await
+        self.raw_session().mount(bridge, mount_path).await
+    }
+
+    /// serve the filesystem on a `/dev/fuse` fd which is already opened and mounted by others,
+    /// see [`raw::Session::mount_with_fd`] for the ownership of `fd` and the ignored options.
+    pub async fn mount_with_fd<FS>(self, fs: FS, fd: OwnedFd) -> io::Result<raw::MountHandle>
+    where
+        FS: PathFilesystem + Send + Sync + 'static,
+    {
+        let bridge = self.bridge(fs);
+
+        self.raw_session().mount_with_fd(bridge, fd).await
     }
 }
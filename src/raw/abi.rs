@@ -21,6 +21,8 @@
 use std::error::Error;
 use std::fmt::{self, Debug, Display, Formatter};
 use std::mem;
+#[cfg(not(target_os = "macos"))]
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
@@ -32,17 +34,48 @@ use serde::{Deserialize, Serialize};
 /// ```
 pub const FUSE_MIN_READ_BUFFER_SIZE: usize = 8 * 1024;
 
+/// The max size of a single read or write request. In Linux kernel the max pages of a request is
+/// limited by `FUSE_DEFAULT_MAX_PAGES_LIMIT`
+///
+/// ```c
+/// /** Maximum of max_pages received in init_out */
+/// #define FUSE_DEFAULT_MAX_PAGES_LIMIT 256
+/// ```
+pub const FUSE_MAX_REQUEST_SIZE: u32 = 256 * 4096;
+
 pub const FUSE_KERNEL_VERSION: u32 = 7;
 
+/// the inode of the filesystem root.
+pub const FUSE_ROOT_ID: u64 = 1;
+
 pub const FUSE_KERNEL_MINOR_VERSION: u32 = 31;
 
+/// since this minor version, kernel sends the umask in `fuse_mknod_in` and `fuse_create_in`.
+pub const FUSE_UMASK_MINOR_VERSION: u32 = 12;
+
+/// since this minor version, kernel accepts the `time_gran` and the later fields of
+/// `fuse_init_out`.
+pub const FUSE_TIME_GRAN_MINOR_VERSION: u32 = 23;
+
+#[cfg(not(target_os = "macos"))]
+/// since this minor version, kernel sends the security context as a request extension, the older
+/// kernel appends it as an extra argument.
+pub const FUSE_EXTENSIONS_MINOR_VERSION: u32 = 38;
+
 pub const DEFAULT_MAX_BACKGROUND: u16 = 12;
 
-pub const DEFAULT_CONGESTION_THRESHOLD: u16 = DEFAULT_MAX_BACKGROUND * 3 / 4;
+#[cfg(not(target_os = "macos"))]
+pub const DEFAULT_OPEN_DEVICE_RETRY_DELAY: Duration = Duration::from_millis(10);
 
 pub const DEFAULT_TIME_GRAN: u32 = 1;
 
-pub const DEFAULT_MAX_PAGES: u16 = u16::MAX;
+/// the max pages of a request when `FUSE_MAX_PAGES` is not negotiated, in Linux kernel it is
+/// `FUSE_DEFAULT_MAX_PAGES_PER_REQ`.
+pub const FUSE_DEFAULT_MAX_PAGES_PER_REQ: u16 = 32;
+
+/// kernel clamps the `max_pages` of init reply to `/proc/sys/fs/fuse/max_pages_limit`, this is
+/// its default value, and the fixed limit before Linux 6.13.
+pub const FUSE_DEFAULT_MAX_PAGES_LIMIT: u16 = 256;
 
 // TODO find valid value
 pub const DEFAULT_MAP_ALIGNMENT: u16 = 0;
@@ -73,7 +106,6 @@ pub const FATTR_FLAGS: u32 = 1 << 31;
 /// asynchronous read requests
 pub const FUSE_ASYNC_READ: u32 = 1 << 0;
 
-#[cfg(feature = "file-lock")]
 /// locking for POSIX file locks
 pub const FUSE_POSIX_LOCKS: u32 = 1 << 1;
 
@@ -104,11 +136,9 @@ pub const FUSE_SPLICE_MOVE: u32 = 1 << 8;
 /// kernel supports splice read on the device
 pub const FUSE_SPLICE_READ: u32 = 1 << 9;
 
-#[allow(dead_code)]
 /// locking for BSD style file locks
 pub const FUSE_FLOCK_LOCKS: u32 = 1 << 10;
 
-#[allow(dead_code)]
 /// kernel supports ioctl on directories
 pub const FUSE_HAS_IOCTL_DIR: u32 = 1 << 11;
 
@@ -140,7 +170,6 @@ pub const FUSE_HANDLE_KILLPRIV: u32 = 1 << 19;
 /// filesystem supports posix acls
 pub const FUSE_POSIX_ACL: u32 = 1 << 20;
 
-#[allow(dead_code)]
 /// reading the device after abort returns ECONNABORTED
 pub const FUSE_ABORT_ERROR: u32 = 1 << 21;
 
@@ -153,14 +182,16 @@ pub const FUSE_CACHE_SYMLINKS: u32 = 1 << 23;
 /// kernel supports zero-message opendir
 pub const FUSE_NO_OPENDIR_SUPPORT: u32 = 1 << 24;
 
-#[allow(dead_code)]
 /// only invalidate cached pages on explicit request
 pub const FUSE_EXPLICIT_INVAL_DATA: u32 = 1 << 25;
 
-#[allow(dead_code)]
 /// map_alignment field is valid
 pub const FUSE_MAP_ALIGNMENT: u32 = 1 << 26;
 
+#[cfg(not(target_os = "macos"))]
+/// extended fuse_init_in request, the flags2 is valid
+pub const FUSE_INIT_EXT: u32 = 1 << 30;
+
 #[cfg(target_os = "macos")]
 pub const FUSE_ALLOCATE: u32 = 1 << 27;
 #[cfg(target_os = "macos")]
@@ -172,10 +203,37 @@ pub const FUSE_VOL_RENAME: u32 = 1 << 30;
 #[cfg(target_os = "macos")]
 pub const FUSE_XTIMES: u32 = 1 << 31;
 
+// the flags2 of init request/reply, the bit 32 to 63 of the init flags
+
+#[cfg(not(target_os = "macos"))]
+/// add security context to create, mkdir, symlink and mknod
+pub const FUSE_SECURITY_CTX: u32 = 1 << 0;
+
+#[cfg(not(target_os = "macos"))]
+/// add supplementary group info to create, mkdir, symlink and mknod (single group that matches
+/// parent)
+pub const FUSE_CREATE_SUPP_GROUP: u32 = 1 << 2;
+
 // CUSE init request/reply flags
 // use unrestricted ioctl
 // pub const CUSE_UNRESTRICTED_IOCTL: u32 = 1 << 0;
 
+// Open reply flags
+/// bypass page cache for this open file
+pub const FOPEN_DIRECT_IO: u32 = 1 << 0;
+
+/// don't invalidate the data cache on open
+pub const FOPEN_KEEP_CACHE: u32 = 1 << 1;
+
+/// the file is not seekable
+pub const FOPEN_NONSEEKABLE: u32 = 1 << 2;
+
+/// allow caching this directory
+pub const FOPEN_CACHE_DIR: u32 = 1 << 3;
+
+/// the file is stream-like (no file position at all)
+pub const FOPEN_STREAM: u32 = 1 << 4;
+
 // Release flags
 pub const FUSE_RELEASE_FLUSH: u32 = 1 << 0;
 
@@ -198,32 +256,29 @@ pub const FUSE_WRITE_CACHE: u32 = 1 << 0;
 /// lock_owner field is valid
 pub const FUSE_WRITE_LOCKOWNER: u32 = 1 << 1;
 
+/// kill suid and sgid bits
+pub const FUSE_WRITE_KILL_PRIV: u32 = 1 << 2;
+
 #[allow(dead_code)]
 // Read flags
 pub const FUSE_READ_LOCKOWNER: u32 = 1 << 1;
 
 // IOCTL flags
-#[allow(dead_code)]
 /// 32bit compat ioctl on 64bit machine
 pub const FUSE_IOCTL_COMPAT: u32 = 1 << 0;
 
-#[allow(dead_code)]
 /// not restricted to well-formed ioctls, retry allowed
 pub const FUSE_IOCTL_UNRESTRICTED: u32 = 1 << 1;
 
-#[allow(dead_code)]
 /// retry with new iovecs
 pub const FUSE_IOCTL_RETRY: u32 = 1 << 2;
 
-#[allow(dead_code)]
 /// 32bit ioctl
 pub const FUSE_IOCTL_32BIT: u32 = 1 << 3;
 
-#[allow(dead_code)]
 /// is a directory
 pub const FUSE_IOCTL_DIR: u32 = 1 << 4;
 
-#[allow(dead_code)]
 /// maximum of in_iovecs + out_iovecs
 pub const FUSE_IOCTL_MAX_IOV: u32 = 256;
 
@@ -231,7 +286,7 @@ pub const FUSE_IOCTL_MAX_IOV: u32 = 256;
 /// request poll notify
 pub const FUSE_POLL_SCHEDULE_NOTIFY: u32 = 1 << 0;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_attr {
     pub ino: u64,
@@ -284,6 +339,7 @@ pub struct fuse_kstatfs {
 
 #[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
+#[cfg_attr(not(feature = "file-lock"), allow(dead_code))]
 pub struct fuse_file_lock {
     pub start: u64,
     pub end: u64,
@@ -295,7 +351,7 @@ pub struct fuse_file_lock {
 #[derive(Debug)]
 pub struct UnknownOpcodeError(pub u32);
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 pub enum fuse_opcode {
     FUSE_LOOKUP = 1,
@@ -338,8 +394,7 @@ pub enum fuse_opcode {
     FUSE_INTERRUPT = 36,
     FUSE_BMAP = 37,
     FUSE_DESTROY = 38,
-    // TODO implement it after get enough info about it
-    // FUSE_IOCTL = 39,
+    FUSE_IOCTL = 39,
     FUSE_POLL = 40,
     FUSE_NOTIFY_REPLY = 41,
     FUSE_BATCH_FORGET = 42,
@@ -350,12 +405,15 @@ pub enum fuse_opcode {
     FUSE_COPY_FILE_RANGE = 47,
     // FUSE_SETUPMAPPING = 48,
     // FUSE_REMOVEMAPPING = 49,
+    FUSE_SYNCFS = 50,
     #[cfg(target_os = "macos")]
     FUSE_SETVOLNAME = 61,
     #[cfg(target_os = "macos")]
     FUSE_GETXTIMES = 62,
     #[cfg(target_os = "macos")]
     FUSE_EXCHANGE = 63,
+    /// only sent by the Android kernels.
+    FUSE_CANONICAL_PATH = 2016,
     // CUSE_INIT = 4096,
 }
 
@@ -365,6 +423,19 @@ impl Display for fuse_opcode {
     }
 }
 
+impl fuse_opcode {
+    /// whether kernel waits the reply of this request.
+    pub(crate) const fn need_reply(self) -> bool {
+        !matches!(
+            self,
+            fuse_opcode::FUSE_FORGET
+                | fuse_opcode::FUSE_BATCH_FORGET
+                | fuse_opcode::FUSE_INTERRUPT
+                | fuse_opcode::FUSE_NOTIFY_REPLY
+        )
+    }
+}
+
 impl TryFrom<u32> for fuse_opcode {
     type Error = UnknownOpcodeError;
 
@@ -409,7 +480,7 @@ impl TryFrom<u32> for fuse_opcode {
             36 => Ok(fuse_opcode::FUSE_INTERRUPT),
             37 => Ok(fuse_opcode::FUSE_BMAP),
             38 => Ok(fuse_opcode::FUSE_DESTROY),
-            // 39 => Ok(fuse_opcode::FUSE_IOCTL),
+            39 => Ok(fuse_opcode::FUSE_IOCTL),
             40 => Ok(fuse_opcode::FUSE_POLL),
             41 => Ok(fuse_opcode::FUSE_NOTIFY_REPLY),
             42 => Ok(fuse_opcode::FUSE_BATCH_FORGET),
@@ -420,12 +491,14 @@ impl TryFrom<u32> for fuse_opcode {
             47 => Ok(fuse_opcode::FUSE_COPY_FILE_RANGE),
             // 48 => Ok(fuse_opcode::FUSE_SETUPMAPPING),
             // 49 => Ok(fuse_opcode::FUSE_REMOVEMAPPING),
+            50 => Ok(fuse_opcode::FUSE_SYNCFS),
             #[cfg(target_os = "macos")]
             61 => Ok(fuse_opcode::FUSE_SETVOLNAME),
             #[cfg(target_os = "macos")]
             62 => Ok(fuse_opcode::FUSE_GETXTIMES),
             #[cfg(target_os = "macos")]
             63 => Ok(fuse_opcode::FUSE_EXCHANGE),
+            2016 => Ok(fuse_opcode::FUSE_CANONICAL_PATH),
 
             // 4096 => Ok(fuse_opcode::CUSE_INIT),
             opcode => Err(UnknownOpcodeError(opcode)),
@@ -491,7 +564,7 @@ impl TryFrom<u32> for fuse_notify_code {
 
 pub const FUSE_ENTRY_OUT_SIZE: usize = mem::size_of::<fuse_entry_out>();
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_entry_out {
     pub nodeid: u64,
@@ -511,23 +584,23 @@ pub struct fuse_forget_in {
 
 pub const FUSE_FORGET_ONE_SIZE: usize = mem::size_of::<fuse_forget_one>();
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_forget_one {
     pub nodeid: u64,
-    pub (crate) _nlookup: u64,
+    pub nlookup: u64,
 }
 
 pub const FUSE_BATCH_FORGET_IN_SIZE: usize = mem::size_of::<fuse_batch_forget_in>();
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_batch_forget_in {
     pub count: u32,
     pub(crate) _dummy: u32,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_getattr_in {
     pub getattr_flags: u32,
@@ -537,7 +610,7 @@ pub struct fuse_getattr_in {
 
 pub const FUSE_ATTR_OUT_SIZE: usize = mem::size_of::<fuse_attr_out>();
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_attr_out {
     pub attr_valid: u64,
@@ -547,7 +620,10 @@ pub struct fuse_attr_out {
 }
 
 #[cfg(target_os = "macos")]
-#[derive(Debug)]
+pub const FUSE_GETXTIMES_OUT_SIZE: usize = mem::size_of::<fuse_getxtimes_out>();
+
+#[cfg(target_os = "macos")]
+#[derive(Debug, Serialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_getxtimes_out {
     pub bkuptime: u64,
@@ -563,10 +639,32 @@ pub const FUSE_MKNOD_IN_SIZE: usize = mem::size_of::<fuse_mknod_in>();
 pub struct fuse_mknod_in {
     pub mode: u32,
     pub rdev: u32,
-    pub(crate) _umask: u32,
+    pub umask: u32,
     _padding: u32,
 }
 
+/// the `fuse_mknod_in` sent by kernel which minor version is less than
+/// [`FUSE_UMASK_MINOR_VERSION`], it doesn't contain the umask.
+pub const FUSE_COMPAT_MKNOD_IN_SIZE: usize = mem::size_of::<fuse_compat_mknod_in>();
+
+#[derive(Debug, Deserialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_compat_mknod_in {
+    pub mode: u32,
+    pub rdev: u32,
+}
+
+impl From<fuse_compat_mknod_in> for fuse_mknod_in {
+    fn from(compat: fuse_compat_mknod_in) -> Self {
+        Self {
+            mode: compat.mode,
+            rdev: compat.rdev,
+            umask: 0,
+            _padding: 0,
+        }
+    }
+}
+
 pub const FUSE_MKDIR_IN_SIZE: usize = mem::size_of::<fuse_mkdir_in>();
 
 #[derive(Debug, Deserialize)]
@@ -586,12 +684,12 @@ pub struct fuse_rename_in {
 
 pub const FUSE_RENAME2_IN_SIZE: usize = mem::size_of::<fuse_rename2_in>();
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_rename2_in {
     pub newdir: u64,
     pub flags: u32,
-    _padding: u32,
+    pub(crate) _padding: u32,
 }
 
 #[cfg(target_os = "macos")]
@@ -646,7 +744,7 @@ pub struct fuse_setattr_in {
     pub flags: u32, // see chflags(2)
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_open_in {
     pub flags: u32,
@@ -660,13 +758,35 @@ pub const FUSE_CREATE_IN_SIZE: usize = mem::size_of::<fuse_create_in>();
 pub struct fuse_create_in {
     pub flags: u32,
     pub mode: u32,
-    pub(crate) _umask: u32,
+    pub umask: u32,
     _padding: u32,
 }
 
+/// the `fuse_create_in` sent by kernel which minor version is less than
+/// [`FUSE_UMASK_MINOR_VERSION`], it doesn't contain the umask.
+pub const FUSE_COMPAT_CREATE_IN_SIZE: usize = mem::size_of::<fuse_compat_create_in>();
+
+#[derive(Debug, Deserialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_compat_create_in {
+    pub flags: u32,
+    pub mode: u32,
+}
+
+impl From<fuse_compat_create_in> for fuse_create_in {
+    fn from(compat: fuse_compat_create_in) -> Self {
+        Self {
+            flags: compat.flags,
+            mode: compat.mode,
+            umask: 0,
+            _padding: 0,
+        }
+    }
+}
+
 pub const FUSE_OPEN_OUT_SIZE: usize = mem::size_of::<fuse_open_out>();
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_open_out {
     pub fh: u64,
@@ -674,7 +794,7 @@ pub struct fuse_open_out {
     pub(crate) _padding: u32,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_release_in {
     pub fh: u64,
@@ -683,16 +803,16 @@ pub struct fuse_release_in {
     pub lock_owner: u64,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_flush_in {
     pub fh: u64,
     pub(crate) _unused: u32,
-    _padding: u32,
+    pub(crate) _padding: u32,
     pub lock_owner: u64,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_read_in {
     pub fh: u64,
@@ -701,12 +821,12 @@ pub struct fuse_read_in {
     pub(crate) _read_flags: u32,
     pub lock_owner: u64,
     pub(crate) _flags: u32,
-    _padding: u32,
+    pub(crate) _padding: u32,
 }
 
 pub const FUSE_WRITE_IN_SIZE: usize = mem::size_of::<fuse_write_in>();
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_write_in {
     pub fh: u64,
@@ -715,12 +835,12 @@ pub struct fuse_write_in {
     pub write_flags: u32,
     pub(crate) _lock_owner: u64,
     pub flags: u32,
-    _padding: u32,
+    pub(crate) _padding: u32,
 }
 
 pub const FUSE_WRITE_OUT_SIZE: usize = mem::size_of::<fuse_write_out>();
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_write_out {
     pub size: u32,
@@ -758,20 +878,20 @@ pub struct fuse_setxattr_in {
 
 pub const FUSE_GETXATTR_IN_SIZE: usize = mem::size_of::<fuse_getxattr_in>();
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_getxattr_in {
     pub size: u32,
-    _padding: u32,
+    pub(crate) _padding: u32,
     #[cfg(target_os = "macos")]
     pub position: u32,
     #[cfg(target_os = "macos")]
-    _padding2: u32,
+    pub(crate) _padding2: u32,
 }
 
 pub const FUSE_GETXATTR_OUT_SIZE: usize = mem::size_of::<fuse_getxattr_out>();
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_getxattr_out {
     pub size: u32,
@@ -779,21 +899,21 @@ pub struct fuse_getxattr_out {
 }
 
 #[cfg(feature = "file-lock")]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_lk_in {
     pub fh: u64,
     pub owner: u64,
     pub lk: fuse_file_lock,
-    pub(crate) _lk_flags: u32,
-    _padding: u32,
+    pub(crate) lk_flags: u32,
+    pub(crate) _padding: u32,
 }
 
 #[cfg(feature = "file-lock")]
 pub const FUSE_LK_OUT_SIZE: usize = mem::size_of::<fuse_lk_out>();
 
 #[cfg(feature = "file-lock")]
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_lk_out {
     pub lk: fuse_file_lock,
@@ -806,17 +926,33 @@ pub struct fuse_access_in {
     _padding: u32,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_init_in {
     pub(crate) _major: u32,
-    pub(crate) _minor: u32,
+    pub minor: u32,
     pub max_readahead: u32,
     pub flags: u32,
 }
 
+#[cfg(not(target_os = "macos"))]
+pub const FUSE_INIT_IN_SIZE: usize = mem::size_of::<fuse_init_in>();
+
+#[cfg(not(target_os = "macos"))]
+/// the rest of `fuse_init_in` since 7.36, the `flags2` is valid when `FUSE_INIT_EXT` is set.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_init_in_ext {
+    pub flags2: u32,
+    pub(crate) _unused: [u32; 11],
+}
+
 pub const FUSE_INIT_OUT_SIZE: usize = mem::size_of::<fuse_init_out>();
 
+/// the `fuse_init_out` accepted by kernel which minor version is less than
+/// [`FUSE_TIME_GRAN_MINOR_VERSION`], such as macFUSE, it ends before the `time_gran`.
+pub const FUSE_COMPAT_22_INIT_OUT_SIZE: usize = 24;
+
 #[derive(Debug, Serialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_init_out {
@@ -830,7 +966,8 @@ pub struct fuse_init_out {
     pub time_gran: u32,
     pub max_pages: u16,
     pub map_alignment: u16,
-    pub unused: [u32; 8],
+    pub flags2: u32,
+    pub unused: [u32; 7],
 }
 
 /*#[derive(Debug)]
@@ -858,7 +995,7 @@ pub struct cuse_init_out {
     pub spare: [u32; 10],
 }*/
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_interrupt_in {
     pub unique: u64,
@@ -880,32 +1017,38 @@ pub struct fuse_bmap_out {
     pub block: u64,
 }
 
-//#[derive(Debug, Deserialize)]
-//#[allow(non_camel_case_types)]
-//pub struct fuse_ioctl_in {
-    //pub fh: u64,
-    //pub flags: u32,
-    //pub cmd: u32,
-    //pub arg: u64,
-    //pub in_size: u32,
-    //pub out_size: u32,
-//}
-
-//#[derive(Debug)]
-//#[allow(non_camel_case_types)]
-//pub struct fuse_ioctl_iovec {
-    //pub base: u64,
-    //pub len: u64,
-//}
-
-//#[derive(Debug)]
-//#[allow(non_camel_case_types)]
-//pub struct fuse_ioctl_out {
-    //pub result: i32,
-    //pub flags: u32,
-    //pub in_iovs: u32,
-    //pub out_iovs: u32,
-//}
+pub const FUSE_IOCTL_IN_SIZE: usize = mem::size_of::<fuse_ioctl_in>();
+
+#[derive(Debug, Deserialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_ioctl_in {
+    pub fh: u64,
+    pub flags: u32,
+    pub cmd: u32,
+    pub arg: u64,
+    pub in_size: u32,
+    pub out_size: u32,
+}
+
+pub const FUSE_IOCTL_IOVEC_SIZE: usize = mem::size_of::<fuse_ioctl_iovec>();
+
+#[derive(Debug, Serialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_ioctl_iovec {
+    pub base: u64,
+    pub len: u64,
+}
+
+pub const FUSE_IOCTL_OUT_SIZE: usize = mem::size_of::<fuse_ioctl_out>();
+
+#[derive(Debug, Serialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_ioctl_out {
+    pub result: i32,
+    pub flags: u32,
+    pub in_iovs: u32,
+    pub out_iovs: u32,
+}
 
 #[derive(Debug, Deserialize)]
 #[allow(non_camel_case_types)]
@@ -945,7 +1088,7 @@ pub struct fuse_fallocate_in {
 
 pub const FUSE_IN_HEADER_SIZE: usize = mem::size_of::<fuse_in_header>();
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_in_header {
     pub len: u32,
@@ -955,12 +1098,14 @@ pub struct fuse_in_header {
     pub uid: u32,
     pub gid: u32,
     pub pid: u32,
-    _padding: u32,
+    /// the size of the extensions appended after the arguments, in units of 8 bytes, since 7.38.
+    pub total_extlen: u16,
+    pub(crate) _padding: u16,
 }
 
 pub const FUSE_OUT_HEADER_SIZE: usize = mem::size_of::<fuse_out_header>();
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_out_header {
     pub len: u32,
@@ -970,7 +1115,7 @@ pub struct fuse_out_header {
 
 pub const FUSE_DIRENT_SIZE: usize = mem::size_of::<fuse_dirent>();
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_dirent {
     pub ino: u64,
@@ -1033,7 +1178,7 @@ pub struct fuse_notify_store_out {
 
 pub const FUSE_NOTIFY_RETRIEVE_OUT_SIZE: usize = mem::size_of::<fuse_notify_retrieve_out>();
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_notify_retrieve_out {
     pub notify_unique: u64,
@@ -1085,3 +1230,40 @@ pub struct fuse_copy_file_range_in {
     pub len: u64,
     pub flags: u64,
 }
+
+#[derive(Debug, Deserialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_syncfs_in {
+    _padding: u64,
+}
+
+/// the `total_extlen` of `fuse_in_header` is in units of this size, each extension is aligned to
+/// it too.
+pub const FUSE_EXT_ALIGN: usize = 8;
+
+/// the extension types which are not larger than it are the security context extension, the type
+/// is the number of the security contexts.
+pub const FUSE_MAX_NR_SECCTX: u32 = 31;
+
+/// the supplementary groups extension.
+pub const FUSE_EXT_GROUPS: u32 = 32;
+
+pub const FUSE_EXT_HEADER_SIZE: usize = mem::size_of::<fuse_ext_header>();
+
+/// the header of each extension, `size` includes the header itself.
+#[derive(Debug, Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_ext_header {
+    pub size: u32,
+    pub r#type: u32,
+}
+
+pub const FUSE_SECCTX_SIZE: usize = mem::size_of::<fuse_secctx>();
+
+/// followed by the null terminated xattr name and the context value of `size` bytes.
+#[derive(Debug, Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_secctx {
+    pub size: u32,
+    pub(crate) _padding: u32,
+}
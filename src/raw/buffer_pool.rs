@@ -0,0 +1,115 @@
+//! reuse the read buffers handed over to the large writes and bound their total memory.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_notify::Notify;
+use bytes::Bytes;
+
+/// the read buffers of the session, a large write takes the buffer which it is read into, the
+/// buffer is returned to the pool when the write request is done.
+#[derive(Debug)]
+pub(crate) struct BufferPool {
+    buffer_size: usize,
+    /// the max number of the allocated buffers, at least 1.
+    max_buffers: usize,
+    state: Mutex<PoolState>,
+    /// notified when a buffer is returned.
+    released: Notify,
+}
+
+#[derive(Debug, Default)]
+struct PoolState {
+    /// the number of the allocated buffers, the free ones included.
+    allocated: usize,
+    free: Vec<Vec<u8>>,
+}
+
+impl BufferPool {
+    pub(crate) fn new(buffer_size: usize, max_bytes: usize) -> Self {
+        Self {
+            buffer_size,
+            max_buffers: (max_bytes / buffer_size).max(1),
+            state: Default::default(),
+            released: Notify::new(),
+        }
+    }
+
+    /// get a free buffer or allocate a new one, when all buffers are in use, wait until one of
+    /// them is returned. Only one task should wait at the same time.
+    pub(crate) async fn acquire(&self) -> Vec<u8> {
+        loop {
+            if let Some(buffer) = self.try_acquire() {
+                return buffer;
+            }
+
+            self.released.notified().await;
+        }
+    }
+
+    fn try_acquire(&self) -> Option<Vec<u8>> {
+        let mut state = self.lock();
+
+        if let Some(buffer) = state.free.pop() {
+            return Some(buffer);
+        }
+
+        if state.allocated < self.max_buffers {
+            state.allocated += 1;
+
+            return Some(vec![0; self.buffer_size]);
+        }
+
+        None
+    }
+
+    /// hand over the buffer as [`Bytes`], it is returned to the pool when the returned
+    /// [`PooledBuffer`] is dropped.
+    pub(crate) fn hand_over(self: &Arc<Self>, buffer: Vec<u8>) -> (Bytes, PooledBuffer) {
+        let data = Bytes::from(buffer);
+
+        let pooled_buffer = PooledBuffer {
+            pool: self.clone(),
+            data: Some(data.clone()),
+        };
+
+        (data, pooled_buffer)
+    }
+
+    fn release(&self, mut buffer: Vec<u8>) {
+        {
+            let mut state = self.lock();
+
+            if buffer.capacity() >= self.buffer_size {
+                buffer.resize(self.buffer_size, 0);
+                state.free.push(buffer);
+            } else {
+                state.allocated -= 1;
+            }
+        }
+
+        self.released.notify();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// return the handed over buffer to the pool when dropped.
+///
+/// when the filesystem still holds the data, the buffer is copied back, so the pool won't wait
+/// for the filesystem.
+#[derive(Debug)]
+pub(crate) struct PooledBuffer {
+    pool: Arc<BufferPool>,
+    data: Option<Bytes>,
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if let Some(data) = self.data.take() {
+            // reuse the allocation without copy if no one else holds the data
+            self.pool.release(Vec::from(data));
+        }
+    }
+}
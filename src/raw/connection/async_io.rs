@@ -1,29 +1,22 @@
-#[cfg(target_os = "linux")]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
 use std::fs::File;
+#[cfg(not(target_os = "macos"))]
 use std::fs::OpenOptions;
 use std::io;
-#[cfg(target_os = "linux")]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
 use std::io::Write;
 use std::io::{IoSlice, IoSliceMut};
 use std::ops::{Deref, DerefMut};
-#[cfg(any(
-    all(target_os = "linux", feature = "unprivileged"),
-    target_os = "freebsd"
-))]
-use std::os::fd::OwnedFd;
-use std::os::fd::{AsFd, BorrowedFd};
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
 use std::os::unix::io::RawFd;
+#[cfg(target_os = "macos")]
+use std::path::Path;
 use std::pin::pin;
 use std::sync::Arc;
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
 use std::{ffi::OsString, path::Path};
 
-#[cfg(any(
-    all(target_os = "linux", feature = "unprivileged"),
-    target_os = "freebsd"
-))]
-use async_io::Async;
 use async_lock::Mutex;
 use async_notify::Notify;
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
@@ -41,8 +34,18 @@ use tracing::debug;
 
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
 use crate::find_fusermount3;
+#[cfg(target_os = "macos")]
+use crate::raw::connection::macfuse;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use crate::raw::connection::set_non_blocking;
+#[cfg(target_os = "linux")]
+use crate::raw::connection::splice::SplicedReply;
 use crate::raw::connection::CompleteIoResult;
-#[cfg(all(target_os = "linux", feature = "unprivileged"))]
+use crate::runtime::{AsyncRuntime, CurrentRuntime};
+#[cfg(any(
+    all(target_os = "linux", feature = "unprivileged"),
+    target_os = "macos"
+))]
 use crate::MountOptions;
 
 #[derive(Debug)]
@@ -52,6 +55,7 @@ pub struct FuseConnection {
 }
 
 impl FuseConnection {
+    #[cfg(not(target_os = "macos"))]
     pub fn new(unmount_notify: Arc<Notify>) -> io::Result<Self> {
         #[cfg(target_os = "freebsd")]
         {
@@ -74,6 +78,52 @@ impl FuseConnection {
         }
     }
 
+    /// use a `/dev/fuse` fd which is opened and mounted by others, the connection owns the fd
+    /// and closes it when dropped.
+    pub fn from_fd(fd: OwnedFd, unmount_notify: Arc<Notify>) -> io::Result<Self> {
+        #[cfg(target_os = "freebsd")]
+        {
+            Ok(Self {
+                unmount_notify,
+                mode: ConnectionMode::NonBlock(NonBlockFuseConnection {
+                    fd: CurrentRuntime::register(fd)?,
+                    read: Mutex::new(()),
+                    write: Mutex::new(()),
+                }),
+            })
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        {
+            // the blocking connection reads in a blocking thread
+            set_non_blocking(fd.as_fd(), false)?;
+
+            Ok(Self {
+                unmount_notify,
+                mode: ConnectionMode::Block(BlockFuseConnection {
+                    file: fd.into(),
+                    read: Mutex::new(()),
+                    write: Mutex::new(()),
+                }),
+            })
+        }
+    }
+
+    /// mount by the macFUSE mount helper, and use the fuse device fd which it passes back.
+    #[cfg(target_os = "macos")]
+    pub async fn new_with_macfuse(
+        mount_options: MountOptions,
+        mount_path: impl AsRef<Path>,
+        unmount_notify: Arc<Notify>,
+    ) -> io::Result<Self> {
+        let mount_path = mount_path.as_ref().to_path_buf();
+        let fd =
+            CurrentRuntime::spawn_blocking(move || macfuse::mount(&mount_options, &mount_path))
+                .await?;
+
+        Self::from_fd(fd, unmount_notify)
+    }
+
     #[cfg(all(target_os = "linux", feature = "unprivileged"))]
     pub async fn new_with_unprivileged(
         mount_options: MountOptions,
@@ -109,7 +159,7 @@ impl FuseConnection {
         data_buf: T,
     ) -> CompleteIoResult<(Vec<u8>, T), usize> {
         match &self.mode {
-            #[cfg(target_os = "linux")]
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
             ConnectionMode::Block(connection) => {
                 connection.read_vectored(header_buf, data_buf).await
             }
@@ -123,13 +173,30 @@ impl FuseConnection {
         }
     }
 
+    /// write the reply which is spliced into a pipe, if kernel rejects the splice write, read the
+    /// reply out of the pipe and write it by `writev(2)`.
+    #[cfg(target_os = "linux")]
+    pub async fn write_spliced(&self, reply: SplicedReply) -> io::Result<usize> {
+        match reply.write(self.as_fd()) {
+            Ok(n) => Ok(n),
+
+            Err((Some(reply), _)) => {
+                let data = reply.into_data()?;
+
+                self.write_vectored::<_, Vec<u8>>(data, None).await.1
+            }
+
+            Err((None, err)) => Err(err),
+        }
+    }
+
     pub async fn write_vectored<T: Deref<Target = [u8]> + Send, U: Deref<Target = [u8]> + Send>(
         &self,
         data: T,
         body_extend_data: Option<U>,
     ) -> CompleteIoResult<(T, Option<U>), usize> {
         match &self.mode {
-            #[cfg(target_os = "linux")]
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
             ConnectionMode::Block(connection) => {
                 connection.write_vectored(data, body_extend_data).await
             }
@@ -146,7 +213,7 @@ impl FuseConnection {
 
 #[derive(Debug)]
 enum ConnectionMode {
-    #[cfg(target_os = "linux")]
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
     Block(BlockFuseConnection),
     #[cfg(any(
         all(target_os = "linux", feature = "unprivileged"),
@@ -155,7 +222,7 @@ enum ConnectionMode {
     NonBlock(NonBlockFuseConnection),
 }
 
-#[cfg(target_os = "linux")]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
 #[derive(Debug)]
 struct BlockFuseConnection {
     file: File,
@@ -163,8 +230,9 @@ struct BlockFuseConnection {
     write: Mutex<()>,
 }
 
-#[cfg(target_os = "linux")]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
 impl BlockFuseConnection {
+    #[cfg(target_os = "linux")]
     pub fn new() -> io::Result<Self> {
         const DEV_FUSE: &str = "/dev/fuse";
 
@@ -189,7 +257,7 @@ impl BlockFuseConnection {
         let _guard = self.read.lock().await;
         let fd = self.file.as_raw_fd();
 
-        let ((header_buf, data_buf), res) = async_global_executor::spawn_blocking(move || {
+        let ((header_buf, data_buf), res) = CurrentRuntime::spawn_blocking(move || {
             // Safety: when we call read, the fd is still valid, when fd is closed and file is
             // dropped, the read operation will return error
             let file = unsafe { File::from_raw_fd(fd) };
@@ -239,9 +307,12 @@ impl BlockFuseConnection {
 ))]
 #[derive(Debug)]
 struct NonBlockFuseConnection {
-    fd: Async<OwnedFd>,
+    fd: <CurrentRuntime as AsyncRuntime>::Fd,
     read: Mutex<()>,
     write: Mutex<()>,
+    /// fusermount3 unmounts the filesystem when this socket is closed.
+    #[cfg(all(target_os = "linux", feature = "unprivileged"))]
+    _auto_unmount_socket: Option<OwnedFd>,
 }
 
 #[cfg(any(
@@ -256,7 +327,7 @@ impl NonBlockFuseConnection {
         let file = OpenOptions::new().write(true).read(true).open(DEV_FUSE)?;
 
         Ok(Self {
-            fd: Async::new(file.into())?,
+            fd: CurrentRuntime::register(file.into())?,
             read: Mutex::new(()),
             write: Mutex::new(()),
         })
@@ -296,15 +367,17 @@ impl NonBlockFuseConnection {
             .args(vec![OsString::from("-o"), options, mount_path])
             .spawn()?;
 
-        if !child.status().await?.success() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "fusermount run failed",
-            ));
+        // with auto_unmount, fusermount3 keeps running until the control socket is closed, so
+        // don't wait it, and close our copy of its socket end, otherwise recvmsg can't see the
+        // socket closed when fusermount3 fails
+        if mount_options.auto_unmount {
+            drop(sock0);
+        } else if !child.status().await?.success() {
+            return Err(io::Error::other("fusermount run failed"));
         }
 
         let fd1 = sock1.as_raw_fd();
-        let fd = async_global_executor::spawn_blocking(move || {
+        let fd = CurrentRuntime::spawn_blocking(move || {
             // let mut buf = vec![0; 10000]; // buf should large enough
             let mut buf = vec![]; // it seems 0 len still works well
 
@@ -325,12 +398,12 @@ impl NonBlockFuseConnection {
 
             let fd = if let Some(ControlMessageOwned::ScmRights(fds)) = msg.cmsgs()?.next() {
                 if fds.is_empty() {
-                    return Err(io::Error::new(io::ErrorKind::Other, "no fuse fd"));
+                    return Err(io::Error::other("no fuse fd"));
                 }
 
                 fds[0]
             } else {
-                return Err(io::Error::new(io::ErrorKind::Other, "get fuse fd failed"));
+                return Err(io::Error::other("get fuse fd failed"));
             };
 
             Ok(fd)
@@ -341,9 +414,10 @@ impl NonBlockFuseConnection {
         let fd = unsafe { OwnedFd::from_raw_fd(fd) };
 
         Ok(Self {
-            fd: Async::new(fd)?,
+            fd: CurrentRuntime::register(fd)?,
             read: Mutex::new(()),
             write: Mutex::new(()),
+            _auto_unmount_socket: mount_options.auto_unmount.then_some(sock1),
         })
     }
 
@@ -354,19 +428,17 @@ impl NonBlockFuseConnection {
     ) -> CompleteIoResult<(Vec<u8>, T), usize> {
         let _guard = self.read.lock().await;
 
-        let res = self
-            .fd
-            .read_with(|fd| {
-                uio::readv(
-                    fd,
-                    &mut [
-                        IoSliceMut::new(&mut header_buf),
-                        IoSliceMut::new(&mut data_buf),
-                    ],
-                )
-                .map_err(Into::into)
-            })
-            .await;
+        let res = CurrentRuntime::read_with(&self.fd, |fd| {
+            uio::readv(
+                fd,
+                &mut [
+                    IoSliceMut::new(&mut header_buf),
+                    IoSliceMut::new(&mut data_buf),
+                ],
+            )
+            .map_err(Into::into)
+        })
+        .await;
 
         ((header_buf, data_buf), res)
     }
@@ -401,7 +473,7 @@ impl NonBlockFuseConnection {
 impl AsFd for FuseConnection {
     fn as_fd(&self) -> BorrowedFd<'_> {
         match &self.mode {
-            #[cfg(target_os = "linux")]
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
             ConnectionMode::Block(connection) => {
                 // Safety: we own the File
                 connection.file.as_fd()
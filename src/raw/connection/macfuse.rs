@@ -0,0 +1,103 @@
+//! mount by the `mount_macfuse` helper of macFUSE, it opens a `/dev/macfuse*` device, passes the
+//! device fd back by a unix socket like `fusermount3`, then mounts the filesystem on it.
+
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, ErrorKind, IoSliceMut};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+
+use nix::sys::socket::{self, AddressFamily, ControlMessageOwned, MsgFlags, SockFlag, SockType};
+use tracing::{debug, warn};
+
+use crate::MountOptions;
+
+/// the mount helper installed with macFUSE.
+const MOUNT_MACFUSE: &str = "/Library/Filesystems/macfuse.fs/Contents/Resources/mount_macfuse";
+
+/// the version of the fd passing protocol between the library and `mount_macfuse`.
+const COMM_VERSION: &str = "2";
+
+/// run `mount_macfuse` to mount `mount_path`, return the fuse device fd passed back by it.
+///
+/// the helper mounts the filesystem after passing the fd, it is waited in background and only its
+/// failure is logged, kernel closes the device when the mount fails, then the session stops with
+/// the read error.
+pub(crate) fn mount(mount_options: &MountOptions, mount_path: &Path) -> io::Result<OwnedFd> {
+    if !Path::new(MOUNT_MACFUSE).exists() {
+        return Err(io::Error::new(
+            ErrorKind::NotFound,
+            format!("{MOUNT_MACFUSE} not found, is macFUSE installed?"),
+        ));
+    }
+
+    let (sock0, sock1) = socket::socketpair(
+        AddressFamily::Unix,
+        SockType::Stream,
+        None,
+        SockFlag::empty(),
+    )?;
+
+    let options = mount_options.build_with_macfuse();
+
+    debug!("mount options {:?}", options);
+
+    let mut child = Command::new(MOUNT_MACFUSE)
+        .env("_FUSE_COMMFD", sock0.as_raw_fd().to_string())
+        .env("_FUSE_COMMVERS", COMM_VERSION)
+        .env("_FUSE_CALL_BY_LIB", "1")
+        .env("_FUSE_DAEMON_PATH", env::current_exe()?)
+        .args([
+            OsString::from("-o"),
+            options,
+            mount_path.as_os_str().to_os_string(),
+        ])
+        .spawn()?;
+
+    // close our copy of the helper socket end, so recvmsg sees the socket closed when the helper
+    // exits without passing the fd
+    drop(sock0);
+
+    let fd = match receive_fd(sock1.as_raw_fd()) {
+        Err(err) => {
+            let status = child.wait()?;
+
+            return Err(io::Error::new(
+                err.kind(),
+                format!("mount_macfuse failed with {status}: {err}"),
+            ));
+        }
+
+        Ok(fd) => fd,
+    };
+
+    thread::spawn(move || match child.wait() {
+        Err(err) => warn!("wait mount_macfuse failed {}", err),
+        Ok(status) if !status.success() => warn!("mount_macfuse failed with {}", status),
+        Ok(_) => {}
+    });
+
+    Ok(fd)
+}
+
+/// receive the fuse device fd sent by `SCM_RIGHTS`.
+fn receive_fd(sock: RawFd) -> io::Result<OwnedFd> {
+    let mut buf = [0; 1];
+    let mut bufs = [IoSliceMut::new(&mut buf)];
+    let mut cmsg_buf = nix::cmsg_space!([RawFd; 1]);
+
+    let msg = socket::recvmsg::<()>(sock, &mut bufs, Some(&mut cmsg_buf), MsgFlags::empty())?;
+
+    for cmsg in msg.cmsgs()? {
+        if let ControlMessageOwned::ScmRights(fds) = cmsg {
+            if let Some(&fd) = fds.first() {
+                // Safety: the fd is received from the helper, no one else owns it
+                return Ok(unsafe { OwnedFd::from_raw_fd(fd) });
+            }
+        }
+    }
+
+    Err(io::Error::other("no fuse fd"))
+}
@@ -1,4 +1,7 @@
 use std::io;
+use std::os::fd::{AsRawFd, BorrowedFd};
+
+use nix::fcntl::{self, FcntlArg, OFlag};
 
 #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
 pub use async_io::FuseConnection;
@@ -7,7 +10,22 @@ pub use tokio::FuseConnection;
 
 #[cfg(feature = "async-io-runtime")]
 mod async_io;
+#[cfg(target_os = "macos")]
+mod macfuse;
+#[cfg(target_os = "linux")]
+pub(crate) mod splice;
 #[cfg(feature = "tokio-runtime")]
 mod tokio;
 
 pub(crate) type CompleteIoResult<T, U> = (T, io::Result<U>);
+
+/// set or clear the `O_NONBLOCK` flag of a `/dev/fuse` fd passed by the user.
+pub(crate) fn set_non_blocking(fd: BorrowedFd<'_>, non_blocking: bool) -> io::Result<()> {
+    let flags = fcntl::fcntl(fd.as_raw_fd(), FcntlArg::F_GETFL)?;
+    let mut flags = OFlag::from_bits_truncate(flags);
+    flags.set(OFlag::O_NONBLOCK, non_blocking);
+
+    fcntl::fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(flags))?;
+
+    Ok(())
+}
@@ -0,0 +1,285 @@
+//! reply the read data from a file to `/dev/fuse` with `splice(2)`.
+
+use std::fs;
+use std::io::{self, ErrorKind, Read};
+use std::os::fd::{AsRawFd, BorrowedFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use nix::errno::Errno;
+use nix::fcntl::{self, FcntlArg, OFlag, SpliceFFlags};
+use nix::sys::stat::{self, SFlag};
+use nix::unistd;
+use tracing::{debug, warn};
+
+/// splice the read reply data from a file into a pipe after the reply header, then splice the
+/// pipe to `/dev/fuse`, so the data is moved inside kernel instead of copied through user space.
+///
+/// # Notes:
+///
+/// a pipe is taken out of the pool while it is filled and written, the pool lock is never held
+/// across the syscalls. When the splice can't be used, such as the pipe can't be created, the
+/// reply is larger than the pipe capacity or the file doesn't support splice,
+/// [`Splicer::fill`] returns `None` and caller should read the data into memory instead.
+#[derive(Debug)]
+pub(crate) struct Splicer {
+    flags: SpliceFFlags,
+    pipe_size: usize,
+    disabled: AtomicBool,
+    /// the empty pipes which can be reused.
+    pipes: Mutex<Vec<Pipe>>,
+}
+
+#[derive(Debug)]
+struct Pipe {
+    read: OwnedFd,
+    write: OwnedFd,
+    capacity: usize,
+}
+
+/// a reply whose header and data are in the pipe, the pipe is returned to the pool after the
+/// whole reply is written, otherwise it is closed with the data left in it.
+#[derive(Debug)]
+pub(crate) struct SplicedReply {
+    splicer: Arc<Splicer>,
+    pipe: Pipe,
+    len: usize,
+}
+
+impl Splicer {
+    /// `pipe_size` is the largest reply size expected, when `move_pages` is true, kernel is
+    /// allowed to steal the pages instead of copying them.
+    pub(crate) fn new(pipe_size: usize, move_pages: bool) -> Self {
+        let flags = if move_pages {
+            SpliceFFlags::SPLICE_F_MOVE
+        } else {
+            SpliceFFlags::empty()
+        };
+
+        Self {
+            flags,
+            pipe_size,
+            disabled: AtomicBool::new(false),
+            pipes: Mutex::new(vec![]),
+        }
+    }
+
+    /// splice at most `len` bytes at `offset` of `fd` into a pipe after the reply header, which
+    /// is built by `header` with the data size. It reads the file, so run it in a blocking
+    /// thread.
+    ///
+    /// the data size is limited by the file size, if the file is truncated at the same time and
+    /// the splice is short, the pipe is dropped and `None` is returned, then caller should read
+    /// the data with `pread(2)`.
+    pub(crate) fn fill(
+        self: &Arc<Self>,
+        fd: BorrowedFd,
+        offset: u64,
+        len: usize,
+        header: impl FnOnce(usize) -> Vec<u8>,
+    ) -> Option<io::Result<(Vec<u8>, SplicedReply)>> {
+        if self.disabled.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let file_stat = match stat::fstat(fd.as_raw_fd()) {
+            Err(err) => return Some(Err(err.into())),
+            Ok(file_stat) => file_stat,
+        };
+
+        // only the size of a regular file is known before splicing
+        if SFlag::from_bits_truncate(file_stat.st_mode) & SFlag::S_IFMT != SFlag::S_IFREG {
+            return None;
+        }
+
+        let len = len.min((file_stat.st_size as u64).saturating_sub(offset) as usize);
+        let header = header(len);
+        let total = header.len() + len;
+
+        let pipe = match self.take_pipe() {
+            Err(err) => {
+                warn!("create splice pipe failed {}, disable splice", err);
+
+                self.disabled.store(true, Ordering::Relaxed);
+
+                return None;
+            }
+
+            Ok(pipe) => pipe,
+        };
+
+        if total > pipe.capacity {
+            debug!(total, pipe.capacity, "reply is larger than splice pipe");
+
+            self.put_pipe(pipe);
+
+            return None;
+        }
+
+        // the pipe is empty and large enough, the write won't block
+        if let Err(err) = write_all(&pipe.write, &header) {
+            debug!("write reply header into splice pipe failed {}", err);
+
+            return None;
+        }
+
+        let mut offset = offset as i64;
+        let mut left = len;
+        while left > 0 {
+            match fcntl::splice(fd, Some(&mut offset), &pipe.write, None, left, self.flags) {
+                Err(Errno::EINTR) => continue,
+
+                // the file doesn't support splice
+                Err(Errno::EINVAL) => {
+                    debug!("file doesn't support splice");
+
+                    return None;
+                }
+
+                Err(err) => return Some(Err(err.into())),
+
+                Ok(0) => {
+                    debug!(len, left, "file is truncated while splicing");
+
+                    return None;
+                }
+
+                Ok(n) => left -= n,
+            }
+        }
+
+        Some(Ok((
+            header,
+            SplicedReply {
+                splicer: self.clone(),
+                pipe,
+                len: total,
+            },
+        )))
+    }
+
+    fn take_pipe(&self) -> io::Result<Pipe> {
+        let pipe = self
+            .pipes
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .pop();
+
+        match pipe {
+            None => Pipe::new(self.pipe_size),
+            Some(pipe) => Ok(pipe),
+        }
+    }
+
+    fn put_pipe(&self, pipe: Pipe) {
+        self.pipes
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(pipe);
+    }
+}
+
+impl SplicedReply {
+    /// splice the whole reply to fuse fd. If kernel rejects the splice write, the splice is
+    /// disabled and the reply is returned back with the error, caller can
+    /// [`read`][SplicedReply::into_data] it out and write it by `writev(2)`.
+    ///
+    /// kernel takes the reply from the pipe as one message, it never splices a part of it, so a
+    /// short splice is an error.
+    pub(crate) fn write(self, fd: BorrowedFd) -> Result<usize, (Option<Self>, io::Error)> {
+        loop {
+            match fcntl::splice(
+                &self.pipe.read,
+                None,
+                fd,
+                None,
+                self.len,
+                self.splicer.flags,
+            ) {
+                Err(Errno::EINTR) => continue,
+
+                Err(err @ (Errno::EINVAL | Errno::ENOSYS)) => {
+                    warn!("kernel rejects splice write {}, disable splice", err);
+
+                    self.splicer.disabled.store(true, Ordering::Relaxed);
+
+                    return Err((Some(self), err.into()));
+                }
+
+                Err(err) => return Err((None, err.into())),
+
+                Ok(n) if n == self.len => {
+                    self.splicer.put_pipe(self.pipe);
+
+                    return Ok(n);
+                }
+
+                Ok(n) => {
+                    return Err((
+                        None,
+                        io::Error::new(
+                            ErrorKind::WriteZero,
+                            format!("splice reply is short, {} of {} bytes", n, self.len),
+                        ),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// read the whole reply out of the pipe.
+    pub(crate) fn into_data(self) -> io::Result<Vec<u8>> {
+        let mut data = vec![0; self.len];
+        fs::File::from(self.pipe.read).read_exact(&mut data)?;
+
+        Ok(data)
+    }
+}
+
+impl Pipe {
+    fn new(size: usize) -> io::Result<Self> {
+        let (read, write) = unistd::pipe2(OFlag::O_CLOEXEC)?;
+
+        let size = size.min(i32::MAX as usize) as i32;
+        let capacity = match fcntl::fcntl(write.as_raw_fd(), FcntlArg::F_SETPIPE_SZ(size)) {
+            Ok(capacity) => capacity,
+
+            Err(err) => {
+                // an unprivileged process can't grow the pipe over /proc/sys/fs/pipe-max-size,
+                // grow it to the limit instead
+                debug!(size, "set splice pipe size failed {}", err);
+
+                let max_size = fs::read_to_string("/proc/sys/fs/pipe-max-size")
+                    .ok()
+                    .and_then(|max_size| max_size.trim().parse::<i32>().ok());
+
+                match max_size.filter(|&max_size| max_size < size) {
+                    Some(max_size) => {
+                        fcntl::fcntl(write.as_raw_fd(), FcntlArg::F_SETPIPE_SZ(max_size))?
+                    }
+                    None => fcntl::fcntl(write.as_raw_fd(), FcntlArg::F_GETPIPE_SZ)?,
+                }
+            }
+        };
+
+        Ok(Self {
+            read,
+            write,
+            capacity: capacity as usize,
+        })
+    }
+}
+
+/// write the whole `data` into the pipe, retry the short write.
+fn write_all(pipe: &OwnedFd, mut data: &[u8]) -> io::Result<()> {
+    while !data.is_empty() {
+        match unistd::write(pipe, data) {
+            Err(Errno::EINTR) => continue,
+            Err(err) => return Err(err.into()),
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(n) => data = &data[n..],
+        }
+    }
+
+    Ok(())
+}
@@ -1,26 +1,21 @@
-#[cfg(target_os = "linux")]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
 use std::fs::File;
+#[cfg(not(target_os = "macos"))]
 use std::fs::OpenOptions;
 use std::io;
-#[cfg(any(
-    all(target_os = "linux", feature = "unprivileged"),
-    target_os = "freebsd"
-))]
+#[cfg(target_os = "freebsd")]
 use std::io::ErrorKind;
-#[cfg(target_os = "linux")]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
 use std::io::Write;
 use std::io::{IoSlice, IoSliceMut};
 use std::ops::{Deref, DerefMut};
-#[cfg(any(
-    all(target_os = "linux", feature = "unprivileged"),
-    target_os = "freebsd"
-))]
-use std::os::fd::OwnedFd;
-use std::os::fd::{AsFd, BorrowedFd};
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
 #[cfg(target_os = "freebsd")]
 use std::os::unix::fs::OpenOptionsExt;
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
 use std::os::unix::io::RawFd;
+#[cfg(target_os = "macos")]
+use std::path::Path;
 use std::pin::pin;
 use std::sync::Arc;
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
@@ -29,34 +24,34 @@ use std::{ffi::OsString, path::Path};
 use async_notify::Notify;
 use futures_util::lock::Mutex;
 use futures_util::{select, FutureExt};
-#[cfg(any(
-    all(target_os = "linux", feature = "unprivileged"),
-    target_os = "freebsd"
-))]
-use nix::sys::uio;
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
-use nix::{
-    fcntl::{FcntlArg, OFlag},
-    sys::socket::{self, AddressFamily, ControlMessageOwned, MsgFlags, SockFlag, SockType},
-};
+use nix::sys::socket::{self, AddressFamily, ControlMessageOwned, MsgFlags, SockFlag, SockType};
 #[cfg(any(
     all(target_os = "linux", feature = "unprivileged"),
     target_os = "freebsd"
 ))]
-use tokio::io::unix::AsyncFd;
+use nix::sys::uio;
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
 use tokio::process::Command;
-#[cfg(target_os = "linux")]
-use tokio::task;
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
 use tracing::debug;
 #[cfg(target_os = "freebsd")]
 use tracing::warn;
 
+#[cfg(target_os = "macos")]
+use super::macfuse;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use super::set_non_blocking;
+#[cfg(target_os = "linux")]
+use super::splice::SplicedReply;
 use super::CompleteIoResult;
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
 use crate::find_fusermount3;
-#[cfg(all(target_os = "linux", feature = "unprivileged"))]
+use crate::runtime::{AsyncRuntime, CurrentRuntime};
+#[cfg(any(
+    all(target_os = "linux", feature = "unprivileged"),
+    target_os = "macos"
+))]
 use crate::MountOptions;
 
 #[derive(Debug)]
@@ -66,6 +61,7 @@ pub struct FuseConnection {
 }
 
 impl FuseConnection {
+    #[cfg(not(target_os = "macos"))]
     pub fn new(unmount_notify: Arc<Notify>) -> io::Result<Self> {
         #[cfg(target_os = "freebsd")]
         {
@@ -88,6 +84,52 @@ impl FuseConnection {
         }
     }
 
+    /// use a `/dev/fuse` fd which is opened and mounted by others, the connection owns the fd
+    /// and closes it when dropped.
+    pub fn from_fd(fd: OwnedFd, unmount_notify: Arc<Notify>) -> io::Result<Self> {
+        #[cfg(target_os = "freebsd")]
+        {
+            Ok(Self {
+                unmount_notify,
+                mode: ConnectionMode::NonBlock(NonBlockFuseConnection {
+                    fd: CurrentRuntime::register(fd)?,
+                    read: Mutex::new(()),
+                    write: Mutex::new(()),
+                }),
+            })
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        {
+            // the blocking connection reads in a blocking thread
+            set_non_blocking(fd.as_fd(), false)?;
+
+            Ok(Self {
+                unmount_notify,
+                mode: ConnectionMode::Block(BlockFuseConnection {
+                    file: fd.into(),
+                    read: Mutex::new(()),
+                    write: Mutex::new(()),
+                }),
+            })
+        }
+    }
+
+    /// mount by the macFUSE mount helper, and use the fuse device fd which it passes back.
+    #[cfg(target_os = "macos")]
+    pub async fn new_with_macfuse(
+        mount_options: MountOptions,
+        mount_path: impl AsRef<Path>,
+        unmount_notify: Arc<Notify>,
+    ) -> io::Result<Self> {
+        let mount_path = mount_path.as_ref().to_path_buf();
+        let fd =
+            CurrentRuntime::spawn_blocking(move || macfuse::mount(&mount_options, &mount_path))
+                .await?;
+
+        Self::from_fd(fd, unmount_notify)
+    }
+
     #[cfg(all(target_os = "linux", feature = "unprivileged"))]
     pub async fn new_with_unprivileged(
         mount_options: MountOptions,
@@ -123,7 +165,7 @@ impl FuseConnection {
         data_buf: T,
     ) -> CompleteIoResult<(Vec<u8>, T), usize> {
         match &self.mode {
-            #[cfg(target_os = "linux")]
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
             ConnectionMode::Block(connection) => {
                 connection.read_vectored(header_buf, data_buf).await
             }
@@ -137,13 +179,30 @@ impl FuseConnection {
         }
     }
 
+    /// write the reply which is spliced into a pipe, if kernel rejects the splice write, read the
+    /// reply out of the pipe and write it by `writev(2)`.
+    #[cfg(target_os = "linux")]
+    pub async fn write_spliced(&self, reply: SplicedReply) -> io::Result<usize> {
+        match reply.write(self.as_fd()) {
+            Ok(n) => Ok(n),
+
+            Err((Some(reply), _)) => {
+                let data = reply.into_data()?;
+
+                self.write_vectored::<_, Vec<u8>>(data, None).await.1
+            }
+
+            Err((None, err)) => Err(err),
+        }
+    }
+
     pub async fn write_vectored<T: Deref<Target = [u8]> + Send, U: Deref<Target = [u8]> + Send>(
         &self,
         data: T,
         body_extend_data: Option<U>,
     ) -> CompleteIoResult<(T, Option<U>), usize> {
         match &self.mode {
-            #[cfg(target_os = "linux")]
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
             ConnectionMode::Block(connection) => {
                 connection.write_vectored(data, body_extend_data).await
             }
@@ -160,7 +219,7 @@ impl FuseConnection {
 
 #[derive(Debug)]
 enum ConnectionMode {
-    #[cfg(target_os = "linux")]
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
     Block(BlockFuseConnection),
     #[cfg(any(
         all(target_os = "linux", feature = "unprivileged"),
@@ -169,7 +228,7 @@ enum ConnectionMode {
     NonBlock(NonBlockFuseConnection),
 }
 
-#[cfg(target_os = "linux")]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
 #[derive(Debug)]
 struct BlockFuseConnection {
     file: File,
@@ -177,8 +236,9 @@ struct BlockFuseConnection {
     write: Mutex<()>,
 }
 
-#[cfg(target_os = "linux")]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
 impl BlockFuseConnection {
+    #[cfg(target_os = "linux")]
     pub fn new() -> io::Result<Self> {
         const DEV_FUSE: &str = "/dev/fuse";
 
@@ -203,7 +263,7 @@ impl BlockFuseConnection {
         let _guard = self.read.lock().await;
         let fd = self.file.as_raw_fd();
 
-        let ((header_buf, data_buf), res) = task::spawn_blocking(move || {
+        let ((header_buf, data_buf), res) = CurrentRuntime::spawn_blocking(move || {
             // Safety: when we call read, the fd is still valid, when fd is closed and file is
             // dropped, the read operation will return error
             let file = unsafe { File::from_raw_fd(fd) };
@@ -217,8 +277,7 @@ impl BlockFuseConnection {
 
             ((header_buf, data_buf), res)
         })
-        .await
-        .unwrap();
+        .await;
 
         ((header_buf, data_buf), res)
     }
@@ -254,9 +313,12 @@ impl BlockFuseConnection {
 ))]
 #[derive(Debug)]
 struct NonBlockFuseConnection {
-    fd: AsyncFd<OwnedFd>,
+    fd: <CurrentRuntime as AsyncRuntime>::Fd,
     read: Mutex<()>,
     write: Mutex<()>,
+    /// fusermount3 unmounts the filesystem when this socket is closed.
+    #[cfg(all(target_os = "linux", feature = "unprivileged"))]
+    _auto_unmount_socket: Option<OwnedFd>,
 }
 
 #[cfg(any(
@@ -281,7 +343,7 @@ impl NonBlockFuseConnection {
                 Err(e)
             }
             Ok(file) => Ok(Self {
-                fd: AsyncFd::new(file.into())?,
+                fd: CurrentRuntime::register(file.into())?,
                 read: Mutex::new(()),
                 write: Mutex::new(()),
             }),
@@ -322,15 +384,17 @@ impl NonBlockFuseConnection {
             .args(vec![OsString::from("-o"), options, mount_path])
             .spawn()?;
 
-        if !child.wait().await?.success() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "fusermount run failed",
-            ));
+        // with auto_unmount, fusermount3 keeps running until the control socket is closed, so
+        // don't wait it, and close our copy of its socket end, otherwise recvmsg can't see the
+        // socket closed when fusermount3 fails
+        if mount_options.auto_unmount {
+            drop(sock0);
+        } else if !child.wait().await?.success() {
+            return Err(io::Error::other("fusermount run failed"));
         }
 
         let fd1 = sock1.as_raw_fd();
-        let fd = task::spawn_blocking(move || {
+        let fd = CurrentRuntime::spawn_blocking(move || {
             // let mut buf = vec![0; 10000]; // buf should large enough
             let mut buf = vec![]; // it seems 0 len still works well
 
@@ -351,42 +415,29 @@ impl NonBlockFuseConnection {
 
             let fd = if let Some(ControlMessageOwned::ScmRights(fds)) = msg.cmsgs()?.next() {
                 if fds.is_empty() {
-                    return Err(io::Error::new(ErrorKind::Other, "no fuse fd"));
+                    return Err(io::Error::other("no fuse fd"));
                 }
 
                 fds[0]
             } else {
-                return Err(io::Error::new(ErrorKind::Other, "get fuse fd failed"));
+                return Err(io::Error::other("get fuse fd failed"));
             };
 
             Ok(fd)
         })
-        .await
-        .unwrap()?;
-
-        Self::set_fd_non_blocking(fd)?;
+        .await?;
 
         // Safety: fd is valid
         let fd = unsafe { OwnedFd::from_raw_fd(fd) };
 
         Ok(Self {
-            fd: AsyncFd::new(fd)?,
+            fd: CurrentRuntime::register(fd)?,
             read: Mutex::new(()),
             write: Mutex::new(()),
+            _auto_unmount_socket: mount_options.auto_unmount.then_some(sock1),
         })
     }
 
-    #[cfg(all(target_os = "linux", feature = "unprivileged"))]
-    fn set_fd_non_blocking(fd: RawFd) -> io::Result<()> {
-        let flags = nix::fcntl::fcntl(fd, FcntlArg::F_GETFL).map_err(io::Error::from)?;
-
-        let flags = OFlag::from_bits_truncate(flags) | OFlag::O_NONBLOCK;
-
-        nix::fcntl::fcntl(fd, FcntlArg::F_SETFL(flags)).map_err(io::Error::from)?;
-
-        Ok(())
-    }
-
     async fn read_vectored<T: DerefMut<Target = [u8]> + Send>(
         &self,
         mut header_buf: Vec<u8>,
@@ -394,27 +445,19 @@ impl NonBlockFuseConnection {
     ) -> CompleteIoResult<(Vec<u8>, T), usize> {
         let _guard = self.read.lock().await;
 
-        loop {
-            let mut read_guard = match self.fd.readable().await {
-                Err(err) => return ((header_buf, data_buf), Err(err)),
-                Ok(read_guard) => read_guard,
-            };
+        let res = CurrentRuntime::read_with(&self.fd, |fd| {
+            uio::readv(
+                fd,
+                &mut [
+                    IoSliceMut::new(&mut header_buf),
+                    IoSliceMut::new(&mut data_buf),
+                ],
+            )
+            .map_err(Into::into)
+        })
+        .await;
 
-            if let Ok(result) = read_guard.try_io(|fd| {
-                uio::readv(
-                    fd,
-                    &mut [
-                        IoSliceMut::new(&mut header_buf),
-                        IoSliceMut::new(&mut data_buf),
-                    ],
-                )
-                .map_err(io::Error::from)
-            }) {
-                return ((header_buf, data_buf), result);
-            } else {
-                continue;
-            }
-        }
+        ((header_buf, data_buf), res)
     }
 
     async fn write_vectored<T: Deref<Target = [u8]> + Send, U: Deref<Target = [u8]> + Send>(
@@ -447,7 +490,7 @@ impl NonBlockFuseConnection {
 impl AsFd for FuseConnection {
     fn as_fd(&self) -> BorrowedFd<'_> {
         match &self.mode {
-            #[cfg(target_os = "linux")]
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
             ConnectionMode::Block(connection) => connection.file.as_fd(),
 
             #[cfg(any(
@@ -0,0 +1,152 @@
+//! negotiated fuse connection information.
+
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::sync::{Arc, OnceLock};
+
+use futures_channel::oneshot::{self, Receiver, Sender};
+use futures_util::future::{FutureExt, Shared};
+
+use crate::raw::flags::InitFlags;
+#[cfg(not(target_os = "macos"))]
+use crate::raw::flags::InitFlags2;
+
+/// the fuse connection information negotiated with kernel during `FUSE_INIT`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ConnectionInfo {
+    pub(crate) major: u32,
+    pub(crate) minor: u32,
+    pub(crate) capabilities: InitFlags,
+    #[cfg(not(target_os = "macos"))]
+    pub(crate) capabilities2: InitFlags2,
+    pub(crate) max_readahead: u32,
+    pub(crate) max_write: u32,
+    pub(crate) max_pages: u16,
+    pub(crate) max_background: u16,
+    pub(crate) congestion_threshold: u16,
+    pub(crate) time_gran: u32,
+}
+
+impl ConnectionInfo {
+    /// the fuse protocol version `(major, minor)` negotiated with kernel.
+    pub fn protocol_version(&self) -> (u32, u32) {
+        (self.major, self.minor)
+    }
+
+    /// the capabilities both requested by fuse3 and supported by kernel.
+    pub fn capabilities(&self) -> InitFlags {
+        self.capabilities
+    }
+
+    /// the `flags2` capabilities both requested by fuse3 and supported by kernel, it is empty if
+    /// [`InitFlags::INIT_EXT`] is not negotiated.
+    #[cfg(not(target_os = "macos"))]
+    pub fn capabilities2(&self) -> InitFlags2 {
+        self.capabilities2
+    }
+
+    /// the max readahead size in bytes replied to kernel.
+    pub fn max_readahead(&self) -> u32 {
+        self.max_readahead
+    }
+
+    /// the max size of a single write request replied to kernel.
+    pub fn max_write(&self) -> u32 {
+        self.max_write
+    }
+
+    /// the max pages of a single read or write request, it is the value replied to kernel when
+    /// [`InitFlags::MAX_PAGES`] is negotiated, otherwise it is the kernel default 32.
+    pub fn max_pages(&self) -> u16 {
+        self.max_pages
+    }
+
+    /// the max number of pending background requests replied to kernel.
+    ///
+    /// # Notes:
+    ///
+    /// when the filesystem is mounted by an unprivileged user, kernel may limit this value by
+    /// `/proc/sys/fs/fuse/max_user_bgreq`.
+    pub fn max_background(&self) -> u16 {
+        self.max_background
+    }
+
+    /// the kernel congestion threshold replied to kernel.
+    ///
+    /// # Notes:
+    ///
+    /// when the filesystem is mounted by an unprivileged user, kernel may limit this value by
+    /// `/proc/sys/fs/fuse/max_user_congthresh`.
+    pub fn congestion_threshold(&self) -> u16 {
+        self.congestion_threshold
+    }
+
+    /// the timestamp granularity in nanoseconds replied to kernel.
+    pub fn time_gran(&self) -> u32 {
+        self.time_gran
+    }
+}
+
+/// create a [`ConnectionInfo`] sender and receiver pair, the receiver can be cloned and wait
+/// until the `FUSE_INIT` is done.
+pub(crate) fn connection_info_channel() -> (ConnectionInfoSender, ConnectionInfoReceiver) {
+    let info = Arc::new(OnceLock::new());
+    let (sender, receiver) = oneshot::channel();
+
+    (
+        ConnectionInfoSender {
+            info: info.clone(),
+            sender: Some(sender),
+        },
+        ConnectionInfoReceiver {
+            info,
+            init_done: receiver.shared(),
+        },
+    )
+}
+
+/// set the [`ConnectionInfo`] when `FUSE_INIT` is done, if it is dropped before set, all
+/// [`ConnectionInfoReceiver`] waiting will get an error.
+#[derive(Debug)]
+pub(crate) struct ConnectionInfoSender {
+    info: Arc<OnceLock<ConnectionInfo>>,
+    sender: Option<Sender<()>>,
+}
+
+impl ConnectionInfoSender {
+    pub(crate) fn set(&mut self, connection_info: ConnectionInfo) {
+        let _ = self.info.set(connection_info);
+
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(());
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ConnectionInfoReceiver {
+    info: Arc<OnceLock<ConnectionInfo>>,
+    init_done: Shared<Receiver<()>>,
+}
+
+impl ConnectionInfoReceiver {
+    pub(crate) fn get(&self) -> Option<ConnectionInfo> {
+        self.info.get().copied()
+    }
+
+    /// wait until the `FUSE_INIT` is done, return an error if the session is stopped before
+    /// `FUSE_INIT` is done.
+    pub(crate) async fn wait(&self) -> IoResult<ConnectionInfo> {
+        if let Some(connection_info) = self.get() {
+            return Ok(connection_info);
+        }
+
+        if self.init_done.clone().await.is_err() {
+            return Err(IoError::new(
+                ErrorKind::NotConnected,
+                "fuse session stopped before init done",
+            ));
+        }
+
+        Ok(self.get().expect("connection info should be set"))
+    }
+}
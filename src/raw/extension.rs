@@ -0,0 +1,233 @@
+//! the request extensions which kernel appends after the request arguments.
+
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStringExt;
+
+use bincode::Options;
+use tracing::{debug, error};
+
+use crate::helper::{get_bincode_config, get_first_null_position};
+use crate::raw::abi::*;
+use crate::{Errno, Result};
+
+/// the security context of a new file, such as the SELinux label, which is computed by the
+/// security module of kernel for the caller.
+///
+/// kernel sends it with create, mkdir, mknod and symlink when
+/// [`MountOptions::security_context`][crate::MountOptions::security_context] is enabled and the
+/// `FUSE_SECURITY_CTX` capability is negotiated. The filesystem should set it as the xattr
+/// [`name`][SecurityContext::name] of the new file when creating it, so the file is never visible
+/// without its label.
+///
+/// # Examples:
+///
+/// ```
+/// # use std::ffi::OsStr;
+/// # use std::time::Duration;
+/// # use fuse3::raw::prelude::*;
+/// # use fuse3::test::{request, TestSession};
+/// # use fuse3::{MountOptions, Result};
+/// # use futures_util::stream::Empty;
+/// struct Fs;
+///
+/// impl Filesystem for Fs {
+/// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+/// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+/// #
+/// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+/// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+/// #   }
+/// #
+/// #   async fn destroy(&self, _req: Request) {}
+/// #
+///     async fn mkdir(
+///         &self,
+///         req: Request,
+///         _parent: u64,
+///         name: &OsStr,
+///         _mode: u32,
+///         _umask: u32,
+///         security_context: Option<&SecurityContext>,
+///     ) -> Result<ReplyEntry> {
+///         let security_context = security_context.unwrap();
+///         assert_eq!(name, "dir");
+///         assert_eq!(security_context.name, "security.selinux");
+///         assert_eq!(security_context.value, b"system_u:object_r:tmp_t:s0\0");
+///         assert_eq!(req.supplementary_group, Some(1000));
+///
+///         // create the directory and set the xattr
+/// #       let attr = FileAttr::from_metadata(2, &std::fs::metadata(".").unwrap());
+///         Ok(ReplyEntry { ttl: Duration::from_secs(1), attr, generation: 0 })
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let mut extensions = Vec::new();
+/// // the security context extension, size and nr_secctx
+/// extensions.extend_from_slice(&64u32.to_ne_bytes());
+/// extensions.extend_from_slice(&1u32.to_ne_bytes());
+/// // fuse_secctx, size and padding, followed by the name and the value
+/// extensions.extend_from_slice(&27u32.to_ne_bytes());
+/// extensions.extend_from_slice(&0u32.to_ne_bytes());
+/// extensions.extend_from_slice(b"security.selinux\0system_u:object_r:tmp_t:s0\0");
+/// extensions.resize(64, 0);
+/// // the supplementary groups extension, size, FUSE_EXT_GROUPS, nr_groups and the group
+/// for value in [16u32, 32, 1, 1000] {
+///     extensions.extend_from_slice(&value.to_ne_bytes());
+/// }
+///
+/// let mut mkdir = Vec::new();
+/// mkdir.extend_from_slice(&(40 + 12 + extensions.len() as u32).to_ne_bytes()); // len
+/// mkdir.extend_from_slice(&9u32.to_ne_bytes()); // FUSE_MKDIR
+/// mkdir.extend_from_slice(&100u64.to_ne_bytes()); // unique
+/// mkdir.extend_from_slice(&1u64.to_ne_bytes()); // nodeid
+/// mkdir.extend_from_slice(&[0; 12]); // uid, gid and pid
+/// mkdir.extend_from_slice(&(extensions.len() as u16 / 8).to_ne_bytes()); // total_extlen
+/// mkdir.extend_from_slice(&[0; 2]); // padding
+/// mkdir.extend_from_slice(&0o755u32.to_ne_bytes()); // mode
+/// mkdir.extend_from_slice(&0u32.to_ne_bytes()); // umask
+/// mkdir.extend_from_slice(b"dir\0");
+/// mkdir.extend_from_slice(&extensions);
+///
+/// let mut mount_options = MountOptions::default();
+/// mount_options.security_context(true).create_supp_group(true);
+///
+/// let mut session = TestSession::new(Fs, mount_options);
+/// session.init(request(0, 0, 0)).await.unwrap();
+/// session.send_message(&mkdir).await.unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SecurityContext {
+    /// the xattr name, such as `security.selinux`.
+    pub name: OsString,
+    /// the xattr value, it may contain the trailing null byte like kernel passes.
+    pub value: Vec<u8>,
+}
+
+/// the parsed extensions of a request.
+#[derive(Debug, Default)]
+pub(crate) struct RequestExtensions {
+    pub(crate) security_context: Option<SecurityContext>,
+    pub(crate) supplementary_group: Option<u32>,
+}
+
+impl RequestExtensions {
+    /// split the extensions whose size is `total_extlen` from the end of the request data,
+    /// return the request arguments and the parsed extensions. Unknown extensions are ignored.
+    pub(crate) fn split(data: &[u8], total_extlen: u16) -> Result<(&[u8], Self)> {
+        let ext_size = total_extlen as usize * FUSE_EXT_ALIGN;
+        if ext_size == 0 {
+            return Ok((data, Self::default()));
+        }
+
+        if ext_size > data.len() {
+            error!(
+                ext_size,
+                data_size = data.len(),
+                "request extensions are larger than the request"
+            );
+
+            return Err(libc::EINVAL.into());
+        }
+
+        let (data, mut ext_data) = data.split_at(data.len() - ext_size);
+        let mut extensions = Self::default();
+
+        while !ext_data.is_empty() {
+            let ext_header = get_bincode_config()
+                .deserialize::<fuse_ext_header>(ext_data)
+                .map_err(|err| {
+                    error!("deserialize fuse_ext_header failed {}", err);
+
+                    Errno::from(libc::EINVAL)
+                })?;
+
+            let size = ext_header.size as usize;
+            if size < FUSE_EXT_HEADER_SIZE || size > ext_data.len() {
+                error!(size, "invalid request extension size");
+
+                return Err(libc::EINVAL.into());
+            }
+
+            let ext_body = &ext_data[FUSE_EXT_HEADER_SIZE..size];
+            ext_data = &ext_data[size..];
+
+            match ext_header.r#type {
+                nr_secctx @ 0..=FUSE_MAX_NR_SECCTX => {
+                    extensions.security_context = parse_security_context(ext_body, nr_secctx)?;
+                }
+
+                FUSE_EXT_GROUPS => {
+                    extensions.supplementary_group = parse_supplementary_group(ext_body)?;
+                }
+
+                ext_type => debug!(ext_type, "ignore unknown request extension"),
+            }
+        }
+
+        Ok((data, extensions))
+    }
+}
+
+/// parse the first security context, kernel sends one at most now.
+fn parse_security_context(data: &[u8], nr_secctx: u32) -> Result<Option<SecurityContext>> {
+    if nr_secctx == 0 {
+        return Ok(None);
+    }
+
+    let secctx = get_bincode_config()
+        .deserialize::<fuse_secctx>(data)
+        .map_err(|err| {
+            error!("deserialize fuse_secctx failed {}", err);
+
+            Errno::from(libc::EINVAL)
+        })?;
+
+    let data = &data[FUSE_SECCTX_SIZE..];
+
+    let name_end = get_first_null_position(data).ok_or_else(|| {
+        error!("security context name doesn't have null");
+
+        Errno::from(libc::EINVAL)
+    })?;
+
+    let value = data
+        .get(name_end + 1..name_end + 1 + secctx.size as usize)
+        .ok_or_else(|| {
+            error!(size = secctx.size, "security context value is truncated");
+
+            Errno::from(libc::EINVAL)
+        })?;
+
+    Ok(Some(SecurityContext {
+        name: OsString::from_vec(data[..name_end].to_vec()),
+        value: value.to_vec(),
+    }))
+}
+
+/// parse the `fuse_supp_groups`, kernel only sends the group of the parent directory.
+fn parse_supplementary_group(data: &[u8]) -> Result<Option<u32>> {
+    let nr_groups = data
+        .get(..4)
+        .and_then(|nr_groups| get_bincode_config().deserialize::<u32>(nr_groups).ok())
+        .ok_or_else(|| {
+            error!("fuse_supp_groups is truncated");
+
+            Errno::from(libc::EINVAL)
+        })?;
+
+    if nr_groups == 0 {
+        return Ok(None);
+    }
+
+    data.get(4..8)
+        .and_then(|group| get_bincode_config().deserialize::<u32>(group).ok())
+        .map(Some)
+        .ok_or_else(|| {
+            error!(nr_groups, "fuse_supp_groups is truncated");
+
+            Errno::from(libc::EINVAL)
+        })
+}
@@ -4,8 +4,16 @@ use bytes::Bytes;
 use futures_util::stream::Stream;
 
 use crate::notify::Notify;
+use crate::raw::extension::SecurityContext;
+use crate::raw::flags::{
+    FallocateMode, OpenFlags, PollEvents, RenameFlags, SetXattrFlags, WriteFlags,
+};
+#[cfg(feature = "file-lock")]
+use crate::raw::lock::{FileLock, LockType};
 use crate::raw::reply::*;
 use crate::raw::request::Request;
+#[cfg(target_os = "macos")]
+use crate::Timestamp;
 use crate::{Inode, Result, SetAttr};
 
 #[allow(unused_variables)]
@@ -21,7 +29,10 @@ pub trait Filesystem {
     /// <https://sourceforge.net/p/fuse/mailman/message/31995737/>
     async fn destroy(&self, req: Request);
 
-    /// look up a directory entry by name and get its attributes.
+    /// look up a directory entry by name and get its attributes. Return
+    /// [`ReplyEntry::negative`] to let kernel cache the missing entry, a plain `ENOENT` is not
+    /// cached. Other entries must not use the root inode or inode 0, see
+    /// [`FUSE_ROOT_ID`][crate::raw::reply::FUSE_ROOT_ID].
     async fn lookup(&self, req: Request, parent: Inode, name: &OsStr) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
@@ -49,6 +60,9 @@ pub trait Filesystem {
     }
 
     /// set file attributes. If `fh` is None, means `fh` is not set.
+    ///
+    /// use [`SetAttr::valid`] to know which attributes are requested. The filesystem should
+    /// update ctime when mode, uid, gid or size is changed, and reply the updated attributes.
     async fn setattr(
         &self,
         req: Request,
@@ -65,12 +79,17 @@ pub trait Filesystem {
     }
 
     /// create a symbolic link.
+    ///
+    /// `security_context` is the security context of the new symbolic link, it is only sent when
+    /// [`security_context`](crate::MountOptions::security_context) is enabled, see
+    /// [`SecurityContext`].
     async fn symlink(
         &self,
         req: Request,
         parent: Inode,
         name: &OsStr,
         link: &OsStr,
+        security_context: Option<&SecurityContext>,
     ) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
@@ -78,6 +97,15 @@ pub trait Filesystem {
     /// create file node. Create a regular file, character device, block device, fifo or socket
     /// node. When creating file, most cases user only need to implement
     /// [`create`][Filesystem::create].
+    ///
+    /// `umask` is the umask of the calling process, unless
+    /// [`dont_mask`](crate::MountOptions::dont_mask) is set, kernel has applied it to `mode`
+    /// already. It is 0 if kernel doesn't send it.
+    ///
+    /// `security_context` is the security context of the new node, it is only sent when
+    /// [`security_context`](crate::MountOptions::security_context) is enabled, see
+    /// [`SecurityContext`].
+    #[allow(clippy::too_many_arguments)]
     async fn mknod(
         &self,
         req: Request,
@@ -85,11 +113,21 @@ pub trait Filesystem {
         name: &OsStr,
         mode: u32,
         rdev: u32,
+        umask: u32,
+        security_context: Option<&SecurityContext>,
     ) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
 
     /// create a directory.
+    ///
+    /// `umask` is the umask of the calling process, unless
+    /// [`dont_mask`](crate::MountOptions::dont_mask) is set, kernel has applied it to `mode`
+    /// already. It is 0 if kernel doesn't send it.
+    ///
+    /// `security_context` is the security context of the new directory, it is only sent when
+    /// [`security_context`](crate::MountOptions::security_context) is enabled, see
+    /// [`SecurityContext`].
     async fn mkdir(
         &self,
         req: Request,
@@ -97,6 +135,7 @@ pub trait Filesystem {
         name: &OsStr,
         mode: u32,
         umask: u32,
+        security_context: Option<&SecurityContext>,
     ) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
@@ -111,7 +150,8 @@ pub trait Filesystem {
         Err(libc::ENOSYS.into())
     }
 
-    /// rename a file or directory.
+    /// rename a file or directory, it is called by the default
+    /// [`rename2`][Filesystem::rename2] when the rename has no flags.
     async fn rename(
         &self,
         req: Request,
@@ -138,17 +178,26 @@ pub trait Filesystem {
     /// available in flags. Filesystem may store an arbitrary file handle (pointer, index, etc) in
     /// fh, and use this in other all other file operations (read, write, flush, release, fsync).
     /// Filesystem may also implement stateless file I/O and not store anything in fh. There are
-    /// also some flags (`direct_io`, `keep_cache`) which the filesystem may set, to change the way
+    /// also some flags ([`direct_io`][ReplyOpen::direct_io], [`keep_cache`][ReplyOpen::keep_cache],
+    /// [`nonseekable`][ReplyOpen::nonseekable]) which the filesystem may set, to change the way
     /// the file is opened. A filesystem need not implement this method if it
     /// sets [`MountOptions::no_open_support`][crate::MountOptions::no_open_support] and if the
-    /// kernel supports `FUSE_NO_OPEN_SUPPORT`.
+    /// kernel supports `FUSE_NO_OPEN_SUPPORT`, the files are opened without sending open and
+    /// release requests then, and `fh` of the other file operations is always 0, see
+    /// [`MountOptions::no_open_support`][crate::MountOptions::no_open_support] for the stateless
+    /// open mode.
     ///
     /// # Notes:
     ///
     /// See `fuse_file_info` structure in
     /// [fuse_common.h](https://libfuse.github.io/doxygen/include_2fuse__common_8h_source.html) for
     /// more details.
-    async fn open(&self, req: Request, inode: Inode, flags: u32) -> Result<ReplyOpen> {
+    ///
+    /// when the caller uses direct io, [`OpenFlags::is_direct`] is true, read doesn't carry the
+    /// open flags, so record it in the state of `fh` if the filesystem needs it in read. Before
+    /// passing the direct io to a backing file opened with `O_DIRECT`, see
+    /// [`DirectIoAlignment`][crate::util::DirectIoAlignment] for the alignment.
+    async fn open(&self, req: Request, inode: Inode, flags: OpenFlags) -> Result<ReplyOpen> {
         Err(libc::ENOSYS.into())
     }
 
@@ -157,6 +206,10 @@ pub trait Filesystem {
     /// when the file has been opened in `direct_io` mode, in which case the return value of the
     /// read system call will reflect the return value of this operation. `fh` will contain the
     /// value set by the open method, or will be undefined if the open method didn't set any value.
+    /// It is 0 with the zero-message open, see [`open`][Self::open].
+    ///
+    /// the `offset` and `size` of a direct io read may not be aligned, see
+    /// [`DirectIoAlignment`][crate::util::DirectIoAlignment].
     async fn read(
         &self,
         req: Request,
@@ -168,13 +221,41 @@ pub trait Filesystem {
         Err(libc::ENOSYS.into())
     }
 
+    /// read data like [`read`][Filesystem::read], but the data can be replied from a file
+    /// without reading it into memory, see [`ReplyReadBuf::File`]. When it returns `ENOSYS`,
+    /// which is the default implementation, [`read`][Filesystem::read] is called instead, and
+    /// it won't be called again.
+    async fn read_buf(
+        &self,
+        req: Request,
+        inode: Inode,
+        fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<ReplyReadBuf> {
+        Err(libc::ENOSYS.into())
+    }
+
     /// write data. Write should return exactly the number of bytes requested except on error. An
     /// exception to this is when the file has been opened in `direct_io` mode, in which case the
     /// return value of the write system call will reflect the return value of this operation. `fh`
     /// will contain the value set by the open method, or will be undefined if the open method
-    /// didn't set any value. When `write_flags` contains
-    /// [`FUSE_WRITE_CACHE`](crate::raw::flags::FUSE_WRITE_CACHE), means the write operation is a
-    /// delay write.
+    /// didn't set any value. It is 0 with the zero-message open.
+    ///
+    /// # Notes:
+    ///
+    /// when `write_flags` contains [`WriteFlags::CACHE`], the data is written back from the page
+    /// cache by kernel, which only happens when
+    /// [`MountOptions::write_back`][crate::MountOptions::write_back] is enabled. In this case
+    /// `fh` is picked by kernel from the opens of `inode` which have write access, it may not be
+    /// the open which the data was written through, and `flags` are the flags of that open. The
+    /// filesystem should locate the file by `inode` and not rely on the per-open state of `fh`,
+    /// such as the open flags or the lock owner. `fh` is still valid because kernel delays its
+    /// [`release`][Filesystem::release] until the cached data is written back. Without
+    /// [`WriteFlags::CACHE`], `fh` is the open which the data was written through.
+    ///
+    /// `flags` are the open flags, [`OpenFlags::is_direct`] tells a direct io write, its `offset`,
+    /// size and data may not be aligned, see [`DirectIoAlignment`][crate::util::DirectIoAlignment].
     #[allow(clippy::too_many_arguments)]
     async fn write(
         &self,
@@ -183,12 +264,39 @@ pub trait Filesystem {
         fh: u64,
         offset: u64,
         data: &[u8],
-        write_flags: u32,
+        write_flags: WriteFlags,
         flags: u32,
     ) -> Result<ReplyWrite> {
         Err(libc::ENOSYS.into())
     }
 
+    /// write data in a [`Bytes`] buffer, it is called by fuse3 instead of
+    /// [`write`][Filesystem::write]. The default implementation calls
+    /// [`write`][Filesystem::write] with the contiguous data.
+    ///
+    /// # Notes:
+    ///
+    /// the large write data is not copied after it is read from `/dev/fuse`, `data` refers to the
+    /// read buffer directly. A filesystem which wants to avoid buffering can keep `data` without
+    /// copy, consume it incrementally by [`Buf`][bytes::Buf], or write it to a backing fd.
+    #[allow(clippy::too_many_arguments)]
+    async fn write_buf(
+        &self,
+        req: Request,
+        inode: Inode,
+        fh: u64,
+        offset: u64,
+        data: Bytes,
+        write_flags: WriteFlags,
+        flags: u32,
+    ) -> Result<ReplyWrite>
+    where
+        Self: Sync,
+    {
+        self.write(req, inode, fh, offset, &data, write_flags, flags)
+            .await
+    }
+
     /// get filesystem statistics.
     async fn statfs(&self, req: Request, inode: Inode) -> Result<ReplyStatFs> {
         Err(libc::ENOSYS.into())
@@ -196,11 +304,21 @@ pub trait Filesystem {
 
     /// release an open file. Release is called when there are no more references to an open file:
     /// all file descriptors are closed and all memory mappings are unmapped. For every open call
-    /// there will be exactly one release call. The filesystem may reply with an error, but error
-    /// values are not returned to `close()` or `munmap()` which triggered the release. `fh` will
-    /// contain the value set by the open method, or will be undefined if the open method didn't
-    /// set any value. `flags` will contain the same flags as for open. `flush` means flush the
-    /// data or not when closing file.
+    /// there will be exactly one release call, except the zero-message open which has no release
+    /// call. The filesystem may reply with an error, but error values are not returned to
+    /// `close()` or `munmap()` which triggered the release. `fh` will contain the value set by the
+    /// open method, or will be undefined if the open method didn't set any value. `flags` will
+    /// contain the same flags as for open.
+    ///
+    /// # Notes:
+    ///
+    /// release is sent after the [`flush`][Filesystem::flush] of the last `close()`, so the
+    /// buffered writes should be flushed and the POSIX locks of the closing process should be
+    /// removed there, because kernel passes their `lock_owner` to flush only. When the file was
+    /// locked with `flock(2)`, `lock_owner` is the owner of the flock locks, and the `flock`
+    /// unlock of it is called before release when the `file-lock` feature is enabled, otherwise
+    /// `lock_owner` is 0. `flush` is true when kernel asks to flush the data on release, Linux
+    /// kernel doesn't ask it now.
     async fn release(
         &self,
         req: Request,
@@ -219,22 +337,26 @@ pub trait Filesystem {
         Ok(())
     }
 
-    /// set an extended attribute.
+    /// set an extended attribute. Use [`SetXattrFlags::check`] to handle `XATTR_CREATE` and
+    /// `XATTR_REPLACE` in `flags`, and [`XattrNamespace::of`][crate::raw::XattrNamespace::of] to
+    /// get the namespace of `name`.
     async fn setxattr(
         &self,
         req: Request,
         inode: Inode,
         name: &OsStr,
         value: &[u8],
-        flags: u32,
+        flags: SetXattrFlags,
         position: u32,
     ) -> Result<()> {
         Err(libc::ENOSYS.into())
     }
 
-    /// Get an extended attribute. If `size` is too small, return `Err<ERANGE>`.
-    /// Otherwise, use [`ReplyXAttr::Data`] to send the attribute data, or
-    /// return an error.
+    /// Get an extended attribute. If `size` is 0, use [`ReplyXAttr::Size`] to return the size of
+    /// the attribute data. If `size` is too small, return `Err<ERANGE>`. Otherwise, use
+    /// [`ReplyXAttr::Data`] to send the attribute data, or return an error. A
+    /// [`ReplyXAttr::Data`] is replied as the size or `ERANGE` automatically, so replying the
+    /// whole data for any `size` is also correct, see [`ReplyXAttr::new`].
     async fn getxattr(
         &self,
         req: Request,
@@ -247,8 +369,10 @@ pub trait Filesystem {
 
     /// List extended attribute names.
     ///
-    /// If `size` is too small, return `Err<ERANGE>`.  Otherwise, use
-    /// [`ReplyXAttr::Data`] to send the attribute list, or return an error.
+    /// If `size` is 0, use [`ReplyXAttr::Size`] to return the size of the attribute list. If
+    /// `size` is too small, return `Err<ERANGE>`.  Otherwise, use [`ReplyXAttr::Data`] to send
+    /// the attribute list, or return an error. The list is the `\0` terminated names, use
+    /// [`ReplyXAttr::from_names`] to create it.
     async fn listxattr(&self, req: Request, inode: Inode, size: u32) -> Result<ReplyXAttr> {
         Err(libc::ENOSYS.into())
     }
@@ -270,6 +394,10 @@ pub trait Filesystem {
     /// flush pending writes. One reason to flush data, is if the filesystem wants to return write
     /// errors. If the filesystem supports file locking operations ([`setlk`][Filesystem::setlk],
     /// [`getlk`][Filesystem::getlk]) it should remove all locks belonging to `lock_owner`.
+    /// `lock_owner` identifies the file table of the closing process, which is the owner of its
+    /// POSIX locks. The `flock(2)` locks belong to the open file instead, they are kept until
+    /// [`release`][Filesystem::release]. If flush replies `ENOSYS`, kernel will not send flush any
+    /// more and the locks can't be removed on `close()`.
     async fn flush(&self, req: Request, inode: Inode, fh: u64, lock_owner: u64) -> Result<()> {
         Err(libc::ENOSYS.into())
     }
@@ -278,10 +406,11 @@ pub trait Filesystem {
     /// `fh`, and use this in other all other directory stream operations
     /// ([`readdir`][Filesystem::readdir], [`releasedir`][Filesystem::releasedir],
     /// [`fsyncdir`][Filesystem::fsyncdir]). Filesystem may also implement stateless directory
-    /// I/O and not store anything in `fh`.  A file system need not implement this method if it
-    /// sets [`MountOptions::no_open_dir_support`][crate::MountOptions::no_open_dir_support] and
-    /// if the kernel supports `FUSE_NO_OPENDIR_SUPPORT`.
-    async fn opendir(&self, req: Request, inode: Inode, flags: u32) -> Result<ReplyOpen> {
+    /// I/O and not store anything in `fh`. Set [`cache_readdir`][ReplyOpen::cache_readdir] to
+    /// let kernel cache the directory entries.  A file system need not implement this method if
+    /// it sets [`MountOptions::no_open_dir_support`][crate::MountOptions::no_open_dir_support]
+    /// and if the kernel supports `FUSE_NO_OPENDIR_SUPPORT`.
+    async fn opendir(&self, req: Request, inode: Inode, flags: OpenFlags) -> Result<ReplyOpen> {
         Err(libc::ENOSYS.into())
     }
 
@@ -292,7 +421,8 @@ pub trait Filesystem {
 
     /// read directory. `offset` is used to track the offset of the directory entries. `fh` will
     /// contain the value set by the [`opendir`][Filesystem::opendir] method, or will be
-    /// undefined if the [`opendir`][Filesystem::opendir] method didn't set any value.
+    /// undefined if the [`opendir`][Filesystem::opendir] method didn't set any value. The
+    /// entries are pulled only until the reply buffer is full, see [`ReplyDirectory`].
     async fn readdir<'a>(
         &'a self,
         req: Request,
@@ -311,56 +441,120 @@ pub trait Filesystem {
         Ok(())
     }
 
-    /// synchronize directory contents. If the `datasync` is true, then only the directory contents
-    /// should be flushed, not the metadata. `fh` will contain the value set by the
+    /// synchronize directory contents, it is called when `fsync(2)` or `fdatasync(2)` is called
+    /// on an open directory, such as making the entries created in it durable. If the `datasync`
+    /// is true, then only the directory contents should be flushed, not the metadata of the
+    /// directory itself, such as the times. `fh` will contain the value set by the
     /// [`opendir`][Filesystem::opendir] method, or will be undefined if the
-    /// [`opendir`][Filesystem::opendir] method didn't set any value.
+    /// [`opendir`][Filesystem::opendir] method didn't set any value. The default implementation
+    /// does nothing and returns success.
+    ///
+    /// # Notes:
+    ///
+    /// when this method returns `ENOSYS`, kernel treats it as success and never sends fsyncdir
+    /// again, a filesystem which has nothing to flush can return `ENOSYS` to avoid the later
+    /// requests.
     async fn fsyncdir(&self, req: Request, inode: Inode, fh: u64, datasync: bool) -> Result<()> {
-        Err(libc::ENOSYS.into())
+        Ok(())
     }
 
     #[cfg(feature = "file-lock")]
-    /// test for a POSIX file lock.
+    /// test for a POSIX file lock. Reply the first lock of another owner which conflicts with
+    /// `lock` by [`ReplyLock::conflict`], or reply [`ReplyLock::unlocked`] if `lock` can be
+    /// placed. The locks of `lock.owner` never conflict with `lock`.
     ///
     /// # Notes:
     ///
     /// this is supported on enable **`file-lock`** feature.
-    #[allow(clippy::too_many_arguments)]
     async fn getlk(
         &self,
         req: Request,
         inode: Inode,
         fh: u64,
-        lock_owner: u64,
-        start: u64,
-        end: u64,
-        r#type: u32,
-        pid: u32,
-    ) -> Result<ReplyLock>;
+        lock: FileLock,
+    ) -> Result<ReplyLock> {
+        Err(libc::ENOSYS.into())
+    }
 
     #[cfg(feature = "file-lock")]
-    /// acquire, modify or release a POSIX file lock.
+    /// acquire, modify or release a POSIX file lock without blocking, return `EAGAIN` if `lock`
+    /// conflicts with a lock of another owner.
     ///
     /// # Notes:
     ///
     /// this is supported on enable **`file-lock`** feature.
-    #[allow(clippy::too_many_arguments)]
     async fn setlk(
         &self,
         req: Request,
         inode: Inode,
         fh: u64,
-        lock_owner: u64,
-        start: u64,
-        end: u64,
-        r#type: u32,
-        pid: u32,
+        lock: FileLock,
+    ) -> Result<()> {
+        Err(libc::ENOSYS.into())
+    }
+
+    #[cfg(feature = "file-lock")]
+    /// acquire a POSIX file lock, wait until the conflicting locks are released.
+    ///
+    /// # Notes:
+    ///
+    /// this is supported on enable **`file-lock`** feature.
+    ///
+    /// the reply is deferred until the returned future is done, the calling process is blocked
+    /// in kernel until then, and [`MountOptions::request_timeout`] doesn't apply to it. When the
+    /// calling process is interrupted by a signal, the future is dropped and `EINTR` is replied.
+    /// If [`MountOptions::forward_interrupt`] is enabled,
+    /// [`interrupt`][Filesystem::interrupt] is called with the unique of this request instead, the
+    /// filesystem should stop waiting and return `EINTR`.
+    ///
+    /// [`MountOptions::request_timeout`]: crate::MountOptions::request_timeout
+    /// [`MountOptions::forward_interrupt`]: crate::MountOptions::forward_interrupt
+    async fn setlkw(
+        &self,
+        req: Request,
+        inode: Inode,
+        fh: u64,
+        lock: FileLock,
+    ) -> Result<()> {
+        Err(libc::ENOSYS.into())
+    }
+
+    #[cfg(feature = "file-lock")]
+    /// acquire, convert or release a BSD style whole file lock of `flock(2)`, it is called only
+    /// when [`MountOptions::flock_locks`] is enabled. `type` is [`LockType::Read`] for
+    /// `LOCK_SH`, [`LockType::Write`] for `LOCK_EX` and [`LockType::Unlock`] for `LOCK_UN`.
+    /// `owner` identifies the open file description, the flock locks are independent of the POSIX
+    /// locks of [`setlk`][Filesystem::setlk], don't check the conflicts between them. If `block`
+    /// is false (`LOCK_NB`), return `EWOULDBLOCK` if `type` conflicts with a lock of another
+    /// owner, otherwise wait until the lock can be placed.
+    ///
+    /// # Notes:
+    ///
+    /// this is supported on enable **`file-lock`** feature.
+    ///
+    /// when `block` is true, the request is deferred and interrupted the same as
+    /// [`setlkw`][Filesystem::setlkw]. When a file which has flock locks is released, `flock` is
+    /// called with [`LockType::Unlock`] and the `lock_owner` before
+    /// [`release`][Filesystem::release].
+    ///
+    /// [`MountOptions::flock_locks`]: crate::MountOptions::flock_locks
+    async fn flock(
+        &self,
+        req: Request,
+        inode: Inode,
+        fh: u64,
+        owner: u64,
+        r#type: LockType,
         block: bool,
-    ) -> Result<()>;
+    ) -> Result<()> {
+        Err(libc::ENOSYS.into())
+    }
 
-    /// check file access permissions. This will be called for the `access()` system call. If the
-    /// `default_permissions` mount option is given, this method is not be called. This method is
-    /// not called under Linux kernel versions 2.4.x.
+    /// check file access permissions. This will be called for the `access()` system call and
+    /// `chdir(2)`. If the [`default_permissions`][crate::MountOptions::default_permissions] mount
+    /// option is given, kernel checks the permissions itself and this method is not called,
+    /// unless [`forward_access`][crate::MountOptions::forward_access] is enabled and kernel
+    /// still sends the request. This method is not called under Linux kernel versions 2.4.x.
     async fn access(&self, req: Request, inode: Inode, mask: u32) -> Result<()> {
         Err(libc::ENOSYS.into())
     }
@@ -376,33 +570,70 @@ pub trait Filesystem {
     /// 2.6.15, the [`mknod`][Filesystem::mknod] and [`open`][Filesystem::open] methods will be
     /// called instead.
     ///
+    /// `umask` is the umask of the calling process, unless
+    /// [`dont_mask`](crate::MountOptions::dont_mask) is set, kernel has applied it to `mode`
+    /// already. It is 0 if kernel doesn't send it.
+    ///
+    /// `security_context` is the security context of the new file, it is only sent when
+    /// [`security_context`](crate::MountOptions::security_context) is enabled, see
+    /// [`SecurityContext`].
+    ///
+    /// A successful reply counts one lookup of the new inode, same as
+    /// [`lookup`][Filesystem::lookup], kernel will [`forget`][Filesystem::forget] it later.
+    ///
+    /// # Fallback:
+    ///
+    /// when this method returns `ENOSYS`, kernel remembers it and never sends create again for
+    /// this mount, each following `open(2)` with `O_CREAT` of a non-existent file becomes a
+    /// [`mknod`][Filesystem::mknod] with a regular file `mode` and `rdev` 0, followed by
+    /// an [`open`][Filesystem::open] without `O_CREAT` and `O_EXCL`, so the two steps are
+    /// not atomic.
+    /// Kernel before 2.6.15 (protocol minor version 1) also uses this fallback. A filesystem
+    /// which doesn't implement create detects the fallback by a `S_IFREG` mknod, and must
+    /// implement both mknod and open.
+    ///
     /// # Notes:
     ///
     /// See `fuse_file_info` structure in
     /// [fuse_common.h](https://libfuse.github.io/doxygen/include_2fuse__common_8h_source.html) for
     /// more details.
+    #[allow(clippy::too_many_arguments)]
     async fn create(
         &self,
         req: Request,
         parent: Inode,
         name: &OsStr,
         mode: u32,
-        flags: u32,
+        flags: OpenFlags,
+        umask: u32,
+        security_context: Option<&SecurityContext>,
     ) -> Result<ReplyCreated> {
         Err(libc::ENOSYS.into())
     }
 
     /// handle interrupt. When a operation is interrupted, an interrupt request will send to fuse
     /// server with the unique id of the operation.
+    ///
+    /// # Notes:
+    ///
+    /// this is only called when [`MountOptions::forward_interrupt`] is enabled, otherwise the
+    /// interrupted operation is cancelled by dropping its future.
+    ///
+    /// [`MountOptions::forward_interrupt`]: crate::MountOptions::forward_interrupt
     async fn interrupt(&self, req: Request, unique: u64) -> Result<()> {
         Err(libc::ENOSYS.into())
     }
 
-    /// map block index within file to block index within device.
+    /// map block index within file to block index within device. `blocksize` is the block size
+    /// of the device, `idx` is the block index within the file in `blocksize` units, the block
+    /// index within device should be replied in the same units. It is used by `ioctl(FIBMAP)` and
+    /// some bootloaders.
     ///
     /// # Notes:
     ///
-    /// This may not works because currently this crate doesn't support fuseblk mode yet.
+    /// kernel only sends bmap for a fuseblk mount, which is backed by a real block device, so it
+    /// is meaningful only for a block device backed filesystem and rarely used, currently this
+    /// crate doesn't support fuseblk mode yet. If ENOSYS is replied, kernel won't send bmap again.
     async fn bmap(
         &self,
         req: Request,
@@ -413,7 +644,24 @@ pub trait Filesystem {
         Err(libc::ENOSYS.into())
     }
 
-    /*async fn ioctl(
+    /// control device. `cmd` and `arg` are the `ioctl(2)` arguments, `in_data` is the input data
+    /// copied from the caller, and `out_size` is the max size of the output data, `flags` is the
+    /// `FUSE_IOCTL_*` flags in [`flags`][crate::raw::flags].
+    ///
+    /// # Notes:
+    ///
+    /// for a normal fuse filesystem kernel sends restricted ioctl, `in_data` and `out_size` are
+    /// decided by the size and direction encoded in `cmd`, and only [`ReplyIoctl::Done`] is
+    /// allowed. When [`FUSE_IOCTL_UNRESTRICTED`] is set, `arg` may be a pointer which kernel
+    /// can't understand, reply [`ReplyIoctl::Retry`] with the caller memory areas to read and
+    /// write, then kernel will send the ioctl again with the `in_data` read from the `in_iovs`
+    /// and `out_size` is the total length of the `out_iovs`. `FUSE_IOCTL_DIR` is set when the
+    /// ioctl is on a directory, which needs [`InitFlags::HAS_IOCTL_DIR`] supported by kernel.
+    ///
+    /// [`FUSE_IOCTL_UNRESTRICTED`]: crate::raw::flags::FUSE_IOCTL_UNRESTRICTED
+    /// [`InitFlags::HAS_IOCTL_DIR`]: crate::raw::flags::InitFlags::HAS_IOCTL_DIR
+    #[allow(clippy::too_many_arguments)]
+    async fn ioctl(
         &self,
         req: Request,
         inode: Inode,
@@ -421,13 +669,21 @@ pub trait Filesystem {
         flags: u32,
         cmd: u32,
         arg: u64,
-        in_size: u32,
+        in_data: &[u8],
         out_size: u32,
     ) -> Result<ReplyIoctl> {
         Err(libc::ENOSYS.into())
-    }*/
+    }
 
-    /// poll for IO readiness events.
+    /// poll for IO readiness events, return the events which are ready now.
+    ///
+    /// # Notes:
+    ///
+    /// `kh` is `Some` only when kernel sets the `FUSE_POLL_SCHEDULE_NOTIFY` flag, it means
+    /// kernel wants to be notified when the events are ready, keep the `kh` and a clone of
+    /// `notify`, and call [`Notify::wakeup`] with the `kh` when the IO is ready. When `kh` is
+    /// `None`, kernel doesn't want to be notified, just return the ready events. When no
+    /// requested event is ready, reply [`ReplyPoll::pending`], see [`ReplyPoll`] for an example.
     #[allow(clippy::too_many_arguments)]
     async fn poll(
         &self,
@@ -436,13 +692,20 @@ pub trait Filesystem {
         fh: u64,
         kh: Option<u64>,
         flags: u32,
-        events: u32,
+        events: PollEvents,
         notify: &Notify,
     ) -> Result<ReplyPoll> {
         Err(libc::ENOSYS.into())
     }
 
     /// receive notify reply from kernel.
+    ///
+    /// # Notes:
+    ///
+    /// the reply of a retrieve notify sent by [`Notify::retrieve`] is delivered to the returned
+    /// [`RetrieveHandle`], only the reply which can't be matched will be passed to this method.
+    ///
+    /// [`RetrieveHandle`]: crate::notify::RetrieveHandle
     async fn notify_reply(
         &self,
         req: Request,
@@ -453,15 +716,25 @@ pub trait Filesystem {
         Err(libc::ENOSYS.into())
     }
 
-    /// forget more than one inode. This is a batch version [`forget`][Filesystem::forget]
-    async fn batch_forget(&self, req: Request, inodes: &[Inode]) {}
+    /// forget more than one inode. This is a batch version [`forget`][Filesystem::forget], each
+    /// item of `inodes` is the `(inode, nlookup)` pair. The default implementation calls
+    /// [`forget`][Filesystem::forget] for each item in order.
+    async fn batch_forget(&self, req: Request, inodes: &[(Inode, u64)])
+    where
+        Self: Sync,
+    {
+        for &(inode, nlookup) in inodes {
+            self.forget(req, inode, nlookup).await;
+        }
+    }
 
     /// allocate space for an open file. This function ensures that required space is allocated for
     /// specified file.
     ///
     /// # Notes:
     ///
-    /// more information about `fallocate`, please see **`man 2 fallocate`**
+    /// more information about `fallocate`, please see **`man 2 fallocate`**, the raw mode is still
+    /// available by [`FallocateMode::bits`].
     async fn fallocate(
         &self,
         req: Request,
@@ -469,7 +742,7 @@ pub trait Filesystem {
         fh: u64,
         offset: u64,
         length: u64,
-        mode: u32,
+        mode: FallocateMode,
     ) -> Result<()> {
         Err(libc::ENOSYS.into())
     }
@@ -480,7 +753,18 @@ pub trait Filesystem {
         Self: 'a;
 
     /// read directory entries, but with their attribute, like [`readdir`][Filesystem::readdir]
-    /// + [`lookup`][Filesystem::lookup] at the same time.
+    /// + [`lookup`][Filesystem::lookup] at the same time, so kernel doesn't need to lookup the
+    /// entries after listing the directory.
+    ///
+    /// # Notes:
+    ///
+    /// kernel counts one lookup for each replied entry except `.` and `..`, just like a
+    /// successful [`lookup`][Filesystem::lookup], and will [`forget`][Filesystem::forget] it
+    /// later. So the lookup count of the entry inode must be increased when the entry is
+    /// produced by the stream, fuse3 forgets the produced entries which are not replied. If the
+    /// lookup count is not increased, the inode may be reclaimed and its number reused while
+    /// kernel still references it, if it is increased but never forgotten, the inode is leaked.
+    /// [`InodeTable`][crate::util::InodeTable] can track the lookup count.
     async fn readdirplus<'a>(
         &'a self,
         req: Request,
@@ -492,7 +776,17 @@ pub trait Filesystem {
         Err(libc::ENOSYS.into())
     }
 
-    /// rename a file or directory with flags.
+    /// rename a file or directory with flags. Kernel sends the legacy rename without flags here
+    /// too, with empty `flags`. The default implementation calls [`rename`][Filesystem::rename]
+    /// when `flags` is empty, or returns ENOSYS.
+    ///
+    /// # Notes:
+    ///
+    /// with [`RenameFlags::EXCHANGE`], the two names should be swapped atomically; with
+    /// [`RenameFlags::NOREPLACE`], return EEXIST if the new name exists. If return ENOSYS for a
+    /// flagged rename, kernel won't send rename with flags anymore, and the `renameat2(2)` with
+    /// flags fails with EINVAL, kernel can't fall back because the flags semantics can't be done
+    /// by a plain rename.
     async fn rename2(
         &self,
         req: Request,
@@ -500,12 +794,26 @@ pub trait Filesystem {
         name: &OsStr,
         new_parent: Inode,
         new_name: &OsStr,
-        flags: u32,
-    ) -> Result<()> {
-        Err(libc::ENOSYS.into())
+        flags: RenameFlags,
+    ) -> Result<()>
+    where
+        Self: Sync,
+    {
+        if !flags.is_empty() {
+            return Err(libc::ENOSYS.into());
+        }
+
+        self.rename(req, parent, name, new_parent, new_name).await
     }
 
     /// find next data or hole after the specified offset.
+    ///
+    /// `whence` is the raw `SEEK_*` value passed to `lseek(2)`, kernel only sends `libc::SEEK_DATA`
+    /// and `libc::SEEK_HOLE` to FUSE server, other `whence` are handled by kernel itself. When
+    /// `whence` is `SEEK_DATA`, reply the start of the next data region at or after `offset`; when
+    /// `whence` is `SEEK_HOLE`, reply the start of the next hole at or after `offset`, the end of
+    /// file is treated as a hole. If there is no more data after `offset`, return ENXIO. If return
+    /// ENOSYS, kernel will treat the whole file as data and never send lseek again.
     async fn lseek(
         &self,
         req: Request,
@@ -537,5 +845,188 @@ pub trait Filesystem {
         Err(libc::ENOSYS.into())
     }
 
+    /// synchronize the whole filesystem, it is called when `syncfs(2)` is called on the mount,
+    /// the filesystem should flush all its dirty state. The default implementation does nothing
+    /// and returns success.
+    ///
+    /// # Notes:
+    ///
+    /// there is no capability to negotiate in init, kernel sends it only when the connection
+    /// enables `sync_fs`, which is only virtiofs now, so a filesystem mounted through
+    /// `/dev/fuse` can't rely on it for durability yet, it should flush in
+    /// [`fsync`][Filesystem::fsync] and [`flush`][Filesystem::flush] too.
+    async fn syncfs(&self, req: Request) -> Result<()> {
+        Ok(())
+    }
+
+    /// get the canonical path of an inode, which kernel resolves to the real file behind it,
+    /// such as the file of the lower filesystem which a stacked filesystem passes through. The
+    /// default implementation returns `ENOSYS`, then kernel uses the fuse file itself.
+    ///
+    /// the path should be absolute, kernel resolves it in the filesystem process. Return it
+    /// without the trailing null byte, fuse3 appends it, a path which is not shorter than
+    /// `PATH_MAX` is replied with `ENAMETOOLONG`.
+    ///
+    /// # Notes:
+    ///
+    /// `FUSE_CANONICAL_PATH` is not in the mainline Linux kernel, only the Android kernels send
+    /// it, when inotify watches a fuse file, so the events of the real file are reported. There
+    /// is no capability to negotiate in init.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use bytes::Bytes;
+    /// # use fuse3::raw::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// struct Fs;
+    ///
+    /// impl Filesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #   }
+    /// #
+    /// #   async fn destroy(&self, _req: Request) {}
+    /// #
+    ///     async fn canonical_path(&self, _req: Request, inode: u64) -> Result<ReplyData> {
+    ///         // the lower file of the inode
+    ///         let path = format!("/data/lower/{inode}");
+    ///
+    ///         Ok(ReplyData { data: Bytes::from(path) })
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut message = Vec::new();
+    /// message.extend_from_slice(&40u32.to_ne_bytes()); // len
+    /// message.extend_from_slice(&2016u32.to_ne_bytes()); // FUSE_CANONICAL_PATH
+    /// message.extend_from_slice(&100u64.to_ne_bytes()); // unique
+    /// message.extend_from_slice(&2u64.to_ne_bytes()); // nodeid
+    /// message.extend_from_slice(&[0; 16]); // uid, gid, pid, total_extlen and padding
+    ///
+    /// let mut session = TestSession::new(Fs, MountOptions::default());
+    /// session.init(request(0, 0, 0)).await.unwrap();
+    ///
+    /// let reply = session.send_message(&message).await.unwrap();
+    /// assert_eq!(reply, &b"/data/lower/2\0"[..]);
+    /// # }
+    /// ```
+    async fn canonical_path(&self, req: Request, inode: Inode) -> Result<ReplyData> {
+        Err(libc::ENOSYS.into())
+    }
+
+    /// get the macOS extended times, the backup time and the creation time, of an inode.
+    ///
+    /// # Notes:
+    ///
+    /// macFUSE sends it only when the [`XTIMES`][crate::raw::flags::InitFlags::XTIMES] init
+    /// flag is enabled.
+    #[cfg(target_os = "macos")]
+    async fn getxtimes(&self, req: Request, inode: Inode) -> Result<ReplyXTimes> {
+        Err(libc::ENOSYS.into())
+    }
+
+    /// set the macOS extended times of an inode, `None` means the time is not changed.
+    ///
+    /// # Notes:
+    ///
+    /// there is no setxtimes request, macFUSE sends the times in setattr, it is called before
+    /// [`setattr`][Filesystem::setattr] when the setattr request sets the backup time or the
+    /// creation time, and the setattr request fails if it fails with an error other than
+    /// `ENOSYS`, so the default implementation doesn't fail the setattr. The times are still in
+    /// the [`SetAttr`] of setattr.
+    #[cfg(target_os = "macos")]
+    async fn setxtimes(
+        &self,
+        req: Request,
+        inode: Inode,
+        bkuptime: Option<Timestamp>,
+        crtime: Option<Timestamp>,
+    ) -> Result<()> {
+        Err(libc::ENOSYS.into())
+    }
+
+    /// handle a request whose opcode is not modeled by fuse3, such as an opcode added by a newer
+    /// kernel or a vendor-specific one. `opcode` and `inode` are the `opcode` and `nodeid` of
+    /// `fuse_in_header`, `data` is the request arguments after the header, the request
+    /// extensions are removed. The default implementation replies `ENOSYS`, like fuse3 did
+    /// before the hook.
+    ///
+    /// # Notes:
+    ///
+    /// fuse3 writes the `fuse_out_header` with the `unique` and `len` of the reply, the
+    /// filesystem only returns the reply arguments in [`ReplyRaw::Data`], they must be the exact
+    /// `*_out` structure kernel expects for the opcode, in native endian and with its padding,
+    /// because kernel checks the reply size. Reply [`ReplyRaw::NoReply`] only for the requests
+    /// which kernel doesn't wait the reply, like forget, or the caller hangs. Return an error to
+    /// reply only the header with the errno.
+    ///
+    /// the request is tracked as in flight, but it isn't observed by the
+    /// [`SessionObserver`][crate::raw::SessionObserver], and can't be interrupted or timed out.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use bytes::Bytes;
+    /// # use fuse3::raw::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// const VENDOR_ECHO: u32 = 4096;
+    ///
+    /// struct Fs;
+    ///
+    /// impl Filesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #   }
+    /// #
+    /// #   async fn destroy(&self, _req: Request) {}
+    /// #
+    ///     async fn raw_request(&self, _req: Request, opcode: u32, _inode: u64, data: &[u8])
+    ///         -> Result<ReplyRaw> {
+    ///         match opcode {
+    ///             VENDOR_ECHO => Ok(ReplyRaw::Data(Bytes::copy_from_slice(data))),
+    ///             _ => Err(libc::ENOSYS.into()),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut message = Vec::new();
+    /// message.extend_from_slice(&48u32.to_ne_bytes()); // len
+    /// message.extend_from_slice(&VENDOR_ECHO.to_ne_bytes()); // opcode
+    /// message.extend_from_slice(&100u64.to_ne_bytes()); // unique
+    /// message.extend_from_slice(&1u64.to_ne_bytes()); // nodeid
+    /// message.extend_from_slice(&[0; 16]); // uid, gid, pid, total_extlen and padding
+    /// message.extend_from_slice(&42u64.to_ne_bytes()); // the vendor argument
+    ///
+    /// let mut session = TestSession::new(Fs, MountOptions::default());
+    /// session.init(request(0, 0, 0)).await.unwrap();
+    ///
+    /// let reply = session.send_message(&message).await.unwrap();
+    /// assert_eq!(reply, &42u64.to_ne_bytes()[..]);
+    /// # }
+    /// ```
+    async fn raw_request(
+        &self,
+        req: Request,
+        opcode: u32,
+        inode: Inode,
+        data: &[u8],
+    ) -> Result<ReplyRaw> {
+        Err(libc::ENOSYS.into())
+    }
+
     // TODO setupmapping and removemapping
 }
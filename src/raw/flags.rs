@@ -1,5 +1,10 @@
 //! request flags.
 
+use bitflags::bitflags;
+
+use crate::raw::abi::*;
+use crate::{Errno, Result};
+
 pub use crate::raw::abi::FUSE_IOCTL_32BIT;
 pub use crate::raw::abi::FUSE_IOCTL_COMPAT;
 pub use crate::raw::abi::FUSE_IOCTL_DIR;
@@ -10,3 +15,541 @@ pub use crate::raw::abi::FUSE_POLL_SCHEDULE_NOTIFY;
 pub use crate::raw::abi::FUSE_READ_LOCKOWNER;
 pub use crate::raw::abi::FUSE_WRITE_CACHE;
 pub use crate::raw::abi::FUSE_WRITE_LOCKOWNER;
+
+bitflags! {
+    /// the `FUSE_INIT` flags, which describe the capabilities of the fuse connection.
+    ///
+    /// fuse3 requests the flags derived from the [`MountOptions`][crate::MountOptions], the
+    /// option of each flag is noted below, the others are requested whenever kernel supports
+    /// them unless noted. [`MountOptions::init_flags`][crate::MountOptions::init_flags] overrides
+    /// the requested flags.
+    #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+    pub struct InitFlags: u32 {
+        /// kernel may send multiple reads of a file at the same time, otherwise the reads are
+        /// serialized.
+        const ASYNC_READ = FUSE_ASYNC_READ;
+        /// the filesystem handles the POSIX file locks, requested when the `file-lock` feature
+        /// is enabled and [`OptionalOps::LOCK`] is supported.
+        const POSIX_LOCKS = FUSE_POSIX_LOCKS;
+        /// kernel sends file handle for fstat, etc...
+        const FILE_OPS = FUSE_FILE_OPS;
+        /// the filesystem handles the `O_TRUNC` open flag, instead of kernel sending a setattr
+        /// before open.
+        const ATOMIC_O_TRUNC = FUSE_ATOMIC_O_TRUNC;
+        /// the filesystem handles lookups of "." and "..", needed to export it by NFS.
+        const EXPORT_SUPPORT = FUSE_EXPORT_SUPPORT;
+        /// the filesystem can handle write size larger than 4kB.
+        const BIG_WRITES = FUSE_BIG_WRITES;
+        /// kernel doesn't apply umask to file mode on create operations, requested by
+        /// [`dont_mask`][crate::MountOptions::dont_mask].
+        const DONT_MASK = FUSE_DONT_MASK;
+        /// kernel supports splice write on the device, requested by
+        /// [`use_splice`][crate::MountOptions::use_splice].
+        #[cfg(not(target_os = "macos"))]
+        const SPLICE_WRITE = FUSE_SPLICE_WRITE;
+        /// kernel supports splice move on the device, requested by
+        /// [`use_splice`][crate::MountOptions::use_splice].
+        #[cfg(not(target_os = "macos"))]
+        const SPLICE_MOVE = FUSE_SPLICE_MOVE;
+        /// kernel supports splice read on the device, requested by
+        /// [`use_splice`][crate::MountOptions::use_splice].
+        #[cfg(not(target_os = "macos"))]
+        const SPLICE_READ = FUSE_SPLICE_READ;
+        /// the filesystem handles the BSD style file locks, requested by `flock_locks` of the
+        /// `file-lock` feature.
+        const FLOCK_LOCKS = FUSE_FLOCK_LOCKS;
+        /// kernel supports ioctl on directories, not requested by default.
+        const HAS_IOCTL_DIR = FUSE_HAS_IOCTL_DIR;
+        /// kernel invalidates the cached pages of a file when its mtime or size is changed.
+        const AUTO_INVAL_DATA = FUSE_AUTO_INVAL_DATA;
+        /// kernel reads the directories by readdirplus, requested when
+        /// [`OptionalOps::READDIRPLUS`] is supported.
+        const DO_READDIRPLUS = FUSE_DO_READDIRPLUS;
+        /// kernel chooses between readdir and readdirplus adaptively, requested when
+        /// [`OptionalOps::READDIRPLUS`] is supported and
+        /// [`force_readdir_plus`][crate::MountOptions::force_readdir_plus] is disabled.
+        const READDIRPLUS_AUTO = FUSE_READDIRPLUS_AUTO;
+        /// kernel submits the direct io asynchronously.
+        const ASYNC_DIO = FUSE_ASYNC_DIO;
+        /// kernel caches the buffered writes and writes them back later, requested by
+        /// [`write_back`][crate::MountOptions::write_back].
+        const WRITEBACK_CACHE = FUSE_WRITEBACK_CACHE;
+        /// kernel supports zero-message opens, requested by
+        /// [`no_open_support`][crate::MountOptions::no_open_support].
+        const NO_OPEN_SUPPORT = FUSE_NO_OPEN_SUPPORT;
+        /// allow parallel lookups and readdir of a directory.
+        const PARALLEL_DIROPS = FUSE_PARALLEL_DIROPS;
+        /// the filesystem kills suid/sgid/cap on write/chown/trunc, requested by
+        /// [`handle_killpriv`][crate::MountOptions::handle_killpriv].
+        const HANDLE_KILLPRIV = FUSE_HANDLE_KILLPRIV;
+        /// kernel checks the posix acls from the `system.posix_acl_*` xattrs, it enables the
+        /// permission checking like `default_permissions`, requested by
+        /// [`posix_acl`][crate::MountOptions::posix_acl].
+        const POSIX_ACL = FUSE_POSIX_ACL;
+        /// reading the device after abort returns `ECONNABORTED` instead of `ENODEV`, not
+        /// requested by default.
+        const ABORT_ERROR = FUSE_ABORT_ERROR;
+        /// the max pages of a request is negotiated, see
+        /// [`max_pages`][crate::MountOptions::max_pages].
+        const MAX_PAGES = FUSE_MAX_PAGES;
+        /// kernel caches the readlink replies.
+        const CACHE_SYMLINKS = FUSE_CACHE_SYMLINKS;
+        /// kernel supports zero-message opendir, requested by
+        /// [`no_open_dir_support`][crate::MountOptions::no_open_dir_support].
+        const NO_OPENDIR_SUPPORT = FUSE_NO_OPENDIR_SUPPORT;
+        /// only invalidate cached pages on explicit request, not requested by default.
+        const EXPLICIT_INVAL_DATA = FUSE_EXPLICIT_INVAL_DATA;
+        /// the map_alignment of DAX is valid, not requested by default.
+        const MAP_ALIGNMENT = FUSE_MAP_ALIGNMENT;
+        /// the init request and reply carry the flags2, see [`InitFlags2`].
+        #[cfg(not(target_os = "macos"))]
+        const INIT_EXT = FUSE_INIT_EXT;
+        /// the filesystem supports fallocate, not requested by default.
+        #[cfg(target_os = "macos")]
+        const ALLOCATE = FUSE_ALLOCATE;
+        /// the filesystem supports exchangedata, not requested by default.
+        #[cfg(target_os = "macos")]
+        const EXCHANGE_DATA = FUSE_EXCHANGE_DATA;
+        /// the filesystem is case insensitive, not requested by default.
+        #[cfg(target_os = "macos")]
+        const CASE_INSENSITIVE = FUSE_CASE_INSENSITIVE;
+        /// the filesystem supports renaming the volume, not requested by default.
+        #[cfg(target_os = "macos")]
+        const VOL_RENAME = FUSE_VOL_RENAME;
+        /// the filesystem supports the extended times, not requested by default.
+        #[cfg(target_os = "macos")]
+        const XTIMES = FUSE_XTIMES;
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+bitflags! {
+    /// the `flags2` of `FUSE_INIT`, the bit 32 to 63 of the init flags, which is exchanged when
+    /// both kernel and fuse3 set [`InitFlags::INIT_EXT`].
+    #[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+    pub struct InitFlags2: u32 {
+        /// add the security context to create, mkdir, symlink and mknod, see
+        /// [`SecurityContext`][crate::raw::SecurityContext].
+        const SECURITY_CTX = FUSE_SECURITY_CTX;
+        /// add the supplementary group to create, mkdir, symlink and mknod, see
+        /// [`Request::supplementary_group`][crate::raw::Request::supplementary_group].
+        const CREATE_SUPP_GROUP = FUSE_CREATE_SUPP_GROUP;
+    }
+}
+
+bitflags! {
+    /// the `FOPEN_*` flags of an open, opendir or create reply, which tell kernel how to cache
+    /// and access the opened file. Unknown bits are retained.
+    #[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+    pub struct FopenFlags: u32 {
+        /// bypass the page cache, each read and write is sent to the filesystem.
+        const DIRECT_IO = FOPEN_DIRECT_IO;
+        /// don't invalidate the data cache of the file on open.
+        const KEEP_CACHE = FOPEN_KEEP_CACHE;
+        /// the file is not seekable, such as a pipe.
+        const NONSEEKABLE = FOPEN_NONSEEKABLE;
+        /// allow kernel to cache the directory entries, only for opendir.
+        const CACHE_DIR = FOPEN_CACHE_DIR;
+        /// the file is stream-like, it has no file position at all.
+        const STREAM = FOPEN_STREAM;
+
+        const _ = !0;
+    }
+}
+
+bitflags! {
+    /// the attributes which are requested to set by a setattr request. Unknown bits sent by
+    /// kernel are retained.
+    #[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+    pub struct SetAttrValid: u32 {
+        /// set mode.
+        const MODE = FATTR_MODE;
+        /// set uid.
+        const UID = FATTR_UID;
+        /// set gid.
+        const GID = FATTR_GID;
+        /// set size.
+        const SIZE = FATTR_SIZE;
+        /// set atime to an explicit timestamp.
+        const ATIME = FATTR_ATIME;
+        /// set mtime to an explicit timestamp.
+        const MTIME = FATTR_MTIME;
+        /// the file handle is set.
+        const FH = FATTR_FH;
+        /// set atime to the current time.
+        const ATIME_NOW = FATTR_ATIME_NOW;
+        /// set mtime to the current time.
+        const MTIME_NOW = FATTR_MTIME_NOW;
+        /// the lock owner is set.
+        const LOCKOWNER = FATTR_LOCKOWNER;
+        /// set ctime.
+        const CTIME = FATTR_CTIME;
+
+        const _ = !0;
+    }
+}
+
+bitflags! {
+    /// the write flags. Unknown bits sent by kernel are retained.
+    #[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+    pub struct WriteFlags: u32 {
+        /// the data is written back from the page cache, not by a `write(2)` directly, the file
+        /// handle is picked by kernel.
+        const CACHE = FUSE_WRITE_CACHE;
+        /// the `lock_owner` field is valid.
+        const LOCKOWNER = FUSE_WRITE_LOCKOWNER;
+        /// kill the suid and sgid bits of the file.
+        const KILL_PRIV = FUSE_WRITE_KILL_PRIV;
+
+        const _ = !0;
+    }
+}
+
+bitflags! {
+    /// the `setxattr(2)` flags. Unknown bits sent by kernel are retained.
+    #[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+    pub struct SetXattrFlags: u32 {
+        /// fail with `EEXIST` if the attribute exists, `XATTR_CREATE`.
+        const CREATE = 1 << 0;
+        /// fail with `ENODATA` if the attribute doesn't exist, `XATTR_REPLACE`.
+        const REPLACE = 1 << 1;
+
+        const _ = !0;
+    }
+}
+
+impl SetXattrFlags {
+    /// check the flags against whether the attribute `exists`, return `EEXIST` if
+    /// [`CREATE`][SetXattrFlags::CREATE] is set and the attribute exists, or `ENODATA` (`ENOATTR`
+    /// on BSD) if [`REPLACE`][SetXattrFlags::REPLACE] is set and the attribute doesn't exist.
+    pub fn check(self, exists: bool) -> Result<()> {
+        if exists && self.contains(SetXattrFlags::CREATE) {
+            return Err(Errno::EEXIST);
+        }
+
+        if !exists && self.contains(SetXattrFlags::REPLACE) {
+            return Err(Errno::ENODATA);
+        }
+
+        Ok(())
+    }
+}
+
+bitflags! {
+    /// the `fallocate(2)` mode flags. Unknown bits sent by kernel are retained, use
+    /// [`bits`][FallocateMode::bits] to get the raw mode.
+    #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+    pub struct FallocateMode: u32 {
+        /// don't change the file size, `FALLOC_FL_KEEP_SIZE`.
+        const KEEP_SIZE = 0x01;
+        /// deallocate the range, `FALLOC_FL_PUNCH_HOLE`.
+        const PUNCH_HOLE = 0x02;
+        /// remove the range without leaving a hole, `FALLOC_FL_COLLAPSE_RANGE`.
+        const COLLAPSE_RANGE = 0x08;
+        /// convert the range to zeros, `FALLOC_FL_ZERO_RANGE`.
+        const ZERO_RANGE = 0x10;
+        /// insert a hole at the range without overwriting data, `FALLOC_FL_INSERT_RANGE`.
+        const INSERT_RANGE = 0x20;
+        /// unshare the shared blocks in the range, `FALLOC_FL_UNSHARE_RANGE`.
+        const UNSHARE_RANGE = 0x40;
+
+        const _ = !0;
+    }
+}
+
+bitflags! {
+    /// the `renameat2(2)` flags. Unknown bits sent by kernel are retained, use
+    /// [`bits`][RenameFlags::bits] to get the raw flags.
+    #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+    pub struct RenameFlags: u32 {
+        /// don't overwrite the new name, fail with `EEXIST` if it exists, `RENAME_NOREPLACE`.
+        const NOREPLACE = 1 << 0;
+        /// atomically exchange the old and new names, both must exist, `RENAME_EXCHANGE`.
+        const EXCHANGE = 1 << 1;
+        /// create a whiteout object at the old name, `RENAME_WHITEOUT`.
+        const WHITEOUT = 1 << 2;
+
+        const _ = !0;
+    }
+}
+
+bitflags! {
+    /// the `poll(2)` events, which are requested by a poll request and replied by
+    /// [`ReplyPoll`][crate::raw::reply::ReplyPoll]. Unknown bits sent by kernel are retained.
+    #[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+    pub struct PollEvents: u32 {
+        /// there is data to read, `POLLIN`.
+        const IN = libc::POLLIN as u32;
+        /// there is some exceptional condition, such as out-of-band data, `POLLPRI`.
+        const PRI = libc::POLLPRI as u32;
+        /// writing is possible now, `POLLOUT`.
+        const OUT = libc::POLLOUT as u32;
+        /// error condition, it is always reported even if not requested, `POLLERR`.
+        const ERR = libc::POLLERR as u32;
+        /// hang up, it is always reported even if not requested, `POLLHUP`.
+        const HUP = libc::POLLHUP as u32;
+        /// invalid request, `POLLNVAL`.
+        const NVAL = libc::POLLNVAL as u32;
+        /// normal data may be read, `POLLRDNORM`.
+        const RDNORM = libc::POLLRDNORM as u32;
+        /// priority data may be read, `POLLRDBAND`.
+        const RDBAND = libc::POLLRDBAND as u32;
+        /// normal data may be written, `POLLWRNORM`.
+        const WRNORM = libc::POLLWRNORM as u32;
+        /// priority data may be written, `POLLWRBAND`.
+        const WRBAND = libc::POLLWRBAND as u32;
+        /// the peer closed the writing half of the connection, `POLLRDHUP`.
+        #[cfg(target_os = "linux")]
+        const RDHUP = libc::POLLRDHUP as u32;
+
+        const _ = !0;
+    }
+}
+
+bitflags! {
+    /// the optional operations which the filesystem implements, declared by
+    /// [`MountOptions::supported_ops`][crate::MountOptions::supported_ops].
+    ///
+    /// an unsupported operation is replied with `ENOSYS` by fuse3 without calling the
+    /// filesystem, kernel handles it the same as the filesystem returns `ENOSYS`, and stops
+    /// sending it. The fallback of kernel is described by each flag.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use fuse3::raw::flags::{InitFlags, OptionalOps};
+    /// # use fuse3::raw::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// struct Fs;
+    ///
+    /// impl Filesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #   }
+    /// #
+    /// #   async fn destroy(&self, _req: Request) {}
+    /// #
+    ///     async fn fsync(&self, _req: Request, _inode: u64, _fh: u64, _datasync: bool)
+    ///         -> Result<()> {
+    ///         unreachable!("unsupported fsync reaches the filesystem")
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut mount_options = MountOptions::default();
+    /// mount_options
+    ///     .supported_ops(OptionalOps::all() - OptionalOps::READDIRPLUS - OptionalOps::FSYNC);
+    ///
+    /// let mut session = TestSession::new(Fs, mount_options);
+    /// let connection_info = session.init(request(0, 0, 0)).await.unwrap();
+    /// assert!(!connection_info.capabilities().contains(InitFlags::DO_READDIRPLUS));
+    ///
+    /// let mut fsync = Vec::new();
+    /// fsync.extend_from_slice(&56u32.to_ne_bytes()); // len
+    /// fsync.extend_from_slice(&20u32.to_ne_bytes()); // FUSE_FSYNC
+    /// fsync.extend_from_slice(&100u64.to_ne_bytes()); // unique
+    /// fsync.extend_from_slice(&2u64.to_ne_bytes()); // nodeid
+    /// fsync.extend_from_slice(&[0; 32]); // uid, gid, pid, padding and fuse_fsync_in
+    ///
+    /// let err = session.send_message(&fsync).await.unwrap_err();
+    /// assert_eq!(err, libc::ENOSYS.into());
+    /// # }
+    /// ```
+    #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+    pub struct OptionalOps: u32 {
+        /// getxattr, setxattr, listxattr and removexattr, kernel fails them with `EOPNOTSUPP`.
+        const XATTR = 1 << 0;
+        /// getlk, setlk and setlkw, `FUSE_POSIX_LOCKS` and `FUSE_FLOCK_LOCKS` are not
+        /// advertised without it, so kernel handles the file locks locally.
+        const LOCK = 1 << 1;
+        /// readdirplus, `FUSE_DO_READDIRPLUS` and `FUSE_READDIRPLUS_AUTO` are not advertised
+        /// without it, so kernel uses readdir, even if
+        /// [`force_readdir_plus`][crate::MountOptions::force_readdir_plus] is set.
+        const READDIRPLUS = 1 << 2;
+        /// create, kernel falls back to mknod and open.
+        const CREATE = 1 << 3;
+        /// access, kernel allows the access.
+        const ACCESS = 1 << 4;
+        /// flush, kernel treats it as success.
+        const FLUSH = 1 << 5;
+        /// fsync, kernel treats it as success.
+        const FSYNC = 1 << 6;
+        /// fsyncdir, kernel treats it as success.
+        const FSYNCDIR = 1 << 7;
+        /// syncfs, kernel treats it as success.
+        const SYNCFS = 1 << 8;
+        /// fallocate, kernel fails it with `EOPNOTSUPP`.
+        const FALLOCATE = 1 << 9;
+        /// lseek, kernel handles `SEEK_DATA` and `SEEK_HOLE` by the file size.
+        const LSEEK = 1 << 10;
+        /// copy_file_range, kernel falls back to copy by read and write.
+        const COPY_FILE_RANGE = 1 << 11;
+        /// rename2, kernel fails the rename with flags with `EINVAL`.
+        const RENAME2 = 1 << 12;
+        /// ioctl, kernel fails it with `ENOTTY`.
+        const IOCTL = 1 << 13;
+        /// poll, kernel treats the file as always readable and writable.
+        const POLL = 1 << 14;
+        /// bmap, kernel replies block 0.
+        const BMAP = 1 << 15;
+    }
+}
+
+impl OptionalOps {
+    /// the optional operation of the opcode, `None` means the opcode is always handled.
+    pub(crate) fn from_opcode(opcode: fuse_opcode) -> Option<Self> {
+        let op = match opcode {
+            fuse_opcode::FUSE_SETXATTR
+            | fuse_opcode::FUSE_GETXATTR
+            | fuse_opcode::FUSE_LISTXATTR
+            | fuse_opcode::FUSE_REMOVEXATTR => Self::XATTR,
+            #[cfg(feature = "file-lock")]
+            fuse_opcode::FUSE_GETLK | fuse_opcode::FUSE_SETLK | fuse_opcode::FUSE_SETLKW => {
+                Self::LOCK
+            }
+            fuse_opcode::FUSE_READDIRPLUS => Self::READDIRPLUS,
+            fuse_opcode::FUSE_CREATE => Self::CREATE,
+            fuse_opcode::FUSE_ACCESS => Self::ACCESS,
+            fuse_opcode::FUSE_FLUSH => Self::FLUSH,
+            fuse_opcode::FUSE_FSYNC => Self::FSYNC,
+            fuse_opcode::FUSE_FSYNCDIR => Self::FSYNCDIR,
+            fuse_opcode::FUSE_SYNCFS => Self::SYNCFS,
+            fuse_opcode::FUSE_FALLOCATE => Self::FALLOCATE,
+            fuse_opcode::FUSE_LSEEK => Self::LSEEK,
+            fuse_opcode::FUSE_COPY_FILE_RANGE => Self::COPY_FILE_RANGE,
+            fuse_opcode::FUSE_RENAME2 => Self::RENAME2,
+            fuse_opcode::FUSE_IOCTL => Self::IOCTL,
+            fuse_opcode::FUSE_POLL => Self::POLL,
+            fuse_opcode::FUSE_BMAP => Self::BMAP,
+            _ => return None,
+        };
+
+        Some(op)
+    }
+}
+
+/// the access mode of [`OpenFlags`], the `O_ACCMODE` bits.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum AccessMode {
+    /// open for reading only, `O_RDONLY`.
+    ReadOnly,
+    /// open for writing only, `O_WRONLY`.
+    WriteOnly,
+    /// open for reading and writing, `O_RDWR`.
+    ReadWrite,
+}
+
+/// the `open(2)` flags of open, opendir and create. The raw bits are kept as they are sent by
+/// kernel, use [`bits`][OpenFlags::bits] to get them.
+///
+/// kernel removes `O_CREAT`, `O_EXCL` and `O_NOCTTY` from the flags of open. `O_TRUNC` is
+/// passed to open because fuse3 enables [`ATOMIC_O_TRUNC`][InitFlags::ATOMIC_O_TRUNC], a kernel
+/// which doesn't support it truncates the file by setattr before open instead.
+///
+/// # Examples:
+///
+/// ```
+/// # use fuse3::raw::flags::{AccessMode, OpenFlags};
+/// let flags = OpenFlags::from((libc::O_WRONLY | libc::O_APPEND) as u32);
+///
+/// assert_eq!(flags.access_mode(), AccessMode::WriteOnly);
+/// assert!(flags.is_write());
+/// assert!(!flags.is_read());
+/// assert!(flags.is_append());
+/// assert!(!flags.is_truncate());
+/// ```
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct OpenFlags(u32);
+
+impl OpenFlags {
+    /// create the flags from the raw bits.
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// the raw bits.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// the access mode. The invalid mode `O_ACCMODE` which Linux allows for `ioctl(2)` only
+    /// opens is treated as [`ReadWrite`][AccessMode::ReadWrite], because it requires both read
+    /// and write permission.
+    pub const fn access_mode(self) -> AccessMode {
+        match self.0 as libc::c_int & libc::O_ACCMODE {
+            libc::O_RDONLY => AccessMode::ReadOnly,
+            libc::O_WRONLY => AccessMode::WriteOnly,
+            _ => AccessMode::ReadWrite,
+        }
+    }
+
+    /// the file is opened for reading.
+    pub const fn is_read(self) -> bool {
+        !matches!(self.access_mode(), AccessMode::WriteOnly)
+    }
+
+    /// the file is opened for writing.
+    pub const fn is_write(self) -> bool {
+        !matches!(self.access_mode(), AccessMode::ReadOnly)
+    }
+
+    /// each write appends to the end of file, `O_APPEND`.
+    pub const fn is_append(self) -> bool {
+        self.contains(libc::O_APPEND)
+    }
+
+    /// truncate the file to zero length, `O_TRUNC`.
+    pub const fn is_truncate(self) -> bool {
+        self.contains(libc::O_TRUNC)
+    }
+
+    /// create the file if it doesn't exist, `O_CREAT`.
+    pub const fn is_create(self) -> bool {
+        self.contains(libc::O_CREAT)
+    }
+
+    /// fail if the file exists, with `O_CREAT`, `O_EXCL`.
+    pub const fn is_exclusive(self) -> bool {
+        self.contains(libc::O_EXCL)
+    }
+
+    /// the caller uses direct io, `O_DIRECT`, it is always false on macOS which has no
+    /// `O_DIRECT`.
+    ///
+    /// kernel bypasses the page cache for the direct io opens, but doesn't check the alignment
+    /// of their reads and writes, see [`DirectIoAlignment`][crate::util::DirectIoAlignment]
+    /// before passing them to a backing file opened with `O_DIRECT`.
+    pub const fn is_direct(self) -> bool {
+        #[cfg(not(target_os = "macos"))]
+        {
+            self.contains(libc::O_DIRECT)
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            false
+        }
+    }
+
+    const fn contains(self, flag: libc::c_int) -> bool {
+        self.0 & flag as u32 != 0
+    }
+}
+
+impl From<u32> for OpenFlags {
+    fn from(bits: u32) -> Self {
+        Self(bits)
+    }
+}
+
+impl From<OpenFlags> for u32 {
+    fn from(flags: OpenFlags) -> Self {
+        flags.0
+    }
+}
@@ -0,0 +1,167 @@
+//! track the in flight filesystem requests.
+
+use std::collections::HashMap;
+use std::pin::pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use async_notify::Notify;
+use futures_util::future::{AbortHandle, AbortRegistration};
+use futures_util::{select, FutureExt};
+
+use crate::raw::session::sleep;
+
+/// the in flight filesystem requests, which are spawned but not done yet.
+#[derive(Debug, Default)]
+pub(crate) struct InFlightRequests {
+    count: AtomicUsize,
+    idle: Notify,
+    /// when set, the session will wait the in flight requests done before destroy the filesystem,
+    /// `None` means wait without timeout.
+    drain_timeout: OnceLock<Option<Duration>>,
+    /// the number of requests which are still in flight when the drain is done, the session
+    /// already stops reading new requests, so no request is added after it is set.
+    abandoned: OnceLock<usize>,
+}
+
+impl InFlightRequests {
+    /// mark a request is in flight until the returned guard is dropped.
+    pub(crate) fn enter(self: &Arc<Self>) -> InFlightGuard {
+        self.count.fetch_add(1, Ordering::AcqRel);
+
+        InFlightGuard {
+            in_flight: self.clone(),
+        }
+    }
+
+    /// the number of in flight requests.
+    pub(crate) fn count(&self) -> usize {
+        self.count.load(Ordering::Acquire)
+    }
+
+    /// ask the session to wait the in flight requests done before destroy the filesystem.
+    pub(crate) fn set_drain_timeout(&self, timeout: Option<Duration>) {
+        let _ = self.drain_timeout.set(timeout);
+    }
+
+    /// the number of requests abandoned by [`drain`][InFlightRequests::drain], `None` if the
+    /// session is stopped without draining.
+    pub(crate) fn abandoned(&self) -> Option<usize> {
+        self.abandoned.get().copied()
+    }
+
+    /// if [`set_drain_timeout`][InFlightRequests::set_drain_timeout] is called, wait until all in
+    /// flight requests are done or the timeout fired, or return immediately.
+    ///
+    /// it must be called after the session stops reading new requests, the requests which are
+    /// still in flight when it returns are recorded as [`abandoned`][InFlightRequests::abandoned].
+    pub(crate) async fn drain(&self) {
+        let timeout = match self.drain_timeout.get() {
+            None => return,
+            Some(timeout) => *timeout,
+        };
+
+        let mut idle = pin!(self.wait_idle().fuse());
+
+        match timeout {
+            None => idle.await,
+
+            Some(timeout) => {
+                let mut timer = pin!(sleep(timeout).fuse());
+
+                select! {
+                    _ = idle => {}
+                    _ = timer => {}
+                }
+            }
+        }
+
+        let _ = self.abandoned.set(self.count());
+    }
+
+    /// wait until there is no in flight request.
+    pub(crate) async fn wait_idle(&self) {
+        while self.count() > 0 {
+            self.idle.notified().await;
+        }
+    }
+}
+
+/// decrease the in flight requests count when dropped.
+#[derive(Debug)]
+pub(crate) struct InFlightGuard {
+    in_flight: Arc<InFlightRequests>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.in_flight.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.in_flight.idle.notify();
+        }
+    }
+}
+
+/// the in flight filesystem requests which can be cancelled by `FUSE_INTERRUPT`.
+#[derive(Debug, Default)]
+pub(crate) struct InterruptibleRequests {
+    handles: Mutex<HashMap<u64, AbortHandle>>,
+}
+
+impl InterruptibleRequests {
+    /// track the request until the returned guard is dropped.
+    pub(crate) fn register(
+        self: &Arc<Self>,
+        unique: u64,
+    ) -> (AbortRegistration, InterruptibleGuard) {
+        let (handle, registration) = AbortHandle::new_pair();
+
+        self.handles
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(unique, handle);
+
+        let guard = InterruptibleGuard {
+            requests: self.clone(),
+            unique,
+        };
+
+        (registration, guard)
+    }
+
+    /// abort the request future, return false if the request is done already.
+    pub(crate) fn interrupt(&self, unique: u64) -> bool {
+        let handle = self
+            .handles
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(&unique);
+
+        match handle {
+            None => false,
+
+            Some(handle) => {
+                handle.abort();
+
+                true
+            }
+        }
+    }
+}
+
+/// stop tracking the interruptible request when dropped.
+#[derive(Debug)]
+pub(crate) struct InterruptibleGuard {
+    requests: Arc<InterruptibleRequests>,
+    unique: u64,
+}
+
+impl Drop for InterruptibleGuard {
+    fn drop(&mut self) {
+        self.requests
+            .handles
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(&self.unique);
+    }
+}
@@ -0,0 +1,71 @@
+//! POSIX file lock types.
+
+use crate::raw::abi::fuse_lk_in;
+use crate::Errno;
+
+/// the type of a POSIX file lock.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum LockType {
+    /// shared read lock, `F_RDLCK`.
+    Read,
+    /// exclusive write lock, `F_WRLCK`.
+    Write,
+    /// no lock or release the lock, `F_UNLCK`.
+    Unlock,
+}
+
+impl TryFrom<u32> for LockType {
+    type Error = Errno;
+
+    fn try_from(r#type: u32) -> Result<Self, Self::Error> {
+        // the lock types are c_short on FreeBSD and macOS
+        match r#type {
+            r#type if r#type == libc::F_RDLCK as u32 => Ok(LockType::Read),
+            r#type if r#type == libc::F_WRLCK as u32 => Ok(LockType::Write),
+            r#type if r#type == libc::F_UNLCK as u32 => Ok(LockType::Unlock),
+            _ => Err(libc::EINVAL.into()),
+        }
+    }
+}
+
+impl From<LockType> for u32 {
+    fn from(r#type: LockType) -> Self {
+        let r#type = match r#type {
+            LockType::Read => libc::F_RDLCK,
+            LockType::Write => libc::F_WRLCK,
+            LockType::Unlock => libc::F_UNLCK,
+        };
+
+        r#type as _
+    }
+}
+
+/// the POSIX file lock argument of getlk, setlk and setlkw.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct FileLock {
+    /// starting offset of the lock.
+    pub start: u64,
+    /// end offset of the lock, inclusive, `u64::MAX` means to the end of file.
+    pub end: u64,
+    /// the lock type.
+    pub r#type: LockType,
+    /// PID of the process which owns the lock.
+    pub pid: u32,
+    /// the lock owner, which identifies the open file description or the process, the locks of
+    /// the same owner don't conflict with each other.
+    pub owner: u64,
+}
+
+impl TryFrom<&fuse_lk_in> for FileLock {
+    type Error = Errno;
+
+    fn try_from(lk_in: &fuse_lk_in) -> Result<Self, Self::Error> {
+        Ok(FileLock {
+            start: lk_in.lk.start,
+            end: lk_in.lk.end,
+            r#type: lk_in.lk.r#type.try_into()?,
+            pid: lk_in.lk.pid,
+            owner: lk_in.owner,
+        })
+    }
+}
@@ -7,28 +7,81 @@
 //! choose.
 
 use bytes::Bytes;
+pub use connection_info::ConnectionInfo;
+pub use extension::SecurityContext;
 pub use filesystem::Filesystem;
 use futures_util::future::Either;
+#[cfg(feature = "file-lock")]
+pub use lock::{FileLock, LockType};
+pub use observer::{Opcode, SessionObserver};
 pub use request::Request;
 #[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
-pub use session::{MountHandle, Session};
+pub use session::{MountBusyError, MountHandle, Session};
+#[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
+pub use session_builder::SessionBuilder;
+pub use xattr::XattrNamespace;
+
+#[cfg(target_os = "linux")]
+use crate::raw::connection::splice::SplicedReply;
 
-pub(crate) type FuseData = Either<Vec<u8>, (Vec<u8>, Bytes)>;
+/// a reply, the header with the extend data which is written after it.
+pub(crate) type FuseData = Either<Vec<u8>, (Vec<u8>, ExtendData)>;
+
+/// the data written after the reply header.
+#[derive(Debug)]
+pub(crate) enum ExtendData {
+    Bytes(Bytes),
+    /// the header and the data are spliced into a pipe already, the header is kept for the
+    /// observers.
+    #[cfg(target_os = "linux")]
+    Spliced(SplicedReply),
+}
+
+impl From<Bytes> for ExtendData {
+    fn from(data: Bytes) -> Self {
+        Self::Bytes(data)
+    }
+}
 
 pub(crate) mod abi;
-mod connection;
+mod buffer_pool;
+pub(crate) mod connection;
+pub(crate) mod connection_info;
+mod extension;
 mod filesystem;
 pub mod flags;
+mod in_flight;
+#[cfg(feature = "file-lock")]
+mod lock;
+mod observer;
 pub mod reply;
 mod request;
+#[cfg(feature = "request-span")]
+mod request_span;
 pub(crate) mod session;
+#[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
+mod session_builder;
+#[cfg(feature = "wire-debug")]
+mod wire_debug;
+mod xattr;
 
 pub mod prelude {
+    pub use super::flags::{
+        AccessMode, FallocateMode, FopenFlags, OpenFlags, OptionalOps, PollEvents, RenameFlags,
+        SetAttrValid, SetXattrFlags, WriteFlags,
+    };
     pub use super::reply::FileAttr;
     pub use super::reply::*;
+    pub use super::ConnectionInfo;
     pub use super::Filesystem;
+    pub use super::Opcode;
     pub use super::Request;
+    pub use super::SecurityContext;
     pub use super::Session;
+    pub use super::SessionObserver;
+    pub use super::XattrNamespace;
+    #[cfg(feature = "file-lock")]
+    pub use super::{FileLock, LockType};
     pub use crate::notify::Notify;
     pub use crate::FileType;
     pub use crate::SetAttr;
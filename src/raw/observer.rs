@@ -0,0 +1,238 @@
+//! observe the fuse requests handled by the session.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bincode::Options;
+
+use crate::helper::get_bincode_config;
+use crate::raw::abi::{fuse_opcode, fuse_out_header, FUSE_OUT_HEADER_SIZE};
+use crate::Errno;
+
+/// the fuse request opcode.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Opcode(pub(crate) fuse_opcode);
+
+impl Opcode {
+    /// the opcode number in fuse protocol.
+    pub fn code(&self) -> u32 {
+        self.0 as u32
+    }
+
+    /// the lowercase operation name, such as `lookup` for `FUSE_LOOKUP`.
+    pub fn name(&self) -> &'static str {
+        match self.0 {
+            fuse_opcode::FUSE_LOOKUP => "lookup",
+            fuse_opcode::FUSE_FORGET => "forget",
+            fuse_opcode::FUSE_GETATTR => "getattr",
+            fuse_opcode::FUSE_SETATTR => "setattr",
+            fuse_opcode::FUSE_READLINK => "readlink",
+            fuse_opcode::FUSE_SYMLINK => "symlink",
+            fuse_opcode::FUSE_MKNOD => "mknod",
+            fuse_opcode::FUSE_MKDIR => "mkdir",
+            fuse_opcode::FUSE_UNLINK => "unlink",
+            fuse_opcode::FUSE_RMDIR => "rmdir",
+            fuse_opcode::FUSE_RENAME => "rename",
+            fuse_opcode::FUSE_LINK => "link",
+            fuse_opcode::FUSE_OPEN => "open",
+            fuse_opcode::FUSE_READ => "read",
+            fuse_opcode::FUSE_WRITE => "write",
+            fuse_opcode::FUSE_STATFS => "statfs",
+            fuse_opcode::FUSE_RELEASE => "release",
+            fuse_opcode::FUSE_FSYNC => "fsync",
+            fuse_opcode::FUSE_SETXATTR => "setxattr",
+            fuse_opcode::FUSE_GETXATTR => "getxattr",
+            fuse_opcode::FUSE_LISTXATTR => "listxattr",
+            fuse_opcode::FUSE_REMOVEXATTR => "removexattr",
+            fuse_opcode::FUSE_FLUSH => "flush",
+            fuse_opcode::FUSE_INIT => "init",
+            fuse_opcode::FUSE_OPENDIR => "opendir",
+            fuse_opcode::FUSE_READDIR => "readdir",
+            fuse_opcode::FUSE_RELEASEDIR => "releasedir",
+            fuse_opcode::FUSE_FSYNCDIR => "fsyncdir",
+            #[cfg(feature = "file-lock")]
+            fuse_opcode::FUSE_GETLK => "getlk",
+            #[cfg(feature = "file-lock")]
+            fuse_opcode::FUSE_SETLK => "setlk",
+            #[cfg(feature = "file-lock")]
+            fuse_opcode::FUSE_SETLKW => "setlkw",
+            fuse_opcode::FUSE_ACCESS => "access",
+            fuse_opcode::FUSE_CREATE => "create",
+            fuse_opcode::FUSE_INTERRUPT => "interrupt",
+            fuse_opcode::FUSE_BMAP => "bmap",
+            fuse_opcode::FUSE_DESTROY => "destroy",
+            fuse_opcode::FUSE_IOCTL => "ioctl",
+            fuse_opcode::FUSE_POLL => "poll",
+            fuse_opcode::FUSE_NOTIFY_REPLY => "notify_reply",
+            fuse_opcode::FUSE_BATCH_FORGET => "batch_forget",
+            fuse_opcode::FUSE_FALLOCATE => "fallocate",
+            fuse_opcode::FUSE_READDIRPLUS => "readdirplus",
+            fuse_opcode::FUSE_RENAME2 => "rename2",
+            fuse_opcode::FUSE_LSEEK => "lseek",
+            fuse_opcode::FUSE_COPY_FILE_RANGE => "copy_file_range",
+            fuse_opcode::FUSE_SYNCFS => "syncfs",
+            #[cfg(target_os = "macos")]
+            fuse_opcode::FUSE_SETVOLNAME => "setvolname",
+            #[cfg(target_os = "macos")]
+            fuse_opcode::FUSE_GETXTIMES => "getxtimes",
+            #[cfg(target_os = "macos")]
+            fuse_opcode::FUSE_EXCHANGE => "exchange",
+            fuse_opcode::FUSE_CANONICAL_PATH => "canonical_path",
+        }
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// observe the fuse requests handled by the session, such as collecting the metrics.
+///
+/// # Notes:
+///
+/// the callbacks are called in the session tasks, they should return quickly. The `FUSE_INIT`
+/// and `FUSE_DESTROY` requests and the malformed requests which are replied before handled by
+/// the filesystem, are not observed. A request which is dropped before replied, such as the
+/// session is stopped, doesn't end.
+pub trait SessionObserver: Send + Sync + 'static {
+    /// a request starts to be handled by the filesystem.
+    fn on_request_start(&self, opcode: Opcode, unique: u64) {
+        let _ = (opcode, unique);
+    }
+
+    /// a request is done, `result` is the error which replied to kernel, `duration` is the time
+    /// since the request started.
+    ///
+    /// the requests which kernel doesn't wait the reply, such as `forget`, are done when the
+    /// filesystem method returns, and `result` is always `Ok`.
+    fn on_request_end(
+        &self,
+        opcode: Opcode,
+        unique: u64,
+        result: Result<(), Errno>,
+        duration: Duration,
+    ) {
+        let _ = (opcode, unique, result, duration);
+    }
+}
+
+/// track the started requests, then call the [`SessionObserver`] when they are replied.
+pub(crate) struct RequestObserver {
+    observer: Arc<dyn SessionObserver>,
+    started: Mutex<HashMap<u64, (Opcode, Instant)>>,
+}
+
+impl RequestObserver {
+    pub(crate) fn new(observer: Arc<dyn SessionObserver>) -> Self {
+        Self {
+            observer,
+            started: Default::default(),
+        }
+    }
+
+    /// start to observe a request, it is done when the returned [`StartedRequest`] is
+    /// [`done`][StartedRequest::done] and its reply is written.
+    pub(crate) fn start(self: &Arc<Self>, opcode: fuse_opcode, unique: u64) -> StartedRequest {
+        let need_reply = opcode.need_reply();
+        let opcode = Opcode(opcode);
+        let start = Instant::now();
+
+        // the requests without reply are never replied, they don't need to be tracked, and the
+        // unique of `FUSE_INTERRUPT` is the unique of the interrupted request with a flag bit
+        if need_reply {
+            self.started
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .insert(unique, (opcode, start));
+        }
+
+        self.observer.on_request_start(opcode, unique);
+
+        StartedRequest {
+            observer: self.clone(),
+            opcode,
+            unique,
+            start,
+            need_reply,
+            done: false,
+        }
+    }
+
+    /// the reply is going to be written to kernel, notifies are ignored.
+    pub(crate) fn reply(&self, data: &[u8]) {
+        if data.len() < FUSE_OUT_HEADER_SIZE {
+            return;
+        }
+
+        let out_header = match get_bincode_config().deserialize::<fuse_out_header>(data) {
+            Err(_) => return,
+            Ok(out_header) => out_header,
+        };
+
+        let result = if out_header.error == 0 {
+            Ok(())
+        } else {
+            Err(Errno::from(-out_header.error))
+        };
+
+        self.finish(out_header.unique, result);
+    }
+
+    fn finish(&self, unique: u64, result: Result<(), Errno>) {
+        if let Some((opcode, start)) = self.forget(unique) {
+            self.observer
+                .on_request_end(opcode, unique, result, start.elapsed());
+        }
+    }
+
+    fn forget(&self, unique: u64) -> Option<(Opcode, Instant)> {
+        self.started
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(&unique)
+    }
+}
+
+/// a request which is being handled, the request without reply ends when it is
+/// [`done`][StartedRequest::done], and the request which is dropped before done, such as the
+/// session is stopped or the handler panics, is forgotten without ending.
+pub(crate) struct StartedRequest {
+    observer: Arc<RequestObserver>,
+    opcode: Opcode,
+    unique: u64,
+    start: Instant,
+    need_reply: bool,
+    done: bool,
+}
+
+impl StartedRequest {
+    /// the filesystem method returns, and the reply, if any, is sent to be written.
+    pub(crate) fn done(mut self) {
+        self.done = true;
+    }
+}
+
+impl Drop for StartedRequest {
+    fn drop(&mut self) {
+        match (self.need_reply, self.done) {
+            (false, true) => self.observer.observer.on_request_end(
+                self.opcode,
+                self.unique,
+                Ok(()),
+                self.start.elapsed(),
+            ),
+
+            (true, false) => {
+                self.observer.forget(self.unique);
+            }
+
+            // the reply is observed when it is written, and the request without reply which is
+            // not done has no result
+            _ => {}
+        }
+    }
+}
@@ -1,19 +1,53 @@
 //! reply structures.
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
+use std::fmt::{self, Debug, Formatter};
+use std::fs::Metadata;
 use std::num::NonZeroU32;
+use std::os::fd::AsFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
 
 use bytes::Bytes;
 use futures_util::stream::Stream;
+#[cfg(unix)]
+use nix::sys::statvfs;
+use tracing::warn;
 
-use crate::helper::mode_from_kind_and_perm;
+use crate::helper::{kind_from_mode, mode_from_kind_and_perm};
 use crate::raw::abi::{
-    fuse_attr, fuse_attr_out, fuse_bmap_out, fuse_entry_out, fuse_kstatfs, fuse_lseek_out,
-    fuse_open_out, fuse_poll_out, fuse_statfs_out, fuse_write_out,
+    fuse_attr, fuse_attr_out, fuse_bmap_out, fuse_entry_out, fuse_ioctl_iovec, fuse_kstatfs,
+    fuse_lseek_out, fuse_opcode, fuse_open_out, fuse_poll_out, fuse_statfs_out, fuse_write_out,
 };
 #[cfg(feature = "file-lock")]
 use crate::raw::abi::{fuse_file_lock, fuse_lk_out};
-use crate::{FileType, Result, Timestamp};
+#[cfg(target_os = "macos")]
+use crate::raw::abi::fuse_getxtimes_out;
+use crate::raw::flags::{FopenFlags, PollEvents};
+#[cfg(feature = "file-lock")]
+use crate::raw::lock::{FileLock, LockType};
+use crate::{FileType, Inode, Result, Timestamp};
+
+/// the inode of the filesystem root.
+///
+/// kernel knows the root without looking it up, the requests on the mount point carry this
+/// inode, so the filesystem must map it to its root directory. The root must live as long as
+/// the filesystem even if kernel forgets it, and its generation must be 0, kernel warns and
+/// resets a non-zero one.
+///
+/// an entry reply, such as [`ReplyEntry`] and [`ReplyCreated`], should never carry the root
+/// inode except for `.` and `..` which resolve to the root, another name with the root inode
+/// makes kernel alias the root directory. Inode 0 is not a valid inode either, kernel treats the
+/// lookup reply with inode 0 as a negative entry, use [`ReplyEntry::negative`] for that, and
+/// fails the other entry replies with `EIO`. fuse3 logs a warning for these replies, the
+/// filesystem should fix them.
+pub use crate::raw::abi::FUSE_ROOT_ID;
+
+/// the unit of [`FileAttr::blocks`], it is always 512 bytes.
+pub(crate) const BLOCK_UNIT_SIZE: u64 = 512;
 
 /// file attributes
 #[derive(Clone, Copy, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
@@ -22,7 +56,9 @@ pub struct FileAttr {
     pub ino: u64,
     /// Size in bytes
     pub size: u64,
-    /// Size in blocks
+    /// Size in 512-byte blocks, it is what `st_blocks` of `stat(2)` reports and what `du` uses,
+    /// no matter what the [`blksize`][Self::blksize] is. Use [`set_size`][Self::set_size] to
+    /// update it with the size.
     pub blocks: u64,
     /// Time of last access
     pub atime: Timestamp,
@@ -30,9 +66,9 @@ pub struct FileAttr {
     pub mtime: Timestamp,
     /// Time of last change
     pub ctime: Timestamp,
-    #[cfg(target_os = "macos")]
-    /// Time of creation (macOS only)
-    pub crtime: Timestamp,
+    /// Time of creation, the birth time, `None` means unknown. It is only replied on macOS, the
+    /// attribute reply of Linux and FreeBSD doesn't carry it, so it is ignored there.
+    pub crtime: Option<Timestamp>,
     /// Kind of file (directory, file, pipe, etc)
     pub kind: FileType,
     /// Permissions
@@ -51,6 +87,113 @@ pub struct FileAttr {
     pub blksize: u32,
 }
 
+impl FileAttr {
+    /// set the size in bytes, and set [`blocks`][Self::blocks] to the number of 512-byte blocks
+    /// which cover the size, that is `size.div_ceil(512)`.
+    ///
+    /// # Notes:
+    ///
+    /// the blocks is the allocated space, not the filesystem blocks of
+    /// [`blksize`][Self::blksize]. When the allocated space differs from the size, such as a
+    /// sparse or compressed file, set `blocks` after calling it.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use fuse3::raw::reply::FileAttr;
+    /// let mut attr = FileAttr::from_metadata(2, &std::fs::metadata(".").unwrap());
+    /// attr.set_size(4097);
+    ///
+    /// assert_eq!(attr.size, 4097);
+    /// assert_eq!(attr.blocks, 9);
+    ///
+    /// // a sparse file occupies less blocks than its size
+    /// attr.set_size(1 << 30).blocks = 8;
+    /// assert_eq!(attr.blocks, 8);
+    /// ```
+    pub fn set_size(&mut self, size: u64) -> &mut Self {
+        self.size = size;
+        self.blocks = size.div_ceil(BLOCK_UNIT_SIZE);
+
+        self
+    }
+
+    /// the mode of the file, which combines the file type bits of [`kind`][Self::kind] and the
+    /// permission bits of [`perm`][Self::perm], like the `st_mode` of `stat(2)`.
+    pub const fn mode(&self) -> u32 {
+        mode_from_kind_and_perm(self.kind, self.perm)
+    }
+
+    /// set [`kind`][Self::kind] and [`perm`][Self::perm] from a mode, the permission bits include
+    /// the suid, sgid and sticky bits. If `mode` doesn't carry a known file type, such as the mode
+    /// of chmod, `kind` is kept.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use fuse3::raw::reply::FileAttr;
+    /// # use fuse3::FileType;
+    /// let mut attr = FileAttr::from_metadata(2, &std::fs::metadata(".").unwrap());
+    /// attr.set_mode(libc::S_IFREG as u32 | 0o4755);
+    ///
+    /// assert_eq!(attr.kind, FileType::RegularFile);
+    /// assert_eq!(attr.perm, 0o4755);
+    /// assert_eq!(attr.mode(), libc::S_IFREG as u32 | 0o4755);
+    /// ```
+    pub fn set_mode(&mut self, mode: u32) -> &mut Self {
+        if let Some(kind) = FileType::from_mode(mode) {
+            self.kind = kind;
+        }
+        self.perm = (mode & 0o7777) as u16;
+
+        self
+    }
+
+    /// build the attributes from the [`Metadata`] of a file, such as the backing file of a
+    /// passthrough filesystem. The size, blocks, times, mode, uid, gid, nlink, rdev and blksize
+    /// are copied.
+    ///
+    /// # Notes:
+    ///
+    /// `ino` is assigned by the filesystem, don't use the inode number of the backing file
+    /// directly, the files of the different backing filesystems may have the same inode number,
+    /// and it may be reused before kernel forgets it.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use fuse3::raw::reply::FileAttr;
+    /// # use fuse3::FileType;
+    /// let metadata = std::fs::metadata(".").unwrap();
+    /// let attr = FileAttr::from_metadata(2, &metadata);
+    ///
+    /// assert_eq!(attr.ino, 2);
+    /// assert_eq!(attr.kind, FileType::Directory);
+    /// ```
+    pub fn from_metadata(ino: Inode, metadata: &Metadata) -> Self {
+        let kind = kind_from_mode(metadata.mode()).unwrap_or(FileType::RegularFile);
+
+        Self {
+            ino,
+            size: metadata.size(),
+            blocks: metadata.blocks(),
+            atime: Timestamp::new(metadata.atime(), metadata.atime_nsec() as u32),
+            mtime: Timestamp::new(metadata.mtime(), metadata.mtime_nsec() as u32),
+            ctime: Timestamp::new(metadata.ctime(), metadata.ctime_nsec() as u32),
+            crtime: metadata.created().ok().map(Into::into),
+            kind,
+            perm: (metadata.mode() & 0o7777) as u16,
+            nlink: metadata.nlink().try_into().unwrap_or(u32::MAX),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            rdev: metadata.rdev() as u32,
+            #[cfg(target_os = "macos")]
+            flags: 0,
+            blksize: metadata.blksize().try_into().unwrap_or(u32::MAX),
+        }
+    }
+}
+
 impl From<FileAttr> for fuse_attr {
     fn from(attr: FileAttr) -> Self {
         fuse_attr {
@@ -63,20 +206,37 @@ impl From<FileAttr> for fuse_attr {
             atime: attr.atime.sec as u64,
             mtime: attr.mtime.sec as u64,
             ctime: attr.ctime.sec as u64,
+            #[cfg(target_os = "macos")]
+            crtime: attr.crtime.map_or(0, |crtime| crtime.sec as u64),
             atimensec: attr.atime.nsec,
             mtimensec: attr.mtime.nsec,
             ctimensec: attr.ctime.nsec,
+            #[cfg(target_os = "macos")]
+            crtimensec: attr.crtime.map_or(0, |crtime| crtime.nsec),
             mode: mode_from_kind_and_perm(attr.kind, attr.perm),
             nlink: attr.nlink,
             uid: attr.uid,
             gid: attr.gid,
             rdev: attr.rdev,
+            #[cfg(target_os = "macos")]
+            flags: attr.flags,
             blksize: attr.blksize,
             _padding: 0,
         }
     }
 }
 
+/// the max entry or attribute TTL, the kernel stores the TTL as signed seconds, a TTL larger than
+/// this is clamped to it. The entry or attribute is practically cached forever with this TTL.
+pub const MAX_TTL: Duration = Duration::from_secs(i64::MAX as u64);
+
+/// clamp the TTL to [`MAX_TTL`] and split it into the seconds and nanoseconds kernel wants.
+pub(crate) fn ttl_to_kernel(ttl: Duration) -> (u64, u32) {
+    let ttl = ttl.min(MAX_TTL);
+
+    (ttl.as_secs(), ttl.subsec_nanos())
+}
+
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// init reply
 pub struct ReplyInit {
@@ -87,25 +247,127 @@ pub struct ReplyInit {
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// entry reply.
 pub struct ReplyEntry {
-    /// the attribute TTL.
+    /// the entry and attribute TTL, it is clamped to [`MAX_TTL`]. A zero TTL makes kernel lookup
+    /// the name again every time, which is the correct choice for volatile filesystems whose
+    /// entries may change without notifying kernel.
     pub ttl: Duration,
     /// the attribute.
     pub attr: FileAttr,
-    /// the generation.
+    /// the inode generation. The pair of inode and generation must be unique during the
+    /// filesystem lifetime, when an inode number is reused for a different file after it is
+    /// forgotten, the generation must be changed, or kernel and NFS export may mistake the new
+    /// file for the old one. [`InodeTable`][crate::util::InodeTable] changes the generation
+    /// for each reused inode number.
     pub generation: u64,
 }
 
+impl ReplyEntry {
+    /// create an entry reply with generation 0, `ttl` is used as both the entry and attribute
+    /// TTL, and is clamped to [`MAX_TTL`].
+    ///
+    /// # Panics:
+    ///
+    /// it debug asserts the inode is not 0, use [`negative`][ReplyEntry::negative] for the
+    /// negative entry.
+    pub fn new(attr: FileAttr, ttl: Duration) -> Self {
+        debug_assert_ne!(attr.ino, 0, "entry inode 0 is not a valid inode");
+
+        Self {
+            ttl: ttl.min(MAX_TTL),
+            attr,
+            generation: 0,
+        }
+    }
+
+    /// create a negative entry reply for lookup, it has inode 0, and kernel caches the missing
+    /// name for `ttl` instead of failing with `ENOENT`, so the same name won't be looked up
+    /// again until the `ttl` expires or the name is created through the filesystem. `ttl` is
+    /// clamped to [`MAX_TTL`].
+    ///
+    /// # Notes:
+    ///
+    /// only lookup can reply it, kernel fails the other entry replies with inode 0 with `EIO`.
+    pub fn negative(ttl: Duration) -> Self {
+        let epoch = Timestamp::new(0, 0);
+
+        Self {
+            ttl: ttl.min(MAX_TTL),
+            attr: FileAttr {
+                ino: 0,
+                size: 0,
+                blocks: 0,
+                atime: epoch,
+                mtime: epoch,
+                ctime: epoch,
+                crtime: None,
+                kind: FileType::RegularFile,
+                perm: 0,
+                nlink: 0,
+                uid: 0,
+                gid: 0,
+                rdev: 0,
+                #[cfg(target_os = "macos")]
+                flags: 0,
+                blksize: 0,
+            },
+            generation: 0,
+        }
+    }
+
+    /// whether it is a [`negative`][ReplyEntry::negative] entry.
+    pub fn is_negative(&self) -> bool {
+        self.attr.ino == 0
+    }
+
+    /// set the inode generation.
+    ///
+    /// # Panics:
+    ///
+    /// it debug asserts the generation of [`FUSE_ROOT_ID`] is 0.
+    pub fn with_generation(mut self, generation: u64) -> Self {
+        debug_assert!(
+            self.attr.ino != FUSE_ROOT_ID || generation == 0,
+            "root generation is not 0"
+        );
+
+        self.generation = generation;
+
+        self
+    }
+}
+
+/// warn the entry reply which kernel rejects or mistakes, see [`FUSE_ROOT_ID`]. It is not an
+/// assertion, because a panic leaves the request unreplied, the constructors of [`ReplyEntry`]
+/// debug assert the mistakes instead, which panic in the filesystem method.
+pub(crate) fn check_entry(opcode: fuse_opcode, name: &OsStr, inode: Inode, generation: u64) {
+    let is_dot = name == OsStr::new(".") || name == OsStr::new("..");
+
+    // readdirplus uses inode 0 to skip the entry lookup
+    if inode == 0 && opcode != fuse_opcode::FUSE_READDIRPLUS {
+        warn!(?opcode, ?name, "entry inode 0 is not a valid inode");
+    }
+
+    if inode == FUSE_ROOT_ID && !is_dot {
+        warn!(?opcode, ?name, "non-root entry has the root inode");
+    }
+
+    if inode == FUSE_ROOT_ID && generation != 0 {
+        warn!(?opcode, ?name, generation, "root generation is not 0");
+    }
+}
+
 impl From<ReplyEntry> for fuse_entry_out {
     fn from(entry: ReplyEntry) -> Self {
         let attr = entry.attr;
+        let (valid, valid_nsec) = ttl_to_kernel(entry.ttl);
 
         fuse_entry_out {
             nodeid: attr.ino,
             generation: entry.generation,
-            entry_valid: entry.ttl.as_secs(),
-            attr_valid: entry.ttl.as_secs(),
-            entry_valid_nsec: entry.ttl.subsec_nanos(),
-            attr_valid_nsec: entry.ttl.subsec_nanos(),
+            entry_valid: valid,
+            attr_valid: valid,
+            entry_valid_nsec: valid_nsec,
+            attr_valid_nsec: valid_nsec,
             attr: attr.into(),
         }
     }
@@ -114,17 +376,30 @@ impl From<ReplyEntry> for fuse_entry_out {
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// reply attr.
 pub struct ReplyAttr {
-    /// the attribute TTL.
+    /// the attribute TTL, it is clamped to [`MAX_TTL`]. A zero TTL makes kernel getattr again
+    /// every time, which is the correct choice for volatile filesystems.
     pub ttl: Duration,
     /// the attribute.
     pub attr: FileAttr,
 }
 
+impl ReplyAttr {
+    /// create an attribute reply, `ttl` is clamped to [`MAX_TTL`].
+    pub fn new(attr: FileAttr, ttl: Duration) -> Self {
+        Self {
+            ttl: ttl.min(MAX_TTL),
+            attr,
+        }
+    }
+}
+
 impl From<ReplyAttr> for fuse_attr_out {
     fn from(attr: ReplyAttr) -> Self {
+        let (attr_valid, attr_valid_nsec) = ttl_to_kernel(attr.ttl);
+
         fuse_attr_out {
-            attr_valid: attr.ttl.as_secs(),
-            attr_valid_nsec: attr.ttl.subsec_nanos(),
+            attr_valid,
+            attr_valid_nsec,
             dummy: 0,
             attr: attr.attr.into(),
         }
@@ -133,17 +408,90 @@ impl From<ReplyAttr> for fuse_attr_out {
 
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// data reply.
+///
+/// the data is written to kernel after the reply header by a vectored write directly, it is not
+/// copied into the reply buffer unless [`reply_writev`][crate::MountOptions::reply_writev] is
+/// disabled. So a filesystem which keeps its data in reference counted [`Bytes`] buffers can
+/// reply a slice of them with [`Bytes::slice`] without allocation or copy.
 pub struct ReplyData {
-    /// the data.
+    /// the data, the part longer than the requested size is dropped.
     pub data: Bytes,
 }
 
+impl ReplyData {
+    /// create an empty data reply, a read reply with no data means EOF.
+    pub const fn empty() -> Self {
+        Self { data: Bytes::new() }
+    }
+
+    /// create a data reply by copying `data`.
+    pub fn from_slice(data: &[u8]) -> Self {
+        Self {
+            data: Bytes::copy_from_slice(data),
+        }
+    }
+
+    /// create a data reply of `len` zero bytes, such as reading a hole of a sparse file.
+    pub fn zeros(len: usize) -> Self {
+        vec![0; len].into()
+    }
+}
+
 impl From<Bytes> for ReplyData {
     fn from(data: Bytes) -> Self {
         Self { data }
     }
 }
 
+impl From<Vec<u8>> for ReplyData {
+    fn from(data: Vec<u8>) -> Self {
+        Self { data: data.into() }
+    }
+}
+
+/// data reply of [`read_buf`][crate::raw::Filesystem::read_buf], the data is in memory or in a
+/// file.
+#[derive(Clone)]
+pub enum ReplyReadBuf {
+    /// the data in memory, it is replied like [`read`][crate::raw::Filesystem::read].
+    Data(ReplyData),
+    /// read `len` bytes at `offset` of the file `fd`, such as the backing file of a passthrough
+    /// filesystem, the data stops at the end of the file.
+    ///
+    /// when [`use_splice`][crate::MountOptions::use_splice] is enabled and kernel supports the
+    /// splice write, the data is moved from the file to kernel by `splice(2)` without copied into
+    /// user space, otherwise it is read by `pread(2)` in a blocking thread and replied like
+    /// [`Data`][ReplyReadBuf::Data].
+    File {
+        /// the file to read, it is kept open until the reply is written.
+        fd: Arc<dyn AsFd + Send + Sync>,
+        /// the offset of the data in the file.
+        offset: u64,
+        /// the size of the data, the part longer than the requested size is dropped.
+        len: u32,
+    },
+}
+
+impl Debug for ReplyReadBuf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplyReadBuf::Data(data) => f.debug_tuple("Data").field(data).finish(),
+            ReplyReadBuf::File { fd, offset, len } => f
+                .debug_struct("File")
+                .field("fd", &fd.as_fd())
+                .field("offset", offset)
+                .field("len", len)
+                .finish(),
+        }
+    }
+}
+
+impl From<ReplyData> for ReplyReadBuf {
+    fn from(data: ReplyData) -> Self {
+        Self::Data(data)
+    }
+}
+
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// open reply.
 pub struct ReplyOpen {
@@ -153,10 +501,59 @@ pub struct ReplyOpen {
     ///
     /// if set fh 0, means use stateless IO.
     pub fh: u64,
-    /// the flags.
+    /// the `FOPEN_*` flags, see [`FopenFlags`].
     pub flags: u32,
 }
 
+impl ReplyOpen {
+    /// create an open reply with the file handle `fh` and no flags.
+    pub const fn new(fh: u64) -> Self {
+        Self { fh, flags: 0 }
+    }
+
+    /// the flags as [`FopenFlags`].
+    pub const fn open_flags(&self) -> FopenFlags {
+        FopenFlags::from_bits_retain(self.flags)
+    }
+
+    /// bypass the page cache, each read and write of this open file is sent to the filesystem
+    /// with the requested size, shared writable `mmap(2)` of the file is not supported.
+    pub fn direct_io(self, direct_io: bool) -> Self {
+        self.set(FopenFlags::DIRECT_IO, direct_io)
+    }
+
+    /// keep the data cache of the file, otherwise kernel invalidates it on open. Set it only
+    /// when the file content is not changed since the last open.
+    pub fn keep_cache(self, keep_cache: bool) -> Self {
+        self.set(FopenFlags::KEEP_CACHE, keep_cache)
+    }
+
+    /// mark the file not seekable, such as a pipe-like file, `lseek(2)` fails with `ESPIPE`
+    /// and the offset of read and write is meaningless.
+    pub fn nonseekable(self, nonseekable: bool) -> Self {
+        self.set(FopenFlags::NONSEEKABLE, nonseekable)
+    }
+
+    /// allow kernel to cache the directory entries, only for opendir.
+    ///
+    /// # Notes:
+    ///
+    /// kernel older than 4.20 (protocol 7.28) ignores this flag. The cache is dropped when
+    /// opendir replies without [`keep_cache`][ReplyOpen::keep_cache] or the directory is
+    /// invalidated by [`Notify::invalid_inode`][crate::notify::Notify::invalid_inode].
+    pub fn cache_readdir(self, cache_readdir: bool) -> Self {
+        self.set(FopenFlags::CACHE_DIR, cache_readdir)
+    }
+
+    fn set(mut self, flag: FopenFlags, enable: bool) -> Self {
+        let mut flags = self.open_flags();
+        flags.set(flag, enable);
+        self.flags = flags.bits();
+
+        self
+    }
+}
+
 impl From<ReplyOpen> for fuse_open_out {
     fn from(opened: ReplyOpen) -> Self {
         fuse_open_out {
@@ -174,6 +571,13 @@ pub struct ReplyWrite {
     pub written: u32,
 }
 
+impl ReplyWrite {
+    /// create a write reply with the `written` size.
+    pub const fn new(written: u32) -> Self {
+        Self { written }
+    }
+}
+
 impl From<ReplyWrite> for fuse_write_out {
     fn from(written: ReplyWrite) -> Self {
         fuse_write_out {
@@ -204,6 +608,124 @@ pub struct ReplyStatFs {
     pub frsize: u32,
 }
 
+impl ReplyStatFs {
+    /// create a statfs reply, all counts are 0, the block size is 4096, the maximum length of
+    /// file name is 255 and the fragment size is the same as the block size.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use fuse3::raw::reply::ReplyStatFs;
+    /// let stat_fs = ReplyStatFs::new().blocks(100).bsize(512);
+    /// assert_eq!((stat_fs.bsize, stat_fs.frsize, stat_fs.namelen), (512, 512, 255));
+    ///
+    /// let stat_fs = ReplyStatFs::new().frsize(1024).bsize(512);
+    /// assert_eq!((stat_fs.bsize, stat_fs.frsize), (512, 1024));
+    /// ```
+    pub const fn new() -> Self {
+        Self {
+            blocks: 0,
+            bfree: 0,
+            bavail: 0,
+            files: 0,
+            ffree: 0,
+            bsize: 4096,
+            namelen: 255,
+            frsize: 4096,
+        }
+    }
+
+    /// set the number of blocks in the filesystem.
+    pub const fn blocks(mut self, blocks: u64) -> Self {
+        self.blocks = blocks;
+
+        self
+    }
+
+    /// set the number of free blocks.
+    pub const fn bfree(mut self, bfree: u64) -> Self {
+        self.bfree = bfree;
+
+        self
+    }
+
+    /// set the number of free blocks for non-priviledge users.
+    pub const fn bavail(mut self, bavail: u64) -> Self {
+        self.bavail = bavail;
+
+        self
+    }
+
+    /// set the number of inodes.
+    pub const fn files(mut self, files: u64) -> Self {
+        self.files = files;
+
+        self
+    }
+
+    /// set the number of free inodes.
+    pub const fn ffree(mut self, ffree: u64) -> Self {
+        self.ffree = ffree;
+
+        self
+    }
+
+    /// set the block size, the fragment size follows it unless it is set to a different value by
+    /// [`frsize`][ReplyStatFs::frsize].
+    pub const fn bsize(mut self, bsize: u32) -> Self {
+        if self.frsize == self.bsize {
+            self.frsize = bsize;
+        }
+        self.bsize = bsize;
+
+        self
+    }
+
+    /// set the maximum length of file name.
+    pub const fn namelen(mut self, namelen: u32) -> Self {
+        self.namelen = namelen;
+
+        self
+    }
+
+    /// set the fragment size.
+    pub const fn frsize(mut self, frsize: u32) -> Self {
+        self.frsize = frsize;
+
+        self
+    }
+
+    /// create a statfs reply from `statvfs(3)` of the backing `path`, useful for a passthrough
+    /// filesystem.
+    ///
+    /// the block counts of `statvfs` are in the unit of the fragment size, so `blocks`, `bfree`
+    /// and `bavail` are copied as is with `frsize` from `f_frsize`, `bsize` is the preferred io
+    /// size `f_bsize` and `namelen` is `f_namemax`.
+    #[cfg(unix)]
+    // the statvfs field types are different between the platforms
+    #[allow(clippy::useless_conversion)]
+    pub fn from_statvfs(path: &Path) -> Result<Self> {
+        let stat = statvfs::statvfs(path)?;
+
+        Ok(Self {
+            blocks: u64::from(stat.blocks()),
+            bfree: u64::from(stat.blocks_free()),
+            bavail: u64::from(stat.blocks_available()),
+            files: u64::from(stat.files()),
+            ffree: u64::from(stat.files_free()),
+            bsize: stat.block_size() as _,
+            namelen: stat.name_max() as _,
+            frsize: stat.fragment_size() as _,
+        })
+    }
+}
+
+impl Default for ReplyStatFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl From<ReplyStatFs> for fuse_statfs_out {
     fn from(stat_fs: ReplyStatFs) -> Self {
         fuse_statfs_out {
@@ -224,12 +746,66 @@ impl From<ReplyStatFs> for fuse_statfs_out {
 }
 
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
-/// xattr reply.
+/// xattr reply of getxattr and listxattr.
+///
+/// kernel asks the size of the value first by a `size` of 0, then asks the value with a buffer of
+/// that size. The filesystem can always reply the whole value by [`ReplyXAttr::Data`], or create
+/// the reply by [`new`][ReplyXAttr::new] or [`from_names`][ReplyXAttr::from_names], the value
+/// is replied as the size when `size` is 0, and `ERANGE` is replied when `size` is too small.
+/// A [`Size`][ReplyXAttr::Size] reply for a non-zero `size` is replied as `ERANGE` too.
 pub enum ReplyXAttr {
+    /// the size of the value, reply it when the requested `size` is 0.
     Size(u32),
+    /// the value.
     Data(Bytes),
 }
 
+impl ReplyXAttr {
+    /// create a xattr reply of `value` for the requested `size`, return the size of `value` if
+    /// `size` is 0, or `ERANGE` if `value` is longer than `size`.
+    pub fn new(size: u32, value: impl Into<Bytes>) -> Result<Self> {
+        ReplyXAttr::Data(value.into()).fit(size)
+    }
+
+    /// create a listxattr reply of the attribute `names` for the requested `size`, each name is
+    /// terminated by a `\0`.
+    pub fn from_names<I, N>(size: u32, names: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = N>,
+        N: AsRef<OsStr>,
+    {
+        let mut list = vec![];
+        for name in names {
+            list.extend_from_slice(name.as_ref().as_bytes());
+            list.push(0);
+        }
+
+        Self::new(size, list)
+    }
+
+    /// make the reply fit the requested `size`, a [`Size`][ReplyXAttr::Size] reply is only valid
+    /// when `size` is 0, kernel expects the value otherwise.
+    pub(crate) fn fit(self, size: u32) -> Result<Self> {
+        match self {
+            ReplyXAttr::Size(_) if size != 0 => Err(libc::ERANGE.into()),
+
+            ReplyXAttr::Size(_) => Ok(self),
+
+            ReplyXAttr::Data(data) => {
+                let len = u32::try_from(data.len()).map_err(|_| libc::E2BIG)?;
+
+                if size == 0 {
+                    Ok(ReplyXAttr::Size(len))
+                } else if len > size {
+                    Err(libc::ERANGE.into())
+                } else {
+                    Ok(ReplyXAttr::Data(data))
+                }
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// directory entry.
 pub struct DirectoryEntry {
@@ -244,28 +820,66 @@ pub struct DirectoryEntry {
 }
 
 /// readdir reply.
+///
+/// `entries` can be an eager stream built from a listing, such as `stream::iter(vec)`, or a lazy
+/// stream which produces entries on demand. The entries are pulled one by one until the reply
+/// buffer of kernel is full or the stream ends, then the stream is dropped, the entries after it
+/// are never pulled. Kernel continues from the `offset` of the last entry it received in the next
+/// readdir, so the stream of the next call should start after that offset.
+///
+/// # Notes:
+///
+/// an eager stream keeps the whole listing in memory for every readdir call, which costs a lot
+/// for directories with millions of entries, a lazy stream only keeps about one reply buffer,
+/// usually a few pages, of entries.
 pub struct ReplyDirectory<S: Stream<Item = Result<DirectoryEntry>>> {
     pub entries: S,
 }
 
 #[cfg(feature = "file-lock")]
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
-/// file lock reply.
+/// file lock reply of getlk.
+///
+/// reply the first lock which conflicts with the requested lock by
+/// [`conflict`][ReplyLock::conflict], or [`unlocked`][ReplyLock::unlocked] if there is no
+/// conflicting lock. Don't reply the requested lock itself, the caller will see its own lock as
+/// a conflict.
 pub struct ReplyLock {
     /// starting offset for lock.
     pub start: u64,
-    /// end offset for lock.
+    /// end offset for lock, inclusive, `u64::MAX` means to the end of file.
     pub end: u64,
-    /// type of lock, such as: [`F_RDLCK`], [`F_WRLCK`] and [`F_UNLCK`]
-    ///
-    /// [`F_RDLCK`]: libc::F_RDLCK
-    /// [`F_WRLCK`]: libc::F_WRLCK
-    /// [`F_UNLCK`]: libc::F_UNLCK
-    pub r#type: u32,
+    /// type of the conflicting lock, [`LockType::Unlock`] if there is no conflicting lock.
+    pub r#type: LockType,
     /// PID of process blocking our lock
     pub pid: u32,
 }
 
+#[cfg(feature = "file-lock")]
+impl ReplyLock {
+    /// reply the conflicting `lock`, which is held by another owner.
+    pub fn conflict(lock: FileLock) -> Self {
+        Self {
+            start: lock.start,
+            end: lock.end,
+            r#type: lock.r#type,
+            pid: lock.pid,
+        }
+    }
+
+    /// reply no conflicting lock for the requested `lock`, the type is [`LockType::Unlock`] and
+    /// the range is the same as the requested lock, like `fcntl(F_GETLK)` leaves the other fields
+    /// unchanged.
+    pub fn unlocked(lock: FileLock) -> Self {
+        Self {
+            start: lock.start,
+            end: lock.end,
+            r#type: LockType::Unlock,
+            pid: 0,
+        }
+    }
+}
+
 #[cfg(feature = "file-lock")]
 impl From<ReplyLock> for fuse_lk_out {
     fn from(lock: ReplyLock) -> Self {
@@ -273,7 +887,7 @@ impl From<ReplyLock> for fuse_lk_out {
             lk: fuse_file_lock {
                 start: lock.start,
                 end: lock.end,
-                r#type: lock.r#type,
+                r#type: lock.r#type.into(),
                 pid: lock.pid,
             },
         }
@@ -281,31 +895,47 @@ impl From<ReplyLock> for fuse_lk_out {
 }
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
-/// crate reply.
+/// create reply, it carries both the new entry and the open file handle, kernel gets them in
+/// one reply, so the file is created and opened atomically.
 pub struct ReplyCreated {
-    /// the attribute TTL.
+    /// the entry and attribute TTL, it is clamped to [`MAX_TTL`].
     pub ttl: Duration,
     /// the attribute of file.
     pub attr: FileAttr,
-    /// the generation of file.
+    /// the inode generation, same as [`ReplyEntry::generation`].
     pub generation: u64,
     /// the file handle.
     pub fh: u64,
-    /// the flags.
+    /// the `FOPEN_*` flags, see [`FopenFlags`].
     pub flags: u32,
 }
 
+impl ReplyCreated {
+    /// create a create reply from the `entry` of the new file and the `open` reply of it, the
+    /// `direct_io` and `keep_cache` flags of `open` are kept.
+    pub fn new(entry: ReplyEntry, open: ReplyOpen) -> Self {
+        Self {
+            ttl: entry.ttl.min(MAX_TTL),
+            attr: entry.attr,
+            generation: entry.generation,
+            fh: open.fh,
+            flags: open.flags,
+        }
+    }
+}
+
 impl From<ReplyCreated> for (fuse_entry_out, fuse_open_out) {
     fn from(created: ReplyCreated) -> Self {
         let attr = created.attr;
+        let (valid, valid_nsec) = ttl_to_kernel(created.ttl);
 
         let entry_out = fuse_entry_out {
             nodeid: attr.ino,
             generation: created.generation,
-            entry_valid: created.ttl.as_secs(),
-            attr_valid: created.ttl.as_secs(),
-            entry_valid_nsec: created.ttl.subsec_micros(),
-            attr_valid_nsec: created.ttl.subsec_micros(),
+            entry_valid: valid,
+            attr_valid: valid,
+            entry_valid_nsec: valid_nsec,
+            attr_valid_nsec: valid_nsec,
             attr: attr.into(),
         };
 
@@ -320,9 +950,9 @@ impl From<ReplyCreated> for (fuse_entry_out, fuse_open_out) {
 }
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
-// TODO need more detail
 /// bmap reply.
 pub struct ReplyBmap {
+    /// the block index within device, in the block size units of the request.
     pub block: u64,
 }
 
@@ -332,21 +962,163 @@ impl From<ReplyBmap> for fuse_bmap_out {
     }
 }
 
-/*#[derive(Debug)]
-pub struct ReplyIoctl {
-    pub result: i32,
-    pub flags: u32,
-    pub in_iovs: u32,
-    pub out_iovs: u32,
-}*/
+#[cfg(target_os = "macos")]
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+/// getxtimes reply, the extended times of macOS.
+pub struct ReplyXTimes {
+    /// the backup time, which is used by Time Machine.
+    pub bkuptime: Timestamp,
+    /// the creation time.
+    pub crtime: Timestamp,
+}
 
+#[cfg(target_os = "macos")]
+impl From<ReplyXTimes> for fuse_getxtimes_out {
+    fn from(xtimes: ReplyXTimes) -> Self {
+        fuse_getxtimes_out {
+            bkuptime: xtimes.bkuptime.sec as u64,
+            crtime: xtimes.crtime.sec as u64,
+            bkuptimensec: xtimes.bkuptime.nsec,
+            crtimensec: xtimes.crtime.nsec,
+        }
+    }
+}
+
+/// a user memory area of the ioctl caller, used by [`ReplyIoctl::Retry`].
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
-// TODO need more detail
-/// poll reply
+pub struct IoctlIovec {
+    /// the address in the caller memory.
+    pub base: u64,
+    /// the length of the area.
+    pub len: u64,
+}
+
+impl From<IoctlIovec> for fuse_ioctl_iovec {
+    fn from(iovec: IoctlIovec) -> Self {
+        fuse_ioctl_iovec {
+            base: iovec.base,
+            len: iovec.len,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+/// ioctl reply.
+pub enum ReplyIoctl {
+    /// the ioctl is done.
+    Done {
+        /// the return value of `ioctl(2)`.
+        result: i32,
+        /// the output data copied to the caller, if it is longer than `out_size`, `EIO` is replied.
+        data: Bytes,
+    },
+
+    /// ask kernel to retry the ioctl with the input and output areas of the caller memory, only
+    /// allowed when the [`FUSE_IOCTL_UNRESTRICTED`] flag is set.
+    ///
+    /// [`FUSE_IOCTL_UNRESTRICTED`]: crate::raw::flags::FUSE_IOCTL_UNRESTRICTED
+    Retry {
+        /// the areas which kernel copies from the caller as `in_data`.
+        in_iovs: Vec<IoctlIovec>,
+        /// the areas which kernel copies the output data to, the total length is `out_size`.
+        out_iovs: Vec<IoctlIovec>,
+    },
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+/// poll reply.
+///
+/// the reply carries the events which are ready now. When no requested event is ready, reply
+/// [`pending`][ReplyPoll::pending], if kernel asked to be notified, which means `kh` of
+/// [`poll`][crate::raw::Filesystem::poll] is `Some`, call [`Notify::wakeup`] with the `kh` when
+/// the events become ready later, then kernel polls again.
+///
+/// [`Notify::wakeup`]: crate::notify::Notify::wakeup
+///
+/// # Examples:
+///
+/// an edge-triggered device file, it becomes readable when an event arrives, and kernel is woken
+/// up only when the device changes from no event to having events, like `EPOLLET`.
+///
+/// ```
+/// # use std::sync::Mutex;
+/// # use fuse3::raw::prelude::*;
+/// # use fuse3::notify::Notify;
+/// # use fuse3::Result;
+/// #[derive(Default)]
+/// struct Device {
+///     state: Mutex<DeviceState>,
+/// }
+///
+/// #[derive(Default)]
+/// struct DeviceState {
+///     /// the number of the events which are not read.
+///     events: u64,
+///     /// the kernel poll handle waiting for the events.
+///     waiter: Option<(u64, Notify)>,
+/// }
+///
+/// impl Device {
+///     /// an event arrives.
+///     async fn push_event(&self) {
+///         let waiter = {
+///             let mut state = self.state.lock().unwrap();
+///             state.events += 1;
+///
+///             // only wake up on the edge
+///             if state.events == 1 {
+///                 state.waiter.take()
+///             } else {
+///                 None
+///             }
+///         };
+///
+///         if let Some((kh, notify)) = waiter {
+///             notify.wakeup(kh).await;
+///         }
+///     }
+///
+///     /// the poll method of the filesystem.
+///     fn poll(&self, kh: Option<u64>, events: PollEvents, notify: &Notify) -> Result<ReplyPoll> {
+///         let mut state = self.state.lock().unwrap();
+///
+///         if state.events > 0 {
+///             return Ok(ReplyPoll::new(events & (PollEvents::IN | PollEvents::RDNORM)));
+///         }
+///
+///         // no event now, kernel will poll again after the wakeup
+///         if let Some(kh) = kh {
+///             state.waiter = Some((kh, notify.clone()));
+///         }
+///
+///         Ok(ReplyPoll::pending())
+///     }
+/// }
+/// ```
 pub struct ReplyPoll {
+    /// the ready events, see [`PollEvents`].
     pub revents: u32,
 }
 
+impl ReplyPoll {
+    /// create a poll reply with the ready events.
+    pub const fn new(revents: PollEvents) -> Self {
+        Self {
+            revents: revents.bits(),
+        }
+    }
+
+    /// create a poll reply which has no ready event.
+    pub const fn pending() -> Self {
+        Self { revents: 0 }
+    }
+
+    /// the ready events as [`PollEvents`].
+    pub const fn poll_events(&self) -> PollEvents {
+        PollEvents::from_bits_retain(self.revents)
+    }
+}
+
 impl From<ReplyPoll> for fuse_poll_out {
     fn from(poll: ReplyPoll) -> Self {
         fuse_poll_out {
@@ -356,12 +1128,22 @@ impl From<ReplyPoll> for fuse_poll_out {
     }
 }
 
+/// the reply of a request whose opcode is not modeled by fuse3, see
+/// [`Filesystem::raw_request`][crate::raw::Filesystem::raw_request].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum ReplyRaw {
+    /// reply the arguments after the `fuse_out_header`, which is written by fuse3.
+    Data(Bytes),
+    /// don't reply, for the requests which kernel doesn't wait the reply.
+    NoReply,
+}
+
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// directory entry with attribute
 pub struct DirectoryEntryPlus {
     /// the entry inode.
     pub inode: u64,
-    /// the entry generation.
+    /// the inode generation, same as [`ReplyEntry::generation`].
     pub generation: u64,
     /// the entry kind.
     pub kind: FileType,
@@ -371,13 +1153,35 @@ pub struct DirectoryEntryPlus {
     pub offset: i64,
     /// the entry attribute.
     pub attr: FileAttr,
-    /// the entry TTL.
+    /// the entry TTL, it is clamped to [`MAX_TTL`].
     pub entry_ttl: Duration,
-    /// the attribute TTL.
+    /// the attribute TTL, it is clamped to [`MAX_TTL`].
     pub attr_ttl: Duration,
 }
 
+impl DirectoryEntryPlus {
+    /// set the entry and attribute TTL together, `ttl` is clamped to [`MAX_TTL`].
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.entry_ttl = ttl.min(MAX_TTL);
+        self.attr_ttl = self.entry_ttl;
+
+        self
+    }
+
+    /// whether kernel counts a lookup of the entry when it is replied, like
+    /// [`lookup`][crate::raw::Filesystem::lookup]. `.`, `..` and the entry which inode is 0 are
+    /// not counted.
+    pub(crate) fn counts_lookup(&self) -> bool {
+        self.attr.ino != 0 && self.name != OsStr::new(".") && self.name != OsStr::new("..")
+    }
+}
+
 /// the readdirplus reply.
+///
+/// `entries` is consumed like [`ReplyDirectory::entries`], only the entries fit in the reply
+/// buffer are pulled. Each pulled entry counts one lookup except `.` and `..`, if an entry is
+/// pulled but not replied, fuse3 will [`forget`][crate::raw::Filesystem::forget] it, so count
+/// the lookup when the entry is produced.
 pub struct ReplyDirectoryPlus<S: Stream<Item = Result<DirectoryEntryPlus>>> {
     pub entries: S,
 }
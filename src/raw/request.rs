@@ -1,3 +1,19 @@
+#[cfg(target_os = "linux")]
+use std::fs;
+
+#[cfg(all(
+    target_os = "linux",
+    not(feature = "tokio-runtime"),
+    feature = "async-io-runtime"
+))]
+use async_fs::read_to_string;
+#[cfg(all(
+    target_os = "linux",
+    not(feature = "async-io-runtime"),
+    feature = "tokio-runtime"
+))]
+use tokio::fs::read_to_string;
+
 use crate::raw::abi::fuse_in_header;
 
 #[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
@@ -10,7 +26,22 @@ pub struct Request {
     /// the gid of this request.
     pub gid: u32,
     /// the pid of this request.
+    ///
+    /// kernel translates it into the pid namespace of the fuse connection, which is the
+    /// namespace of the process opened `/dev/fuse`, usually the filesystem process, not the host
+    /// namespace nor the namespace of the caller. It is 0 if the caller is not visible in that
+    /// namespace, such as a process in a sibling container. Use
+    /// [`pid_in_ns`][Request::pid_in_ns] to get the pid which the caller sees itself.
     pub pid: u32,
+    /// the group of the parent directory when the caller is a member of it by a supplementary
+    /// group instead of its `gid`, only sent with create, mkdir, mknod and symlink when
+    /// [`MountOptions::create_supp_group`][crate::MountOptions::create_supp_group] is enabled and
+    /// the `FUSE_CREATE_SUPP_GROUP` capability is negotiated.
+    ///
+    /// a filesystem which creates the file with the `uid` and `gid` of the caller, such as by
+    /// `setfsuid(2)` and `setfsgid(2)`, can add this group to its supplementary groups, so the
+    /// permission check of the backing filesystem is the same as the caller.
+    pub supplementary_group: Option<u32>,
 }
 
 impl From<&fuse_in_header> for Request {
@@ -20,6 +51,89 @@ impl From<&fuse_in_header> for Request {
             uid: header.uid,
             gid: header.gid,
             pid: header.pid,
+            supplementary_group: None,
+        }
+    }
+}
+
+impl Request {
+    /// get the supplementary group IDs of the process which sends this request.
+    ///
+    /// # Notes:
+    ///
+    /// fuse protocol doesn't send the supplementary groups in the request header. When the request
+    /// carries the [`supplementary_group`][Request::supplementary_group] extension, the group is
+    /// returned without looking up the process, it is the only supplementary group kernel checks
+    /// for the new file. Otherwise, like `fuse_req_getgroups` of libfuse, they are read from
+    /// `/proc/<pid>/task/<pid>/status`, which is only supported on Linux. The file is read by the
+    /// async fs of the runtime, which runs the read on its blocking thread pool instead of the
+    /// executor thread. The result may be empty when the process already exits, the filesystem
+    /// runs in a different pid namespace or on other platforms, and it may be not exactly the same
+    /// as the groups when the request is sent. If the filesystem wants to do permission checks
+    /// reliably, mount with `default_permissions` and let kernel do them.
+    pub async fn groups(&self) -> Vec<u32> {
+        if let Some(group) = self.supplementary_group {
+            return vec![group];
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let path = format!("/proc/{}/task/{}/status", self.pid, self.pid);
+            let status = match read_to_string(path).await {
+                Err(_) => return vec![],
+                Ok(status) => status,
+            };
+
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("Groups:"))
+                .map(|groups| {
+                    groups
+                        .split_whitespace()
+                        .filter_map(|gid| gid.parse().ok())
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            vec![]
+        }
+    }
+
+    /// get the pid of the process which sends this request in its own pid namespace, such as the
+    /// pid inside a container, `None` if it can't be found.
+    ///
+    /// # Notes:
+    ///
+    /// it is the last pid of the `NSpid` line of `/proc/<pid>/status`, which is only supported on
+    /// Linux 4.1 and later. The `/proc` must be mounted in the pid namespace of the fuse
+    /// connection, as the usual filesystem process does. The result is `None` when
+    /// [`pid`][Request::pid] is 0, the process already exits or on other platforms, and the pid
+    /// may be reused by another process after the caller exits, so don't rely on it for
+    /// security without checking the process again.
+    pub fn pid_in_ns(&self) -> Option<u32> {
+        #[cfg(target_os = "linux")]
+        {
+            if self.pid == 0 {
+                return None;
+            }
+
+            let status = fs::read_to_string(format!("/proc/{}/status", self.pid)).ok()?;
+
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("NSpid:"))?
+                .split_whitespace()
+                .last()?
+                .parse()
+                .ok()
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
         }
     }
 }
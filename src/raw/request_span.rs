@@ -0,0 +1,75 @@
+//! the tracing spans of the fuse requests handled by the session.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use bincode::Options;
+use tracing::{field, info_span, Span};
+
+use crate::helper::get_bincode_config;
+use crate::raw::abi::{fuse_opcode, fuse_out_header, FUSE_OUT_HEADER_SIZE};
+use crate::raw::observer::Opcode;
+use crate::raw::Request;
+use crate::Inode;
+
+/// track the `fuse_request` spans until their replies are written to kernel.
+#[derive(Debug, Default)]
+pub(crate) struct RequestSpans {
+    spans: Mutex<HashMap<u64, Span>>,
+}
+
+impl RequestSpans {
+    /// create the `fuse_request` span of a request, if the request needs reply, the span is
+    /// kept until the reply is written.
+    pub(crate) fn start(
+        &self,
+        opcode: fuse_opcode,
+        request: Request,
+        nodeid: Inode,
+        need_reply: bool,
+    ) -> Span {
+        let span = info_span!(
+            "fuse_request",
+            opcode = %Opcode(opcode),
+            unique = request.unique,
+            nodeid,
+            uid = request.uid,
+            pid = request.pid,
+            error = field::Empty,
+        );
+
+        if need_reply {
+            self.spans
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .insert(request.unique, span.clone());
+        }
+
+        span
+    }
+
+    /// the reply is written to kernel, record the error code and close the span, notifies are
+    /// ignored.
+    pub(crate) fn reply(&self, data: &[u8]) {
+        if data.len() < FUSE_OUT_HEADER_SIZE {
+            return;
+        }
+
+        let out_header = match get_bincode_config().deserialize::<fuse_out_header>(data) {
+            Err(_) => return,
+            Ok(out_header) => out_header,
+        };
+
+        let span = self
+            .spans
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(&out_header.unique);
+
+        if let Some(span) = span {
+            if out_header.error != 0 {
+                span.record("error", -out_header.error);
+            }
+        }
+    }
+}
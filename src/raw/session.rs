@@ -1,26 +1,35 @@
+use std::error::Error;
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
 use std::ffi::OsStr;
 use std::ffi::OsString;
-use std::fmt::{Debug, Formatter};
+use std::fmt::{self, Debug, Display, Formatter};
+use std::fs::File;
 use std::future::Future;
 use std::io::Error as IoError;
 use std::io::ErrorKind;
 use std::io::Result as IoResult;
+use std::mem::{self, ManuallyDrop};
 use std::num::NonZeroU32;
-use std::os::fd::AsFd;
+use std::os::fd::{AsFd, BorrowedFd, FromRawFd, OwnedFd};
 use std::os::unix::ffi::OsStrExt;
 use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::FileExt;
 use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
 use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+#[cfg(target_os = "linux")]
+use std::sync::{Mutex, Once, TryLockError};
 use std::task::Context;
 use std::task::Poll;
+use std::time::Duration;
 
 #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
 use async_fs::read_dir;
 #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
 use async_global_executor::{self as task, Task as JoinHandle};
+use async_lock::Semaphore;
 #[cfg(all(
     target_os = "linux",
     not(feature = "tokio-runtime"),
@@ -31,12 +40,12 @@ use async_process::Command;
 use bincode::Options;
 use bytes::Bytes;
 use futures_channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
-use futures_util::future::{Either, FutureExt};
+use futures_util::future::{self, Abortable, Either, FutureExt};
 use futures_util::select;
 use futures_util::sink::{Sink, SinkExt};
 use futures_util::stream::StreamExt;
+use nix::errno::Errno as NixErrno;
 use nix::mount;
-#[cfg(target_os = "freebsd")]
 use nix::mount::MntFlags;
 #[cfg(all(
     target_os = "linux",
@@ -54,53 +63,395 @@ use tracing::{debug, debug_span, error, instrument, warn, Instrument, Span};
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
 use crate::find_fusermount3;
 use crate::helper::*;
-use crate::notify::Notify;
+use crate::notify::{Notify, NotifyQueue, PendingRetrieves};
 use crate::raw::abi::*;
+use crate::raw::buffer_pool::{BufferPool, PooledBuffer};
+#[cfg(target_os = "linux")]
+use crate::raw::connection::splice::Splicer;
 #[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
 use crate::raw::connection::FuseConnection;
+use crate::raw::connection_info::{
+    connection_info_channel, ConnectionInfo, ConnectionInfoReceiver, ConnectionInfoSender,
+};
+use crate::raw::extension::{RequestExtensions, SecurityContext};
 use crate::raw::filesystem::Filesystem;
-use crate::raw::reply::ReplyXAttr;
+#[cfg(not(target_os = "macos"))]
+use crate::raw::flags::InitFlags2;
+use crate::raw::flags::{
+    FallocateMode, InitFlags, OptionalOps, PollEvents, RenameFlags, SetXattrFlags, WriteFlags,
+};
+use crate::raw::in_flight::{InFlightRequests, InterruptibleRequests};
+#[cfg(feature = "file-lock")]
+use crate::raw::lock::{FileLock, LockType};
+use crate::raw::observer::{RequestObserver, SessionObserver};
+use crate::raw::reply::{
+    check_entry, ttl_to_kernel, ReplyIoctl, ReplyRaw, ReplyReadBuf, ReplyXAttr,
+};
 use crate::raw::request::Request;
-use crate::raw::FuseData;
+#[cfg(feature = "request-span")]
+use crate::raw::request_span::RequestSpans;
+#[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
+use crate::raw::session_builder::SessionBuilder;
+#[cfg(feature = "wire-debug")]
+use crate::raw::wire_debug;
+use crate::raw::{ExtendData, FuseData};
+use crate::runtime::{AsyncRuntime, CurrentRuntime};
 use crate::MountOptions;
-use crate::{Errno, SetAttr};
+use crate::{Errno, Inode, SetAttr};
 
 /// A Future which returns when a file system is unmounted
 ///
-/// when drop the [`MountHandle`], it will unmount Filesystem in background task, if user want to
-/// wait unmount completely, use [`MountHandle::unmount`]
+/// when drop the [`MountHandle`], it will unmount Filesystem in background task, if the mount
+/// point is busy, it will be detached lazily. If user want to wait unmount completely, use
+/// [`MountHandle::unmount`]
+///
+/// the drop never blocks the runtime. If the background task can't run to the end, such as the
+/// handle is dropped outside the runtime or the runtime is shutting down, the mount point is
+/// detached by `umount2(2)` with `MNT_DETACH` synchronously instead, which doesn't wait for the
+/// filesystem, and the `/dev/fuse` fd is closed with the session, so a forgotten handle doesn't
+/// leave a stale mount. The unprivileged mount may not be detached in this way.
+///
+/// the session is spawned onto the runtime when mounted, the filesystem is served in background
+/// without awaiting the handle, so the caller task can do other work and keep the handle to stop
+/// the session later:
+///
+/// - await the handle to wait until the filesystem is unmounted by others, such as `umount(8)`.
+/// - [`unmount`][MountHandle::unmount] stops the session cleanly, the filesystem
+///   [`destroy`][crate::raw::Filesystem::destroy] is called, then the mount point is unmounted,
+///   it fails with [`MountBusyError`] if the mount point is busy.
+/// - [`unmount_gracefully`][MountHandle::unmount_gracefully] also waits for the in flight
+///   requests before unmounting.
+/// - [`abort`][MountHandle::abort] is the hard stop, the session is canceled at once without
+///   calling [`destroy`][crate::raw::Filesystem::destroy], and the mount point is detached even
+///   if it is busy.
+///
+/// # Examples:
+///
+/// ```no_run
+/// # use fuse3::raw::prelude::*;
+/// # use fuse3::MountOptions;
+/// # async fn run<FS: Filesystem + Send + Sync + 'static>(fs: FS) -> std::io::Result<()> {
+/// let mount_handle = Session::new(MountOptions::default())
+///     .mount(fs, "/mnt/fuse3")
+///     .await?;
+///
+/// // the filesystem is served in background, do other work here
+///
+/// mount_handle.unmount().await
+/// # }
+/// ```
 #[derive(Debug)]
 pub struct MountHandle {
     inner: Option<MountHandleInner>,
 }
 
 impl MountHandle {
+    /// get the [`ConnectionInfo`] negotiated with kernel, return `None` if the `FUSE_INIT` is
+    /// not done yet.
+    pub fn connection_info(&self) -> Option<ConnectionInfo> {
+        self.inner
+            .as_ref()
+            .and_then(|inner| inner.connection_info.get())
+    }
+
+    /// get the fuse protocol version `(major, minor)` negotiated with kernel, it will wait until
+    /// the `FUSE_INIT` is done, return an error if the session is stopped before that.
+    pub async fn protocol_version(&self) -> IoResult<(u32, u32)> {
+        let connection_info = self
+            .inner
+            .as_ref()
+            .expect("inner should be Some()")
+            .connection_info
+            .wait()
+            .await?;
+
+        Ok(connection_info.protocol_version())
+    }
+
+    /// get the capabilities both requested by fuse3 and supported by kernel, it will wait until
+    /// the `FUSE_INIT` is done, return an error if the session is stopped before that.
+    pub async fn negotiated_capabilities(&self) -> IoResult<InitFlags> {
+        let connection_info = self
+            .inner
+            .as_ref()
+            .expect("inner should be Some()")
+            .connection_info
+            .wait()
+            .await?;
+
+        Ok(connection_info.capabilities())
+    }
+
+    /// get the mount point path.
+    pub fn mountpoint(&self) -> &Path {
+        &self
+            .inner
+            .as_ref()
+            .expect("inner should be Some()")
+            .mount_path
+    }
+
+    /// whether the filesystem session is still serving, it returns `false` after the session is
+    /// stopped, such as the filesystem is unmounted by `umount(8)` or `fusermount3 -u`.
+    ///
+    /// it only checks the internal state, so it is cheap and never blocks, even if the
+    /// filesystem is hung.
+    pub fn is_mounted(&self) -> bool {
+        self.inner
+            .as_ref()
+            .is_some_and(|inner| !inner.task.is_finished())
+    }
+
+    /// get the id of the fuse connection, the connection is controlled by the files in
+    /// `/sys/fs/fuse/connections/<id>/`, such as `waiting` which shows the number of the requests
+    /// waiting for replies, and `abort` which aborts the connection.
+    ///
+    /// the id is the device number of the filesystem, it is looked up from
+    /// `/proc/self/mountinfo`, so no request is sent to the filesystem and it works even if the
+    /// filesystem is hung.
+    ///
+    /// # Errors:
+    ///
+    /// return the [`ErrorKind::NotFound`] error if no fuse filesystem is mounted at the mount
+    /// point, such as it is unmounted by others. The handle of
+    /// [`mount_with_fd`][Session::mount_with_fd] always returns it, its mount point is
+    /// `/dev/fd/N`, and the `/dev/fuse` fd doesn't tell which mount it serves.
+    #[cfg(target_os = "linux")]
+    pub fn connection_id(&self) -> IoResult<u32> {
+        fuse_connection_id(self.mountpoint())
+    }
+
+    /// abort the fuse connection by writing the `/sys/fs/fuse/connections/<id>/abort` control
+    /// file, it is the last resort to tear down a wedged connection, such as the filesystem is
+    /// deadlocked and even [`abort`][MountHandle::abort] can't stop it, because the process
+    /// which is stuck in the filesystem can't be killed.
+    ///
+    /// kernel fails all the waiting and the new requests with `ECONNABORTED` at once, the
+    /// session stops after that, but the mount point is left mounted until the handle is
+    /// unmounted or dropped, the processes using it see `ENOTCONN`.
+    ///
+    /// # Notes:
+    ///
+    /// the fusectl filesystem must be mounted at `/sys/fs/fuse/connections`, the `abort` file is
+    /// only writable by the owner of the mount, which is root unless the filesystem is mounted by
+    /// [`mount_with_unprivileged`][Session::mount_with_unprivileged], otherwise it fails with
+    /// `EACCES` or `ENOENT`.
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use fuse3::raw::MountHandle;
+    /// # async fn run(mount_handle: MountHandle) -> std::io::Result<()> {
+    /// let id = mount_handle.connection_id()?;
+    /// let waiting = std::fs::read_to_string(format!("/sys/fs/fuse/connections/{id}/waiting"))?;
+    ///
+    /// if waiting.trim() != "0" {
+    ///     mount_handle.abort_connection()?;
+    /// }
+    ///
+    /// mount_handle.unmount().await
+    /// # }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn abort_connection(&self) -> IoResult<()> {
+        let id = self.connection_id()?;
+
+        std::fs::write(format!("/sys/fs/fuse/connections/{id}/abort"), "1")
+    }
+
+    /// get a [`Notify`] to notify kernel, it can be used in any task when the filesystem is
+    /// running.
+    pub fn notify(&self) -> Notify {
+        self.inner
+            .as_ref()
+            .expect("inner should be Some()")
+            .notify
+            .clone()
+    }
+
+    /// stop the filesystem session and unmount it, wait until unmount completely.
+    ///
+    /// the filesystem is unmounted by `umount2(2)` directly, which doesn't need `fusermount3`.
+    /// Only when the filesystem is mounted by
+    /// [`mount_with_unprivileged`][Session::mount_with_unprivileged] and `fusermount3` is found,
+    /// it will call `fusermount3 -u` to unmount. The filesystem served by
+    /// [`mount_with_fd`][Session::mount_with_fd] is not unmounted, only the session is stopped.
+    ///
+    /// # Errors:
+    ///
+    /// if the mount point is busy, return the error which wraps [`MountBusyError`], the session
+    /// is already stopped, unmount it by `umount(8)` after the mount point is not used anymore.
+    ///
+    /// ```no_run
+    /// # use fuse3::raw::{MountBusyError, MountHandle};
+    /// # async fn run(mount_handle: MountHandle) -> std::io::Result<()> {
+    /// if let Err(err) = mount_handle.unmount().await {
+    ///     match err.get_ref().and_then(|err| err.downcast_ref::<MountBusyError>()) {
+    ///         Some(busy) => eprintln!("{:?} is still used", busy.mount_path()),
+    ///         None => return Err(err),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
     pub async fn unmount(mut self) -> IoResult<()> {
         self.inner
             .take()
             .expect("unmount call twice")
-            .inner_unmount()
+            .inner_unmount(false)
             .await
     }
+
+    /// stop accepting new requests, wait until the in flight requests are done or the `timeout`
+    /// fired, then unmount like [`unmount`][MountHandle::unmount]. If `timeout` is `None`, it will
+    /// wait until all in flight requests are done.
+    ///
+    /// return the number of requests which are still in flight when the timeout fired, their
+    /// replies will be dropped. Dropping [`MountHandle`] is still the hard stop way. It fails with
+    /// [`MountBusyError`] if the mount point is busy, as [`unmount`][MountHandle::unmount] does.
+    pub async fn unmount_gracefully(mut self, timeout: Option<Duration>) -> IoResult<usize> {
+        let inner = self.inner.take().expect("unmount call twice");
+        let in_flight = inner.in_flight.clone();
+
+        in_flight.set_drain_timeout(timeout);
+
+        inner.inner_unmount(false).await?;
+
+        // the session is stopped when unmount returns, if it isn't stopped by the drain, such as
+        // the filesystem is unmounted by others, the left requests are abandoned as well
+        Ok(in_flight.abandoned().unwrap_or_else(|| in_flight.count()))
+    }
+
+    /// cancel the filesystem session at once and detach the mount point, it never blocks and
+    /// doesn't need to be awaited.
+    ///
+    /// unlike [`unmount`][MountHandle::unmount], the filesystem
+    /// [`destroy`][crate::raw::Filesystem::destroy] is not called, the session stops reading the
+    /// requests at its next await point, the unfinished requests are not replied, kernel fails
+    /// them with `ENOTCONN` when the `/dev/fuse` fd is closed. The mount point is detached by
+    /// `umount2(2)` with `MNT_DETACH` even if it is busy, the processes using it see it
+    /// disconnected. The unprivileged mount may not be detached in this way. The filesystem
+    /// served by [`mount_with_fd`][Session::mount_with_fd] is not unmounted, only the session is
+    /// canceled.
+    pub fn abort(mut self) {
+        let mut inner = self.inner.take().expect("inner should be Some()");
+
+        // the session task is done, the filesystem is unmounted already
+        if inner.task.is_finished() && !inner.is_task_canceled() {
+            return;
+        }
+
+        let guard = DropUnmountGuard::new(&inner);
+
+        #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
+        inner.task.abort();
+
+        // the async-io task is canceled when dropped
+        drop(inner);
+        drop(guard);
+    }
 }
 
 impl Drop for MountHandle {
     fn drop(&mut self) {
-        if let Some(inner) = self.inner.take() {
+        if let Some(mut inner) = self.inner.take() {
             if inner.task.is_finished() {
+                // the session task is canceled when the runtime is shut down, but the filesystem
+                // is still mounted
+                if inner.is_task_canceled() {
+                    drop(DropUnmountGuard::new(&inner));
+                }
+
                 return;
             }
 
+            let guard = DropUnmountGuard::new(&inner);
+            #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
+            let destroy_notify = inner.destroy_notify.clone();
+            let unmount_task = async move {
+                match inner.inner_unmount(true).await {
+                    // the session may fail before unmounting, for example it is stopped before
+                    // FUSE_INIT, then the guard detaches the mount point
+                    Err(err) => warn!("unmount the dropped mount handle failed {}", err),
+
+                    Ok(()) => guard.disarm(),
+                }
+            };
+
             #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
-            {
-                task::spawn(inner.inner_unmount()).detach();
-            }
+            CurrentRuntime::spawn(unmount_task);
 
             #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
-            {
-                task::spawn(inner.inner_unmount());
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn(unmount_task);
+                }
+
+                // no runtime to run the unmount task, drop it then the guard unmounts
+                // synchronously
+                Err(_) => {
+                    destroy_notify.notify();
+
+                    drop(unmount_task);
+                }
+            }
+        }
+    }
+}
+
+/// unmount the filesystem synchronously when the unmount task of a dropped [`MountHandle`] is
+/// dropped before it is done, or when the [`MountHandle`] is aborted.
+struct DropUnmountGuard {
+    // None if the mount point should not be unmounted
+    mount_path: Option<PathBuf>,
+}
+
+impl DropUnmountGuard {
+    fn new(inner: &MountHandleInner) -> Self {
+        Self {
+            mount_path: (!inner.passed_fd).then(|| inner.mount_path.clone()),
+        }
+    }
+
+    fn disarm(mut self) {
+        self.mount_path.take();
+    }
+}
+
+impl Drop for DropUnmountGuard {
+    fn drop(&mut self) {
+        let mount_path = match self.mount_path.take() {
+            None => return,
+            Some(mount_path) => mount_path,
+        };
+
+        // kernel doesn't wait for the filesystem when detach or force unmount the fuse mount, so
+        // it won't block even the session task runs in the same thread
+        #[cfg(target_os = "linux")]
+        {
+            unregister_auto_unmount(&mount_path);
+
+            match mount::umount2(&mount_path, MntFlags::MNT_DETACH) {
+                // not mounted any more
+                Ok(()) | Err(NixErrno::EINVAL) => {}
+
+                Err(err) => warn!(
+                    "detach the dropped mount point {:?} failed {}",
+                    mount_path.display(),
+                    err
+                ),
             }
         }
+
+        #[cfg(any(target_os = "freebsd", target_os = "macos"))]
+        if let Err(err) = mount::unmount(&mount_path, MntFlags::MNT_FORCE) {
+            warn!(
+                "unmount the dropped mount point {:?} failed {}",
+                mount_path.display(),
+                err
+            );
+        }
     }
 }
 
@@ -109,12 +460,37 @@ struct MountHandleInner {
     task: JoinHandle<IoResult<()>>,
     mount_path: PathBuf,
     destroy_notify: Arc<async_notify::Notify>,
+    connection_info: ConnectionInfoReceiver,
+    notify: Notify,
+    in_flight: Arc<InFlightRequests>,
+    // mounted by others and passed by Session::mount_with_fd, don't unmount it
+    passed_fd: bool,
+    // the task output is taken by polling MountHandle, the task can't be polled again
+    #[cfg(feature = "tokio-runtime")]
+    task_returned: bool,
     #[cfg(all(target_os = "linux", feature = "unprivileged"))]
     unprivileged: bool,
 }
 
 impl MountHandleInner {
-    async fn inner_unmount(self) -> IoResult<()> {
+    /// whether the finished session task is canceled instead of returned.
+    #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
+    fn is_task_canceled(&mut self) -> bool {
+        // the global executor never cancels the spawned tasks
+        false
+    }
+
+    /// whether the finished session task is canceled instead of returned.
+    #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
+    fn is_task_canceled(&mut self) -> bool {
+        !self.task_returned
+            && matches!((&mut self.task).now_or_never(), Some(Err(err)) if err.is_cancelled())
+    }
+
+    /// stop the session and unmount, when `detach` is true and the mount point is busy, detach it
+    /// lazily.
+    #[cfg_attr(target_os = "freebsd", allow(unused_variables))]
+    async fn inner_unmount(self, detach: bool) -> IoResult<()> {
         self.destroy_notify.notify();
 
         #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
@@ -122,34 +498,61 @@ impl MountHandleInner {
             // wait destroy done
             self.task.await?;
 
+            if self.passed_fd {
+                return Ok(());
+            }
+
             // TODO: freebsd mount is unprivileged, then unmount is unprivileged too?
             #[cfg(target_os = "freebsd")]
             {
                 task::spawn_blocking(move || {
                     mount::unmount(&self.mount_path, MntFlags::MNT_SYNCHRONOUS)
+                        .map_err(|err| unmount_error(&self.mount_path, err))
+                })
+                .await?;
+            }
+
+            // macOS can't detach the mount point lazily, unmount it forcibly
+            #[cfg(target_os = "macos")]
+            {
+                let flags = if detach {
+                    MntFlags::MNT_FORCE
+                } else {
+                    MntFlags::empty()
+                };
+
+                task::spawn_blocking(move || {
+                    mount::unmount(&self.mount_path, flags)
+                        .map_err(|err| unmount_error(&self.mount_path, err))
                 })
                 .await?;
             }
 
             #[cfg(target_os = "linux")]
             {
+                unregister_auto_unmount(&self.mount_path);
+
                 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
                 if self.unprivileged {
-                    let binary_path = find_fusermount3()?;
-                    let mut child = Command::new(binary_path)
-                        .args([OsStr::new("-u"), self.mount_path.as_os_str()])
-                        .spawn()?;
-                    if !child.status().await?.success() {
-                        return Err(IoError::new(
-                            ErrorKind::Other,
-                            "call fusermount3 -u to unmount failed",
-                        ));
+                    match find_fusermount3() {
+                        Err(err) => warn!("{}, try to unmount by umount2", err),
+
+                        Ok(binary_path) => {
+                            let mut child = Command::new(binary_path)
+                                .args([OsStr::new("-u"), self.mount_path.as_os_str()])
+                                .spawn()?;
+                            if !child.status().await?.success() {
+                                return Err(IoError::other(
+                                    "call fusermount3 -u to unmount failed",
+                                ));
+                            }
+
+                            return Ok(());
+                        }
                     }
-
-                    return Ok(());
                 }
 
-                task::spawn_blocking(move || mount::umount(&self.mount_path)).await?;
+                task::spawn_blocking(move || umount(&self.mount_path, detach)).await?;
             }
         }
 
@@ -158,11 +561,33 @@ impl MountHandleInner {
             // wait destroy done
             self.task.await.unwrap()?;
 
+            if self.passed_fd {
+                return Ok(());
+            }
+
             // TODO: freebsd mount is unprivileged, then unmount is unprivileged too?
             #[cfg(target_os = "freebsd")]
             {
                 task::spawn_blocking(move || {
                     mount::unmount(&self.mount_path, MntFlags::MNT_SYNCHRONOUS)
+                        .map_err(|err| unmount_error(&self.mount_path, err))
+                })
+                .await
+                .unwrap()?;
+            }
+
+            // macOS can't detach the mount point lazily, unmount it forcibly
+            #[cfg(target_os = "macos")]
+            {
+                let flags = if detach {
+                    MntFlags::MNT_FORCE
+                } else {
+                    MntFlags::empty()
+                };
+
+                task::spawn_blocking(move || {
+                    mount::unmount(&self.mount_path, flags)
+                        .map_err(|err| unmount_error(&self.mount_path, err))
                 })
                 .await
                 .unwrap()?;
@@ -170,23 +595,29 @@ impl MountHandleInner {
 
             #[cfg(target_os = "linux")]
             {
+                unregister_auto_unmount(&self.mount_path);
+
                 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
                 if self.unprivileged {
-                    let binary_path = find_fusermount3()?;
-                    let mut child = Command::new(binary_path)
-                        .args([OsStr::new("-u"), self.mount_path.as_os_str()])
-                        .spawn()?;
-                    if !child.wait().await?.success() {
-                        return Err(IoError::new(
-                            ErrorKind::Other,
-                            "call fusermount3 -u to unmount failed",
-                        ));
+                    match find_fusermount3() {
+                        Err(err) => warn!("{}, try to unmount by umount2", err),
+
+                        Ok(binary_path) => {
+                            let mut child = Command::new(binary_path)
+                                .args([OsStr::new("-u"), self.mount_path.as_os_str()])
+                                .spawn()?;
+                            if !child.wait().await?.success() {
+                                return Err(IoError::other(
+                                    "call fusermount3 -u to unmount failed",
+                                ));
+                            }
+
+                            return Ok(());
+                        }
                     }
-
-                    return Ok(());
                 }
 
-                task::spawn_blocking(move || mount::umount(&self.mount_path))
+                task::spawn_blocking(move || umount(&self.mount_path, detach))
                     .await
                     .unwrap()?;
             }
@@ -196,6 +627,186 @@ impl MountHandleInner {
     }
 }
 
+/// unmount the filesystem by `umount2(2)`, when `detach` is true and the mount point is busy, retry
+/// with `MNT_DETACH`.
+#[cfg(target_os = "linux")]
+fn umount(mount_path: &Path, detach: bool) -> IoResult<()> {
+    match mount::umount2(mount_path, MntFlags::empty()) {
+        Err(NixErrno::EBUSY) if detach => {
+            warn!(
+                "mount point {:?} is busy, detach it lazily",
+                mount_path.display()
+            );
+
+            mount::umount2(mount_path, MntFlags::MNT_DETACH)?;
+
+            Ok(())
+        }
+
+        Err(err) => Err(unmount_error(mount_path, err)),
+
+        Ok(()) => Ok(()),
+    }
+}
+
+/// convert the unmount error, the busy mount point is reported as [`MountBusyError`].
+fn unmount_error(mount_path: &Path, err: NixErrno) -> IoError {
+    if err == NixErrno::EBUSY {
+        IoError::other(MountBusyError {
+            mount_path: mount_path.to_path_buf(),
+        })
+    } else {
+        err.into()
+    }
+}
+
+/// The error of unmounting a busy mount point, the mount point is still used by some processes,
+/// such as their current directory or opened files.
+///
+/// it is wrapped in the [`IoError`] returned by [`MountHandle::unmount`] and
+/// [`MountHandle::unmount_gracefully`], get it by [`IoError::get_ref`] and
+/// `downcast_ref`.
+#[derive(Debug)]
+pub struct MountBusyError {
+    mount_path: PathBuf,
+}
+
+impl MountBusyError {
+    /// the busy mount point.
+    pub fn mount_path(&self) -> &Path {
+        &self.mount_path
+    }
+}
+
+impl Display for MountBusyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "mount point {} is busy", self.mount_path.display())
+    }
+}
+
+impl Error for MountBusyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&NixErrno::EBUSY)
+    }
+}
+
+/// find the fuse filesystem mounted at `mount_path` from `/proc/self/mountinfo`, return its
+/// device number in the kernel encoding, which is the name of its directory in
+/// `/sys/fs/fuse/connections`.
+#[cfg(target_os = "linux")]
+fn fuse_connection_id(mount_path: &Path) -> IoResult<u32> {
+    // don't stat the mount point itself, it sends the request to the filesystem
+    let mount_path = match (mount_path.parent(), mount_path.file_name()) {
+        (Some(parent), Some(name)) if parent.as_os_str().is_empty() => {
+            Path::new(".").canonicalize()?.join(name)
+        }
+        (Some(parent), Some(name)) => parent.canonicalize()?.join(name),
+        _ => mount_path.canonicalize()?,
+    };
+
+    let mountinfo = std::fs::read("/proc/self/mountinfo")?;
+
+    // the filesystem may be mounted over another one, the last one is on the top
+    mountinfo
+        .rsplit(|&b| b == b'\n')
+        .find_map(|line| {
+            // 36 35 0:50 / /mnt/fuse3 rw,nosuid - fuse.fuse3 fuse rw,user_id=0
+            let mut fields = line.split(|&b| b == b' ');
+            let device = fields.nth(2)?;
+            let mount_point = fields.nth(1)?;
+            let fs_type = fields.skip_while(|&field| field != b"-").nth(1)?;
+
+            let is_fuse = fs_type == b"fuse" || fs_type.starts_with(b"fuse.");
+            if !is_fuse || unescape_mountinfo(mount_point) != mount_path.as_os_str().as_bytes() {
+                return None;
+            }
+
+            let (major, minor) = std::str::from_utf8(device).ok()?.split_once(':')?;
+
+            // the kernel dev_t is major << 20 | minor
+            Some((major.parse::<u32>().ok()? << 20) | minor.parse::<u32>().ok()?)
+        })
+        .ok_or_else(|| {
+            IoError::new(
+                ErrorKind::NotFound,
+                format!("no fuse filesystem is mounted at {}", mount_path.display()),
+            )
+        })
+}
+
+/// unescape the `\ooo` octal escapes of the space, tab, newline and backslash in mountinfo.
+#[cfg(target_os = "linux")]
+fn unescape_mountinfo(field: &[u8]) -> Vec<u8> {
+    let mut unescaped = Vec::with_capacity(field.len());
+    let mut rest = field;
+
+    while let Some((&b, tail)) = rest.split_first() {
+        let escaped = (b == b'\\')
+            .then(|| tail.get(..3))
+            .flatten()
+            .and_then(|octal| std::str::from_utf8(octal).ok())
+            .and_then(|octal| u8::from_str_radix(octal, 8).ok());
+
+        match escaped {
+            Some(escaped) => {
+                unescaped.push(escaped);
+                rest = &tail[3..];
+            }
+
+            None => {
+                unescaped.push(b);
+                rest = tail;
+            }
+        }
+    }
+
+    unescaped
+}
+
+/// the mount points mounted by [`Session::mount`] with
+/// [`auto_unmount`][MountOptions::auto_unmount], they are unmounted at process exit.
+#[cfg(target_os = "linux")]
+static AUTO_UNMOUNT_PATHS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+#[cfg(target_os = "linux")]
+fn register_auto_unmount(mount_path: &Path) {
+    static REGISTER_AT_EXIT: Once = Once::new();
+
+    REGISTER_AT_EXIT.call_once(|| {
+        // Safety: unmount_at_exit is a valid extern "C" fn without arguments
+        if unsafe { libc::atexit(unmount_at_exit) } != 0 {
+            warn!("register auto unmount at exit failed");
+        }
+    });
+
+    AUTO_UNMOUNT_PATHS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .push(mount_path.to_path_buf());
+}
+
+#[cfg(target_os = "linux")]
+fn unregister_auto_unmount(mount_path: &Path) {
+    AUTO_UNMOUNT_PATHS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .retain(|path| path != mount_path);
+}
+
+#[cfg(target_os = "linux")]
+extern "C" fn unmount_at_exit() {
+    // don't block the exit when another thread holds the lock
+    let paths = match AUTO_UNMOUNT_PATHS.try_lock() {
+        Err(TryLockError::WouldBlock) => return,
+        Err(TryLockError::Poisoned(err)) => err.into_inner(),
+        Ok(paths) => paths,
+    };
+
+    for path in paths.iter() {
+        let _ = mount::umount2(path, MntFlags::MNT_DETACH);
+    }
+}
+
 impl Future for MountHandle {
     type Output = IoResult<()>;
 
@@ -209,9 +820,11 @@ impl Future for MountHandle {
         // The unwrap is necessary in order to provide the same API for both runtimes, and actually
         // unwrap should not panic, when MountHandle is canceled by unmount method, user has no
         // chance to poll again
-        Pin::new(&mut self.inner.as_mut().expect("inner should be Some()").task)
-            .poll(cx)
-            .map(Result::unwrap)
+        let inner = self.inner.as_mut().expect("inner should be Some()");
+        let result = std::task::ready!(Pin::new(&mut inner.task).poll(cx));
+        inner.task_returned = true;
+
+        Poll::Ready(result.unwrap())
     }
 }
 
@@ -222,7 +835,24 @@ pub struct Session<FS> {
     filesystem: Option<Arc<FS>>,
     response_sender: UnboundedSender<FuseData>,
     response_receiver: Option<UnboundedReceiver<FuseData>>,
+    notify_queue: Option<Arc<NotifyQueue>>,
     mount_options: MountOptions,
+    connection_info: ConnectionInfoSender,
+    connection_info_receiver: ConnectionInfoReceiver,
+    pending_retrieves: Arc<PendingRetrieves>,
+    in_flight: Arc<InFlightRequests>,
+    /// the slots of the running filesystem requests, limited by
+    /// [`MountOptions::max_concurrent_requests`].
+    request_slots: Option<Arc<Semaphore>>,
+    interruptible: Arc<InterruptibleRequests>,
+    observer: Option<Arc<RequestObserver>>,
+    #[cfg(feature = "request-span")]
+    request_spans: Arc<RequestSpans>,
+    /// the filesystem replies `ENOSYS` to `read_buf`, only call `read`.
+    read_buf_unsupported: Arc<AtomicBool>,
+    /// splice the file data of the read replies when the splice write is negotiated.
+    #[cfg(target_os = "linux")]
+    splicer: Option<Arc<Splicer>>,
 }
 
 enum ReadResult {
@@ -246,31 +876,264 @@ impl Debug for ReadResult {
     }
 }
 
+/// the fuse request handled by a spawned filesystem request future.
+#[derive(Debug, Copy, Clone)]
+struct RequestContext {
+    request: Request,
+    opcode: fuse_opcode,
+    nodeid: Inode,
+}
+
+impl RequestContext {
+    fn new(opcode: fuse_opcode, request: Request, nodeid: Inode) -> Self {
+        Self {
+            request,
+            opcode,
+            nodeid,
+        }
+    }
+
+    /// whether kernel waits the reply of this request.
+    fn need_reply(&self) -> bool {
+        self.opcode.need_reply()
+    }
+
+    /// the requests which kernel doesn't wait the reply, and the `FUSE_SETLKW` which may wait
+    /// the lock for long, won't be cancelled by the request timeout.
+    fn can_timeout(&self) -> bool {
+        #[cfg(feature = "file-lock")]
+        if self.opcode == fuse_opcode::FUSE_SETLKW {
+            return false;
+        }
+
+        self.need_reply()
+    }
+}
+
 #[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
 impl<FS> Session<FS> {
     /// new a fuse filesystem session.
     pub fn new(mount_options: MountOptions) -> Self {
         let (sender, receiver) = unbounded();
+        let (connection_info, connection_info_receiver) = connection_info_channel();
+        let notify_queue = mount_options.notify_queue_size.map(|notify_queue_size| {
+            Arc::new(NotifyQueue::new(
+                notify_queue_size,
+                mount_options.notify_queue_policy,
+            ))
+        });
+        let request_slots = mount_options
+            .max_concurrent_requests
+            .map(|max_concurrent_requests| Arc::new(Semaphore::new(max_concurrent_requests)));
 
         Self {
             fuse_connection: None,
             filesystem: None,
             response_sender: sender,
             response_receiver: Some(receiver),
+            notify_queue,
             mount_options,
+            connection_info,
+            connection_info_receiver,
+            pending_retrieves: Default::default(),
+            in_flight: Default::default(),
+            request_slots,
+            interruptible: Default::default(),
+            observer: None,
+            #[cfg(feature = "request-span")]
+            request_spans: Default::default(),
+            read_buf_unsupported: Default::default(),
+            #[cfg(target_os = "linux")]
+            splicer: None,
         }
     }
 
+    /// create a [`SessionBuilder`] to set the runtime, mount options and observer in one place.
+    pub fn builder() -> SessionBuilder<FS> {
+        SessionBuilder::new()
+    }
+
+    /// set the [`SessionObserver`] which observes the requests handled by the filesystem.
+    pub fn observer(mut self, observer: Arc<dyn SessionObserver>) -> Self {
+        self.observer
+            .replace(Arc::new(RequestObserver::new(observer)));
+
+        self
+    }
+
     /// get a [`notify`].
     ///
     /// [`notify`]: Notify
     fn get_notify(&self) -> Notify {
-        Notify::new(self.response_sender.clone())
+        Notify::new(
+            self.response_sender.clone(),
+            self.notify_queue.clone(),
+            self.connection_info_receiver.clone(),
+            self.pending_retrieves.clone(),
+        )
+    }
+
+    /// spawn a filesystem request future, it is tracked as in flight until done.
+    ///
+    /// if [`MountOptions::request_timeout`] is set and the request can timeout, the future will
+    /// be cancelled when timeout and reply `EIO` to kernel.
+    fn spawn<F>(&self, span: Span, context: RequestContext, fut: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let guard = self.in_flight.enter();
+
+        // the handler span is entered inside the request span, which is closed after the reply
+        // is written
+        #[cfg(feature = "request-span")]
+        let (fut, span) = {
+            let request_span = self.request_spans.start(
+                context.opcode,
+                context.request,
+                context.nodeid,
+                context.need_reply(),
+            );
+
+            (fut.instrument(span), request_span)
+        };
+
+        // wait for a slot inside the interruptible future, so a waiting request can be interrupted
+        // and the session keeps reading the requests
+        let fut = match &self.request_slots {
+            Some(request_slots) if context.need_reply() => {
+                let request_slots = request_slots.clone();
+
+                Either::Left(async move {
+                    let _slot = request_slots.acquire_arc().await;
+
+                    fut.await
+                })
+            }
+
+            _ => Either::Right(fut),
+        };
+
+        // the interrupted request future is dropped, then reply EINTR
+        let fut = if context.need_reply() && !self.mount_options.forward_interrupt {
+            let (registration, interruptible_guard) =
+                self.interruptible.register(context.request.unique);
+            let resp_sender = self.response_sender.clone();
+
+            Either::Left(async move {
+                let _interruptible_guard = interruptible_guard;
+
+                if Abortable::new(fut, registration).await.is_err() {
+                    debug!(
+                        unique = context.request.unique,
+                        opcode = ?context.opcode,
+                        "fuse request is interrupted, reply EINTR"
+                    );
+
+                    reply_error_in_place(libc::EINTR.into(), context.request, resp_sender).await;
+                }
+            })
+        } else {
+            Either::Right(fut)
+        };
+
+        // the started request is dropped with the spawned task
+        let started = self
+            .observer
+            .as_ref()
+            .map(|observer| observer.start(context.opcode, context.request.unique));
+
+        let timeout = match self.mount_options.request_timeout {
+            Some(timeout) if context.can_timeout() => timeout,
+
+            _ => {
+                spawn(span, async move {
+                    let _guard = guard;
+
+                    fut.await;
+
+                    if let Some(started) = started {
+                        started.done();
+                    }
+                });
+
+                return;
+            }
+        };
+
+        let resp_sender = self.response_sender.clone();
+
+        spawn(span, async move {
+            let _guard = guard;
+
+            let done = {
+                let mut fut = pin!(fut.fuse());
+                let mut timer = pin!(sleep(timeout).fuse());
+
+                select! {
+                    _ = fut => true,
+                    _ = timer => false,
+                }
+            };
+
+            // the filesystem future is dropped here when timeout, before reply
+            if !done {
+                error!(
+                    unique = context.request.unique,
+                    opcode = ?context.opcode,
+                    inode = context.nodeid,
+                    ?timeout,
+                    "fuse request timeout, reply EIO"
+                );
+
+                reply_error_in_place(libc::EIO.into(), context.request, resp_sender).await;
+            }
+
+            if let Some(started) = started {
+                started.done();
+            }
+        })
+    }
+
+    /// get the negotiated fuse protocol minor version, if `FUSE_INIT` is not done yet, return
+    /// [`FUSE_KERNEL_MINOR_VERSION`].
+    fn minor_version(&self) -> u32 {
+        self.connection_info_receiver
+            .get()
+            .map(|connection_info| connection_info.protocol_version().1)
+            .unwrap_or(FUSE_KERNEL_MINOR_VERSION)
     }
 }
 
 #[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
 impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
+    /// open `/dev/fuse`, retry with exponential backoff when failed with `EPERM`.
+    #[cfg(not(target_os = "macos"))]
+    async fn open_fuse_connection(
+        &self,
+        unmount_notify: Arc<async_notify::Notify>,
+    ) -> IoResult<FuseConnection> {
+        let mut retries = self.mount_options.open_device_retries;
+        let mut delay = self
+            .mount_options
+            .open_device_retry_delay
+            .unwrap_or(DEFAULT_OPEN_DEVICE_RETRY_DELAY);
+
+        loop {
+            match FuseConnection::new(unmount_notify.clone()) {
+                Err(err) if retries > 0 && err.raw_os_error() == Some(libc::EPERM) => {
+                    warn!(retries, ?delay, "open /dev/fuse failed {}, retry", err);
+
+                    sleep(delay).await;
+
+                    retries -= 1;
+                    delay = delay.saturating_mul(2);
+                }
+
+                result => return result,
+            }
+        }
+    }
+
     async fn mount_empty_check(&self, mount_path: &Path) -> IoResult<()> {
         #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
         if !self.mount_options.nonempty
@@ -298,7 +1161,10 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     // On FreeBSD, no special interface is required to mount unprivileged.
     // If vfs.usermount=1 and the user has access to the mountpoint, it will
     // just work.
-    #[cfg(all(target_os = "freebsd", feature = "unprivileged"))]
+    #[cfg(all(
+        any(target_os = "freebsd", target_os = "macos"),
+        feature = "unprivileged"
+    ))]
     pub async fn mount_with_unprivileged<P: AsRef<Path>>(
         self,
         fs: FS,
@@ -318,11 +1184,11 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         self.mount_empty_check(mount_path).await?;
 
-        let notify = Arc::new(async_notify::Notify::new());
+        let destroy_notify = Arc::new(async_notify::Notify::new());
         let fuse_connection = FuseConnection::new_with_unprivileged(
             self.mount_options.clone(),
             mount_path,
-            notify.clone(),
+            destroy_notify.clone(),
         )
         .await?;
 
@@ -332,11 +1198,21 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         debug!("mount {:?} success", mount_path);
 
+        let connection_info = self.connection_info_receiver.clone();
+        let notify = self.get_notify();
+        let in_flight = self.in_flight.clone();
+
         Ok(MountHandle {
             inner: Some(MountHandleInner {
                 task: task::spawn(self.inner_mount()),
                 mount_path: mount_path.to_path_buf(),
-                destroy_notify: notify,
+                destroy_notify,
+                connection_info,
+                notify,
+                in_flight,
+                passed_fd: false,
+                #[cfg(feature = "tokio-runtime")]
+                task_returned: false,
                 unprivileged: true,
             }),
         })
@@ -349,8 +1225,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         self.mount_empty_check(mount_path).await?;
 
-        let notify = Arc::new(async_notify::Notify::new());
-        let fuse_connection = FuseConnection::new(notify.clone())?;
+        let destroy_notify = Arc::new(async_notify::Notify::new());
+        let fuse_connection = self.open_fuse_connection(destroy_notify.clone()).await?;
 
         let fd = fuse_connection.as_fd().as_raw_fd();
 
@@ -362,12 +1238,18 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Some("fuse")
         };
 
+        // the volume name is the subtype of the filesystem type
+        let fs_type = match &self.mount_options.volume_name {
+            None => "fuse".to_string(),
+            Some(volume_name) => format!("fuse.{volume_name}"),
+        };
+
         debug!("mount options {:?}", options);
 
         if let Err(err) = mount::mount(
             fs_name,
             mount_path,
-            Some("fuse"),
+            Some(fs_type.as_str()),
             self.mount_options.flags(),
             Some(options.as_os_str()),
         ) {
@@ -376,17 +1258,31 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             return Err(err.into());
         }
 
+        if self.mount_options.auto_unmount {
+            register_auto_unmount(mount_path);
+        }
+
         self.fuse_connection.replace(Arc::new(fuse_connection));
 
         self.filesystem.replace(Arc::new(fs));
 
         debug!("mount {:?} success", mount_path);
 
+        let connection_info = self.connection_info_receiver.clone();
+        let notify = self.get_notify();
+        let in_flight = self.in_flight.clone();
+
         Ok(MountHandle {
             inner: Some(MountHandleInner {
                 task: task::spawn(self.inner_mount()),
                 mount_path: mount_path.to_path_buf(),
-                destroy_notify: notify,
+                destroy_notify,
+                connection_info,
+                notify,
+                in_flight,
+                passed_fd: false,
+                #[cfg(feature = "tokio-runtime")]
+                task_returned: false,
                 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
                 unprivileged: false,
             }),
@@ -400,8 +1296,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         self.mount_empty_check(mount_path).await?;
 
-        let notify = Arc::new(async_notify::Notify::new());
-        let fuse_connection = FuseConnection::new(notify.clone())?;
+        let destroy_notify = Arc::new(async_notify::Notify::new());
+        let fuse_connection = self.open_fuse_connection(destroy_notify.clone()).await?;
 
         let fd = fuse_connection.as_fd().as_raw_fd();
 
@@ -425,11 +1321,121 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         debug!("mount {:?} success", mount_path);
 
+        let connection_info = self.connection_info_receiver.clone();
+        let notify = self.get_notify();
+        let in_flight = self.in_flight.clone();
+
+        Ok(MountHandle {
+            inner: Some(MountHandleInner {
+                task: task::spawn(self.inner_mount()),
+                mount_path: mount_path.to_path_buf(),
+                destroy_notify,
+                connection_info,
+                notify,
+                in_flight,
+                passed_fd: false,
+                #[cfg(feature = "tokio-runtime")]
+                task_returned: false,
+            }),
+        })
+    }
+
+    /// mount the filesystem by the `mount_macfuse` helper of macFUSE, it doesn't need root
+    /// permission.
+    ///
+    /// # Notes:
+    ///
+    /// macFUSE 4 or later should be installed. The helper mounts the filesystem in background
+    /// after the device is passed back, so a failed mount is not returned here, the session stops
+    /// with an error instead, such as the mount point doesn't exist or the `allow_other`
+    /// permission is not granted.
+    #[cfg(target_os = "macos")]
+    pub async fn mount<P: AsRef<Path>>(mut self, fs: FS, mount_path: P) -> IoResult<MountHandle> {
+        let mount_path = mount_path.as_ref();
+
+        self.mount_empty_check(mount_path).await?;
+
+        let destroy_notify = Arc::new(async_notify::Notify::new());
+        let fuse_connection = FuseConnection::new_with_macfuse(
+            self.mount_options.clone(),
+            mount_path,
+            destroy_notify.clone(),
+        )
+        .await?;
+
+        self.fuse_connection.replace(Arc::new(fuse_connection));
+
+        self.filesystem.replace(Arc::new(fs));
+
+        debug!("mount {:?} success", mount_path);
+
+        let connection_info = self.connection_info_receiver.clone();
+        let notify = self.get_notify();
+        let in_flight = self.in_flight.clone();
+
         Ok(MountHandle {
             inner: Some(MountHandleInner {
                 task: task::spawn(self.inner_mount()),
                 mount_path: mount_path.to_path_buf(),
-                destroy_notify: notify,
+                destroy_notify,
+                connection_info,
+                notify,
+                in_flight,
+                passed_fd: false,
+                #[cfg(feature = "tokio-runtime")]
+                task_returned: false,
+            }),
+        })
+    }
+
+    /// serve the filesystem on a `/dev/fuse` fd which is already opened and mounted by a more
+    /// privileged helper, such as systemd or a container runtime, the session doesn't open the
+    /// device or mount the filesystem itself.
+    ///
+    /// # Notes:
+    ///
+    /// the session takes the ownership of `fd` and closes it when the session is stopped, which
+    /// aborts the connection. [`MountHandle::unmount`] and dropping [`MountHandle`] only stop the
+    /// session, they don't unmount the mount point, let the helper which mounts it unmount it.
+    /// [`MountHandle::mountpoint`] returns `/dev/fd/N` like libfuse.
+    ///
+    /// the options used to open the device and mount, such as
+    /// [`open_device_retries`][MountOptions::open_device_retries], `fs_name`, `nonempty`,
+    /// `auto_unmount` and the mount flags are ignored, `allow_other` and `default_permissions`
+    /// should match the options used by the helper. The options negotiated in `FUSE_INIT`, such
+    /// as `max_write`, still apply.
+    ///
+    /// the fd is switched to blocking mode on Linux and non-blocking mode on FreeBSD, the other
+    /// copies of the fd share this mode.
+    pub async fn mount_with_fd(mut self, fs: FS, fd: OwnedFd) -> IoResult<MountHandle> {
+        let mount_path = PathBuf::from(format!("/dev/fd/{}", fd.as_raw_fd()));
+
+        let destroy_notify = Arc::new(async_notify::Notify::new());
+        let fuse_connection = FuseConnection::from_fd(fd, destroy_notify.clone())?;
+
+        self.fuse_connection.replace(Arc::new(fuse_connection));
+
+        self.filesystem.replace(Arc::new(fs));
+
+        debug!("serve {:?} success", mount_path);
+
+        let connection_info = self.connection_info_receiver.clone();
+        let notify = self.get_notify();
+        let in_flight = self.in_flight.clone();
+
+        Ok(MountHandle {
+            inner: Some(MountHandleInner {
+                task: task::spawn(self.inner_mount()),
+                mount_path,
+                destroy_notify,
+                connection_info,
+                notify,
+                in_flight,
+                passed_fd: true,
+                #[cfg(feature = "tokio-runtime")]
+                task_returned: false,
+                #[cfg(all(target_os = "linux", feature = "unprivileged"))]
+                unprivileged: false,
             }),
         })
     }
@@ -438,44 +1444,156 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let fuse_write_connection = self.fuse_connection.as_ref().unwrap().clone();
 
         let receiver = self.response_receiver.take().unwrap();
+        let response_sender = self.response_sender.clone();
+        let notify_queue = self.notify_queue.clone();
+        let reply_notify_queue = self.notify_queue.clone();
+        let pending_retrieves = self.pending_retrieves.clone();
+        let reply_pending_retrieves = self.pending_retrieves.clone();
+        let observer = self.observer.clone();
+        let reply_writev = self.mount_options.reply_writev.unwrap_or(true);
+        #[cfg(feature = "request-span")]
+        let request_spans = self.request_spans.clone();
 
         let dispatch_task = self.dispatch().fuse();
         let mut dispatch_task = pin!(dispatch_task);
 
         #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
-        let reply_task =
-            task::spawn(async move { Self::reply_fuse(fuse_write_connection, receiver).await })
-                .fuse();
+        let reply_task = task::spawn(async move {
+            Self::reply_fuse(
+                fuse_write_connection,
+                receiver,
+                reply_notify_queue,
+                reply_pending_retrieves,
+                observer,
+                reply_writev,
+                #[cfg(feature = "request-span")]
+                request_spans,
+            )
+            .await
+        })
+        .fuse();
         #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
-        let reply_task = task::spawn(Self::reply_fuse(fuse_write_connection, receiver))
-            .map(Result::unwrap)
-            .fuse();
+        let reply_task = task::spawn(Self::reply_fuse(
+            fuse_write_connection,
+            receiver,
+            reply_notify_queue,
+            reply_pending_retrieves,
+            observer,
+            reply_writev,
+            #[cfg(feature = "request-span")]
+            request_spans,
+        ))
+        .map(Result::unwrap)
+        .fuse();
 
         let mut reply_task = pin!(reply_task);
 
-        select! {
-            reply_result = reply_task => {
-                reply_result?;
-            }
+        let result = select! {
+            reply_result = reply_task => reply_result,
 
             dispatch_result = dispatch_task => {
-                dispatch_result?;
+                // close the channel so the notify will know the filesystem is unmounted
+                response_sender.close_channel();
+                if let Some(notify_queue) = &notify_queue {
+                    notify_queue.close();
+                }
+
+                dispatch_result
             }
-        }
+        };
 
-        Ok(())
+        // kernel won't reply the retrieve notify anymore
+        pending_retrieves.clear();
+
+        result
     }
 
     async fn reply_fuse(
         fuse_connection: Arc<FuseConnection>,
         mut response_receiver: UnboundedReceiver<FuseData>,
+        notify_queue: Option<Arc<NotifyQueue>>,
+        pending_retrieves: Arc<PendingRetrieves>,
+        observer: Option<Arc<RequestObserver>>,
+        reply_writev: bool,
+        #[cfg(feature = "request-span")] request_spans: Arc<RequestSpans>,
     ) -> IoResult<()> {
-        while let Some(response) = response_receiver.next().await {
+        loop {
+            let response = match &notify_queue {
+                None => response_receiver.next().await,
+
+                // the queued notifications are written along with the replies
+                Some(notify_queue) => {
+                    let pop = pin!(notify_queue.pop());
+
+                    match future::select(response_receiver.next(), pop).await {
+                        Either::Left((response, _)) => response,
+                        Either::Right((notify, _)) => Some(notify),
+                    }
+                }
+            };
+
+            let Some(response) = response else {
+                break;
+            };
+
             let (data, extend_data) = match response {
                 Either::Left(data) => (data, None),
+
+                Either::Right((mut data, ExtendData::Bytes(extend_data))) if !reply_writev => {
+                    data.extend_from_slice(&extend_data);
+
+                    (data, None)
+                }
+
                 Either::Right((data, extend_data)) => (data, Some(extend_data)),
             };
-            if let Err(err) = fuse_connection.write_vectored(data, extend_data).await.1 {
+
+            if let Some(observer) = &observer {
+                observer.reply(&data);
+            }
+
+            #[cfg(feature = "wire-debug")]
+            wire_debug::log_reply(
+                &data,
+                match &extend_data {
+                    Some(ExtendData::Bytes(extend_data)) => Some(&extend_data[..]),
+                    _ => None,
+                },
+            );
+
+            let (data, result) = match extend_data {
+                None => {
+                    let ((data, _), result) =
+                        fuse_connection.write_vectored::<_, Bytes>(data, None).await;
+
+                    (data, result)
+                }
+
+                Some(ExtendData::Bytes(extend_data)) => {
+                    let ((data, _), result) = fuse_connection
+                        .write_vectored(data, Some(extend_data))
+                        .await;
+
+                    (data, result)
+                }
+
+                #[cfg(target_os = "linux")]
+                Some(ExtendData::Spliced(spliced)) => {
+                    (data, fuse_connection.write_spliced(spliced).await)
+                }
+            };
+
+            #[cfg(feature = "request-span")]
+            request_spans.reply(&data);
+
+            if let Err(err) = result {
+                // kernel rejects the retrieve notify, it won't reply
+                if let Some(notify_unique) = get_notify_retrieve_unique(&data) {
+                    warn!(notify_unique, "notify retrieve failed {}", err);
+
+                    pending_retrieves.take(notify_unique);
+                }
+
                 if err.kind() == ErrorKind::NotFound {
                     warn!(
                         "may reply interrupted fuse request, ignore this error {}",
@@ -532,10 +1650,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
                 reply_error_in_place(libc::ENOSYS.into(), request, &self.response_sender).await;
 
-                return Err(IoError::new(
-                    ErrorKind::Other,
-                    format!("receive unknown opcode {}", err.0),
-                ));
+                return Err(IoError::other(format!("receive unknown opcode {}", err.0)));
             }
 
             Ok(opcode) => opcode,
@@ -546,10 +1661,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         if opcode != fuse_opcode::FUSE_INIT {
             error!(?opcode, "received unexpected opcode");
 
-            return Err(IoError::new(
-                ErrorKind::Other,
-                format!("unexpected opcode {opcode:?}"),
-            ));
+            return Err(IoError::other(format!("unexpected opcode {opcode:?}")));
         }
 
         let data_size = in_header.len as usize - FUSE_IN_HEADER_SIZE;
@@ -582,8 +1694,9 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let n = match res {
             Err(err) => {
                 if let Some(errno) = err.raw_os_error() {
-                    if errno == libc::ENODEV {
-                        debug!("read from /dev/fuse failed with ENODEV");
+                    // the aborted connection returns ECONNABORTED with FUSE_ABORT_ERROR
+                    if errno == libc::ENODEV || errno == libc::ECONNABORTED {
+                        debug!("read from /dev/fuse failed with {}", err);
 
                         return ReadResult::Destroy;
                     }
@@ -603,38 +1716,35 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         debug!(n, "read fuse request done");
 
-        if n < FUSE_IN_HEADER_SIZE {
-            error!(
-                n,
-                FUSE_IN_HEADER_SIZE, "read_vectored n is less then FUSE_IN_HEADER_SIZE"
-            );
-
-            return ReadResult::Request {
-                in_header: Err(IoError::new(
-                    ErrorKind::Other,
-                    "read_vectored n is less then FUSE_IN_HEADER_SIZE",
-                )),
-                header_buffer,
-                data_buffer,
-            };
-        }
+        #[cfg(feature = "wire-debug")]
+        wire_debug::log_request(&header_buffer, &data_buffer, n);
 
-        let in_header = match get_bincode_config().deserialize::<fuse_in_header>(&header_buffer) {
+        let in_header = match decode_in_header(&header_buffer, n) {
             Err(err) => {
-                error!("deserialize fuse_in_header failed {}", err);
+                // reply the request whose unique is known, otherwise the caller hangs on it
+                match malformed_request_unique(&header_buffer, n) {
+                    None => error!(n, "receive malformed fuse request, drop it: {}", err),
+
+                    Some(unique) => {
+                        error!(n, unique, "malformed fuse request, reply EIO: {}", err);
+
+                        let request = Request {
+                            unique,
+                            ..Default::default()
+                        };
+                        reply_error_in_place(libc::EIO.into(), request, &self.response_sender)
+                            .await;
+                    }
+                }
 
-                return ReadResult::Request {
-                    in_header: Err(IoError::new(ErrorKind::Other, err)),
-                    header_buffer,
-                    data_buffer,
-                };
+                Err(err)
             }
 
-            Ok(in_header) => in_header,
+            Ok(in_header) => Ok(in_header),
         };
 
         ReadResult::Request {
-            in_header: Ok(in_header),
+            in_header,
             header_buffer,
             data_buffer,
         }
@@ -647,8 +1757,18 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let max_write = self.init_filesystem(&fs, &fuse_connection).await?.get() as usize;
         let buffer_size = (max_write + FUSE_WRITE_IN_SIZE).max(FUSE_MIN_READ_BUFFER_SIZE);
 
+        let buffer_pool = self
+            .mount_options
+            .max_buffer_pool_bytes
+            .map(|max_buffer_pool_bytes| {
+                Arc::new(BufferPool::new(buffer_size, max_buffer_pool_bytes))
+            });
+
         let mut header_buffer = vec![0; FUSE_IN_HEADER_SIZE];
-        let mut data_buffer = vec![0; buffer_size];
+        let mut data_buffer = match &buffer_pool {
+            None => vec![0; buffer_size],
+            Some(buffer_pool) => buffer_pool.acquire().await,
+        };
 
         loop {
             let in_header = match self
@@ -656,13 +1776,9 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 .await
             {
                 ReadResult::Destroy => {
-                    fs.destroy(Request {
-                        unique: 0,
-                        uid: 0,
-                        gid: 0,
-                        pid: 0,
-                    })
-                    .await;
+                    self.in_flight.drain().await;
+
+                    fs.destroy(Request::default()).await;
 
                     return Ok(());
                 }
@@ -678,260 +1794,478 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     match in_header {
                         Err(_) => continue,
 
-                        Ok(in_header) => in_header,
-                    }
-                }
+                        Ok(in_header) => in_header,
+                    }
+                }
+            };
+
+            let request = Request::from(&in_header);
+
+            let data_size = in_header.len as usize - FUSE_IN_HEADER_SIZE;
+            let data_ref = &data_buffer[..data_size];
+
+            let opcode = match fuse_opcode::try_from(in_header.opcode) {
+                Err(err) => {
+                    debug!("receive unknown opcode {}", err.0);
+
+                    self.handle_raw_request(request, in_header, data_ref, &fs)
+                        .await;
+
+                    continue;
+                }
+
+                Ok(opcode) => opcode,
+            };
+
+            debug!("receive opcode {}", opcode);
+
+            match opcode {
+                fuse_opcode::FUSE_INIT => {
+                    warn!("duplicated fuse init request");
+
+                    self.handle_init(request, data_ref, &fuse_connection, &fs)
+                        .await?;
+                }
+
+                fuse_opcode::FUSE_DESTROY => {
+                    debug!("receive fuse destroy");
+
+                    fs.destroy(request).await;
+
+                    debug!("fuse destroyed");
+
+                    return Ok(());
+                }
+
+                // hand over the read buffer to a large write instead of copying the data, a new
+                // buffer is allocated for the next request
+                fuse_opcode::FUSE_WRITE if data_size >= buffer_size / 2 => {
+                    match &buffer_pool {
+                        None => {
+                            let data = mem::replace(&mut data_buffer, vec![0; buffer_size]);
+                            let data = Bytes::from(data).slice(..data_size);
+
+                            self.handle_write(request, in_header, data, None, &fs).await;
+                        }
+
+                        // wait for a free buffer after the write is spawned, so the write can
+                        // return its buffer even if it is the only one
+                        Some(buffer_pool) => {
+                            let (data, pooled_buffer) =
+                                buffer_pool.hand_over(mem::take(&mut data_buffer));
+                            let data = data.slice(..data_size);
+
+                            self.handle_write(request, in_header, data, Some(pooled_buffer), &fs)
+                                .await;
+
+                            data_buffer = buffer_pool.acquire().await;
+                        }
+                    }
+                }
+
+                opcode => {
+                    self.handle_request(request, in_header, opcode, data_ref, &fs)
+                        .await;
+                }
+            }
+        }
+    }
+
+    /// get the [`ConnectionInfo`] if `FUSE_INIT` is done.
+    pub(crate) fn connection_info(&self) -> Option<ConnectionInfo> {
+        self.connection_info_receiver.get()
+    }
+
+    /// wait until the spawned filesystem requests are done, used by
+    /// [`TestSession`][crate::test::TestSession] for the requests without reply.
+    pub(crate) async fn wait_in_flight(&self) {
+        self.in_flight.wait_idle().await
+    }
+
+    /// take the response receiver, so the replies can be read without kernel.
+    pub(crate) fn take_response_receiver(&mut self) -> UnboundedReceiver<FuseData> {
+        self.response_receiver
+            .take()
+            .expect("response receiver is taken")
+    }
+
+    /// observe the reply read from the response receiver taken by
+    /// [`take_response_receiver`][Session::take_response_receiver].
+    pub(crate) fn observe_reply(&self, data: &[u8]) {
+        if let Some(observer) = &self.observer {
+            observer.reply(data);
+        }
+
+        #[cfg(feature = "request-span")]
+        self.request_spans.reply(data);
+    }
+
+    /// handle a fuse request without kernel, the reply will be sent by the response sender, used
+    /// by [`TestSession`][crate::test::TestSession].
+    pub(crate) async fn handle_request_in_process(
+        &mut self,
+        in_header: fuse_in_header,
+        data: &[u8],
+        fs: &Arc<FS>,
+    ) {
+        let request = Request::from(&in_header);
+
+        let opcode = match fuse_opcode::try_from(in_header.opcode) {
+            Err(err) => {
+                debug!("receive unknown opcode {}", err.0);
+
+                self.handle_raw_request(request, in_header, data, fs).await;
+
+                return;
+            }
+
+            Ok(opcode) => opcode,
+        };
+
+        match opcode {
+            fuse_opcode::FUSE_INIT => {
+                let (data, result) = self.init_reply(request, data, fs).await;
+
+                if let Ok((_, connection_info)) = result {
+                    self.connection_info.set(connection_info);
+                }
+
+                let _ = self.response_sender.send(Either::Left(data)).await;
+            }
+
+            fuse_opcode::FUSE_DESTROY => {
+                fs.destroy(request).await;
+
+                self.response_sender.close_channel();
+            }
+
+            opcode => {
+                self.handle_request(request, in_header, opcode, data, fs)
+                    .await
+            }
+        }
+    }
+
+    /// handle the fuse request whose opcode is unknown by fuse3 with
+    /// [`Filesystem::raw_request`], it is tracked as in flight, but not observed, and can't be
+    /// interrupted or timed out, because they need a known opcode.
+    async fn handle_raw_request(
+        &mut self,
+        mut request: Request,
+        in_header: fuse_in_header,
+        data: &[u8],
+        fs: &Arc<FS>,
+    ) {
+        let (data, extensions) = match RequestExtensions::split(data, in_header.total_extlen) {
+            Err(err) => {
+                reply_error_in_place(err, request, &self.response_sender).await;
+
+                return;
+            }
+
+            Ok(split) => split,
+        };
+        request.supplementary_group = extensions.supplementary_group;
+
+        let data = data.to_vec();
+        let guard = self.in_flight.enter();
+        let request_slots = self.request_slots.clone();
+        let mut resp_sender = self.response_sender.clone();
+        let fs = fs.clone();
+
+        spawn(debug_span!("fuse_raw_request"), async move {
+            let _guard = guard;
+            let _slot = match &request_slots {
+                None => None,
+                Some(request_slots) => Some(request_slots.acquire_arc().await),
             };
 
-            let request = Request::from(&in_header);
+            debug!(
+                "raw request unique {} opcode {} inode {}",
+                request.unique, in_header.opcode, in_header.nodeid
+            );
 
-            let opcode = match fuse_opcode::try_from(in_header.opcode) {
+            let data = match fs
+                .raw_request(request, in_header.opcode, in_header.nodeid, &data)
+                .await
+            {
                 Err(err) => {
-                    debug!("receive unknown opcode {}", err.0);
-
-                    reply_error_in_place(libc::ENOSYS.into(), request, &self.response_sender).await;
+                    reply_error_in_place(err, request, resp_sender).await;
 
-                    continue;
+                    return;
                 }
 
-                Ok(opcode) => opcode,
-            };
+                Ok(ReplyRaw::NoReply) => return,
 
-            debug!("receive opcode {}", opcode);
+                Ok(ReplyRaw::Data(data)) => data,
+            };
 
-            let data_size = in_header.len as usize - FUSE_IN_HEADER_SIZE;
-            let data_ref = &data_buffer[..data_size];
+            let out_header = fuse_out_header {
+                len: (FUSE_OUT_HEADER_SIZE + data.len()) as u32,
+                error: 0,
+                unique: request.unique,
+            };
 
-            match opcode {
-                fuse_opcode::FUSE_INIT => {
-                    warn!("duplicated fuse init request");
+            let header = get_bincode_config()
+                .serialize(&out_header)
+                .expect("won't happened");
 
-                    self.handle_init(request, data_ref, &fuse_connection, &fs)
-                        .await?;
-                }
+            let _ = resp_sender.send(Either::Right((header, data.into()))).await;
+        });
+    }
 
-                fuse_opcode::FUSE_DESTROY => {
-                    debug!("receive fuse destroy");
+    /// handle the fuse request except `FUSE_INIT` and `FUSE_DESTROY`, the reply will be sent by
+    /// the response sender.
+    async fn handle_request(
+        &mut self,
+        mut request: Request,
+        in_header: fuse_in_header,
+        opcode: fuse_opcode,
+        data_ref: &[u8],
+        fs: &Arc<FS>,
+    ) {
+        if let Some(op) = OptionalOps::from_opcode(opcode) {
+            if !self.mount_options.is_supported(op) {
+                debug!("{:?} is not supported, reply ENOSYS", opcode);
 
-                    fs.destroy(request).await;
+                reply_error_in_place(libc::ENOSYS.into(), request, &self.response_sender).await;
 
-                    debug!("fuse destroyed");
+                return;
+            }
+        }
 
-                    return Ok(());
-                }
+        let (data_ref, extensions) =
+            match RequestExtensions::split(data_ref, in_header.total_extlen) {
+                Err(err) => {
+                    reply_error_in_place(err, request, &self.response_sender).await;
 
-                fuse_opcode::FUSE_LOOKUP => {
-                    self.handle_lookup(request, in_header, data_ref, &fs).await;
+                    return;
                 }
 
-                fuse_opcode::FUSE_FORGET => {
-                    self.handle_forget(request, in_header, data_ref, &fs).await;
-                }
+                Ok(split) => split,
+            };
+        request.supplementary_group = extensions.supplementary_group;
+        let security_context = extensions.security_context;
 
-                fuse_opcode::FUSE_GETATTR => {
-                    self.handle_getattr(request, in_header, data_ref, &fs).await;
-                }
+        match opcode {
+            fuse_opcode::FUSE_INIT | fuse_opcode::FUSE_DESTROY => {
+                unreachable!("{:?} should be handled by dispatch", opcode)
+            }
 
-                fuse_opcode::FUSE_SETATTR => {
-                    self.handle_setattr(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_LOOKUP => {
+                self.handle_lookup(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_READLINK => {
-                    self.handle_readlink(request, in_header, &fs).await;
-                }
+            fuse_opcode::FUSE_FORGET => {
+                self.handle_forget(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_SYMLINK => {
-                    self.handle_symlink(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_GETATTR => {
+                self.handle_getattr(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_MKNOD => {
-                    self.handle_mknod(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_SETATTR => {
+                self.handle_setattr(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_MKDIR => {
-                    self.handle_mkdir(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_READLINK => {
+                self.handle_readlink(request, in_header, fs).await;
+            }
 
-                fuse_opcode::FUSE_UNLINK => {
-                    self.handle_unlink(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_SYMLINK => {
+                self.handle_symlink(request, in_header, data_ref, security_context, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_RMDIR => {
-                    self.handle_rmdir(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_MKNOD => {
+                self.handle_mknod(request, in_header, data_ref, security_context, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_RENAME => {
-                    self.handle_rename(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_MKDIR => {
+                self.handle_mkdir(request, in_header, data_ref, security_context, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_LINK => {
-                    self.handle_link(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_UNLINK => {
+                self.handle_unlink(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_OPEN => {
-                    self.handle_open(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_RMDIR => {
+                self.handle_rmdir(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_READ => {
-                    self.handle_read(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_RENAME => {
+                self.handle_rename(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_WRITE => {
-                    self.handle_write(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_LINK => {
+                self.handle_link(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_STATFS => {
-                    self.handle_statfs(request, in_header, &fs).await;
-                }
+            fuse_opcode::FUSE_OPEN => {
+                self.handle_open(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_RELEASE => {
-                    self.handle_release(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_READ => {
+                self.handle_read(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_FSYNC => {
-                    self.handle_fsync(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_WRITE => {
+                self.handle_write(
+                    request,
+                    in_header,
+                    Bytes::copy_from_slice(data_ref),
+                    None,
+                    fs,
+                )
+                .await;
+            }
 
-                fuse_opcode::FUSE_SETXATTR => {
-                    self.handle_setxattr(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_STATFS => {
+                self.handle_statfs(request, in_header, fs).await;
+            }
 
-                fuse_opcode::FUSE_GETXATTR => {
-                    self.handle_getxattr(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_RELEASE => {
+                self.handle_release(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_LISTXATTR => {
-                    self.handle_listxattr(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_FSYNC => {
+                self.handle_fsync(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_REMOVEXATTR => {
-                    self.handle_removexattr(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_SETXATTR => {
+                self.handle_setxattr(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_FLUSH => {
-                    self.handle_flush(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_GETXATTR => {
+                self.handle_getxattr(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_OPENDIR => {
-                    self.handle_opendir(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_LISTXATTR => {
+                self.handle_listxattr(request, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_READDIR => {
-                    self.handle_readdir(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_REMOVEXATTR => {
+                self.handle_removexattr(request, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_RELEASEDIR => {
-                    self.handle_releasedir(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_FLUSH => {
+                self.handle_flush(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_FSYNCDIR => {
-                    self.handle_fsyncdir(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_OPENDIR => {
+                self.handle_opendir(request, in_header, data_ref, fs).await;
+            }
 
-                #[cfg(feature = "file-lock")]
-                fuse_opcode::FUSE_GETLK => {
-                    self.handle_getlk(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_READDIR => {
+                self.handle_readdir(request, in_header, data_ref, fs).await;
+            }
 
-                #[cfg(feature = "file-lock")]
-                fuse_opcode::FUSE_SETLK | fuse_opcode::FUSE_SETLKW => {
-                    self.handle_setlk(
-                        request,
-                        in_header,
-                        data_ref,
-                        opcode == fuse_opcode::FUSE_SETLKW,
-                        &fs,
-                    )
+            fuse_opcode::FUSE_RELEASEDIR => {
+                self.handle_releasedir(request, in_header, data_ref, fs)
                     .await;
-                }
+            }
 
-                fuse_opcode::FUSE_ACCESS => {
-                    self.handle_access(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_FSYNCDIR => {
+                self.handle_fsyncdir(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_CREATE => {
-                    self.handle_create(request, in_header, data_ref, &fs).await;
-                }
+            #[cfg(feature = "file-lock")]
+            fuse_opcode::FUSE_GETLK => {
+                self.handle_getlk(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_INTERRUPT => {
-                    self.handle_interrupt(request, data_ref, &fs).await;
-                }
+            #[cfg(feature = "file-lock")]
+            fuse_opcode::FUSE_SETLK | fuse_opcode::FUSE_SETLKW => {
+                self.handle_setlk(
+                    request,
+                    in_header,
+                    data_ref,
+                    opcode == fuse_opcode::FUSE_SETLKW,
+                    fs,
+                )
+                .await;
+            }
 
-                fuse_opcode::FUSE_BMAP => {
-                    self.handle_bmap(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_ACCESS => {
+                self.handle_access(request, in_header, data_ref, fs).await;
+            }
 
-                /*fuse_opcode::FUSE_IOCTL => {
-                    let mut resp_sender = self.response_sender.clone();
+            fuse_opcode::FUSE_CREATE => {
+                self.handle_create(request, in_header, data_ref, security_context, fs)
+                    .await;
+            }
 
-                    let ioctl_in = match get_bincode_config().deserialize::<fuse_ioctl_in>(data) {
-                        Err(err) => {
-                            error!("deserialize fuse_ioctl_in failed {}", err);
+            fuse_opcode::FUSE_INTERRUPT => {
+                self.handle_interrupt(request, data_ref, fs).await;
+            }
 
-                             reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+            fuse_opcode::FUSE_BMAP => {
+                self.handle_bmap(request, in_header, data_ref, fs).await;
+            }
 
-                            continue;
-                        }
+            fuse_opcode::FUSE_IOCTL => {
+                self.handle_ioctl(request, in_header, data_ref, fs).await;
+            }
 
-                        Ok(ioctl_in) => ioctl_in,
-                    };
+            fuse_opcode::FUSE_POLL => {
+                self.handle_poll(request, in_header, data_ref, fs).await;
+            }
 
-                    let ioctl_data = (&data[FUSE_IOCTL_IN_SIZE..]).to_vec();
+            fuse_opcode::FUSE_NOTIFY_REPLY => {
+                self.handle_notify_reply(request, in_header, data_ref, fs)
+                    .await;
+            }
 
-                    let fs = fs.clone();
-                }*/
-                fuse_opcode::FUSE_POLL => {
-                    self.handle_poll(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_BATCH_FORGET => {
+                self.handle_batch_forget(request, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_NOTIFY_REPLY => {
-                    self.handle_notify_reply(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_FALLOCATE => {
+                self.handle_fallocate(request, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_BATCH_FORGET => {
-                    self.handle_batch_forget(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_READDIRPLUS => {
+                self.handle_readdirplus(request, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_FALLOCATE => {
-                    self.handle_fallocate(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_RENAME2 => {
+                self.handle_rename2(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_READDIRPLUS => {
-                    self.handle_readdirplus(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_LSEEK => {
+                self.handle_lseek(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_RENAME2 => {
-                    self.handle_rename2(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_COPY_FILE_RANGE => {
+                self.handle_copy_file_range(request, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_LSEEK => {
-                    self.handle_lseek(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_SYNCFS => {
+                self.handle_syncfs(request, in_header, data_ref, fs).await;
+            }
 
-                fuse_opcode::FUSE_COPY_FILE_RANGE => {
-                    self.handle_copy_file_range(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            #[cfg(target_os = "macos")]
+            fuse_opcode::FUSE_SETVOLNAME => {}
 
-                #[cfg(target_os = "macos")]
-                fuse_opcode::FUSE_SETVOLNAME => {}
+            #[cfg(target_os = "macos")]
+            fuse_opcode::FUSE_GETXTIMES => {
+                self.handle_getxtimes(request, in_header, fs).await;
+            }
 
-                #[cfg(target_os = "macos")]
-                fuse_opcode::FUSE_GETXTIMES => {}
+            #[cfg(target_os = "macos")]
+            fuse_opcode::FUSE_EXCHANGE => {}
 
-                #[cfg(target_os = "macos")]
-                fuse_opcode::FUSE_EXCHANGE => {} // fuse_opcode::CUSE_INIT => {}
-            }
+            fuse_opcode::FUSE_CANONICAL_PATH => {
+                self.handle_canonical_path(request, in_header, fs).await;
+            } // fuse_opcode::CUSE_INIT => {}
         }
     }
 
@@ -943,6 +2277,56 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         fuse_connection: &FuseConnection,
         fs: &FS,
     ) -> IoResult<NonZeroU32> {
+        let (data, result) = self.init_reply(request, data, fs).await;
+
+        #[cfg(feature = "wire-debug")]
+        wire_debug::log_reply(&data, None);
+
+        if let Err(err) = fuse_connection
+            .write_vectored::<_, Vec<u8>>(data, None)
+            .await
+            .1
+        {
+            error!("write init out data to /dev/fuse failed {}", err);
+
+            result?;
+
+            return Err(err);
+        }
+
+        let (max_write, connection_info) = result?;
+
+        #[cfg(target_os = "linux")]
+        if connection_info
+            .capabilities
+            .contains(InitFlags::SPLICE_WRITE)
+        {
+            debug!("enable splice write");
+
+            // kernel won't send a read request larger than FUSE_MAX_REQUEST_SIZE
+            self.splicer = Some(Arc::new(Splicer::new(
+                FUSE_OUT_HEADER_SIZE + FUSE_MAX_REQUEST_SIZE as usize,
+                connection_info
+                    .capabilities
+                    .contains(InitFlags::SPLICE_MOVE),
+            )));
+        }
+
+        debug!("fuse init done");
+
+        self.connection_info.set(connection_info);
+
+        Ok(max_write)
+    }
+
+    /// negotiate with the `FUSE_INIT` request, return the reply data which should be written to
+    /// kernel, and the max write size with the [`ConnectionInfo`] if init success.
+    async fn init_reply(
+        &mut self,
+        request: Request,
+        data: &[u8],
+        fs: &FS,
+    ) -> (Vec<u8>, IoResult<(NonZeroU32, ConnectionInfo)>) {
         let init_in = match get_bincode_config().deserialize::<fuse_init_in>(data) {
             Err(err) => {
                 error!(
@@ -960,15 +2344,10 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     .serialize(&init_out_header)
                     .expect("won't happened");
 
-                if let Err(err) = fuse_connection
-                    .write_vectored::<_, Vec<u8>>(init_out_header_data, None)
-                    .await
-                    .1
-                {
-                    error!("write error init out data to /dev/fuse failed {}", err);
-                }
-
-                return Err(IoError::from_raw_os_error(libc::EINVAL));
+                return (
+                    init_out_header_data,
+                    Err(IoError::from_raw_os_error(libc::EINVAL)),
+                );
             }
 
             Ok(init_in) => init_in,
@@ -978,7 +2357,6 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         let mut reply_flags = 0;
 
-        // TODO: most of these FUSE_* flags should be controllable by the consuming crate.
         if init_in.flags & FUSE_ASYNC_READ > 0 {
             debug!("enable FUSE_ASYNC_READ");
 
@@ -986,7 +2364,9 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         }
 
         #[cfg(feature = "file-lock")]
-        if init_in.flags & FUSE_POSIX_LOCKS > 0 {
+        if init_in.flags & FUSE_POSIX_LOCKS > 0
+            && self.mount_options.is_supported(OptionalOps::LOCK)
+        {
             debug!("enable FUSE_POSIX_LOCKS");
 
             reply_flags |= FUSE_POSIX_LOCKS;
@@ -1023,30 +2403,35 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         }
 
         #[cfg(not(target_os = "macos"))]
-        if init_in.flags & FUSE_SPLICE_WRITE > 0 {
+        if init_in.flags & FUSE_SPLICE_WRITE > 0 && self.mount_options.use_splice {
             debug!("enable FUSE_SPLICE_WRITE");
 
             reply_flags |= FUSE_SPLICE_WRITE;
         }
 
         #[cfg(not(target_os = "macos"))]
-        if init_in.flags & FUSE_SPLICE_MOVE > 0 {
+        if init_in.flags & FUSE_SPLICE_MOVE > 0 && self.mount_options.use_splice {
             debug!("enable FUSE_SPLICE_MOVE");
 
             reply_flags |= FUSE_SPLICE_MOVE;
         }
 
         #[cfg(not(target_os = "macos"))]
-        if init_in.flags & FUSE_SPLICE_READ > 0 {
+        if init_in.flags & FUSE_SPLICE_READ > 0 && self.mount_options.use_splice {
             debug!("enable FUSE_SPLICE_READ");
 
             reply_flags |= FUSE_SPLICE_READ;
         }
 
-        // posix lock used, maybe we don't need bsd lock
-        /*if init_in.flags&FUSE_FLOCK_LOCKS>0 {
+        #[cfg(feature = "file-lock")]
+        if init_in.flags & FUSE_FLOCK_LOCKS > 0
+            && self.mount_options.flock_locks
+            && self.mount_options.is_supported(OptionalOps::LOCK)
+        {
+            debug!("enable FUSE_FLOCK_LOCKS");
+
             reply_flags |= FUSE_FLOCK_LOCKS;
-        }*/
+        }
 
         /*if init_in.flags & FUSE_HAS_IOCTL_DIR > 0 {
             debug!("enable FUSE_HAS_IOCTL_DIR");
@@ -1060,13 +2445,20 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             reply_flags |= FUSE_AUTO_INVAL_DATA;
         }
 
-        if init_in.flags & FUSE_DO_READDIRPLUS > 0 || self.mount_options.force_readdir_plus {
+        let readdir_plus = self.mount_options.is_supported(OptionalOps::READDIRPLUS);
+
+        if readdir_plus
+            && (init_in.flags & FUSE_DO_READDIRPLUS > 0 || self.mount_options.force_readdir_plus)
+        {
             debug!("enable FUSE_DO_READDIRPLUS");
 
             reply_flags |= FUSE_DO_READDIRPLUS;
         }
 
-        if init_in.flags & FUSE_READDIRPLUS_AUTO > 0 && !self.mount_options.force_readdir_plus {
+        if readdir_plus
+            && init_in.flags & FUSE_READDIRPLUS_AUTO > 0
+            && !self.mount_options.force_readdir_plus
+        {
             debug!("enable FUSE_READDIRPLUS_AUTO");
 
             reply_flags |= FUSE_READDIRPLUS_AUTO;
@@ -1088,6 +2480,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             debug!("enable FUSE_NO_OPEN_SUPPORT");
 
             reply_flags |= FUSE_NO_OPEN_SUPPORT;
+        } else if self.mount_options.no_open_support {
+            warn!("kernel doesn't support FUSE_NO_OPEN_SUPPORT, open replied ENOSYS will fail");
         }
 
         if init_in.flags & FUSE_PARALLEL_DIROPS > 0 {
@@ -1102,7 +2496,12 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             reply_flags |= FUSE_HANDLE_KILLPRIV;
         }
 
-        if init_in.flags & FUSE_POSIX_ACL > 0 && self.mount_options.default_permissions {
+        let posix_acl = self
+            .mount_options
+            .posix_acl
+            .unwrap_or(self.mount_options.default_permissions);
+
+        if init_in.flags & FUSE_POSIX_ACL > 0 && posix_acl {
             debug!("enable FUSE_POSIX_ACL");
 
             reply_flags |= FUSE_POSIX_ACL;
@@ -1126,6 +2525,52 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             reply_flags |= FUSE_NO_OPENDIR_SUPPORT;
         }
 
+        if let Some(init_flags) = self.mount_options.init_flags {
+            debug!("override the init flags with {:?}", init_flags);
+
+            reply_flags = init_flags.bits() & init_in.flags;
+        }
+
+        #[cfg(not(target_os = "macos"))]
+        let (flags2, reply_flags2) = {
+            // the flags2 follows the fuse_init_in when FUSE_INIT_EXT is set
+            let flags2 = match data.get(FUSE_INIT_IN_SIZE..) {
+                Some(ext_data) if init_in.flags & FUSE_INIT_EXT > 0 => get_bincode_config()
+                    .deserialize::<fuse_init_in_ext>(ext_data)
+                    .map_or(0, |init_in_ext| init_in_ext.flags2),
+
+                _ => 0,
+            };
+            let mut reply_flags2 = 0;
+
+            if init_in.flags & FUSE_INIT_EXT > 0 {
+                debug!("enable FUSE_INIT_EXT");
+
+                reply_flags |= FUSE_INIT_EXT;
+            }
+
+            // the older kernel appends the security context as an extra argument, which is not
+            // supported
+            if flags2 & FUSE_SECURITY_CTX > 0
+                && init_in.minor >= FUSE_EXTENSIONS_MINOR_VERSION
+                && self.mount_options.security_context
+            {
+                debug!("enable FUSE_SECURITY_CTX");
+
+                reply_flags2 |= FUSE_SECURITY_CTX;
+            }
+
+            if flags2 & FUSE_CREATE_SUPP_GROUP > 0 && self.mount_options.create_supp_group {
+                debug!("enable FUSE_CREATE_SUPP_GROUP");
+
+                reply_flags2 |= FUSE_CREATE_SUPP_GROUP;
+            }
+
+            (flags2, reply_flags2)
+        };
+        #[cfg(target_os = "macos")]
+        let reply_flags2 = 0;
+
         // TODO: pass init_in to init, so the file system will know which flags are in use.
         let reply = match fs.init(request).await {
             Err(err) => {
@@ -1139,38 +2584,98 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     .serialize(&init_out_header)
                     .expect("won't happened");
 
-                if let Err(err) = fuse_connection
-                    .write_vectored::<_, Vec<u8>>(init_out_header_data, None)
-                    .await
-                    .1
-                {
-                    error!("write error init out data to /dev/fuse failed {}", err);
-                }
-
-                return Err(err.into());
+                return (init_out_header_data, Err(err.into()));
             }
 
             Ok(reply) => reply,
         };
 
+        let mut max_write = reply.max_write.get().min(FUSE_MAX_REQUEST_SIZE);
+        if self.mount_options.max_write > 0 {
+            max_write = max_write.min(self.mount_options.max_write);
+        }
+
+        // kernel never sends a read or write larger than the max pages, it uses the fixed max
+        // pages without FUSE_MAX_PAGES
+        let page_size = page_size();
+        let max_pages = if reply_flags & FUSE_MAX_PAGES > 0 {
+            let max_read = match self.mount_options.max_read {
+                0 => FUSE_MAX_REQUEST_SIZE,
+                max_read => max_read,
+            };
+
+            self.mount_options
+                .max_pages
+                .unwrap_or_else(|| {
+                    max_write
+                        .max(max_read)
+                        .div_ceil(page_size)
+                        .try_into()
+                        .unwrap_or(u16::MAX)
+                })
+                .clamp(1, max_pages_limit())
+        } else {
+            FUSE_DEFAULT_MAX_PAGES_PER_REQ
+        };
+        let max_write = max_write.min(u32::from(max_pages) * page_size);
+        let max_write = NonZeroU32::new(max_write).expect("max_write can't be 0");
+
+        let max_background = self
+            .mount_options
+            .max_background
+            .unwrap_or(DEFAULT_MAX_BACKGROUND);
+        let congestion_threshold = match self.mount_options.congestion_threshold {
+            None => (max_background as u32 * 3 / 4) as u16,
+            Some(congestion_threshold) if congestion_threshold > max_background => {
+                warn!(
+                    congestion_threshold,
+                    max_background, "congestion_threshold is larger than max_background, clamp it"
+                );
+
+                max_background
+            }
+            Some(congestion_threshold) => congestion_threshold,
+        };
+
+        let time_gran = self.mount_options.time_gran.unwrap_or(DEFAULT_TIME_GRAN);
+
+        // kernel never uses a readahead larger than it offers
+        let max_readahead = self
+            .mount_options
+            .max_readahead
+            .map_or(init_in.max_readahead, |max_readahead| {
+                max_readahead.min(init_in.max_readahead)
+            });
+
+        // kernel will use the smaller minor version
+        let minor = init_in.minor.min(FUSE_KERNEL_MINOR_VERSION);
+
         let init_out = fuse_init_out {
             major: FUSE_KERNEL_VERSION,
             minor: FUSE_KERNEL_MINOR_VERSION,
-            max_readahead: init_in.max_readahead,
+            max_readahead,
             flags: reply_flags,
-            max_background: DEFAULT_MAX_BACKGROUND,
-            congestion_threshold: DEFAULT_CONGESTION_THRESHOLD,
-            max_write: reply.max_write.get(),
-            time_gran: DEFAULT_TIME_GRAN,
-            max_pages: DEFAULT_MAX_PAGES,
+            max_background,
+            congestion_threshold,
+            max_write: max_write.get(),
+            time_gran,
+            max_pages,
             map_alignment: DEFAULT_MAP_ALIGNMENT,
-            unused: [0; 8],
+            flags2: reply_flags2,
+            unused: [0; 7],
         };
 
         debug!("fuse init out {:?}", init_out);
 
+        // the older kernel, such as macFUSE, rejects the init reply larger than it knows
+        let init_out_size = if init_in.minor < FUSE_TIME_GRAN_MINOR_VERSION {
+            FUSE_COMPAT_22_INIT_OUT_SIZE
+        } else {
+            FUSE_INIT_OUT_SIZE
+        };
+
         let out_header = fuse_out_header {
-            len: (FUSE_OUT_HEADER_SIZE + FUSE_INIT_OUT_SIZE) as u32,
+            len: (FUSE_OUT_HEADER_SIZE + init_out_size) as u32,
             error: 0,
             unique: request.unique,
         };
@@ -1183,20 +2688,23 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         get_bincode_config()
             .serialize_into(&mut data, &init_out)
             .expect("won't happened");
+        data.truncate(FUSE_OUT_HEADER_SIZE + init_out_size);
 
-        if let Err(err) = fuse_connection
-            .write_vectored::<_, Vec<u8>>(data, None)
-            .await
-            .1
-        {
-            error!("write init out data to /dev/fuse failed {}", err);
-
-            return Err(err);
-        }
-
-        debug!("fuse init done");
+        let connection_info = ConnectionInfo {
+            major: FUSE_KERNEL_VERSION,
+            minor,
+            capabilities: InitFlags::from_bits_truncate(reply_flags & init_in.flags),
+            #[cfg(not(target_os = "macos"))]
+            capabilities2: InitFlags2::from_bits_truncate(reply_flags2 & flags2),
+            max_readahead,
+            max_write: max_write.get(),
+            max_pages,
+            max_background,
+            congestion_threshold,
+            time_gran,
+        };
 
-        Ok(reply.max_write)
+        (data, Ok((max_write, connection_info)))
     }
 
     #[instrument(skip(self, data, fs))]
@@ -1222,13 +2730,27 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_lookup"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_LOOKUP, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_lookup"), context, async move {
             debug!(
                 "lookup unique {} name {:?} in parent {}",
                 request.unique, name, in_header.nodeid
             );
 
-            let data = match fs.lookup(request, in_header.nodeid, &name).await {
+            let entry = match fs.lookup(request, in_header.nodeid, &name).await {
+                // nodeid 0 tells kernel to cache the negative entry
+                Ok(entry) if entry.is_negative() => Ok(fuse_entry_out::from(entry)),
+
+                Ok(entry) => {
+                    check_entry(fuse_opcode::FUSE_LOOKUP, &name, entry.attr.ino, entry.generation);
+
+                    Ok(entry.into())
+                }
+
+                Err(err) => Err(err),
+            };
+
+            let data = match entry {
                 Err(err) => {
                     let out_header = fuse_out_header {
                         len: FUSE_OUT_HEADER_SIZE as u32,
@@ -1241,9 +2763,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                         .expect("won't happened")
                 }
 
-                Ok(entry) => {
-                    let entry_out: fuse_entry_out = entry.into();
-
+                Ok(entry_out) => {
                     debug!("lookup response {:?}", entry_out);
 
                     let out_header = fuse_out_header {
@@ -1294,7 +2814,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_forget"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_FORGET, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_forget"), context, async move {
             debug!(
                 "forget unique {} inode {} nlookup {}",
                 request.unique, in_header.nodeid, forget_in.nlookup
@@ -1331,7 +2852,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_getattr"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_GETATTR, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_getattr"), context, async move {
             debug!(
                 "getattr unique {} inode {}",
                 request.unique, in_header.nodeid
@@ -1360,9 +2882,10 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 }
 
                 Ok(attr) => {
+                    let (attr_valid, attr_valid_nsec) = ttl_to_kernel(attr.ttl);
                     let attr_out = fuse_attr_out {
-                        attr_valid: attr.ttl.as_secs(),
-                        attr_valid_nsec: attr.ttl.subsec_nanos(),
+                        attr_valid,
+                        attr_valid_nsec,
                         dummy: getattr_in.dummy,
                         attr: attr.attr.into(),
                     };
@@ -1416,7 +2939,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_setattr"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_SETATTR, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_setattr"), context, async move {
             let set_attr = SetAttr::from(&setattr_in);
 
             let fh = if setattr_in.valid & FATTR_FH > 0 {
@@ -1430,6 +2954,30 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 request.unique, in_header.nodeid, set_attr
             );
 
+            // a filesystem which doesn't implement setxtimes handles the times in setattr
+            #[cfg(target_os = "macos")]
+            if set_attr.bkuptime.is_some() || set_attr.crtime.is_some() {
+                match fs
+                    .setxtimes(
+                        request,
+                        in_header.nodeid,
+                        set_attr.bkuptime,
+                        set_attr.crtime,
+                    )
+                    .await
+                {
+                    Err(err) if err == Errno::ENOSYS => {}
+
+                    Err(err) => {
+                        reply_error_in_place(err, request, resp_sender).await;
+
+                        return;
+                    }
+
+                    Ok(()) => {}
+                }
+            }
+
             let data = match fs.setattr(request, in_header.nodeid, fh, set_attr).await {
                 Err(err) => {
                     let out_header = fuse_out_header {
@@ -1474,7 +3022,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_readlink"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_READLINK, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_readlink"), context, async move {
             debug!(
                 "readlink unique {} inode {}",
                 request.unique, in_header.nodeid
@@ -1508,7 +3057,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                         .serialize_into(&mut data_buf, &out_header)
                         .expect("won't happened");
 
-                    Either::Right((data_buf, data.data))
+                    Either::Right((data_buf, data.data.into()))
                 }
             };
 
@@ -1522,6 +3071,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         request: Request,
         in_header: fuse_in_header,
         mut data: &[u8],
+        security_context: Option<SecurityContext>,
         fs: &Arc<FS>,
     ) {
         let (name, first_null_index) = match get_first_null_position(data) {
@@ -1556,14 +3106,21 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_symlink"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_SYMLINK, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_symlink"), context, async move {
             debug!(
                 "symlink unique {} parent {} name {:?} link {:?}",
                 request.unique, in_header.nodeid, name, link_name
             );
 
             let data = match fs
-                .symlink(request, in_header.nodeid, &name, &link_name)
+                .symlink(
+                    request,
+                    in_header.nodeid,
+                    &name,
+                    &link_name,
+                    security_context.as_ref(),
+                )
                 .await
             {
                 Err(err) => {
@@ -1579,6 +3136,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 }
 
                 Ok(entry) => {
+                    check_entry(fuse_opcode::FUSE_SYMLINK, &name, entry.attr.ino, entry.generation);
+
                     let entry_out: fuse_entry_out = entry.into();
 
                     let out_header = fuse_out_header {
@@ -1610,9 +3169,21 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         request: Request,
         in_header: fuse_in_header,
         mut data: &[u8],
+        security_context: Option<SecurityContext>,
         fs: &Arc<FS>,
     ) {
-        let mknod_in = match get_bincode_config().deserialize::<fuse_mknod_in>(data) {
+        // old kernel doesn't send the umask
+        let compat = self.minor_version() < FUSE_UMASK_MINOR_VERSION;
+
+        let mknod_in = if compat {
+            get_bincode_config()
+                .deserialize::<fuse_compat_mknod_in>(data)
+                .map(fuse_mknod_in::from)
+        } else {
+            get_bincode_config().deserialize::<fuse_mknod_in>(data)
+        };
+
+        let mknod_in = match mknod_in {
             Err(err) => {
                 error!(
                     "deserialize fuse_mknod_in failed {}, request unique {}",
@@ -1627,7 +3198,11 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(mknod_in) => mknod_in,
         };
 
-        data = &data[FUSE_MKNOD_IN_SIZE..];
+        data = if compat {
+            &data[FUSE_COMPAT_MKNOD_IN_SIZE..]
+        } else {
+            &data[FUSE_MKNOD_IN_SIZE..]
+        };
 
         let name = match get_first_null_position(data) {
             None => {
@@ -1647,7 +3222,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_mknod"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_MKNOD, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_mknod"), context, async move {
             debug!(
                 "mknod unique {} parent {} name {:?} {:?}",
                 request.unique, in_header.nodeid, name, mknod_in
@@ -1660,6 +3236,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     &name,
                     mknod_in.mode,
                     mknod_in.rdev,
+                    mknod_in.umask,
+                    security_context.as_ref(),
                 )
                 .await
             {
@@ -1668,6 +3246,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 }
 
                 Ok(entry) => {
+                    check_entry(fuse_opcode::FUSE_MKNOD, &name, entry.attr.ino, entry.generation);
+
                     let entry_out: fuse_entry_out = entry.into();
 
                     let out_header = fuse_out_header {
@@ -1697,6 +3277,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         request: Request,
         in_header: fuse_in_header,
         mut data: &[u8],
+        security_context: Option<SecurityContext>,
         fs: &Arc<FS>,
     ) {
         let mkdir_in = match get_bincode_config().deserialize::<fuse_mkdir_in>(data) {
@@ -1734,7 +3315,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_mkdir"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_MKDIR, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_mkdir"), context, async move {
             debug!(
                 "mkdir unique {} parent {} name {:?} {:?}",
                 request.unique, in_header.nodeid, name, mkdir_in
@@ -1747,6 +3329,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     &name,
                     mkdir_in.mode,
                     mkdir_in.umask,
+                    security_context.as_ref(),
                 )
                 .await
             {
@@ -1755,6 +3338,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 }
 
                 Ok(entry) => {
+                    check_entry(fuse_opcode::FUSE_MKDIR, &name, entry.attr.ino, entry.generation);
+
                     let entry_out: fuse_entry_out = entry.into();
 
                     let out_header = fuse_out_header {
@@ -1804,7 +3389,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_unlink"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_UNLINK, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_unlink"), context, async move {
             debug!(
                 "unlink unique {} parent {} name {:?}",
                 request.unique, in_header.nodeid, name
@@ -1856,7 +3442,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_rmdir"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_RMDIR, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_rmdir"), context, async move {
             debug!(
                 "rmdir unique {} parent {} name {:?}",
                 request.unique, in_header.nodeid, name
@@ -1942,19 +3529,21 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_rename"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_RENAME, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_rename"), context, async move {
             debug!(
                 "rename unique {} parent {} name {:?} new parent {} new name {:?}",
                 request.unique, in_header.nodeid, name, rename_in.newdir, new_name
             );
 
             let resp_value = if let Err(err) = fs
-                .rename(
+                .rename2(
                     request,
                     in_header.nodeid,
                     &name,
                     rename_in.newdir,
                     &new_name,
+                    RenameFlags::empty(),
                 )
                 .await
             {
@@ -2020,7 +3609,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_link"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_LINK, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_link"), context, async move {
             debug!(
                 "link unique {} inode {} new parent {} new name {:?}",
                 request.unique, link_in.oldnodeid, in_header.nodeid, name
@@ -2035,6 +3625,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 }
 
                 Ok(entry) => {
+                    check_entry(fuse_opcode::FUSE_LINK, &name, entry.attr.ino, entry.generation);
+
                     let entry_out: fuse_entry_out = entry.into();
 
                     let out_header = fuse_out_header {
@@ -2084,13 +3676,17 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_open"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_OPEN, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_open"), context, async move {
             debug!(
                 "open unique {} inode {} flags {}",
                 request.unique, in_header.nodeid, open_in.flags
             );
 
-            let opened = match fs.open(request, in_header.nodeid, open_in.flags).await {
+            let opened = match fs
+                .open(request, in_header.nodeid, open_in.flags.into())
+                .await
+            {
                 Err(err) => {
                     reply_error_in_place(err, request, resp_sender).await;
 
@@ -2146,63 +3742,135 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
+        let read_buf_unsupported = self.read_buf_unsupported.clone();
+        #[cfg(target_os = "linux")]
+        let splicer = self.splicer.clone();
 
-        spawn(debug_span!("fuse_read"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_READ, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_read"), context, async move {
             debug!(
                 "read unique {} inode {} {:?}",
                 request.unique, in_header.nodeid, read_in
             );
 
-            let mut reply_data = match fs
-                .read(
-                    request,
-                    in_header.nodeid,
-                    read_in.fh,
-                    read_in.offset,
-                    read_in.size,
-                )
-                .await
-            {
+            let reply = if read_buf_unsupported.load(Ordering::Relaxed) {
+                None
+            } else {
+                match fs
+                    .read_buf(
+                        request,
+                        in_header.nodeid,
+                        read_in.fh,
+                        read_in.offset,
+                        read_in.size,
+                    )
+                    .await
+                {
+                    Err(err) if err == Errno::ENOSYS => {
+                        debug!("read_buf is not implemented, use read");
+
+                        read_buf_unsupported.store(true, Ordering::Relaxed);
+
+                        None
+                    }
+
+                    reply => Some(reply),
+                }
+            };
+
+            let reply = match reply {
+                Some(reply) => reply,
+
+                None => fs
+                    .read(
+                        request,
+                        in_header.nodeid,
+                        read_in.fh,
+                        read_in.offset,
+                        read_in.size,
+                    )
+                    .await
+                    .map(ReplyReadBuf::Data),
+            };
+
+            let mut reply_data = match reply {
                 Err(err) => {
                     reply_error_in_place(err, request, resp_sender).await;
 
                     return;
                 }
 
-                Ok(reply_data) => reply_data.data,
+                Ok(ReplyReadBuf::Data(reply_data)) => reply_data.data,
+
+                Ok(ReplyReadBuf::File { fd, offset, len }) => {
+                    let len = len.min(read_in.size) as usize;
+
+                    #[cfg(target_os = "linux")]
+                    if let Some(splicer) = splicer {
+                        let file = fd.clone();
+                        let spliced = spawn_blocking(move || {
+                            splicer.fill(file.as_fd(), offset, len, |len| {
+                                data_out_header(request.unique, len)
+                            })
+                        })
+                        .await;
+
+                        match spliced {
+                            // fall back to read the data into memory
+                            None => {}
+
+                            Some(Err(err)) => {
+                                reply_error_in_place(err.into(), request, resp_sender).await;
+
+                                return;
+                            }
+
+                            Some(Ok((header, spliced))) => {
+                                let _ = resp_sender
+                                    .send(Either::Right((header, ExtendData::Spliced(spliced))))
+                                    .await;
+
+                                return;
+                            }
+                        }
+                    }
+
+                    match spawn_blocking(move || read_file_at(fd.as_fd(), offset, len)).await {
+                        Err(err) => {
+                            reply_error_in_place(err.into(), request, resp_sender).await;
+
+                            return;
+                        }
+
+                        Ok(data) => data.into(),
+                    }
+                }
             };
 
             if reply_data.len() > read_in.size as _ {
                 reply_data.truncate(read_in.size as _);
             }
 
-            let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + reply_data.len()) as u32,
-                error: 0,
-                unique: request.unique,
-            };
-
-            let mut data_buf = Vec::with_capacity(FUSE_OUT_HEADER_SIZE);
-
-            get_bincode_config()
-                .serialize_into(&mut data_buf, &out_header)
-                .expect("won't happened");
+            let data_buf = data_out_header(request.unique, reply_data.len());
 
             let _ = resp_sender
-                .send(Either::Right((data_buf, reply_data)))
+                .send(Either::Right((data_buf, reply_data.into())))
                 .await;
         });
     }
 
-    #[instrument(skip(self, data, fs))]
+    /// handle a write request, `pooled_buffer` returns the read buffer of `data` to the buffer
+    /// pool when the write is done.
+    #[instrument(skip(self, data, pooled_buffer, fs))]
     async fn handle_write(
         &mut self,
         request: Request,
         in_header: fuse_in_header,
-        mut data: &[u8],
+        data: Bytes,
+        pooled_buffer: Option<PooledBuffer>,
         fs: &Arc<FS>,
     ) {
-        let write_in = match get_bincode_config().deserialize::<fuse_write_in>(data) {
+        let write_in = match get_bincode_config().deserialize::<fuse_write_in>(&data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_write_in failed {}, request unique {}",
@@ -2217,7 +3885,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(write_in) => write_in,
         };
 
-        data = &data[FUSE_WRITE_IN_SIZE..];
+        let data = data.slice(FUSE_WRITE_IN_SIZE..);
 
         if write_in.size as usize != data.len() {
             error!("fuse_write_in body len is invalid");
@@ -2227,29 +3895,31 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             return;
         }
 
-        let data = data.to_vec();
-
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_write"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_WRITE, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_write"), context, async move {
             debug!(
                 "write unique {} inode {} {:?}",
                 request.unique, in_header.nodeid, write_in
             );
 
-            let reply_write = match fs
-                .write(
+            let result = fs
+                .write_buf(
                     request,
                     in_header.nodeid,
                     write_in.fh,
                     write_in.offset,
-                    &data,
-                    write_in.write_flags,
+                    data,
+                    WriteFlags::from_bits_retain(write_in.write_flags),
                     write_in.flags,
                 )
-                .await
-            {
+                .await;
+
+            drop(pooled_buffer);
+
+            let reply_write = match result {
                 Err(err) => {
                     reply_error_in_place(err, request, resp_sender).await;
 
@@ -2285,7 +3955,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_statfs"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_STATFS, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_statfs"), context, async move {
             debug!(
                 "statfs unique {} inode {}",
                 request.unique, in_header.nodeid
@@ -2348,7 +4019,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_release"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_RELEASE, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_release"), context, async move {
             let flush = release_in.release_flags & FUSE_RELEASE_FLUSH > 0;
 
             debug!(
@@ -2361,6 +4033,27 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 flush
             );
 
+            // kernel asks to release the flock locks of the closed file
+            #[cfg(feature = "file-lock")]
+            if release_in.release_flags & FUSE_RELEASE_FLOCK_UNLOCK > 0 {
+                if let Err(err) = fs
+                    .flock(
+                        request,
+                        in_header.nodeid,
+                        release_in.fh,
+                        release_in.lock_owner,
+                        LockType::Unlock,
+                        false,
+                    )
+                    .await
+                {
+                    debug!(
+                        "release flock of lock_owner {} failed {}, request unique {}",
+                        release_in.lock_owner, err, request.unique
+                    );
+                }
+            }
+
             let resp_value = if let Err(err) = fs
                 .release(
                     request,
@@ -2417,7 +4110,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_fsync"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_FSYNC, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_fsync"), context, async move {
             let data_sync = fsync_in.fsync_flags & 1 > 0;
 
             debug!(
@@ -2505,7 +4199,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_setxattr"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_SETXATTR, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_setxattr"), context, async move {
             debug!(
                 "setxattr unique {} inode {}",
                 request.unique, in_header.nodeid
@@ -2518,7 +4213,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     in_header.nodeid,
                     &name,
                     &data,
-                    setxattr_in.flags,
+                    SetXattrFlags::from_bits_retain(setxattr_in.flags),
                     0,
                 )
                 .await
@@ -2582,7 +4277,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_getxattr"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_GETXATTR, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_getxattr"), context, async move {
             debug!(
                 "getxattr unique {} inode {}",
                 request.unique, in_header.nodeid
@@ -2591,6 +4287,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             let xattr = match fs
                 .getxattr(request, in_header.nodeid, &name, getxattr_in.size)
                 .await
+                .and_then(|xattr| xattr.fit(getxattr_in.size))
             {
                 Err(err) => {
                     reply_error_in_place(err, request, resp_sender).await;
@@ -2607,11 +4304,12 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
                     let out_header = fuse_out_header {
                         len: (FUSE_OUT_HEADER_SIZE + FUSE_GETXATTR_OUT_SIZE) as u32,
-                        error: libc::ERANGE,
+                        error: 0,
                         unique: request.unique,
                     };
 
-                    let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_STATFS_OUT_SIZE);
+                    let mut data =
+                        Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_GETXATTR_OUT_SIZE);
 
                     get_bincode_config()
                         .serialize_into(&mut data, &out_header)
@@ -2624,8 +4322,6 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 }
 
                 ReplyXAttr::Data(xattr_data) => {
-                    // TODO check is right way or not
-                    // TODO should we check data length or not
                     let out_header = fuse_out_header {
                         len: (FUSE_OUT_HEADER_SIZE + xattr_data.len()) as u32,
                         error: 0,
@@ -2638,7 +4334,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                         .serialize_into(&mut data, &out_header)
                         .expect("won't happened");
 
-                    Either::Right((data, xattr_data))
+                    Either::Right((data, xattr_data.into()))
                 }
             };
 
@@ -2672,7 +4368,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_listxattr"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_LISTXATTR, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_listxattr"), context, async move {
             debug!(
                 "listxattr unique {} inode {} size {}",
                 request.unique, in_header.nodeid, listxattr_in.size
@@ -2681,6 +4378,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             let xattr = match fs
                 .listxattr(request, in_header.nodeid, listxattr_in.size)
                 .await
+                .and_then(|xattr| xattr.fit(listxattr_in.size))
             {
                 Err(err) => {
                     reply_error_in_place(err, request, resp_sender).await;
@@ -2701,7 +4399,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                         unique: request.unique,
                     };
 
-                    let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_STATFS_OUT_SIZE);
+                    let mut data =
+                        Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_GETXATTR_OUT_SIZE);
 
                     get_bincode_config()
                         .serialize_into(&mut data, &out_header)
@@ -2714,8 +4413,6 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 }
 
                 ReplyXAttr::Data(xattr_data) => {
-                    // TODO check is right way or not
-                    // TODO should we check data length or not
                     let out_header = fuse_out_header {
                         len: (FUSE_OUT_HEADER_SIZE + xattr_data.len()) as u32,
                         error: 0,
@@ -2728,7 +4425,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                         .serialize_into(&mut data, &out_header)
                         .expect("won't happened");
 
-                    Either::Right((data, xattr_data))
+                    Either::Right((data, xattr_data.into()))
                 }
             };
 
@@ -2762,7 +4459,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_removexattr"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_REMOVEXATTR, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_removexattr"), context, async move {
             debug!(
                 "removexattr unique {} inode {}",
                 request.unique, in_header.nodeid
@@ -2815,7 +4513,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_flush"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_FLUSH, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_flush"), context, async move {
             debug!(
                 "flush unique {} inode {} fh {} lock_owner {}",
                 request.unique, in_header.nodeid, flush_in.fh, flush_in.lock_owner
@@ -2870,13 +4569,17 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_opendir"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_OPENDIR, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_opendir"), context, async move {
             debug!(
                 "opendir unique {} inode {} flags {}",
                 request.unique, in_header.nodeid, open_in.flags
             );
 
-            let reply_open = match fs.opendir(request, in_header.nodeid, open_in.flags).await {
+            let reply_open = match fs
+                .opendir(request, in_header.nodeid, open_in.flags.into())
+                .await
+            {
                 Err(err) => {
                     reply_error_in_place(err, request, resp_sender).await;
 
@@ -2939,7 +4642,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_readdir"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_READDIR, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_readdir"), context, async move {
             debug!(
                 "readdir unique {} inode {} fh {} offset {}",
                 request.unique, in_header.nodeid, read_in.fh, read_in.offset
@@ -2964,16 +4668,21 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
             let entries = reply_readdir.entries;
             let mut entries = pin!(entries);
+            let mut last_offset = None;
 
-            while let Some(entry) = entries.next().await {
-                let entry = match entry {
-                    Err(err) => {
+            // stop pulling entries when the buffer can't hold one more entry, so a lazy
+            // stream only produces the entries sent to kernel
+            while max_size.saturating_sub(entry_data.len()) > FUSE_DIRENT_SIZE {
+                let entry = match entries.next().await {
+                    None => break,
+
+                    Some(Err(err)) => {
                         reply_error_in_place(err, request, resp_sender).await;
 
                         return;
                     }
 
-                    Ok(entry) => entry,
+                    Some(Ok(entry)) => entry,
                 };
 
                 let name = &entry.name;
@@ -2982,10 +4691,12 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
                 let padding_size = get_padding_size(dir_entry_size);
 
-                if entry_data.len() + dir_entry_size > max_size {
+                if entry_data.len() + dir_entry_size + padding_size > max_size {
                     break;
                 }
 
+                last_offset = Some(entry.offset);
+
                 let dir_entry = fuse_dirent {
                     ino: entry.inode,
                     off: entry.offset as u64,
@@ -3004,6 +4715,13 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 entry_data.resize(entry_data.len() + padding_size, 0);
             }
 
+            debug!(
+                "readdir unique {} reply {} bytes, last offset {:?}",
+                request.unique,
+                entry_data.len(),
+                last_offset
+            );
+
             // TODO find a way to avoid multi allocate
 
             let out_header = fuse_out_header {
@@ -3019,7 +4737,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 .expect("won't happened");
 
             let _ = resp_sender
-                .send(Either::Right((data, entry_data.into())))
+                .send(Either::Right((data, Bytes::from(entry_data).into())))
                 .await;
         });
     }
@@ -3050,7 +4768,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_releasedir"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_RELEASEDIR, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_releasedir"), context, async move {
             debug!(
                 "releasedir unique {} inode {} fh {} flags {}",
                 request.unique, in_header.nodeid, release_in.fh, release_in.flags
@@ -3105,7 +4824,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_fsyncdir"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_FSYNCDIR, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_fsyncdir"), context, async move {
             let data_sync = fsync_in.fsync_flags & 1 > 0;
 
             debug!(
@@ -3160,28 +4880,32 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(getlk_in) => getlk_in,
         };
 
+        let lock = match FileLock::try_from(&getlk_in) {
+            Err(err) => {
+                error!(
+                    "getlk lock type {} is invalid, request unique {}",
+                    getlk_in.lk.r#type, request.unique
+                );
+
+                reply_error_in_place(err, request, &self.response_sender).await;
+
+                return;
+            }
+
+            Ok(lock) => lock,
+        };
+
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_getlk"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_GETLK, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_getlk"), context, async move {
             debug!(
                 "getlk unique {} inode {} {:?}",
                 request.unique, in_header.nodeid, getlk_in
             );
 
-            let reply_lock = match fs
-                .getlk(
-                    request,
-                    in_header.nodeid,
-                    getlk_in.fh,
-                    getlk_in.owner,
-                    getlk_in.lk.start,
-                    getlk_in.lk.end,
-                    getlk_in.lk.r#type,
-                    getlk_in.lk.pid,
-                )
-                .await
-            {
+            let reply_lock = match fs.getlk(request, in_header.nodeid, getlk_in.fh, lock).await {
                 Err(err) => {
                     reply_error_in_place(err, request, resp_sender).await;
 
@@ -3243,34 +4967,57 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(setlk_in) => setlk_in,
         };
 
+        let lock = match FileLock::try_from(&setlk_in) {
+            Err(err) => {
+                error!(
+                    "setlk lock type {} is invalid, request unique {}",
+                    setlk_in.lk.r#type, request.unique
+                );
+
+                reply_error_in_place(err, request, &self.response_sender).await;
+
+                return;
+            }
+
+            Ok(lock) => lock,
+        };
+
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
+        let opcode = if block {
+            fuse_opcode::FUSE_SETLKW
+        } else {
+            fuse_opcode::FUSE_SETLK
+        };
+
+        let context = RequestContext::new(opcode, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_setlk"), context, async move {
+            let flock = setlk_in.lk_flags & FUSE_LK_FLOCK > 0;
 
-        spawn(debug_span!("fuse_setlk"), async move {
             debug!(
-                "setlk unique {} inode {} block {} {:?}",
-                request.unique, in_header.nodeid, block, setlk_in
+                "setlk unique {} inode {} block {} flock {} {:?}",
+                request.unique, in_header.nodeid, block, flock, setlk_in
             );
 
-            let resp = if let Err(err) = fs
-                .setlk(
+            let result = if flock {
+                fs.flock(
                     request,
                     in_header.nodeid,
                     setlk_in.fh,
                     setlk_in.owner,
-                    setlk_in.lk.start,
-                    setlk_in.lk.end,
-                    setlk_in.lk.r#type,
-                    setlk_in.lk.pid,
+                    lock.r#type,
                     block,
                 )
                 .await
-            {
-                err.into()
+            } else if block {
+                fs.setlkw(request, in_header.nodeid, setlk_in.fh, lock)
+                    .await
             } else {
-                0
+                fs.setlk(request, in_header.nodeid, setlk_in.fh, lock).await
             };
 
+            let resp = if let Err(err) = result { err.into() } else { 0 };
+
             let out_header = fuse_out_header {
                 len: FUSE_OUT_HEADER_SIZE as u32,
                 error: resp,
@@ -3309,9 +5056,33 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         };
 
         let mut resp_sender = self.response_sender.clone();
+
+        // kernel has checked the permissions itself
+        if self.mount_options.default_permissions && !self.mount_options.forward_access {
+            debug!(
+                "access unique {} inode {} mask {} is allowed by default_permissions",
+                request.unique, in_header.nodeid, access_in.mask
+            );
+
+            let out_header = fuse_out_header {
+                len: FUSE_OUT_HEADER_SIZE as u32,
+                error: 0,
+                unique: request.unique,
+            };
+
+            let data = get_bincode_config()
+                .serialize(&out_header)
+                .expect("won't happened");
+
+            let _ = resp_sender.send(Either::Left(data)).await;
+
+            return;
+        }
+
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_access"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_ACCESS, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_access"), context, async move {
             debug!(
                 "access unique {} inode {} mask {}",
                 request.unique, in_header.nodeid, access_in.mask
@@ -3346,9 +5117,21 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         request: Request,
         in_header: fuse_in_header,
         mut data: &[u8],
+        security_context: Option<SecurityContext>,
         fs: &Arc<FS>,
     ) {
-        let create_in = match get_bincode_config().deserialize::<fuse_create_in>(data) {
+        // old kernel doesn't send the umask
+        let compat = self.minor_version() < FUSE_UMASK_MINOR_VERSION;
+
+        let create_in = if compat {
+            get_bincode_config()
+                .deserialize::<fuse_compat_create_in>(data)
+                .map(fuse_create_in::from)
+        } else {
+            get_bincode_config().deserialize::<fuse_create_in>(data)
+        };
+
+        let create_in = match create_in {
             Err(err) => {
                 error!(
                     "deserialize fuse_create_in failed {}, request unique {}",
@@ -3363,7 +5146,11 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(create_in) => create_in,
         };
 
-        data = &data[FUSE_CREATE_IN_SIZE..];
+        data = if compat {
+            &data[FUSE_COMPAT_CREATE_IN_SIZE..]
+        } else {
+            &data[FUSE_CREATE_IN_SIZE..]
+        };
 
         let name = match get_first_null_position(data) {
             None => {
@@ -3383,7 +5170,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_create"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_CREATE, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_create"), context, async move {
             debug!(
                 "create unique {} parent {} name {:?} mode {} flags {}",
                 request.unique, in_header.nodeid, name, create_in.mode, create_in.flags
@@ -3395,7 +5183,9 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     in_header.nodeid,
                     &name,
                     create_in.mode,
-                    create_in.flags,
+                    create_in.flags.into(),
+                    create_in.umask,
+                    security_context.as_ref(),
                 )
                 .await
             {
@@ -3408,6 +5198,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 Ok(created) => created,
             };
 
+            check_entry(fuse_opcode::FUSE_CREATE, &name, created.attr.ino, created.generation);
+
             let (entry_out, open_out): (fuse_entry_out, fuse_open_out) = created.into();
 
             let out_header = fuse_out_header {
@@ -3450,10 +5242,22 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(interrupt_in) => interrupt_in,
         };
 
+        if !self.mount_options.forward_interrupt {
+            let interrupted = self.interruptible.interrupt(interrupt_in.unique);
+
+            debug!(
+                "interrupt_in unique {} interrupt unique {} interrupted {}",
+                request.unique, interrupt_in.unique, interrupted
+            );
+
+            return;
+        }
+
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_interrupt"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_INTERRUPT, request, 0);
+        self.spawn(debug_span!("fuse_interrupt"), context, async move {
             debug!(
                 "interrupt_in unique {} interrupt unique {}",
                 request.unique, interrupt_in.unique
@@ -3466,13 +5270,80 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             };
 
             let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
+                len: FUSE_OUT_HEADER_SIZE as u32,
+                error: resp_value,
+                unique: request.unique,
+            };
+
+            let data = get_bincode_config()
+                .serialize(&out_header)
+                .expect("won't happened");
+
+            let _ = resp_sender.send(Either::Left(data)).await;
+        });
+    }
+
+    #[instrument(skip(self, data, fs))]
+    async fn handle_bmap(
+        &mut self,
+        request: Request,
+        in_header: fuse_in_header,
+        data: &[u8],
+        fs: &Arc<FS>,
+    ) {
+        let bmap_in = match get_bincode_config().deserialize::<fuse_bmap_in>(data) {
+            Err(err) => {
+                error!(
+                    "deserialize fuse_bmap_in failed {}, request unique {}",
+                    err, request.unique
+                );
+
+                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+
+                return;
+            }
+
+            Ok(bmap_in) => bmap_in,
+        };
+
+        let mut resp_sender = self.response_sender.clone();
+        let fs = fs.clone();
+
+        let context = RequestContext::new(fuse_opcode::FUSE_BMAP, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_bmap"), context, async move {
+            debug!(
+                "bmap unique {} inode {} block size {} idx {}",
+                request.unique, in_header.nodeid, bmap_in.blocksize, bmap_in.block
+            );
+
+            let reply_bmap = match fs
+                .bmap(request, in_header.nodeid, bmap_in.blocksize, bmap_in.block)
+                .await
+            {
+                Err(err) => {
+                    reply_error_in_place(err, request, resp_sender).await;
+
+                    return;
+                }
+
+                Ok(reply_bmap) => reply_bmap,
+            };
+
+            let bmap_out: fuse_bmap_out = reply_bmap.into();
+
+            let out_header = fuse_out_header {
+                len: (FUSE_OUT_HEADER_SIZE + FUSE_BMAP_OUT_SIZE) as u32,
+                error: 0,
                 unique: request.unique,
             };
 
-            let data = get_bincode_config()
-                .serialize(&out_header)
+            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_BMAP_OUT_SIZE);
+
+            get_bincode_config()
+                .serialize_into(&mut data, &out_header)
+                .expect("won't happened");
+            get_bincode_config()
+                .serialize_into(&mut data, &bmap_out)
                 .expect("won't happened");
 
             let _ = resp_sender.send(Either::Left(data)).await;
@@ -3480,17 +5351,17 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     }
 
     #[instrument(skip(self, data, fs))]
-    async fn handle_bmap(
+    async fn handle_ioctl(
         &mut self,
         request: Request,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let bmap_in = match get_bincode_config().deserialize::<fuse_bmap_in>(data) {
+        let ioctl_in = match get_bincode_config().deserialize::<fuse_ioctl_in>(data) {
             Err(err) => {
                 error!(
-                    "deserialize fuse_bmap_in failed {}, request unique {}",
+                    "deserialize fuse_ioctl_in failed {}, request unique {}",
                     err, request.unique
                 );
 
@@ -3499,20 +5370,52 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 return;
             }
 
-            Ok(bmap_in) => bmap_in,
+            Ok(ioctl_in) => ioctl_in,
         };
 
+        let in_data =
+            match data.get(FUSE_IOCTL_IN_SIZE..FUSE_IOCTL_IN_SIZE + ioctl_in.in_size as usize) {
+                None => {
+                    error!(
+                        "ioctl in_size {} is larger than the request data, request unique {}",
+                        ioctl_in.in_size, request.unique
+                    );
+
+                    reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+
+                    return;
+                }
+
+                Some(in_data) => in_data.to_vec(),
+            };
+
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_bmap"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_IOCTL, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_ioctl"), context, async move {
             debug!(
-                "bmap unique {} inode {} block size {} idx {}",
-                request.unique, in_header.nodeid, bmap_in.blocksize, bmap_in.block
+                "ioctl unique {} inode {} fh {} flags {} cmd {} in size {} out size {}",
+                request.unique,
+                in_header.nodeid,
+                ioctl_in.fh,
+                ioctl_in.flags,
+                ioctl_in.cmd,
+                ioctl_in.in_size,
+                ioctl_in.out_size
             );
 
-            let reply_bmap = match fs
-                .bmap(request, in_header.nodeid, bmap_in.blocksize, bmap_in.block)
+            let reply_ioctl = match fs
+                .ioctl(
+                    request,
+                    in_header.nodeid,
+                    ioctl_in.fh,
+                    ioctl_in.flags,
+                    ioctl_in.cmd,
+                    ioctl_in.arg,
+                    &in_data,
+                    ioctl_in.out_size,
+                )
                 .await
             {
                 Err(err) => {
@@ -3521,27 +5424,98 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     return;
                 }
 
-                Ok(reply_bmap) => reply_bmap,
+                Ok(reply_ioctl) => reply_ioctl,
             };
 
-            let bmap_out: fuse_bmap_out = reply_bmap.into();
+            let (ioctl_out, extend_data) = match reply_ioctl {
+                ReplyIoctl::Done { result, data } => {
+                    if data.len() > ioctl_in.out_size as usize {
+                        error!(
+                            size = data.len(),
+                            out_size = ioctl_in.out_size,
+                            unique = request.unique,
+                            "ioctl reply data is larger than out size"
+                        );
+
+                        reply_error_in_place(libc::EIO.into(), request, resp_sender).await;
+
+                        return;
+                    }
+
+                    let ioctl_out = fuse_ioctl_out {
+                        result,
+                        flags: 0,
+                        in_iovs: 0,
+                        out_iovs: 0,
+                    };
+
+                    (ioctl_out, data)
+                }
+
+                ReplyIoctl::Retry { in_iovs, out_iovs } => {
+                    if ioctl_in.flags & FUSE_IOCTL_UNRESTRICTED == 0 {
+                        error!(
+                            "restricted ioctl can't be retried, request unique {}",
+                            request.unique
+                        );
+
+                        reply_error_in_place(libc::EIO.into(), request, resp_sender).await;
+
+                        return;
+                    }
+
+                    if in_iovs.len() + out_iovs.len() > FUSE_IOCTL_MAX_IOV as usize {
+                        error!(
+                            "ioctl retry iovecs count {} is larger than {}, request unique {}",
+                            in_iovs.len() + out_iovs.len(),
+                            FUSE_IOCTL_MAX_IOV,
+                            request.unique
+                        );
+
+                        reply_error_in_place(libc::ENOMEM.into(), request, resp_sender).await;
+
+                        return;
+                    }
+
+                    let ioctl_out = fuse_ioctl_out {
+                        result: 0,
+                        flags: FUSE_IOCTL_RETRY,
+                        in_iovs: in_iovs.len() as u32,
+                        out_iovs: out_iovs.len() as u32,
+                    };
+
+                    let mut iovecs = Vec::with_capacity(
+                        (in_iovs.len() + out_iovs.len()) * FUSE_IOCTL_IOVEC_SIZE,
+                    );
+
+                    for iovec in in_iovs.into_iter().chain(out_iovs) {
+                        get_bincode_config()
+                            .serialize_into(&mut iovecs, &fuse_ioctl_iovec::from(iovec))
+                            .expect("won't happened");
+                    }
+
+                    (ioctl_out, iovecs.into())
+                }
+            };
 
             let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + FUSE_BMAP_OUT_SIZE) as u32,
+                len: (FUSE_OUT_HEADER_SIZE + FUSE_IOCTL_OUT_SIZE + extend_data.len()) as u32,
                 error: 0,
                 unique: request.unique,
             };
 
-            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_BMAP_OUT_SIZE);
+            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_IOCTL_OUT_SIZE);
 
             get_bincode_config()
                 .serialize_into(&mut data, &out_header)
                 .expect("won't happened");
             get_bincode_config()
-                .serialize_into(&mut data, &bmap_out)
+                .serialize_into(&mut data, &ioctl_out)
                 .expect("won't happened");
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            let _ = resp_sender
+                .send(Either::Right((data, extend_data.into())))
+                .await;
         });
     }
 
@@ -3573,7 +5547,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         let notify = self.get_notify();
 
-        spawn(debug_span!("fuse_poll"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_POLL, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_poll"), context, async move {
             debug!(
                 "poll unique {} inode {} {:?}",
                 request.unique, in_header.nodeid, poll_in
@@ -3592,7 +5567,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     poll_in.fh,
                     kh,
                     poll_in.flags,
-                    poll_in.events,
+                    PollEvents::from_bits_retain(poll_in.events),
                     &notify,
                 )
                 .await
@@ -3666,9 +5641,18 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         let data = data[..notify_retrieve_in.size as usize].to_vec();
 
+        // the notify_unique is the unique of the request
+        if let Some(sender) = self.pending_retrieves.take(request.unique) {
+            let _ = sender.send(data.into());
+
+            return;
+        }
+
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_notify_reply"), async move {
+        let context =
+            RequestContext::new(fuse_opcode::FUSE_NOTIFY_REPLY, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_notify_reply"), context, async move {
             if let Err(err) = fs
                 .notify_reply(
                     request,
@@ -3687,7 +5671,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_batch_forget(
         &mut self,
         request: Request,
-        _in_header: fuse_in_header,
+        in_header: fuse_in_header,
         mut data: &[u8],
         fs: &Arc<FS>,
     ) {
@@ -3738,10 +5722,12 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_batch_forget"), async move {
+        let context =
+            RequestContext::new(fuse_opcode::FUSE_BATCH_FORGET, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_batch_forget"), context, async move {
             let inodes = forgets
                 .into_iter()
-                .map(|forget_one| forget_one.nodeid)
+                .map(|forget_one| (forget_one.nodeid, forget_one.nlookup))
                 .collect::<Vec<_>>();
 
             debug!("batch_forget unique {} inodes {:?}", request.unique, inodes);
@@ -3776,7 +5762,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_fallocate"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_FALLOCATE, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_fallocate"), context, async move {
             debug!(
                 "fallocate unique {} inode {} {:?}",
                 request.unique, in_header.nodeid, fallocate_in
@@ -3789,7 +5776,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     fallocate_in.fh,
                     fallocate_in.offset,
                     fallocate_in.length,
-                    fallocate_in.mode,
+                    FallocateMode::from_bits_retain(fallocate_in.mode),
                 )
                 .await
             {
@@ -3838,7 +5825,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_readdirplus"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_READDIRPLUS, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_readdirplus"), context, async move {
             debug!(
                 "readdirplus unique {} parent {} {:?}",
                 request.unique, in_header.nodeid, readdirplus_in
@@ -3869,38 +5857,64 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
             let entries = directory_plus.entries;
             let mut entries = pin!(entries);
+            let mut last_offset = None;
+            // the lookups counted by the entries in entry_data
+            let mut lookups = vec![];
+
+            // stop pulling entries when the buffer can't hold one more entry, so a lazy
+            // stream only produces the entries sent to kernel
+            while max_size.saturating_sub(entry_data.len()) > FUSE_DIRENTPLUS_SIZE {
+                let entry = match entries.next().await {
+                    None => break,
+
+                    Some(Err(err)) => {
+                        // kernel won't forget the entries which are not replied
+                        if !lookups.is_empty() {
+                            fs.batch_forget(request, &lookups).await;
+                        }
 
-            while let Some(entry) = entries.next().await {
-                let entry = match entry {
-                    Err(err) => {
                         reply_error_in_place(err, request, resp_sender).await;
 
                         return;
                     }
 
-                    Ok(entry) => entry,
+                    Some(Ok(entry)) => entry,
                 };
 
                 let name = &entry.name;
 
+                check_entry(fuse_opcode::FUSE_READDIRPLUS, name, entry.attr.ino, entry.generation);
+
                 let dir_entry_size = FUSE_DIRENTPLUS_SIZE + name.len();
 
                 let padding_size = get_padding_size(dir_entry_size);
 
-                if entry_data.len() + dir_entry_size > max_size {
+                if entry_data.len() + dir_entry_size + padding_size > max_size {
+                    if entry.counts_lookup() {
+                        fs.forget(request, entry.attr.ino, 1).await;
+                    }
+
                     break;
                 }
 
+                if entry.counts_lookup() {
+                    lookups.push((entry.attr.ino, 1));
+                }
+
+                last_offset = Some(entry.offset);
+
                 let attr = entry.attr;
+                let (entry_valid, entry_valid_nsec) = ttl_to_kernel(entry.entry_ttl);
+                let (attr_valid, attr_valid_nsec) = ttl_to_kernel(entry.attr_ttl);
 
                 let dir_entry = fuse_direntplus {
                     entry_out: fuse_entry_out {
                         nodeid: attr.ino,
                         generation: entry.generation,
-                        entry_valid: entry.entry_ttl.as_secs(),
-                        attr_valid: entry.attr_ttl.as_secs(),
-                        entry_valid_nsec: entry.entry_ttl.subsec_nanos(),
-                        attr_valid_nsec: entry.attr_ttl.subsec_nanos(),
+                        entry_valid,
+                        attr_valid,
+                        entry_valid_nsec,
+                        attr_valid_nsec,
                         attr: attr.into(),
                     },
                     dirent: fuse_dirent {
@@ -3922,6 +5936,13 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 entry_data.resize(entry_data.len() + padding_size, 0);
             }
 
+            debug!(
+                "readdirplus unique {} reply {} bytes, last offset {:?}",
+                request.unique,
+                entry_data.len(),
+                last_offset
+            );
+
             // TODO find a way to avoid multi allocate
 
             let out_header = fuse_out_header {
@@ -3937,7 +5958,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 .expect("won't happened");
 
             let _ = resp_sender
-                .send(Either::Right((data, entry_data.into())))
+                .send(Either::Right((data, Bytes::from(entry_data).into())))
                 .await;
         });
     }
@@ -4002,7 +6023,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         let mut resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_rename2"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_RENAME2, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_rename2"), context, async move {
             debug!(
                 "rename2 unique {} parent {} name {:?} new parent {} new name {:?} flags {}",
                 request.unique,
@@ -4020,7 +6042,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     &old_name,
                     rename2_in.newdir,
                     &new_name,
-                    rename2_in.flags,
+                    RenameFlags::from_bits_retain(rename2_in.flags),
                 )
                 .await
             {
@@ -4070,7 +6092,8 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_lseek"), async move {
+        let context = RequestContext::new(fuse_opcode::FUSE_LSEEK, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_lseek"), context, async move {
             debug!(
                 "lseek unique {} inode {} {:?}",
                 request.unique, in_header.nodeid, lseek_in
@@ -4144,7 +6167,9 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_copy_file_range"), async move {
+        let context =
+            RequestContext::new(fuse_opcode::FUSE_COPY_FILE_RANGE, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_copy_file_range"), context, async move {
             debug!(
                 "reply_copy_file_range unique {} inode {} {:?}",
                 request.unique, in_header.nodeid, copy_file_range_in
@@ -4193,11 +6218,222 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             let _ = resp_sender.send(Either::Left(data)).await;
         });
     }
+
+    #[instrument(skip(self, data, fs))]
+    async fn handle_syncfs(
+        &mut self,
+        request: Request,
+        in_header: fuse_in_header,
+        data: &[u8],
+        fs: &Arc<FS>,
+    ) {
+        if let Err(err) = get_bincode_config().deserialize::<fuse_syncfs_in>(data) {
+            error!(
+                "deserialize fuse_syncfs_in failed {}, request unique {}",
+                err, request.unique
+            );
+
+            reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+
+            return;
+        }
+
+        let mut resp_sender = self.response_sender.clone();
+        let fs = fs.clone();
+
+        let context = RequestContext::new(fuse_opcode::FUSE_SYNCFS, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_syncfs"), context, async move {
+            debug!("syncfs unique {}", request.unique);
+
+            let resp_value = if let Err(err) = fs.syncfs(request).await {
+                err.into()
+            } else {
+                0
+            };
+
+            let out_header = fuse_out_header {
+                len: FUSE_OUT_HEADER_SIZE as u32,
+                error: resp_value,
+                unique: request.unique,
+            };
+
+            let data = get_bincode_config()
+                .serialize(&out_header)
+                .expect("won't happened");
+
+            let _ = resp_sender.send(Either::Left(data)).await;
+        });
+    }
+
+    #[instrument(skip(self, fs))]
+    async fn handle_canonical_path(
+        &mut self,
+        request: Request,
+        in_header: fuse_in_header,
+        fs: &Arc<FS>,
+    ) {
+        let mut resp_sender = self.response_sender.clone();
+        let fs = fs.clone();
+
+        let context =
+            RequestContext::new(fuse_opcode::FUSE_CANONICAL_PATH, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_canonical_path"), context, async move {
+            debug!(
+                "canonical_path unique {} inode {}",
+                request.unique, in_header.nodeid
+            );
+
+            let path = match fs.canonical_path(request, in_header.nodeid).await {
+                Err(err) => {
+                    reply_error_in_place(err, request, resp_sender).await;
+
+                    return;
+                }
+
+                Ok(path) => path.data,
+            };
+
+            // kernel receives the null terminated path in a PATH_MAX buffer
+            if path.len() >= libc::PATH_MAX as usize {
+                warn!(len = path.len(), "canonical path is longer than PATH_MAX");
+
+                reply_error_in_place(libc::ENAMETOOLONG.into(), request, resp_sender).await;
+
+                return;
+            }
+
+            let out_header = fuse_out_header {
+                len: (FUSE_OUT_HEADER_SIZE + path.len() + 1) as u32,
+                error: 0,
+                unique: request.unique,
+            };
+
+            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + path.len() + 1);
+
+            get_bincode_config()
+                .serialize_into(&mut data, &out_header)
+                .expect("won't happened");
+            data.extend_from_slice(&path);
+            data.push(0);
+
+            let _ = resp_sender.send(Either::Left(data)).await;
+        });
+    }
+
+    #[cfg(target_os = "macos")]
+    async fn handle_getxtimes(
+        &mut self,
+        request: Request,
+        in_header: fuse_in_header,
+        fs: &Arc<FS>,
+    ) {
+        let mut resp_sender = self.response_sender.clone();
+        let fs = fs.clone();
+
+        let context = RequestContext::new(fuse_opcode::FUSE_GETXTIMES, request, in_header.nodeid);
+        self.spawn(debug_span!("fuse_getxtimes"), context, async move {
+            debug!(
+                "getxtimes unique {} inode {}",
+                request.unique, in_header.nodeid
+            );
+
+            let xtimes = match fs.getxtimes(request, in_header.nodeid).await {
+                Err(err) => {
+                    reply_error_in_place(err, request, resp_sender).await;
+
+                    return;
+                }
+
+                Ok(xtimes) => xtimes,
+            };
+
+            let getxtimes_out: fuse_getxtimes_out = xtimes.into();
+
+            let out_header = fuse_out_header {
+                len: (FUSE_OUT_HEADER_SIZE + FUSE_GETXTIMES_OUT_SIZE) as u32,
+                error: 0,
+                unique: request.unique,
+            };
+
+            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_GETXTIMES_OUT_SIZE);
+
+            get_bincode_config()
+                .serialize_into(&mut data, &out_header)
+                .expect("won't happened");
+            get_bincode_config()
+                .serialize_into(&mut data, &getxtimes_out)
+                .expect("won't happened");
+
+            let _ = resp_sender.send(Either::Left(data)).await;
+        });
+    }
+}
+
+/// decode the in header of a request message whose size is `n`, reject a message which is
+/// shorter than the in header or whose `len` field doesn't match `n`, so the request data can be
+/// sliced by the `len` field safely.
+pub(crate) fn decode_in_header(header_buffer: &[u8], n: usize) -> IoResult<fuse_in_header> {
+    if n < FUSE_IN_HEADER_SIZE || header_buffer.len() < FUSE_IN_HEADER_SIZE {
+        return Err(IoError::new(
+            ErrorKind::InvalidData,
+            format!("request size {n} is less than FUSE_IN_HEADER_SIZE {FUSE_IN_HEADER_SIZE}"),
+        ));
+    }
+
+    let in_header = get_bincode_config()
+        .deserialize::<fuse_in_header>(&header_buffer[..FUSE_IN_HEADER_SIZE])
+        .map_err(|err| IoError::new(ErrorKind::InvalidData, err))?;
+
+    if in_header.len as usize != n {
+        return Err(IoError::new(
+            ErrorKind::InvalidData,
+            format!(
+                "request len field {} doesn't match the request size {n}, unique {}",
+                in_header.len, in_header.unique
+            ),
+        ));
+    }
+
+    Ok(in_header)
+}
+
+/// get the `unique` of a malformed request message whose size is `n`, `None` if the message is
+/// too short to contain it or the request doesn't need a reply.
+fn malformed_request_unique(header_buffer: &[u8], n: usize) -> Option<u64> {
+    // the in header starts with the len, opcode and unique fields
+    let header = header_buffer.get(..n.min(16))?;
+    let opcode = u32::from_le_bytes(header.get(4..8)?.try_into().ok()?);
+    let unique = u64::from_le_bytes(header.get(8..16)?.try_into().ok()?);
+
+    if unique == 0 {
+        return None;
+    }
+
+    match fuse_opcode::try_from(opcode) {
+        Ok(opcode) if !opcode.need_reply() => None,
+        _ => Some(unique),
+    }
+}
+
+/// get the `notify_unique` if the data is a `FUSE_NOTIFY_RETRIEVE` notify.
+pub(crate) fn get_notify_retrieve_unique(data: &[u8]) -> Option<u64> {
+    let out_header = get_bincode_config()
+        .deserialize::<fuse_out_header>(data.get(..FUSE_OUT_HEADER_SIZE)?)
+        .ok()?;
+    if out_header.unique != 0 || out_header.error != fuse_notify_code::FUSE_NOTIFY_RETRIEVE as i32 {
+        return None;
+    }
+
+    let retrieve_out = get_bincode_config()
+        .deserialize::<fuse_notify_retrieve_out>(data.get(FUSE_OUT_HEADER_SIZE..)?)
+        .ok()?;
+
+    Some(retrieve_out.notify_unique)
 }
 
 async fn reply_error_in_place<S>(err: Errno, request: Request, sender: S)
 where
-    S: Sink<Either<Vec<u8>, (Vec<u8>, Bytes)>>,
+    S: Sink<FuseData>,
 {
     let out_header = fuse_out_header {
         len: FUSE_OUT_HEADER_SIZE as u32,
@@ -4212,15 +6448,86 @@ where
     let _ = pin!(sender).send(Either::Left(data)).await;
 }
 
+/// the page size of the system, kernel counts the request size in pages.
+fn page_size() -> u32 {
+    // Safety: sysconf has no side effect
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        page_size if page_size > 0 => page_size as u32,
+        _ => 4096,
+    }
+}
+
+/// the max pages kernel accepts in the init reply, `/proc/sys/fs/fuse/max_pages_limit` is added
+/// in Linux 6.13, the older kernel uses the fixed limit.
+fn max_pages_limit() -> u16 {
+    #[cfg(target_os = "linux")]
+    if let Some(max_pages_limit) = std::fs::read_to_string("/proc/sys/fs/fuse/max_pages_limit")
+        .ok()
+        .and_then(|max_pages_limit| max_pages_limit.trim().parse().ok())
+        .filter(|max_pages_limit| *max_pages_limit > 0)
+    {
+        return max_pages_limit;
+    }
+
+    FUSE_DEFAULT_MAX_PAGES_LIMIT
+}
+
+/// the header of a reply with `len` bytes data.
+fn data_out_header(unique: u64, len: usize) -> Vec<u8> {
+    let out_header = fuse_out_header {
+        len: (FUSE_OUT_HEADER_SIZE + len) as u32,
+        error: 0,
+        unique,
+    };
+
+    let mut data_buf = Vec::with_capacity(FUSE_OUT_HEADER_SIZE);
+
+    get_bincode_config()
+        .serialize_into(&mut data_buf, &out_header)
+        .expect("won't happened");
+
+    data_buf
+}
+
+/// read at most `len` bytes at `offset` of the file `fd`, stop at the end of the file.
+fn read_file_at(fd: BorrowedFd, offset: u64, len: usize) -> IoResult<Vec<u8>> {
+    // Safety: the fd is valid while it is borrowed, and the file is never dropped to close it
+    let file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd.as_raw_fd()) });
+    let mut data = vec![0; len];
+    let mut read = 0;
+
+    while read < len {
+        match file.read_at(&mut data[read..], offset + read as u64) {
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+            Ok(0) => break,
+            Ok(n) => read += n,
+        }
+    }
+
+    data.truncate(read);
+
+    Ok(data)
+}
+
 #[inline]
+pub(crate) async fn sleep(duration: Duration) {
+    CurrentRuntime::sleep(duration).await
+}
+
+/// run the blocking `f` in the blocking thread pool of the runtime.
+async fn spawn_blocking<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    CurrentRuntime::spawn_blocking(f).await
+}
+
 fn spawn<F>(span: Span, fut: F)
 where
     F: Future + Send + 'static,
     F::Output: Send + 'static,
 {
-    #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
-    task::spawn(fut.instrument(span));
-
-    #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
-    task::spawn(fut.instrument(span)).detach()
+    CurrentRuntime::spawn(fut.instrument(span))
 }
@@ -0,0 +1,124 @@
+//! the builder of [`Session`].
+
+use std::fmt::{self, Debug, Formatter};
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{Session, SessionObserver};
+use crate::{MountOptions, Runtime};
+
+/// build a [`Session`] with the runtime, mount options and observer in one place.
+///
+/// # Examples:
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use fuse3::raw::Session;
+/// # use fuse3::{MountOptions, Runtime};
+/// # fn example<FS>() -> std::io::Result<Session<FS>> {
+/// let session = Session::builder()
+///     .runtime(Runtime::Tokio)
+///     .mount_options(MountOptions::default())
+///     .request_timeout(Duration::from_secs(30))
+///     .build()?;
+/// # Ok(session)
+/// # }
+/// ```
+pub struct SessionBuilder<FS> {
+    runtime: Option<Runtime>,
+    mount_options: MountOptions,
+    observer: Option<Arc<dyn SessionObserver>>,
+    _filesystem: PhantomData<fn() -> FS>,
+}
+
+impl<FS> Debug for SessionBuilder<FS> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionBuilder")
+            .field("runtime", &self.runtime)
+            .field("mount_options", &self.mount_options)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<FS> Default for SessionBuilder<FS> {
+    fn default() -> Self {
+        Self {
+            runtime: None,
+            mount_options: Default::default(),
+            observer: None,
+            _filesystem: PhantomData,
+        }
+    }
+}
+
+impl<FS> SessionBuilder<FS> {
+    /// create a builder with the default mount options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// require the session to run on `runtime`, default is [`Runtime::enabled`].
+    /// [`build`][SessionBuilder::build] fails when the runtime is not enabled by the features.
+    pub fn runtime(mut self, runtime: Runtime) -> Self {
+        self.runtime.replace(runtime);
+
+        self
+    }
+
+    /// set the mount options, it replaces the options set by
+    /// [`request_timeout`][SessionBuilder::request_timeout] and
+    /// [`max_write`][SessionBuilder::max_write] before.
+    pub fn mount_options(mut self, mount_options: MountOptions) -> Self {
+        self.mount_options = mount_options;
+
+        self
+    }
+
+    /// set the [`SessionObserver`] which observes the requests handled by the filesystem.
+    pub fn observer(mut self, observer: Arc<dyn SessionObserver>) -> Self {
+        self.observer.replace(observer);
+
+        self
+    }
+
+    /// set the timeout of a filesystem request, see [`MountOptions::request_timeout`].
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.mount_options.request_timeout(request_timeout);
+
+        self
+    }
+
+    /// set the max size of a single write request, the request buffer size is the max write
+    /// size plus the request header, see [`MountOptions::max_write`].
+    pub fn max_write(mut self, max_write: u32) -> Self {
+        self.mount_options.max_write(max_write);
+
+        self
+    }
+
+    /// build the session, return [`ErrorKind::Unsupported`] error if the required runtime is
+    /// not enabled by the features.
+    pub fn build(self) -> IoResult<Session<FS>> {
+        let runtime = self.runtime.unwrap_or(Runtime::enabled());
+        if !runtime.is_enabled() {
+            return Err(IoError::new(
+                ErrorKind::Unsupported,
+                format!(
+                    "{} runtime is required, but `{}` feature is not enabled, {} runtime is used",
+                    runtime,
+                    runtime.feature(),
+                    Runtime::enabled()
+                ),
+            ));
+        }
+
+        let session = Session::new(self.mount_options);
+
+        Ok(match self.observer {
+            None => session,
+            Some(observer) => session.observer(observer),
+        })
+    }
+}
@@ -0,0 +1,101 @@
+//! log the raw bytes of the fuse requests and replies, enabled by the `wire-debug` feature.
+
+use std::fmt::{self, Display, Formatter};
+
+use bincode::Options;
+use tracing::trace;
+
+use crate::helper::get_bincode_config;
+use crate::raw::abi::{
+    fuse_in_header, fuse_notify_code, fuse_opcode, fuse_out_header, FUSE_IN_HEADER_SIZE,
+    FUSE_OUT_HEADER_SIZE,
+};
+use crate::raw::observer::Opcode;
+
+/// the bytes per line of the hex dump.
+const BYTES_PER_LINE: usize = 16;
+
+/// log a request read from `/dev/fuse`, `n` is the read size, the first [`FUSE_IN_HEADER_SIZE`]
+/// bytes are in `header` and the rest are in `data`. Malformed requests are logged too.
+pub(crate) fn log_request(header: &[u8], data: &[u8], n: usize) {
+    let header = &header[..n.min(header.len())];
+    let data = &data[..n.saturating_sub(header.len()).min(data.len())];
+
+    match get_bincode_config().deserialize::<fuse_in_header>(header) {
+        Ok(in_header) if header.len() == FUSE_IN_HEADER_SIZE => {
+            let opcode = fuse_opcode::try_from(in_header.opcode)
+                .map_or("unknown", |opcode| Opcode(opcode).name());
+
+            trace!(
+                opcode,
+                code = in_header.opcode,
+                unique = in_header.unique,
+                nodeid = in_header.nodeid,
+                n,
+                bytes = %HexDump(&[header, data]),
+                "fuse request"
+            );
+        }
+
+        _ => trace!(n, bytes = %HexDump(&[header, data]), "malformed fuse request"),
+    }
+}
+
+/// log a reply or a notification written to `/dev/fuse`, the `extend_data` is written after the
+/// `data`.
+pub(crate) fn log_reply(data: &[u8], extend_data: Option<&[u8]>) {
+    let extend_data = extend_data.unwrap_or_default();
+    let dump = HexDump(&[data, extend_data]);
+    let n = data.len() + extend_data.len();
+
+    let out_header = match data.get(..FUSE_OUT_HEADER_SIZE) {
+        None => None,
+        Some(header) => get_bincode_config()
+            .deserialize::<fuse_out_header>(header)
+            .ok(),
+    };
+
+    match out_header {
+        None => trace!(n, bytes = %dump, "malformed fuse reply"),
+
+        // the error of a notification is the notify code
+        Some(out_header) if out_header.unique == 0 => {
+            let notify = fuse_notify_code::try_from(out_header.error as u32).ok();
+
+            trace!(notify = ?notify, code = out_header.error, n, bytes = %dump, "fuse notify");
+        }
+
+        Some(out_header) => {
+            trace!(
+                unique = out_header.unique,
+                error = out_header.error,
+                n,
+                bytes = %dump,
+                "fuse reply"
+            );
+        }
+    }
+}
+
+/// the hex dump of the bytes in the slices as if they are concatenated, each line starts with a
+/// line break so the dump follows the other fields. It is formatted only when the log is enabled.
+struct HexDump<'a>(&'a [&'a [u8]]);
+
+impl Display for HexDump<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut bytes = self.0.iter().flat_map(|slice| slice.iter()).peekable();
+        let mut offset = 0;
+
+        while bytes.peek().is_some() {
+            write!(f, "\n{offset:08x}:")?;
+
+            for byte in bytes.by_ref().take(BYTES_PER_LINE) {
+                write!(f, " {byte:02x}")?;
+            }
+
+            offset += BYTES_PER_LINE;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+//! extended attribute types.
+
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+
+/// the namespace of an extended attribute name, which is the prefix of the name.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum XattrNamespace {
+    /// `user.`, the attributes of regular files and directories, their permissions are the same
+    /// as the file permissions.
+    User,
+    /// `trusted.`, only visible and accessible to the process with `CAP_SYS_ADMIN`.
+    Trusted,
+    /// `security.`, used by the security modules, such as `security.capability` and
+    /// `security.selinux`.
+    Security,
+    /// `system.`, used by kernel, such as the POSIX ACLs `system.posix_acl_access` and
+    /// `system.posix_acl_default`.
+    System,
+}
+
+impl XattrNamespace {
+    /// get the namespace of an extended attribute name, return `None` if the name has no known
+    /// namespace prefix, the filesystem should usually return `EOPNOTSUPP` for it.
+    pub fn of(name: &OsStr) -> Option<Self> {
+        Self::split(name).map(|(namespace, _)| namespace)
+    }
+
+    /// split an extended attribute name into the namespace and the name without prefix.
+    pub fn split(name: &OsStr) -> Option<(Self, &OsStr)> {
+        [Self::User, Self::Trusted, Self::Security, Self::System]
+            .into_iter()
+            .find_map(|namespace| {
+                name.as_bytes()
+                    .strip_prefix(namespace.prefix().as_bytes())
+                    .map(|name| (namespace, OsStr::from_bytes(name)))
+            })
+    }
+
+    /// the prefix of the namespace, include the trailing `.`.
+    pub const fn prefix(self) -> &'static str {
+        match self {
+            XattrNamespace::User => "user.",
+            XattrNamespace::Trusted => "trusted.",
+            XattrNamespace::Security => "security.",
+            XattrNamespace::System => "system.",
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::future::Future;
+#[cfg(any(
+    all(target_os = "linux", feature = "unprivileged"),
+    target_os = "freebsd"
+))]
+use std::io;
+use std::os::fd::OwnedFd;
+#[cfg(any(
+    all(target_os = "linux", feature = "unprivileged"),
+    target_os = "freebsd"
+))]
+use std::os::fd::{AsFd, BorrowedFd};
+use std::time::Duration;
+
+use async_io::{Async, Timer};
+
+use super::AsyncRuntime;
+
+#[derive(Debug)]
+pub(crate) struct AsyncIoRuntime;
+
+impl AsyncRuntime for AsyncIoRuntime {
+    type Fd = Async<OwnedFd>;
+
+    fn spawn<F>(fut: F)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        async_global_executor::spawn(fut).detach();
+    }
+
+    fn spawn_blocking<F, T>(f: F) -> impl Future<Output = T> + Send
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        async_global_executor::spawn_blocking(f)
+    }
+
+    async fn sleep(duration: Duration) {
+        Timer::after(duration).await;
+    }
+
+    #[cfg(any(
+        all(target_os = "linux", feature = "unprivileged"),
+        target_os = "freebsd"
+    ))]
+    fn register(fd: OwnedFd) -> io::Result<Self::Fd> {
+        // Async::new sets the fd non-blocking
+        Async::new(fd)
+    }
+
+    #[cfg(any(
+        all(target_os = "linux", feature = "unprivileged"),
+        target_os = "freebsd"
+    ))]
+    fn read_with<T, R>(fd: &Self::Fd, mut read: R) -> impl Future<Output = io::Result<T>> + Send
+    where
+        T: Send,
+        R: FnMut(BorrowedFd<'_>) -> io::Result<T> + Send,
+    {
+        fd.read_with(move |fd| read(fd.as_fd()))
+    }
+}
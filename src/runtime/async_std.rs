@@ -0,0 +1,67 @@
+use std::future::Future;
+#[cfg(any(
+    all(target_os = "linux", feature = "unprivileged"),
+    target_os = "freebsd"
+))]
+use std::io;
+use std::os::fd::OwnedFd;
+#[cfg(any(
+    all(target_os = "linux", feature = "unprivileged"),
+    target_os = "freebsd"
+))]
+use std::os::fd::{AsFd, BorrowedFd};
+use std::time::Duration;
+
+use async_io::Async;
+use async_std::task;
+
+use super::AsyncRuntime;
+
+/// async-std doesn't expose its reactor for a raw fd, it runs on the global async-io reactor, so
+/// the fd is registered there directly.
+#[derive(Debug)]
+pub(crate) struct AsyncStdRuntime;
+
+impl AsyncRuntime for AsyncStdRuntime {
+    type Fd = Async<OwnedFd>;
+
+    fn spawn<F>(fut: F)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        task::spawn(fut);
+    }
+
+    fn spawn_blocking<F, T>(f: F) -> impl Future<Output = T> + Send
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        task::spawn_blocking(f)
+    }
+
+    fn sleep(duration: Duration) -> impl Future<Output = ()> + Send {
+        task::sleep(duration)
+    }
+
+    #[cfg(any(
+        all(target_os = "linux", feature = "unprivileged"),
+        target_os = "freebsd"
+    ))]
+    fn register(fd: OwnedFd) -> io::Result<Self::Fd> {
+        Async::new(fd)
+    }
+
+    #[cfg(any(
+        all(target_os = "linux", feature = "unprivileged"),
+        target_os = "freebsd"
+    ))]
+    fn read_with<T, R>(fd: &Self::Fd, mut read: R) -> impl Future<Output = io::Result<T>> + Send
+    where
+        T: Send,
+        R: FnMut(BorrowedFd<'_>) -> io::Result<T> + Send,
+    {
+        fd.read_with(move |fd| read(fd.as_fd()))
+    }
+}
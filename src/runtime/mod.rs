@@ -0,0 +1,136 @@
+//! the async runtime which drives the session.
+
+use std::fmt::{self, Debug, Display, Formatter};
+use std::future::Future;
+#[cfg(any(
+    all(target_os = "linux", feature = "unprivileged"),
+    target_os = "freebsd"
+))]
+use std::io;
+use std::os::fd::AsFd;
+#[cfg(any(
+    all(target_os = "linux", feature = "unprivileged"),
+    target_os = "freebsd"
+))]
+use std::os::fd::{BorrowedFd, OwnedFd};
+use std::time::Duration;
+
+#[cfg(all(feature = "async-io-runtime", not(feature = "async-std-runtime")))]
+mod async_io;
+#[cfg(feature = "async-std-runtime")]
+mod async_std;
+#[cfg(feature = "tokio-runtime")]
+mod tokio;
+
+/// the runtime selected by the features, the session and connection spawn tasks, run blocking
+/// calls, sleep and wait `/dev/fuse` through it.
+#[cfg(all(
+    not(feature = "tokio-runtime"),
+    feature = "async-io-runtime",
+    not(feature = "async-std-runtime")
+))]
+pub(crate) type CurrentRuntime = self::async_io::AsyncIoRuntime;
+#[cfg(all(not(feature = "tokio-runtime"), feature = "async-std-runtime"))]
+pub(crate) type CurrentRuntime = self::async_std::AsyncStdRuntime;
+#[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
+pub(crate) type CurrentRuntime = self::tokio::TokioRuntime;
+
+/// the operations which the session needs from an async runtime, each runtime feature
+/// implements it by its own task spawn, blocking thread pool, timer and reactor.
+pub(crate) trait AsyncRuntime {
+    /// a non-blocking fd registered in the reactor of the runtime.
+    type Fd: AsFd + Debug + Send + Sync;
+
+    /// spawn a detached task.
+    fn spawn<F>(fut: F)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static;
+
+    /// run the blocking `f` in the blocking thread pool.
+    fn spawn_blocking<F, T>(f: F) -> impl Future<Output = T> + Send
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static;
+
+    /// wait until `duration` elapses.
+    fn sleep(duration: Duration) -> impl Future<Output = ()> + Send;
+
+    /// register `fd` into the reactor, it is set non-blocking.
+    #[cfg(any(
+        all(target_os = "linux", feature = "unprivileged"),
+        target_os = "freebsd"
+    ))]
+    fn register(fd: OwnedFd) -> io::Result<Self::Fd>;
+
+    /// wait until `fd` is readable then call `read`, wait again when it returns
+    /// [`WouldBlock`][io::ErrorKind::WouldBlock].
+    #[cfg(any(
+        all(target_os = "linux", feature = "unprivileged"),
+        target_os = "freebsd"
+    ))]
+    fn read_with<T, R>(fd: &Self::Fd, read: R) -> impl Future<Output = io::Result<T>> + Send
+    where
+        T: Send,
+        R: FnMut(BorrowedFd<'_>) -> io::Result<T> + Send;
+}
+
+/// the async runtime which drives the session io and tasks, it is selected by the runtime
+/// features at compile time, see the [crate level documentation][crate#runtimes].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Runtime {
+    /// [tokio](https://docs.rs/tokio), enabled by `tokio-runtime` feature.
+    Tokio,
+    /// [async_io](https://docs.rs/async-io) and
+    /// [async-global-executor](https://docs.rs/async-global-executor), enabled by
+    /// `async-io-runtime` feature.
+    AsyncIo,
+    /// [async-std](https://docs.rs/async-std), enabled by `async-std-runtime` feature, the tasks,
+    /// blocking calls and timers run on async-std, the fd io shares the async-io reactor which
+    /// async-std runs on.
+    AsyncStd,
+}
+
+impl Runtime {
+    /// the runtime enabled by the features.
+    pub const fn enabled() -> Self {
+        if cfg!(feature = "tokio-runtime") {
+            Runtime::Tokio
+        } else if cfg!(feature = "async-std-runtime") {
+            Runtime::AsyncStd
+        } else {
+            Runtime::AsyncIo
+        }
+    }
+
+    /// whether the session can run on this runtime with the enabled features.
+    pub const fn is_enabled(self) -> bool {
+        matches!(
+            (self, Self::enabled()),
+            (Runtime::Tokio, Runtime::Tokio)
+                | (Runtime::AsyncIo, Runtime::AsyncIo)
+                | (Runtime::AsyncStd, Runtime::AsyncStd)
+        )
+    }
+
+    /// the feature which enables this runtime.
+    pub const fn feature(self) -> &'static str {
+        match self {
+            Runtime::Tokio => "tokio-runtime",
+            Runtime::AsyncIo => "async-io-runtime",
+            Runtime::AsyncStd => "async-std-runtime",
+        }
+    }
+}
+
+impl Display for Runtime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Runtime::Tokio => "tokio",
+            Runtime::AsyncIo => "async-io",
+            Runtime::AsyncStd => "async-std",
+        };
+
+        f.write_str(name)
+    }
+}
@@ -0,0 +1,78 @@
+use std::future::Future;
+#[cfg(any(
+    all(target_os = "linux", feature = "unprivileged"),
+    target_os = "freebsd"
+))]
+use std::io;
+use std::os::fd::OwnedFd;
+#[cfg(any(
+    all(target_os = "linux", feature = "unprivileged"),
+    target_os = "freebsd"
+))]
+use std::os::fd::{AsFd, BorrowedFd};
+use std::time::Duration;
+
+use tokio::io::unix::AsyncFd;
+use tokio::task;
+
+use super::AsyncRuntime;
+#[cfg(any(
+    all(target_os = "linux", feature = "unprivileged"),
+    target_os = "freebsd"
+))]
+use crate::raw::connection::set_non_blocking;
+
+#[derive(Debug)]
+pub(crate) struct TokioRuntime;
+
+impl AsyncRuntime for TokioRuntime {
+    type Fd = AsyncFd<OwnedFd>;
+
+    fn spawn<F>(fut: F)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        task::spawn(fut);
+    }
+
+    async fn spawn_blocking<F, T>(f: F) -> T
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        task::spawn_blocking(f).await.unwrap()
+    }
+
+    fn sleep(duration: Duration) -> impl Future<Output = ()> + Send {
+        tokio::time::sleep(duration)
+    }
+
+    #[cfg(any(
+        all(target_os = "linux", feature = "unprivileged"),
+        target_os = "freebsd"
+    ))]
+    fn register(fd: OwnedFd) -> io::Result<Self::Fd> {
+        set_non_blocking(fd.as_fd(), true)?;
+
+        AsyncFd::new(fd)
+    }
+
+    #[cfg(any(
+        all(target_os = "linux", feature = "unprivileged"),
+        target_os = "freebsd"
+    ))]
+    async fn read_with<T, R>(fd: &Self::Fd, mut read: R) -> io::Result<T>
+    where
+        T: Send,
+        R: FnMut(BorrowedFd<'_>) -> io::Result<T> + Send,
+    {
+        loop {
+            let mut read_guard = fd.readable().await?;
+
+            if let Ok(result) = read_guard.try_io(|fd| read(fd.as_fd())) {
+                return result;
+            }
+        }
+    }
+}
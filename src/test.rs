@@ -0,0 +1,1425 @@
+//! in-process test driver for [`Filesystem`] implementations.
+//!
+//! [`TestSession`] encodes the fuse requests like kernel, feeds them into the same request
+//! handlers used by a mounted [`Session`], and decodes the replies, so a filesystem can be tested
+//! without `/dev/fuse` and mount permission.
+//!
+//! # Notes:
+//!
+//! the filesystem methods are spawned like a mounted session, so the test must run in the async
+//! runtime enabled by `tokio-runtime` or `async-io-runtime` feature.
+//!
+//! # Examples:
+//!
+//! ```no_run
+//! # use fuse3::raw::prelude::*;
+//! # use fuse3::test::{request, TestSession};
+//! # use fuse3::MountOptions;
+//! # async fn test<FS: Filesystem + Send + Sync + 'static>(fs: FS) {
+//! let mut session = TestSession::new(fs, MountOptions::default());
+//! session.init(request(0, 0, 0)).await.unwrap();
+//!
+//! let entry = session.lookup(request(1000, 1000, 1), 1, "file".as_ref()).await.unwrap();
+//! let data = session
+//!     .read(request(1000, 1000, 1), entry.attr.ino, 0, 0, 4096)
+//!     .await
+//!     .unwrap();
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bincode::Options;
+use bytes::Bytes;
+use futures_channel::mpsc::UnboundedReceiver;
+use futures_util::future::Either;
+use futures_util::stream::StreamExt;
+use libc::mode_t;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::helper::{
+    get_bincode_config, get_padding_size, kind_from_mode, perm_from_mode_and_kind,
+};
+use crate::path::{self, InodePathBridge, PathFilesystem};
+use crate::raw::abi::*;
+#[cfg(not(target_os = "macos"))]
+use crate::raw::flags::InitFlags2;
+use crate::raw::flags::{InitFlags, RenameFlags};
+#[cfg(feature = "file-lock")]
+use crate::raw::reply::ReplyLock;
+use crate::raw::reply::{DirectoryEntry, FileAttr, ReplyAttr, ReplyEntry, ReplyOpen, ReplyXAttr};
+use crate::raw::session::decode_in_header;
+use crate::raw::{
+    ConnectionInfo, ExtendData, Filesystem, FuseData, Request, Session, SessionObserver,
+};
+#[cfg(feature = "file-lock")]
+use crate::raw::{FileLock, LockType};
+use crate::{Errno, FileType, Inode, MountOptions, Result, Timestamp};
+
+/// the max readahead sent in the `FUSE_INIT` request.
+const MAX_READAHEAD: u32 = 128 * 1024;
+
+/// the buffer size sent in the `FUSE_READDIR` request.
+const READDIR_SIZE: u32 = 4096;
+
+/// build a [`Request`] with the caller uid, gid and pid, the unique will be set by
+/// [`TestSession`] when the request is sent.
+pub fn request(uid: u32, gid: u32, pid: u32) -> Request {
+    Request {
+        unique: 0,
+        uid,
+        gid,
+        pid,
+        supplementary_group: None,
+    }
+}
+
+/// drive a [`Filesystem`] in process without kernel.
+///
+/// # Panics:
+///
+/// the methods panic if the filesystem session doesn't reply or the reply is malformed, which
+/// means a bug in fuse3 rather than in the filesystem.
+pub struct TestSession<FS> {
+    session: Session<FS>,
+    fs: Arc<FS>,
+    response_receiver: UnboundedReceiver<FuseData>,
+    unique: u64,
+    interrupt_next: bool,
+    /// the filesystem replied `ENOSYS` to open, the files are opened without sending request.
+    no_open: bool,
+}
+
+impl<FS: Filesystem + Send + Sync + 'static> TestSession<FS> {
+    /// create a test session with the filesystem and mount options.
+    pub fn new(fs: FS, mount_options: MountOptions) -> Self {
+        let mut session = Session::new(mount_options);
+        let response_receiver = session.take_response_receiver();
+
+        Self {
+            session,
+            fs: Arc::new(fs),
+            response_receiver,
+            unique: 0,
+            interrupt_next: false,
+            no_open: false,
+        }
+    }
+
+    /// set the [`SessionObserver`] which observes the requests sent by the test session.
+    pub fn observer(mut self, observer: Arc<dyn SessionObserver>) -> Self {
+        self.session = self.session.observer(observer);
+
+        self
+    }
+
+    /// get the filesystem.
+    pub fn filesystem(&self) -> &FS {
+        &self.fs
+    }
+
+    /// send the `FUSE_INIT` request which supports all capabilities of the latest protocol
+    /// version, return the negotiated [`ConnectionInfo`].
+    pub async fn init(&mut self, req: Request) -> Result<ConnectionInfo> {
+        let init_in = fuse_init_in {
+            _major: FUSE_KERNEL_VERSION,
+            minor: FUSE_KERNEL_MINOR_VERSION,
+            max_readahead: MAX_READAHEAD,
+            flags: InitFlags::all().bits(),
+        };
+
+        #[cfg_attr(target_os = "macos", allow(unused_mut))]
+        let mut data = encode(&init_in);
+        #[cfg(not(target_os = "macos"))]
+        data.extend_from_slice(&encode(&fuse_init_in_ext {
+            flags2: InitFlags2::all().bits(),
+            ..Default::default()
+        }));
+
+        self.send(req, fuse_opcode::FUSE_INIT, 0, &data).await?;
+
+        Ok(self
+            .session
+            .connection_info()
+            .expect("connection info should be set after init"))
+    }
+
+    /// look up a directory entry by name.
+    ///
+    /// a negative entry replied by [`ReplyEntry::negative`] returns `ENOENT`, it is not cached, so
+    /// the TTL is dropped. To check the TTL, send the lookup by
+    /// [`send_message`][TestSession::send_message] and decode the `fuse_entry_out`.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use std::ffi::OsStr;
+    /// # use std::sync::atomic::{AtomicUsize, Ordering};
+    /// # use std::time::Duration;
+    /// # use fuse3::raw::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// #[derive(Default)]
+    /// struct Fs {
+    ///     lookups: AtomicUsize,
+    /// }
+    ///
+    /// impl Filesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #   }
+    /// #
+    /// #   async fn destroy(&self, _req: Request) {}
+    /// #
+    ///     async fn lookup(&self, _req: Request, _parent: u64, _name: &OsStr)
+    ///         -> Result<ReplyEntry> {
+    ///         self.lookups.fetch_add(1, Ordering::Relaxed);
+    ///
+    ///         Ok(ReplyEntry::negative(Duration::from_secs(60)))
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut session = TestSession::new(Fs::default(), MountOptions::default());
+    /// session.init(request(0, 0, 0)).await.unwrap();
+    ///
+    /// let err = session
+    ///     .lookup(request(1000, 1000, 1), 1, "missing".as_ref())
+    ///     .await
+    ///     .unwrap_err();
+    /// assert!(err.is_not_exist());
+    ///
+    /// let mut message = Vec::new();
+    /// message.extend_from_slice(&48u32.to_ne_bytes()); // len
+    /// message.extend_from_slice(&1u32.to_ne_bytes()); // FUSE_LOOKUP
+    /// message.extend_from_slice(&100u64.to_ne_bytes()); // unique
+    /// message.extend_from_slice(&1u64.to_ne_bytes()); // nodeid
+    /// message.extend_from_slice(&[0; 16]); // uid, gid, pid and padding
+    /// message.extend_from_slice(b"missing\0");
+    ///
+    /// // fuse_entry_out starts with nodeid, generation, entry_valid and attr_valid
+    /// let entry_out = session.send_message(&message).await.unwrap();
+    /// let field = |i: usize| u64::from_ne_bytes(entry_out[i * 8..][..8].try_into().unwrap());
+    /// assert_eq!(field(0), 0);
+    /// assert_eq!(field(2), 60);
+    ///
+    /// assert_eq!(session.filesystem().lookups.load(Ordering::Relaxed), 2);
+    /// # }
+    /// ```
+    pub async fn lookup(
+        &mut self,
+        req: Request,
+        parent: Inode,
+        name: &OsStr,
+    ) -> Result<ReplyEntry> {
+        let data = self
+            .send(req, fuse_opcode::FUSE_LOOKUP, parent, &encode_name(name))
+            .await?;
+
+        let entry_out: fuse_entry_out = decode(&data);
+        if entry_out.nodeid == 0 {
+            return Err(Errno::new_not_exist());
+        }
+
+        Ok(decode_entry(entry_out))
+    }
+
+    /// send the `FUSE_BATCH_FORGET` request with the `(inode, nlookup)` pairs, it has no reply, so
+    /// wait until the filesystem has handled it.
+    pub async fn batch_forget(&mut self, req: Request, inodes: &[(Inode, u64)]) {
+        let batch_forget_in = fuse_batch_forget_in {
+            count: inodes.len() as u32,
+            _dummy: 0,
+        };
+        let mut data = encode(&batch_forget_in);
+        for &(nodeid, nlookup) in inodes {
+            data.extend_from_slice(&encode(&fuse_forget_one { nodeid, nlookup }));
+        }
+
+        let _ = self
+            .send(req, fuse_opcode::FUSE_BATCH_FORGET, 0, &data)
+            .await;
+    }
+
+    /// get file attributes.
+    pub async fn getattr(
+        &mut self,
+        req: Request,
+        inode: Inode,
+        fh: Option<u64>,
+    ) -> Result<ReplyAttr> {
+        let getattr_in = fuse_getattr_in {
+            getattr_flags: if fh.is_some() { FUSE_GETATTR_FH } else { 0 },
+            dummy: 0,
+            fh: fh.unwrap_or(0),
+        };
+
+        let data = self
+            .send(req, fuse_opcode::FUSE_GETATTR, inode, &encode(&getattr_in))
+            .await?;
+
+        let attr_out: fuse_attr_out = decode(&data);
+
+        Ok(ReplyAttr {
+            ttl: Duration::new(attr_out.attr_valid, attr_out.attr_valid_nsec),
+            attr: decode_attr(attr_out.attr),
+        })
+    }
+
+    /// rename `name` in `parent` to `new_name` in `new_parent` with the `renameat2(2)` flags.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use std::ffi::{OsStr, OsString};
+    /// # use std::sync::{Arc, Mutex};
+    /// # use std::time::Duration;
+    /// # use fuse3::path::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// #[derive(Default)]
+    /// struct Fs {
+    ///     paths: Arc<Mutex<Vec<OsString>>>,
+    /// }
+    ///
+    /// impl PathFilesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #   }
+    /// #
+    /// #   async fn destroy(&self, _req: Request) {}
+    /// #
+    ///     async fn lookup(&self, _req: Request, _parent: &OsStr, _name: &OsStr)
+    ///         -> Result<ReplyEntry> {
+    ///         let attr = FileAttr::from_metadata(&std::fs::metadata(".").unwrap());
+    ///
+    ///         Ok(ReplyEntry::new(attr, Duration::from_secs(1)))
+    ///     }
+    ///
+    ///     async fn getattr(&self, _req: Request, path: Option<&OsStr>, _fh: Option<u64>,
+    ///         _flags: u32) -> Result<ReplyAttr> {
+    ///         self.paths.lock().unwrap().push(path.unwrap().to_owned());
+    ///
+    ///         let attr = FileAttr::from_metadata(&std::fs::metadata(".").unwrap());
+    ///
+    ///         Ok(ReplyAttr { ttl: Duration::from_secs(1), attr })
+    ///     }
+    ///
+    ///     async fn rename2(&self, _req: Request, _origin_parent: &OsStr, _origin_name: &OsStr,
+    ///         _parent: &OsStr, _name: &OsStr, _flags: RenameFlags) -> Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let fs = Fs::default();
+    /// let paths = fs.paths.clone();
+    ///
+    /// let mut session = TestSession::new_path(fs, Session::new(MountOptions::default()));
+    /// session.init(request(0, 0, 0)).await.unwrap();
+    ///
+    /// let req = request(0, 0, 0);
+    /// let a = session.lookup(req, 1, "a".as_ref()).await.unwrap().attr.ino;
+    /// let child = session.lookup(req, a, "child".as_ref()).await.unwrap().attr.ino;
+    /// let b = session.lookup(req, 1, "b".as_ref()).await.unwrap().attr.ino;
+    ///
+    /// session
+    ///     .rename2(req, 1, "a".as_ref(), 1, "b".as_ref(), RenameFlags::EXCHANGE)
+    ///     .await
+    ///     .unwrap();
+    ///
+    /// // the exchanged inodes and the children of them follow the new names
+    /// for inode in [a, child, b] {
+    ///     session.getattr(req, inode, None).await.unwrap();
+    /// }
+    ///
+    /// assert_eq!(*paths.lock().unwrap(), ["/b", "/b/child", "/a"]);
+    /// # }
+    /// ```
+    pub async fn rename2(
+        &mut self,
+        req: Request,
+        parent: Inode,
+        name: &OsStr,
+        new_parent: Inode,
+        new_name: &OsStr,
+        flags: RenameFlags,
+    ) -> Result<()> {
+        let rename2_in = fuse_rename2_in {
+            newdir: new_parent,
+            flags: flags.bits(),
+            _padding: 0,
+        };
+
+        let mut data = encode(&rename2_in);
+        data.extend_from_slice(&encode_name(name));
+        data.extend_from_slice(&encode_name(new_name));
+
+        self.send(req, fuse_opcode::FUSE_RENAME2, parent, &data)
+            .await?;
+
+        Ok(())
+    }
+
+    /// open a file with the open `flags`.
+    ///
+    /// like kernel, when `FUSE_NO_OPEN_SUPPORT` is negotiated and the filesystem replies `ENOSYS`,
+    /// the open succeeds with `fh` 0, and the later opens and releases are not sent, see
+    /// [`MountOptions::no_open_support`][crate::MountOptions::no_open_support]. Without it, the
+    /// `ENOSYS` is returned.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use std::sync::atomic::{AtomicUsize, Ordering};
+    /// # use bytes::Bytes;
+    /// # use fuse3::raw::flags::InitFlags;
+    /// # use fuse3::raw::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// #[derive(Default)]
+    /// struct Fs {
+    ///     opens: AtomicUsize,
+    ///     releases: AtomicUsize,
+    /// }
+    ///
+    /// impl Filesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #   }
+    /// #
+    /// #   async fn destroy(&self, _req: Request) {}
+    /// #
+    ///     async fn open(&self, _req: Request, _inode: u64, _flags: OpenFlags)
+    ///         -> Result<ReplyOpen> {
+    ///         self.opens.fetch_add(1, Ordering::Relaxed);
+    ///
+    ///         Err(libc::ENOSYS.into())
+    ///     }
+    ///
+    ///     async fn read(&self, _req: Request, inode: u64, fh: u64, _offset: u64, _size: u32)
+    ///         -> Result<ReplyData> {
+    ///         // stateless, locate the file by the inode only
+    ///         assert_eq!(fh, 0);
+    ///
+    ///         Ok(Bytes::from(format!("inode {inode}")).into())
+    ///     }
+    ///
+    ///     async fn release(
+    ///         &self,
+    ///         _req: Request,
+    ///         _inode: u64,
+    ///         _fh: u64,
+    ///         _flags: u32,
+    ///         _lock_owner: u64,
+    ///         _flush: bool,
+    ///     ) -> Result<()> {
+    ///         self.releases.fetch_add(1, Ordering::Relaxed);
+    ///
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut mount_options = MountOptions::default();
+    /// mount_options.no_open_support(true);
+    ///
+    /// let mut session = TestSession::new(Fs::default(), mount_options);
+    /// let connection_info = session.init(request(0, 0, 0)).await.unwrap();
+    /// assert!(connection_info.capabilities().contains(InitFlags::NO_OPEN_SUPPORT));
+    ///
+    /// for _ in 0..2 {
+    ///     let req = request(1000, 1000, 1);
+    ///     let opened = session.open(req, 2, libc::O_RDONLY as _).await.unwrap();
+    ///     assert_eq!(opened.fh, 0);
+    ///
+    ///     let data = session.read(req, 2, opened.fh, 0, 4096).await.unwrap();
+    ///     assert_eq!(data, "inode 2");
+    ///
+    ///     session.release(req, 2, opened.fh, 0).await.unwrap();
+    /// }
+    ///
+    /// assert_eq!(session.filesystem().opens.load(Ordering::Relaxed), 1);
+    /// assert_eq!(session.filesystem().releases.load(Ordering::Relaxed), 0);
+    ///
+    /// // not negotiated, the open fails
+    /// let mut session = TestSession::new(Fs::default(), MountOptions::default());
+    /// let connection_info = session.init(request(0, 0, 0)).await.unwrap();
+    /// assert!(!connection_info.capabilities().contains(InitFlags::NO_OPEN_SUPPORT));
+    ///
+    /// for _ in 0..2 {
+    ///     let err = session
+    ///         .open(request(1000, 1000, 1), 2, libc::O_RDONLY as _)
+    ///         .await
+    ///         .unwrap_err();
+    ///     assert_eq!(err, libc::ENOSYS.into());
+    /// }
+    ///
+    /// assert_eq!(session.filesystem().opens.load(Ordering::Relaxed), 2);
+    /// # }
+    /// ```
+    pub async fn open(&mut self, req: Request, inode: Inode, flags: u32) -> Result<ReplyOpen> {
+        if self.no_open {
+            return Ok(ReplyOpen::new(0));
+        }
+
+        let open_in = fuse_open_in { flags, _unused: 0 };
+
+        let data = match self
+            .send(req, fuse_opcode::FUSE_OPEN, inode, &encode(&open_in))
+            .await
+        {
+            Err(err) if err == Errno::ENOSYS && self.is_no_open_supported() => {
+                self.no_open = true;
+
+                return Ok(ReplyOpen::new(0));
+            }
+
+            result => result?,
+        };
+
+        let open_out: fuse_open_out = decode(&data);
+
+        Ok(ReplyOpen {
+            fh: open_out.fh,
+            flags: open_out.open_flags,
+        })
+    }
+
+    /// release an open file, it is not sent when the file is opened without sending request.
+    pub async fn release(&mut self, req: Request, inode: Inode, fh: u64, flags: u32) -> Result<()> {
+        if self.no_open {
+            return Ok(());
+        }
+
+        let release_in = fuse_release_in {
+            fh,
+            flags,
+            release_flags: 0,
+            lock_owner: 0,
+        };
+
+        self.send(req, fuse_opcode::FUSE_RELEASE, inode, &encode(&release_in))
+            .await?;
+
+        Ok(())
+    }
+
+    /// flush an open file like `close()` does, `lock_owner` is the owner of the POSIX locks which
+    /// should be removed.
+    pub async fn flush(
+        &mut self,
+        req: Request,
+        inode: Inode,
+        fh: u64,
+        lock_owner: u64,
+    ) -> Result<()> {
+        let flush_in = fuse_flush_in {
+            fh,
+            _unused: 0,
+            _padding: 0,
+            lock_owner,
+        };
+
+        self.send(req, fuse_opcode::FUSE_FLUSH, inode, &encode(&flush_in))
+            .await?;
+
+        Ok(())
+    }
+
+    /// read data.
+    pub async fn read(
+        &mut self,
+        req: Request,
+        inode: Inode,
+        fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        let read_in = fuse_read_in {
+            fh,
+            offset,
+            size,
+            _read_flags: 0,
+            lock_owner: 0,
+            _flags: 0,
+            _padding: 0,
+        };
+
+        self.send(req, fuse_opcode::FUSE_READ, inode, &encode(&read_in))
+            .await
+    }
+
+    /// send the reads of `(offset, size)` at once, like the concurrent readers, then wait all the
+    /// replies, return the results in the order of `reads`.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use std::sync::atomic::{AtomicUsize, Ordering};
+    /// # use bytes::Bytes;
+    /// # use fuse3::raw::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// #[derive(Default)]
+    /// struct Fs {
+    ///     running: AtomicUsize,
+    ///     max_running: AtomicUsize,
+    /// }
+    ///
+    /// impl Filesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #   }
+    /// #
+    /// #   async fn destroy(&self, _req: Request) {}
+    /// #
+    ///     async fn read(&self, _req: Request, _inode: u64, _fh: u64, offset: u64, _size: u32)
+    ///         -> Result<ReplyData> {
+    ///         let running = self.running.fetch_add(1, Ordering::Relaxed) + 1;
+    ///         self.max_running.fetch_max(running, Ordering::Relaxed);
+    ///
+    ///         // let the other reads run
+    ///         tokio::task::yield_now().await;
+    ///
+    ///         self.running.fetch_sub(1, Ordering::Relaxed);
+    ///
+    ///         Ok(Bytes::from(offset.to_string()).into())
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut mount_options = MountOptions::default();
+    /// mount_options.max_concurrent_requests(2);
+    ///
+    /// let mut session = TestSession::new(Fs::default(), mount_options);
+    /// session.init(request(0, 0, 0)).await.unwrap();
+    ///
+    /// let reads = (0..16).map(|offset| (offset, 4096)).collect::<Vec<_>>();
+    /// let replies = session.read_many(request(1000, 1000, 1), 2, 0, &reads).await;
+    ///
+    /// for (offset, reply) in replies.into_iter().enumerate() {
+    ///     assert_eq!(reply.unwrap(), offset.to_string());
+    /// }
+    ///
+    /// assert_eq!(session.filesystem().max_running.load(Ordering::Relaxed), 2);
+    /// # }
+    /// ```
+    pub async fn read_many(
+        &mut self,
+        req: Request,
+        inode: Inode,
+        fh: u64,
+        reads: &[(u64, u32)],
+    ) -> Vec<Result<Bytes>> {
+        let mut uniques = Vec::with_capacity(reads.len());
+
+        for &(offset, size) in reads {
+            let read_in = fuse_read_in {
+                fh,
+                offset,
+                size,
+                _read_flags: 0,
+                lock_owner: 0,
+                _flags: 0,
+                _padding: 0,
+            };
+            let data = encode(&read_in);
+
+            self.unique += 1;
+            let in_header = in_header(req, self.unique, fuse_opcode::FUSE_READ, inode, data.len());
+
+            self.session
+                .handle_request_in_process(in_header, &data, &self.fs)
+                .await;
+
+            uniques.push(self.unique);
+        }
+
+        let mut replies = HashMap::with_capacity(uniques.len());
+        while replies.len() < uniques.len() {
+            let (unique, reply) = self.next_reply().await;
+
+            replies.insert(unique, reply);
+        }
+
+        uniques
+            .iter()
+            .map(|unique| replies.remove(unique).expect("reply is missing"))
+            .collect()
+    }
+
+    /// write data, return the written size.
+    pub async fn write(
+        &mut self,
+        req: Request,
+        inode: Inode,
+        fh: u64,
+        offset: u64,
+        data: &[u8],
+        flags: u32,
+    ) -> Result<u32> {
+        let write_in = fuse_write_in {
+            fh,
+            offset,
+            size: data.len() as u32,
+            write_flags: 0,
+            _lock_owner: 0,
+            flags,
+            _padding: 0,
+        };
+
+        let mut body = encode(&write_in);
+        body.extend_from_slice(data);
+
+        let data = self
+            .send(req, fuse_opcode::FUSE_WRITE, inode, &body)
+            .await?;
+
+        let write_out: fuse_write_out = decode(&data);
+
+        Ok(write_out.size)
+    }
+
+    /// get the extended attribute `name`, the value size is returned when `size` is 0.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use std::ffi::OsStr;
+    /// # use fuse3::raw::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// struct Fs;
+    ///
+    /// impl Filesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #   }
+    /// #
+    /// #   async fn destroy(&self, _req: Request) {}
+    /// #
+    ///     async fn getxattr(&self, _req: Request, _inode: u64, name: &OsStr, size: u32)
+    ///         -> Result<ReplyXAttr> {
+    ///         match name.to_str() {
+    ///             Some("user.data") => ReplyXAttr::new(size, "value"),
+    ///             // wrong, the size is only for the size query
+    ///             _ => Ok(ReplyXAttr::Size(5)),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut session = TestSession::new(Fs, MountOptions::default());
+    /// session.init(request(0, 0, 0)).await.unwrap();
+    /// let req = request(1000, 1000, 1);
+    ///
+    /// let name = OsStr::new("user.data");
+    /// let size = session.getxattr(req, 1, name, 0).await.unwrap();
+    /// assert_eq!(size, ReplyXAttr::Size(5));
+    /// let value = session.getxattr(req, 1, name, 5).await.unwrap();
+    /// assert_eq!(value, ReplyXAttr::Data("value".into()));
+    /// let err = session.getxattr(req, 1, name, 4).await.unwrap_err();
+    /// assert_eq!(err, libc::ERANGE.into());
+    ///
+    /// let name = OsStr::new("user.size");
+    /// assert_eq!(session.getxattr(req, 1, name, 0).await.unwrap(), ReplyXAttr::Size(5));
+    /// let err = session.getxattr(req, 1, name, 5).await.unwrap_err();
+    /// assert_eq!(err, libc::ERANGE.into());
+    /// # }
+    /// ```
+    pub async fn getxattr(
+        &mut self,
+        req: Request,
+        inode: Inode,
+        name: &OsStr,
+        size: u32,
+    ) -> Result<ReplyXAttr> {
+        let getxattr_in = fuse_getxattr_in {
+            size,
+            _padding: 0,
+            #[cfg(target_os = "macos")]
+            position: 0,
+            #[cfg(target_os = "macos")]
+            _padding2: 0,
+        };
+
+        let mut body = encode(&getxattr_in);
+        body.extend_from_slice(&encode_name(name));
+
+        let data = self
+            .send(req, fuse_opcode::FUSE_GETXATTR, inode, &body)
+            .await?;
+
+        if size == 0 {
+            let getxattr_out: fuse_getxattr_out = decode(&data);
+
+            Ok(ReplyXAttr::Size(getxattr_out.size))
+        } else {
+            Ok(ReplyXAttr::Data(data))
+        }
+    }
+
+    /// read directory entries start from `offset`, return the entries fit in one reply.
+    pub async fn readdir(
+        &mut self,
+        req: Request,
+        inode: Inode,
+        fh: u64,
+        offset: u64,
+    ) -> Result<Vec<DirectoryEntry>> {
+        let read_in = fuse_read_in {
+            fh,
+            offset,
+            size: READDIR_SIZE,
+            _read_flags: 0,
+            lock_owner: 0,
+            _flags: 0,
+            _padding: 0,
+        };
+
+        let mut data = &self
+            .send(req, fuse_opcode::FUSE_READDIR, inode, &encode(&read_in))
+            .await?[..];
+
+        let mut entries = vec![];
+
+        while data.len() >= FUSE_DIRENT_SIZE {
+            let dirent: fuse_dirent = decode(data);
+            data = &data[FUSE_DIRENT_SIZE..];
+
+            let namelen = dirent.namelen as usize;
+            let name = OsString::from_vec(data[..namelen].to_vec());
+            data =
+                &data[(namelen + get_padding_size(FUSE_DIRENT_SIZE + namelen)).min(data.len())..];
+
+            entries.push(DirectoryEntry {
+                inode: dirent.ino,
+                kind: decode_kind(dirent.r#type << 12),
+                name,
+                offset: dirent.off as i64,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// test for a POSIX file lock.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use std::sync::Mutex;
+    /// # use fuse3::raw::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// #[derive(Default)]
+    /// struct Fs {
+    ///     locks: Mutex<Vec<FileLock>>,
+    /// }
+    ///
+    /// impl Filesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #   }
+    /// #
+    /// #   async fn destroy(&self, _req: Request) {}
+    /// #
+    ///     async fn getlk(&self, _req: Request, _inode: u64, _fh: u64, lock: FileLock)
+    ///         -> Result<ReplyLock> {
+    ///         let locks = self.locks.lock().unwrap();
+    ///         let conflict = locks.iter().find(|held| {
+    ///             held.owner != lock.owner
+    ///                 && held.start <= lock.end
+    ///                 && lock.start <= held.end
+    ///                 && (held.r#type == LockType::Write || lock.r#type == LockType::Write)
+    ///         });
+    ///
+    ///         Ok(conflict.map_or(ReplyLock::unlocked(lock), |held| ReplyLock::conflict(*held)))
+    ///     }
+    ///
+    ///     async fn setlk(&self, _req: Request, _inode: u64, _fh: u64, lock: FileLock)
+    ///         -> Result<()> {
+    ///         // no conflict check and unlock for brevity
+    ///         self.locks.lock().unwrap().push(lock);
+    ///
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut session = TestSession::new(Fs::default(), MountOptions::default());
+    /// session.init(request(0, 0, 0)).await.unwrap();
+    ///
+    /// let write_lock = FileLock {
+    ///     start: 0,
+    ///     end: 99,
+    ///     r#type: LockType::Write,
+    ///     pid: 1,
+    ///     owner: 1,
+    /// };
+    /// session.setlk(request(1000, 1000, 1), 2, 0, write_lock).await.unwrap();
+    ///
+    /// // another owner sees the write lock
+    /// let read_lock = FileLock {
+    ///     start: 50,
+    ///     end: u64::MAX,
+    ///     r#type: LockType::Read,
+    ///     pid: 2,
+    ///     owner: 2,
+    /// };
+    /// let reply = session.getlk(request(1000, 1000, 2), 2, 0, read_lock).await.unwrap();
+    /// assert_eq!(reply, ReplyLock::conflict(write_lock));
+    ///
+    /// // the owner itself has no conflict, the requested range is kept
+    /// let read_lock = FileLock { owner: 1, ..read_lock };
+    /// let reply = session.getlk(request(1000, 1000, 1), 2, 0, read_lock).await.unwrap();
+    /// assert_eq!(reply.r#type, LockType::Unlock);
+    /// assert_eq!((reply.start, reply.end), (50, u64::MAX));
+    /// # }
+    /// ```
+    #[cfg(feature = "file-lock")]
+    pub async fn getlk(
+        &mut self,
+        req: Request,
+        inode: Inode,
+        fh: u64,
+        lock: FileLock,
+    ) -> Result<ReplyLock> {
+        let lk_in = encode(&lk_in(fh, lock));
+        let data = self
+            .send(req, fuse_opcode::FUSE_GETLK, inode, &lk_in)
+            .await?;
+
+        let lk_out: fuse_lk_out = decode(&data);
+
+        Ok(ReplyLock {
+            start: lk_out.lk.start,
+            end: lk_out.lk.end,
+            r#type: LockType::try_from(lk_out.lk.r#type).expect("invalid lock type in reply"),
+            pid: lk_out.lk.pid,
+        })
+    }
+
+    /// acquire, modify or release a POSIX file lock without blocking.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use std::sync::Mutex;
+    /// # use fuse3::raw::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// #[derive(Default)]
+    /// struct Fs {
+    ///     locks: Mutex<Vec<FileLock>>,
+    /// }
+    ///
+    /// impl Filesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #   }
+    /// #
+    /// #   async fn destroy(&self, _req: Request) {}
+    /// #
+    ///     async fn getlk(&self, _req: Request, _inode: u64, _fh: u64, lock: FileLock)
+    ///         -> Result<ReplyLock> {
+    ///         let locks = self.locks.lock().unwrap();
+    ///         let conflict = locks.iter().find(|held| held.owner != lock.owner);
+    ///
+    ///         Ok(conflict.map_or(ReplyLock::unlocked(lock), |held| ReplyLock::conflict(*held)))
+    ///     }
+    ///
+    ///     async fn setlk(&self, _req: Request, _inode: u64, _fh: u64, lock: FileLock)
+    ///         -> Result<()> {
+    ///         self.locks.lock().unwrap().push(lock);
+    ///
+    ///         Ok(())
+    ///     }
+    ///
+    ///     async fn flush(&self, _req: Request, _inode: u64, _fh: u64, lock_owner: u64)
+    ///         -> Result<()> {
+    ///         // the closing owner releases all its POSIX locks
+    ///         self.locks.lock().unwrap().retain(|held| held.owner != lock_owner);
+    ///
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut session = TestSession::new(Fs::default(), MountOptions::default());
+    /// session.init(request(0, 0, 0)).await.unwrap();
+    ///
+    /// let write_lock = FileLock {
+    ///     start: 0,
+    ///     end: u64::MAX,
+    ///     r#type: LockType::Write,
+    ///     pid: 1,
+    ///     owner: 1,
+    /// };
+    /// session.setlk(request(1000, 1000, 1), 2, 0, write_lock).await.unwrap();
+    ///
+    /// let read_lock = FileLock { r#type: LockType::Read, pid: 2, owner: 2, ..write_lock };
+    /// let reply = session.getlk(request(1000, 1000, 2), 2, 0, read_lock).await.unwrap();
+    /// assert_eq!(reply, ReplyLock::conflict(write_lock));
+    ///
+    /// // closing a fd of another file table doesn't release the lock
+    /// session.flush(request(1000, 1000, 2), 2, 0, 2).await.unwrap();
+    /// let reply = session.getlk(request(1000, 1000, 2), 2, 0, read_lock).await.unwrap();
+    /// assert_eq!(reply, ReplyLock::conflict(write_lock));
+    ///
+    /// session.flush(request(1000, 1000, 1), 2, 0, 1).await.unwrap();
+    /// let reply = session.getlk(request(1000, 1000, 2), 2, 0, read_lock).await.unwrap();
+    /// assert_eq!(reply.r#type, LockType::Unlock);
+    /// # }
+    /// ```
+    #[cfg(feature = "file-lock")]
+    pub async fn setlk(
+        &mut self,
+        req: Request,
+        inode: Inode,
+        fh: u64,
+        lock: FileLock,
+    ) -> Result<()> {
+        let lk_in = encode(&lk_in(fh, lock));
+        self.send(req, fuse_opcode::FUSE_SETLK, inode, &lk_in)
+            .await?;
+
+        Ok(())
+    }
+
+    /// send a `FUSE_INTERRUPT` request for the next request right after it is sent, before
+    /// waiting its reply, like a signal is delivered to the calling process.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use std::future;
+    /// # use fuse3::raw::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// struct Fs;
+    ///
+    /// impl Filesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #   }
+    /// #
+    /// #   async fn destroy(&self, _req: Request) {}
+    /// #
+    ///     async fn read(
+    ///         &self,
+    ///         _req: Request,
+    ///         _inode: u64,
+    ///         _fh: u64,
+    ///         _offset: u64,
+    ///         _size: u32,
+    ///     ) -> Result<ReplyData> {
+    ///         // never done
+    ///         future::pending().await
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut session = TestSession::new(Fs, MountOptions::default());
+    /// session.init(request(0, 0, 0)).await.unwrap();
+    ///
+    /// session.interrupt_next_request();
+    /// let err = session
+    ///     .read(request(1000, 1000, 1), 2, 0, 0, 4096)
+    ///     .await
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(err, libc::EINTR.into());
+    /// # }
+    /// ```
+    pub fn interrupt_next_request(&mut self) {
+        self.interrupt_next = true;
+    }
+
+    /// send a raw request message, the in header included, like it is read from `/dev/fuse`,
+    /// and wait the reply, it is used to test the malformed requests.
+    ///
+    /// the message is checked by the same decoder as a mounted session, a message shorter than
+    /// the in header or whose `len` field doesn't match the message size returns `EIO` without
+    /// calling the filesystem, a mounted session replies `EIO` as well when the message is long
+    /// enough to contain the `unique` field, otherwise it logs and drops the message. The
+    /// request without reply, such as `FUSE_FORGET`, returns the empty data after the filesystem
+    /// has handled it.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use std::ffi::OsStr;
+    /// # use fuse3::raw::prelude::*;
+    /// # use fuse3::test::{request, TestSession};
+    /// # use fuse3::{Errno, MountOptions, Result};
+    /// # use futures_util::stream::Empty;
+    /// struct Fs;
+    ///
+    /// impl Filesystem for Fs {
+    /// #   type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    /// #   type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+    /// #
+    /// #   async fn init(&self, _req: Request) -> Result<ReplyInit> {
+    /// #       Ok(ReplyInit { max_write: 4096.try_into().unwrap() })
+    /// #   }
+    /// #
+    /// #   async fn destroy(&self, _req: Request) {}
+    /// #
+    ///     async fn lookup(&self, _req: Request, _parent: u64, _name: &OsStr)
+    ///         -> Result<ReplyEntry> {
+    ///         unreachable!("malformed request reaches the filesystem")
+    ///     }
+    /// }
+    ///
+    /// fn raw_message(len: u32, opcode: u32, data: &[u8]) -> Vec<u8> {
+    ///     let mut message = Vec::new();
+    ///     message.extend_from_slice(&len.to_ne_bytes());
+    ///     message.extend_from_slice(&opcode.to_ne_bytes());
+    ///     message.extend_from_slice(&100u64.to_ne_bytes()); // unique
+    ///     message.extend_from_slice(&1u64.to_ne_bytes()); // nodeid
+    ///     message.extend_from_slice(&[0; 16]); // uid, gid, pid and padding
+    ///     message.extend_from_slice(data);
+    ///
+    ///     message
+    /// }
+    ///
+    /// fn lookup_message(len: u32, name: &[u8]) -> Vec<u8> {
+    ///     raw_message(len, 1, name) // FUSE_LOOKUP
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut session = TestSession::new(Fs, MountOptions::default());
+    /// session.init(request(0, 0, 0)).await.unwrap();
+    ///
+    /// // truncated in header
+    /// let message = lookup_message(45, b"file\0");
+    /// let err = session.send_message(&message[..20]).await.unwrap_err();
+    /// assert_eq!(err, libc::EIO.into());
+    ///
+    /// // the len field is larger than the message
+    /// let err = session
+    ///     .send_message(&lookup_message(4096, b"file\0"))
+    ///     .await
+    ///     .unwrap_err();
+    /// assert_eq!(err, libc::EIO.into());
+    ///
+    /// // the name isn't null terminated
+    /// let err = session
+    ///     .send_message(&lookup_message(44, b"file"))
+    ///     .await
+    ///     .unwrap_err();
+    /// assert_eq!(err, libc::EINVAL.into());
+    ///
+    /// // FUSE_FORGET has no reply, it returns after the filesystem has handled it
+    /// let forget = raw_message(48, 2, &1u64.to_ne_bytes());
+    /// assert!(session.send_message(&forget).await.unwrap().is_empty());
+    /// # }
+    /// ```
+    pub async fn send_message(&mut self, message: &[u8]) -> Result<Bytes> {
+        let in_header = decode_in_header(message, message.len())?;
+        let unique = in_header.unique;
+        let need_reply =
+            fuse_opcode::try_from(in_header.opcode).map_or(true, |opcode| opcode.need_reply());
+
+        self.session
+            .handle_request_in_process(in_header, &message[FUSE_IN_HEADER_SIZE..], &self.fs)
+            .await;
+
+        if !need_reply {
+            self.session.wait_in_flight().await;
+
+            return Ok(Bytes::new());
+        }
+
+        self.wait_reply(unique).await
+    }
+
+    /// send the `FUSE_DESTROY` request, the session can't be used anymore.
+    pub async fn destroy(mut self, req: Request) {
+        self.unique += 1;
+
+        let in_header = in_header(req, self.unique, fuse_opcode::FUSE_DESTROY, 0, 0);
+
+        self.session
+            .handle_request_in_process(in_header, &[], &self.fs)
+            .await;
+    }
+
+    /// whether `FUSE_NO_OPEN_SUPPORT` is negotiated by `FUSE_INIT`.
+    fn is_no_open_supported(&self) -> bool {
+        self.session
+            .connection_info()
+            .is_some_and(|info| info.capabilities().contains(InitFlags::NO_OPEN_SUPPORT))
+    }
+
+    /// send a `FUSE_INTERRUPT` request for the `interrupt_unique` request, the interrupt request
+    /// itself has no reply to wait.
+    async fn interrupt(&mut self, req: Request, interrupt_unique: u64) {
+        self.unique += 1;
+
+        let interrupt_in = fuse_interrupt_in {
+            unique: interrupt_unique,
+        };
+        let data = encode(&interrupt_in);
+
+        let in_header = in_header(
+            req,
+            self.unique,
+            fuse_opcode::FUSE_INTERRUPT,
+            0,
+            data.len(),
+        );
+
+        self.session
+            .handle_request_in_process(in_header, &data, &self.fs)
+            .await;
+    }
+
+    /// send a request and wait the reply, return the reply data without the out header. The
+    /// request without reply returns the empty data after the filesystem has handled it.
+    async fn send(
+        &mut self,
+        req: Request,
+        opcode: fuse_opcode,
+        nodeid: Inode,
+        data: &[u8],
+    ) -> Result<Bytes> {
+        self.unique += 1;
+        let unique = self.unique;
+
+        let in_header = in_header(req, unique, opcode, nodeid, data.len());
+
+        self.session
+            .handle_request_in_process(in_header, data, &self.fs)
+            .await;
+
+        if self.interrupt_next {
+            self.interrupt_next = false;
+            self.interrupt(req, unique).await;
+        }
+
+        if !opcode.need_reply() {
+            self.session.wait_in_flight().await;
+
+            return Ok(Bytes::new());
+        }
+
+        self.wait_reply(unique).await
+    }
+
+    /// wait the reply of the `unique` request, skip the notify and the other replies.
+    async fn wait_reply(&mut self, unique: u64) -> Result<Bytes> {
+        loop {
+            let (reply_unique, reply) = self.next_reply().await;
+
+            if reply_unique == unique {
+                return reply;
+            }
+        }
+    }
+
+    /// wait the next reply, skip the notify, return the `unique` of the replied request.
+    async fn next_reply(&mut self) -> (u64, Result<Bytes>) {
+        loop {
+            let reply = match self
+                .response_receiver
+                .next()
+                .await
+                .expect("filesystem session is stopped")
+            {
+                Either::Left(data) => data,
+                Either::Right((mut data, ExtendData::Bytes(extend_data))) => {
+                    data.extend_from_slice(&extend_data);
+
+                    data
+                }
+                // the spliced reply is in the pipe with its header
+                #[cfg(target_os = "linux")]
+                Either::Right((_, ExtendData::Spliced(spliced))) => {
+                    spliced.into_data().expect("read the spliced reply")
+                }
+            };
+
+            self.session.observe_reply(&reply);
+
+            let out_header: fuse_out_header = decode(&reply);
+
+            // skip the notify
+            if out_header.unique == 0 {
+                continue;
+            }
+
+            assert_eq!(
+                out_header.len as usize,
+                reply.len(),
+                "reply length is mismatch"
+            );
+
+            if out_header.error != 0 {
+                return (out_header.unique, Err(Errno::from(-out_header.error)));
+            }
+
+            return (
+                out_header.unique,
+                Ok(Bytes::from(reply).slice(FUSE_OUT_HEADER_SIZE..)),
+            );
+        }
+    }
+}
+
+impl<FS: PathFilesystem + Send + Sync + 'static> TestSession<InodePathBridge<FS>> {
+    /// create a test session with the path based filesystem, it is bridged like
+    /// [`path::Session`] mounts it, with the mount options, the observer and the
+    /// [`relative_path`][path::Session::relative_path] of `session`.
+    pub fn new_path(fs: FS, session: path::Session) -> Self {
+        let (mount_options, observer, bridge) = session.into_bridge(fs);
+        let test_session = Self::new(bridge, mount_options);
+
+        match observer {
+            None => test_session,
+            Some(observer) => test_session.observer(observer),
+        }
+    }
+}
+
+fn in_header(
+    req: Request,
+    unique: u64,
+    opcode: fuse_opcode,
+    nodeid: Inode,
+    data_len: usize,
+) -> fuse_in_header {
+    fuse_in_header {
+        len: (FUSE_IN_HEADER_SIZE + data_len) as u32,
+        opcode: opcode as u32,
+        unique,
+        nodeid,
+        uid: req.uid,
+        gid: req.gid,
+        pid: req.pid,
+        total_extlen: 0,
+        _padding: 0,
+    }
+}
+
+#[cfg(feature = "file-lock")]
+fn lk_in(fh: u64, lock: FileLock) -> fuse_lk_in {
+    fuse_lk_in {
+        fh,
+        owner: lock.owner,
+        lk: fuse_file_lock {
+            start: lock.start,
+            end: lock.end,
+            r#type: lock.r#type.into(),
+            pid: lock.pid,
+        },
+        lk_flags: 0,
+        _padding: 0,
+    }
+}
+
+fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+    get_bincode_config()
+        .serialize(value)
+        .expect("won't happened")
+}
+
+fn encode_name(name: &OsStr) -> Vec<u8> {
+    let mut data = name.as_bytes().to_vec();
+    data.push(0);
+
+    data
+}
+
+fn decode<T: DeserializeOwned>(data: &[u8]) -> T {
+    get_bincode_config()
+        .deserialize(data)
+        .expect("malformed reply")
+}
+
+fn decode_kind(mode: u32) -> FileType {
+    kind_from_mode(mode).expect("invalid file type in reply")
+}
+
+#[allow(clippy::unnecessary_cast)] // Not unnecessary on all platforms.
+fn decode_attr(attr: fuse_attr) -> FileAttr {
+    let kind = decode_kind(attr.mode);
+
+    FileAttr {
+        ino: attr.ino,
+        size: attr.size,
+        blocks: attr.blocks,
+        atime: Timestamp::new(attr.atime as i64, attr.atimensec),
+        mtime: Timestamp::new(attr.mtime as i64, attr.mtimensec),
+        ctime: Timestamp::new(attr.ctime as i64, attr.ctimensec),
+        #[cfg(target_os = "macos")]
+        crtime: Some(Timestamp::new(attr.crtime as i64, attr.crtimensec)),
+        #[cfg(not(target_os = "macos"))]
+        crtime: None,
+        kind,
+        perm: perm_from_mode_and_kind(kind, attr.mode as mode_t),
+        nlink: attr.nlink,
+        uid: attr.uid,
+        gid: attr.gid,
+        rdev: attr.rdev,
+        #[cfg(target_os = "macos")]
+        flags: attr.flags,
+        blksize: attr.blksize,
+    }
+}
+
+fn decode_entry(entry_out: fuse_entry_out) -> ReplyEntry {
+    ReplyEntry {
+        ttl: Duration::new(entry_out.entry_valid, entry_out.entry_valid_nsec),
+        attr: decode_attr(entry_out.attr),
+        generation: entry_out.generation,
+    }
+}
@@ -0,0 +1,84 @@
+//! align the direct io requests for a backing file opened with `O_DIRECT`.
+
+/// the offset, size and memory alignment required by a backing file opened with `O_DIRECT`.
+///
+/// when the caller opens a file with `O_DIRECT`,
+/// [`OpenFlags::is_direct`][crate::raw::flags::OpenFlags::is_direct] is true for the flags of
+/// open and create, and for the `flags` of write. Read doesn't carry the open flags, record it in
+/// the state of `fh` when open.
+///
+/// kernel doesn't check the alignment of the direct io of a FUSE file, the offset and size of
+/// read and write are what the caller passed, and the write data isn't aligned in memory. A
+/// filesystem which passes them to a backing file opened with `O_DIRECT` must honor the alignment
+/// of the backing file, or the backing io fails with `EINVAL`:
+///
+/// - the offset and size must be multiples of the alignment, which is usually the logical block
+///   size of the backing device, `statx(2)` with `STATX_DIOALIGN` reports it on Linux 6.1 and
+///   later.
+/// - the memory buffer must be aligned too, copy the write data into an aligned buffer, and read
+///   into an aligned buffer.
+///
+/// an unaligned read can be widened by [`align`][DirectIoAlignment::align], read the aligned
+/// range and reply the requested part. An unaligned write has to read, modify and write the head
+/// and tail blocks, or use a backing file opened without `O_DIRECT`.
+///
+/// # Examples:
+///
+/// ```
+/// # use fuse3::util::DirectIoAlignment;
+/// let alignment = DirectIoAlignment::new(4096);
+///
+/// assert!(alignment.is_aligned(8192, 4096));
+/// assert!(!alignment.is_aligned(4196, 100));
+///
+/// // read 4096 bytes from offset 4096, then reply the 100 bytes from offset 4196
+/// assert_eq!(alignment.align(4196, 100), (4096, 4096));
+/// assert_eq!(alignment.align(4000, 200), (0, 8192));
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct DirectIoAlignment(u64);
+
+impl DirectIoAlignment {
+    /// create the alignment, 0 is treated as 1, which means no alignment.
+    ///
+    /// # Panics:
+    ///
+    /// panic if `alignment` is not a power of two.
+    pub const fn new(alignment: u64) -> Self {
+        if alignment == 0 {
+            return Self(1);
+        }
+
+        assert!(alignment.is_power_of_two(), "alignment must be a power of two");
+
+        Self(alignment)
+    }
+
+    /// the alignment in bytes.
+    pub const fn alignment(self) -> u64 {
+        self.0
+    }
+
+    /// whether both `offset` and `size` are multiples of the alignment.
+    pub const fn is_aligned(self, offset: u64, size: u64) -> bool {
+        (offset | size) & self.mask() == 0
+    }
+
+    /// whether the memory address of `buf` is aligned.
+    pub fn is_buffer_aligned(self, buf: &[u8]) -> bool {
+        buf.as_ptr() as u64 & self.mask() == 0
+    }
+
+    /// the smallest aligned range which covers `offset..offset + size`, return the aligned
+    /// offset and size.
+    pub const fn align(self, offset: u64, size: u64) -> (u64, u64) {
+        let start = offset & !self.mask();
+        let end = offset.saturating_add(size).saturating_add(self.mask()) & !self.mask();
+
+        (start, end - start)
+    }
+
+    const fn mask(self) -> u64 {
+        self.0 - 1
+    }
+}
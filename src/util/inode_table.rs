@@ -0,0 +1,189 @@
+//! track the kernel lookup count of the inodes.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use slab::Slab;
+
+use crate::Inode;
+
+/// the root inode number.
+const ROOT_INODE: Inode = 1;
+
+#[derive(Debug)]
+struct InodeEntry<T> {
+    value: T,
+    nlookup: u64,
+    generation: u64,
+}
+
+/// allocate inode numbers and track their kernel lookup count, an inode is reclaimed only when
+/// its lookup count drops to zero by [`forget`][InodeTable::forget] or
+/// [`batch_forget`][InodeTable::batch_forget], so its number won't be reused while kernel still
+/// references it.
+///
+/// every reply which makes kernel reference an inode, such as `lookup`, `create`, `mkdir`,
+/// `mknod`, `symlink`, `link` and each entry of `readdirplus`, must count one lookup, use
+/// [`insert`][InodeTable::insert] for a new inode and [`lookup`][InodeTable::lookup] for an
+/// existing one. The root inode is inserted by [`new`][InodeTable::new] and never reclaimed.
+///
+/// each inserted inode gets a new [`generation`][InodeTable::generation], so when a reclaimed
+/// inode number is reused, the reply can tell kernel it is a different file by the generation.
+///
+/// # Concurrency:
+///
+/// all methods take `&self` and hold an internal mutex only inside the call, never across an
+/// await, so the table can be shared by the concurrent filesystem requests directly. Two calls
+/// are not atomic together, count the lookup only when the reply is decided, a failed request
+/// must not count a lookup because kernel won't forget it.
+///
+/// # Examples:
+///
+/// ```
+/// # use fuse3::util::InodeTable;
+/// let table = InodeTable::new("/");
+///
+/// // lookup a new file
+/// let inode = table.insert("/file");
+/// // lookup it again
+/// table.lookup(inode);
+///
+/// assert_eq!(table.forget(inode, 1), None);
+/// assert_eq!(table.get(inode), Some("/file"));
+///
+/// // kernel doesn't reference it anymore
+/// let generation = table.generation(inode);
+/// assert_eq!(table.forget(inode, 1), Some("/file"));
+/// assert_eq!(table.get(inode), None);
+///
+/// // the inode number is reused with a new generation
+/// let new_inode = table.insert("/new_file");
+/// assert_eq!(new_inode, inode);
+/// assert_ne!(table.generation(new_inode), generation);
+/// ```
+#[derive(Debug)]
+pub struct InodeTable<T> {
+    // the inode is the slab key + 1, so the root inode is key 0
+    entries: Mutex<Slab<InodeEntry<T>>>,
+    next_generation: AtomicU64,
+}
+
+impl<T> InodeTable<T> {
+    /// create a table with the root inode.
+    pub fn new(root: T) -> Self {
+        let mut entries = Slab::new();
+        entries.insert(InodeEntry {
+            value: root,
+            nlookup: 0,
+            generation: 0,
+        });
+
+        Self {
+            entries: Mutex::new(entries),
+            next_generation: AtomicU64::new(1),
+        }
+    }
+
+    /// allocate an inode for `value` with lookup count 1, the inode number of a reclaimed inode
+    /// may be reused, but with a new generation.
+    pub fn insert(&self, value: T) -> Inode {
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        let key = self.lock().insert(InodeEntry {
+            value,
+            nlookup: 1,
+            generation,
+        });
+
+        key as Inode + 1
+    }
+
+    /// increase the lookup count of an existing inode, return `false` if the inode doesn't
+    /// exist.
+    pub fn lookup(&self, inode: Inode) -> bool {
+        match self.lock().get_mut(key(inode)) {
+            None => false,
+
+            Some(entry) => {
+                entry.nlookup += 1;
+
+                true
+            }
+        }
+    }
+
+    /// get the value of an inode.
+    pub fn get(&self, inode: Inode) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.lock().get(key(inode)).map(|entry| entry.value.clone())
+    }
+
+    /// get the generation of an inode, reply it as the `generation` of
+    /// [`ReplyEntry`][crate::raw::reply::ReplyEntry] and the other entry replies. `None` means
+    /// the inode doesn't exist.
+    pub fn generation(&self, inode: Inode) -> Option<u64> {
+        self.lock().get(key(inode)).map(|entry| entry.generation)
+    }
+
+    /// call `f` with the value of an inode, the table is locked during the call, don't call the
+    /// other methods of the table in `f`.
+    pub fn with<R>(&self, inode: Inode, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.lock()
+            .get_mut(key(inode))
+            .map(|entry| f(&mut entry.value))
+    }
+
+    /// decrease the lookup count of an inode by `nlookup`, call it in
+    /// [`forget`][crate::raw::Filesystem::forget]. When the lookup count drops to zero, the
+    /// inode is reclaimed and its value is returned. The root inode is never reclaimed.
+    pub fn forget(&self, inode: Inode, nlookup: u64) -> Option<T> {
+        Self::forget_locked(&mut self.lock(), inode, nlookup)
+    }
+
+    /// forget more than one inode, call it in
+    /// [`batch_forget`][crate::raw::Filesystem::batch_forget], return the values of the
+    /// reclaimed inodes.
+    pub fn batch_forget(&self, inodes: &[(Inode, u64)]) -> Vec<T> {
+        let mut entries = self.lock();
+
+        inodes
+            .iter()
+            .filter_map(|&(inode, nlookup)| Self::forget_locked(&mut entries, inode, nlookup))
+            .collect()
+    }
+
+    /// the number of inodes in the table, including the root inode.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// whether the table is empty, it is always `false` because of the root inode.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn forget_locked(entries: &mut Slab<InodeEntry<T>>, inode: Inode, nlookup: u64) -> Option<T> {
+        if inode == ROOT_INODE {
+            return None;
+        }
+
+        let key = key(inode);
+        let entry = entries.get_mut(key)?;
+        entry.nlookup = entry.nlookup.saturating_sub(nlookup);
+        if entry.nlookup > 0 {
+            return None;
+        }
+
+        Some(entries.remove(key).value)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Slab<InodeEntry<T>>> {
+        self.entries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+fn key(inode: Inode) -> usize {
+    // inode 0 is invalid, map it to a key never used
+    inode.wrapping_sub(1) as usize
+}
@@ -0,0 +1,108 @@
+//! clear the privileges of a file when it is modified.
+
+use nix::sys::stat::mode_t;
+
+use crate::raw::flags::InitFlags;
+
+/// the extended attribute name of the file capabilities.
+pub const CAPABILITY_XATTR: &str = "security.capability";
+
+/// the operation which kills the privileges of a file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum KillPrivOp {
+    /// write data to the file.
+    Write,
+    /// change the file size by setattr.
+    Truncate,
+    /// change the owner or group of the file by setattr.
+    Chown,
+}
+
+/// clear the `suid` and `sgid` bits and the file capabilities the same as kernel, when
+/// [`MountOptions::handle_killpriv`][crate::MountOptions::handle_killpriv] is negotiated.
+///
+/// when `FUSE_HANDLE_KILLPRIV` is negotiated, kernel doesn't clear the privileges itself, the
+/// filesystem must do it on write, truncate and chown:
+///
+/// - the `suid` bit is cleared.
+/// - the `sgid` bit is cleared only if the group execute bit is set, otherwise it marks the
+///   mandatory locking and is kept.
+/// - the [`CAPABILITY_XATTR`] extended attribute is removed, see
+///   [`kill_capability`][KillPriv::kill_capability].
+///
+/// write and truncate only clear the privileges of a regular file, chown clears them of any file
+/// type except directory. When it is not negotiated, kernel clears the `suid` and `sgid` bits by
+/// a setattr with the new mode, the helper returns the mode unchanged.
+///
+/// # Notes:
+///
+/// kernel doesn't clear the privileges on write and truncate when the caller has `CAP_FSETID`,
+/// the filesystem can't know it from a normal request, except
+/// [`WriteFlags::KILL_PRIV`][crate::raw::flags::WriteFlags::KILL_PRIV] tells the caller of a
+/// direct io write doesn't have it. When a setattr of chown sets the mode too, the requested mode
+/// wins.
+///
+/// # Examples:
+///
+/// ```
+/// # use fuse3::raw::flags::InitFlags;
+/// # use fuse3::util::{KillPriv, KillPrivOp};
+/// let kill_priv = KillPriv::new(InitFlags::HANDLE_KILLPRIV);
+///
+/// // suid and sgid with group execute are cleared
+/// assert_eq!(kill_priv.mode(libc::S_IFREG | 0o6755, KillPrivOp::Write), libc::S_IFREG | 0o755);
+/// // sgid without group execute is kept
+/// assert_eq!(kill_priv.mode(libc::S_IFREG | 0o6745, KillPrivOp::Chown), libc::S_IFREG | 0o2745);
+/// // directory is not changed
+/// assert_eq!(kill_priv.mode(libc::S_IFDIR | 0o2755, KillPrivOp::Chown), libc::S_IFDIR | 0o2755);
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct KillPriv {
+    enabled: bool,
+}
+
+impl KillPriv {
+    /// create the helper from the negotiated capabilities, such as
+    /// [`ConnectionInfo::capabilities`][crate::raw::ConnectionInfo::capabilities].
+    pub fn new(capabilities: InitFlags) -> Self {
+        Self {
+            enabled: capabilities.contains(InitFlags::HANDLE_KILLPRIV),
+        }
+    }
+
+    /// whether the filesystem should clear the privileges.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// get the mode after `op` from the current `mode`, which includes the file type bits.
+    pub fn mode(&self, mode: mode_t, op: KillPrivOp) -> mode_t {
+        if !self.applies(mode, op) {
+            return mode;
+        }
+
+        let mut mode = mode & !libc::S_ISUID;
+        if mode & libc::S_IXGRP > 0 {
+            mode &= !libc::S_ISGID;
+        }
+
+        mode
+    }
+
+    /// whether the [`CAPABILITY_XATTR`] of a file with `mode` should be removed after `op`.
+    pub fn kill_capability(&self, mode: mode_t, op: KillPrivOp) -> bool {
+        self.applies(mode, op)
+    }
+
+    fn applies(&self, mode: mode_t, op: KillPrivOp) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        let kind = mode & libc::S_IFMT;
+        match op {
+            KillPrivOp::Write | KillPrivOp::Truncate => kind == libc::S_IFREG,
+            KillPrivOp::Chown => kind != libc::S_IFDIR,
+        }
+    }
+}
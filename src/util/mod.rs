@@ -0,0 +1,9 @@
+//! optional helpers for implementing a [`Filesystem`][crate::raw::Filesystem].
+
+pub use direct_io::DirectIoAlignment;
+pub use inode_table::InodeTable;
+pub use kill_priv::{KillPriv, KillPrivOp, CAPABILITY_XATTR};
+
+mod direct_io;
+mod inode_table;
+mod kill_priv;
@@ -0,0 +1,173 @@
+//! reply the entries which kernel rejects or mistakes through `TestSession`, and check the
+//! warnings logged for them. The warnings are collected by a subscriber of the test thread, the
+//! session runs on a current thread runtime, so the requests are handled on the same thread.
+
+use std::ffi::OsStr;
+use std::fmt::Debug;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use fuse3::raw::prelude::*;
+use fuse3::raw::reply::FUSE_ROOT_ID;
+use fuse3::test::{request, TestSession};
+use fuse3::{MountOptions, Result};
+use futures_util::stream::Empty;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+const TTL: Duration = Duration::from_secs(1);
+
+/// collect the messages of the warnings.
+#[derive(Debug, Default, Clone)]
+struct WarnCollector {
+    warnings: Arc<Mutex<Vec<String>>>,
+}
+
+struct MessageVisitor<'a>(&'a mut String);
+
+impl Visit for MessageVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            *self.0 = format!("{value:?}");
+        }
+    }
+}
+
+impl Subscriber for WarnCollector {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        if *event.metadata().level() != Level::WARN {
+            return;
+        }
+
+        let mut message = String::new();
+        event.record(&mut MessageVisitor(&mut message));
+
+        self.warnings.lock().unwrap().push(message);
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+/// reply the entry of each name in the root directory.
+#[derive(Debug, Default)]
+struct EntryFs {
+    /// the generation of `.` and `..`, it is wrong if not 0, the constructor debug asserts it.
+    root_generation: u64,
+}
+
+impl Filesystem for EntryFs {
+    type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+    type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+
+    async fn init(&self, _req: Request) -> Result<ReplyInit> {
+        Ok(ReplyInit {
+            max_write: 4096.try_into().unwrap(),
+        })
+    }
+
+    async fn destroy(&self, _req: Request) {}
+
+    async fn lookup(&self, _req: Request, _parent: u64, name: &OsStr) -> Result<ReplyEntry> {
+        let metadata = fs::metadata(".").unwrap();
+
+        let entry = match name.to_str() {
+            Some("." | "..") => ReplyEntry {
+                ttl: TTL,
+                attr: FileAttr::from_metadata(FUSE_ROOT_ID, &metadata),
+                generation: self.root_generation,
+            },
+            Some("file") => ReplyEntry::new(FileAttr::from_metadata(2, &metadata), TTL),
+            // wrong, only `.` and `..` resolve to the root
+            Some("root-alias") => {
+                ReplyEntry::new(FileAttr::from_metadata(FUSE_ROOT_ID, &metadata), TTL)
+            }
+            _ => ReplyEntry::negative(TTL),
+        };
+
+        Ok(entry)
+    }
+}
+
+/// look up `name` in the root directory, return the warnings logged for it.
+fn lookup_warnings(fs: EntryFs, name: &str) -> Vec<String> {
+    let collector = WarnCollector::default();
+    let _guard = tracing::subscriber::set_default(collector.clone());
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        let mut session = TestSession::new(fs, MountOptions::default());
+        session.init(request(0, 0, 0)).await.unwrap();
+
+        let _ = session
+            .lookup(request(0, 0, 0), FUSE_ROOT_ID, OsStr::new(name))
+            .await;
+    });
+
+    let warnings = collector.warnings.lock().unwrap();
+
+    warnings.clone()
+}
+
+#[test]
+fn valid_entry_no_warning() {
+    for name in [".", "..", "file", "missing"] {
+        assert_eq!(
+            lookup_warnings(EntryFs::default(), name),
+            Vec::<String>::new(),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn non_root_entry_with_root_inode() {
+    assert_eq!(
+        lookup_warnings(EntryFs::default(), "root-alias"),
+        ["non-root entry has the root inode"]
+    );
+}
+
+#[test]
+fn root_entry_with_generation() {
+    let fs = EntryFs { root_generation: 1 };
+
+    assert_eq!(lookup_warnings(fs, ".."), ["root generation is not 0"]);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "entry inode 0 is not a valid inode")]
+fn new_entry_with_inode_0() {
+    let metadata = fs::metadata(".").unwrap();
+
+    ReplyEntry::new(FileAttr::from_metadata(0, &metadata), TTL);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "root generation is not 0")]
+fn root_entry_with_generation_by_constructor() {
+    let metadata = fs::metadata(".").unwrap();
+
+    ReplyEntry::new(FileAttr::from_metadata(FUSE_ROOT_ID, &metadata), TTL).with_generation(1);
+}
@@ -0,0 +1,840 @@
+//! mount an in-memory filesystem in an unprivileged user namespace, and access it by `std::fs`.
+//!
+//! each test re-executes itself in a new user and mount namespace, where the mount is allowed
+//! without root and is torn down with the namespace. It is skipped when `/dev/fuse` can't be
+//! opened or the user namespace is not allowed.
+
+#![cfg(target_os = "linux")]
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::fs::{self, File, OpenOptions};
+use std::future::Future;
+use std::io::{Error as IoError, Result as IoResult};
+use std::num::NonZeroU32;
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use bytes::Bytes;
+use fuse3::notify::Notify;
+use fuse3::raw::flags::{OpenFlags, OptionalOps};
+use fuse3::raw::prelude::*;
+use fuse3::raw::{MountBusyError, MountHandle};
+use fuse3::{MountOptions, Result};
+use futures_util::stream::{self, Empty, Iter};
+use tokio::runtime::Runtime;
+
+/// set in the re-executed test process, which is in the new namespaces.
+const IN_NAMESPACE_ENV: &str = "FUSE3_MOUNT_TEST_IN_NAMESPACE";
+/// the exit code of the re-executed test process when mounting is not allowed.
+const SKIP_EXIT_CODE: i32 = 77;
+
+const ROOT_INODE: u64 = 1;
+/// the block size of the files, a block filled with zeros is a hole.
+const BLOCK_SIZE: u64 = 4096;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default)]
+struct MemFs {
+    inner: Mutex<Inner>,
+    /// the ttl of the replied entries, the kernel doesn't cache them by default.
+    entry_ttl: Duration,
+    /// the count of the lookup requests.
+    lookups: Arc<AtomicUsize>,
+    /// the `whence` of the lseek requests.
+    whences: Arc<Mutex<Vec<u32>>>,
+    /// reply the reads from this file by `read_buf` instead of the in-memory data.
+    read_from: Option<Arc<File>>,
+    /// enable [`MountOptions::use_splice`].
+    use_splice: bool,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    names: BTreeMap<OsString, u64>,
+    files: BTreeMap<u64, Vec<u8>>,
+    last_inode: u64,
+}
+
+fn attr(ino: u64, kind: FileType, size: u64) -> FileAttr {
+    let now = SystemTime::now().into();
+    let mut attr = FileAttr {
+        ino,
+        size: 0,
+        blocks: 0,
+        atime: now,
+        mtime: now,
+        ctime: now,
+        crtime: None,
+        kind,
+        perm: if kind == FileType::Directory {
+            0o755
+        } else {
+            0o644
+        },
+        nlink: if kind == FileType::Directory { 2 } else { 1 },
+        uid: 0,
+        gid: 0,
+        rdev: 0,
+        blksize: BLOCK_SIZE as _,
+    };
+    attr.set_size(size);
+
+    attr
+}
+
+impl Inner {
+    fn attr(&self, inode: u64) -> Result<FileAttr> {
+        if inode == ROOT_INODE {
+            return Ok(attr(ROOT_INODE, FileType::Directory, 0));
+        }
+
+        let data = self.files.get(&inode).ok_or(libc::ENOENT)?;
+
+        Ok(attr(inode, FileType::RegularFile, data.len() as _))
+    }
+}
+
+impl Filesystem for MemFs {
+    type DirEntryStream<'a> = Iter<std::vec::IntoIter<Result<DirectoryEntry>>>;
+    type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+
+    async fn init(&self, _req: Request) -> Result<ReplyInit> {
+        Ok(ReplyInit {
+            max_write: NonZeroU32::new(128 * 1024).unwrap(),
+        })
+    }
+
+    async fn destroy(&self, _req: Request) {}
+
+    async fn lookup(&self, _req: Request, parent: u64, name: &OsStr) -> Result<ReplyEntry> {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+
+        let inner = self.inner.lock().unwrap();
+
+        if parent != ROOT_INODE {
+            return Err(libc::ENOTDIR.into());
+        }
+
+        let inode = *inner.names.get(name).ok_or(libc::ENOENT)?;
+
+        Ok(ReplyEntry {
+            ttl: self.entry_ttl,
+            attr: inner.attr(inode)?,
+            generation: 0,
+        })
+    }
+
+    async fn getattr(
+        &self,
+        _req: Request,
+        inode: u64,
+        _fh: Option<u64>,
+        _flags: u32,
+    ) -> Result<ReplyAttr> {
+        Ok(ReplyAttr {
+            ttl: Duration::ZERO,
+            attr: self.inner.lock().unwrap().attr(inode)?,
+        })
+    }
+
+    async fn setattr(
+        &self,
+        _req: Request,
+        inode: u64,
+        _fh: Option<u64>,
+        set_attr: SetAttr,
+    ) -> Result<ReplyAttr> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if let Some(size) = set_attr.size {
+            let data = inner.files.get_mut(&inode).ok_or(libc::EISDIR)?;
+            data.resize(size as _, 0);
+        }
+
+        Ok(ReplyAttr {
+            ttl: Duration::ZERO,
+            attr: inner.attr(inode)?,
+        })
+    }
+
+    async fn unlink(&self, _req: Request, parent: u64, name: &OsStr) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if parent != ROOT_INODE {
+            return Err(libc::ENOTDIR.into());
+        }
+
+        let inode = inner.names.remove(name).ok_or(libc::ENOENT)?;
+        inner.files.remove(&inode);
+
+        Ok(())
+    }
+
+    async fn open(&self, _req: Request, inode: u64, flags: OpenFlags) -> Result<ReplyOpen> {
+        let mut inner = self.inner.lock().unwrap();
+        let data = inner.files.get_mut(&inode).ok_or(libc::EISDIR)?;
+
+        // the O_TRUNC is passed to open with FUSE_ATOMIC_O_TRUNC
+        if flags.is_truncate() {
+            data.clear();
+        }
+
+        Ok(ReplyOpen::new(0))
+    }
+
+    async fn read(
+        &self,
+        _req: Request,
+        inode: u64,
+        _fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<ReplyData> {
+        let inner = self.inner.lock().unwrap();
+        let data = inner.files.get(&inode).ok_or(libc::EISDIR)?;
+
+        let start = (offset as usize).min(data.len());
+        let end = start.saturating_add(size as _).min(data.len());
+
+        Ok(ReplyData {
+            data: Bytes::copy_from_slice(&data[start..end]),
+        })
+    }
+
+    async fn read_buf(
+        &self,
+        _req: Request,
+        _inode: u64,
+        _fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<ReplyReadBuf> {
+        let file = self.read_from.clone().ok_or(libc::ENOSYS)?;
+
+        Ok(ReplyReadBuf::File {
+            fd: file,
+            offset,
+            len: size,
+        })
+    }
+
+    async fn write(
+        &self,
+        _req: Request,
+        inode: u64,
+        _fh: u64,
+        offset: u64,
+        data: &[u8],
+        _write_flags: WriteFlags,
+        _flags: u32,
+    ) -> Result<ReplyWrite> {
+        let mut inner = self.inner.lock().unwrap();
+        let file = inner.files.get_mut(&inode).ok_or(libc::EISDIR)?;
+
+        let end = offset as usize + data.len();
+        if file.len() < end {
+            file.resize(end, 0);
+        }
+        file[offset as usize..end].copy_from_slice(data);
+
+        Ok(ReplyWrite::new(data.len() as _))
+    }
+
+    async fn release(
+        &self,
+        _req: Request,
+        _inode: u64,
+        _fh: u64,
+        _flags: u32,
+        _lock_owner: u64,
+        _flush: bool,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn lseek(
+        &self,
+        _req: Request,
+        inode: u64,
+        _fh: u64,
+        offset: u64,
+        whence: u32,
+    ) -> Result<ReplyLSeek> {
+        self.whences.lock().unwrap().push(whence);
+
+        let inner = self.inner.lock().unwrap();
+        let data = inner.files.get(&inode).ok_or(libc::EISDIR)?;
+        let len = data.len() as u64;
+
+        let seek_hole = match whence as i32 {
+            libc::SEEK_DATA => false,
+            libc::SEEK_HOLE => true,
+            _ => return Err(libc::EINVAL.into()),
+        };
+
+        if offset >= len {
+            return Err(libc::ENXIO.into());
+        }
+
+        for start in (offset - offset % BLOCK_SIZE..len).step_by(BLOCK_SIZE as _) {
+            let end = (start + BLOCK_SIZE).min(len);
+            let is_hole = data[start as usize..end as usize]
+                .iter()
+                .all(|&byte| byte == 0);
+
+            if is_hole == seek_hole {
+                return Ok(ReplyLSeek {
+                    offset: start.max(offset),
+                });
+            }
+        }
+
+        // the end of file is a hole
+        if seek_hole {
+            Ok(ReplyLSeek { offset: len })
+        } else {
+            Err(libc::ENXIO.into())
+        }
+    }
+
+    async fn opendir(&self, _req: Request, inode: u64, _flags: OpenFlags) -> Result<ReplyOpen> {
+        if inode != ROOT_INODE {
+            return Err(libc::ENOTDIR.into());
+        }
+
+        Ok(ReplyOpen::new(0))
+    }
+
+    async fn readdir(
+        &self,
+        _req: Request,
+        inode: u64,
+        _fh: u64,
+        offset: i64,
+    ) -> Result<ReplyDirectory<Self::DirEntryStream<'_>>> {
+        let inner = self.inner.lock().unwrap();
+
+        if inode != ROOT_INODE {
+            return Err(libc::ENOTDIR.into());
+        }
+
+        let dots = [".", ".."]
+            .into_iter()
+            .map(|name| (OsString::from(name), ROOT_INODE, FileType::Directory));
+        let files = inner
+            .names
+            .iter()
+            .map(|(name, &inode)| (name.clone(), inode, FileType::RegularFile));
+
+        let entries = dots
+            .chain(files)
+            .enumerate()
+            .skip(offset as _)
+            .map(|(i, (name, inode, kind))| {
+                Ok(DirectoryEntry {
+                    inode,
+                    kind,
+                    name,
+                    offset: i as i64 + 1,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(ReplyDirectory {
+            entries: stream::iter(entries),
+        })
+    }
+
+    async fn create(
+        &self,
+        _req: Request,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _flags: OpenFlags,
+        _umask: u32,
+        _security_context: Option<&SecurityContext>,
+    ) -> Result<ReplyCreated> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if parent != ROOT_INODE {
+            return Err(libc::ENOTDIR.into());
+        }
+
+        if inner.names.contains_key(name) {
+            return Err(libc::EEXIST.into());
+        }
+
+        inner.last_inode = inner.last_inode.max(ROOT_INODE) + 1;
+        let inode = inner.last_inode;
+
+        inner.names.insert(name.to_owned(), inode);
+        inner.files.insert(inode, Vec::new());
+
+        Ok(ReplyCreated {
+            ttl: self.entry_ttl,
+            attr: inner.attr(inode)?,
+            generation: 0,
+            fh: 0,
+            flags: 0,
+        })
+    }
+}
+
+/// access the mounted filesystem like a normal directory.
+fn access_filesystem(mount_path: &Path) -> IoResult<()> {
+    let path = mount_path.join("hello");
+
+    fs::write(&path, "hello world")?;
+    assert_eq!(fs::read_to_string(&path)?, "hello world");
+
+    OpenOptions::new()
+        .write(true)
+        .open(&path)?
+        .write_all_at(b"fuse3", 6)?;
+    assert_eq!(fs::read_to_string(&path)?, "hello fuse3");
+
+    // truncate and write again
+    fs::write(&path, "hi")?;
+    assert_eq!(fs::metadata(&path)?.len(), 2);
+    assert_eq!(fs::read_to_string(&path)?, "hi");
+
+    let names = fs::read_dir(mount_path)?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<IoResult<Vec<_>>>()?;
+    assert_eq!(names, ["hello"]);
+
+    fs::remove_file(&path)?;
+    assert!(!path.exists());
+    assert_eq!(fs::read_dir(mount_path)?.count(), 0);
+
+    Ok(())
+}
+
+/// create the temporary mount point.
+fn create_mount_path() -> PathBuf {
+    let mount_path = env::temp_dir().join(format!("fuse3-mount-test-{}", process::id()));
+    fs::create_dir_all(&mount_path).unwrap();
+
+    mount_path
+}
+
+fn new_runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+/// mount the filesystem, exit with [`SKIP_EXIT_CODE`] if the mount is not allowed.
+async fn mount(fs: MemFs, mount_path: &Path) -> MountHandle {
+    let mut mount_options = MountOptions::default();
+    mount_options
+        .fs_name("fuse3-test")
+        .supported_ops(OptionalOps::all() - OptionalOps::READDIRPLUS)
+        .use_splice(fs.use_splice);
+
+    match Session::new(mount_options).mount(fs, mount_path).await {
+        Err(err) => {
+            eprintln!("skip, mount in the user namespace failed: {err}");
+
+            process::exit(SKIP_EXIT_CODE);
+        }
+
+        Ok(mount_handle) => mount_handle,
+    }
+}
+
+/// check the mount table of the current mount namespace.
+fn is_mounted(mount_path: &Path) -> bool {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo").unwrap();
+
+    // the fifth field is the mount point
+    mountinfo
+        .lines()
+        .any(|line| line.split(' ').nth(4).map(Path::new) == Some(mount_path))
+}
+
+/// mount the filesystem in a temporary directory and run `test` with the mount path and the
+/// notify of the mount, then unmount it. It runs in the new namespaces.
+fn with_mount<F, Fut>(fs: MemFs, test: F)
+where
+    F: FnOnce(PathBuf, Notify) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mount_path = create_mount_path();
+
+    new_runtime().block_on(async {
+        let mount_handle = mount(fs, &mount_path).await;
+
+        test(mount_path.clone(), mount_handle.notify()).await;
+
+        mount_handle.unmount().await.unwrap();
+    });
+
+    fs::remove_dir(&mount_path).unwrap();
+}
+
+/// run the blocking filesystem access `f` out of the runtime, so the session can serve it.
+async fn blocking<T, F>(f: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> IoResult<T> + Send + 'static,
+{
+    tokio::time::timeout(TIMEOUT, tokio::task::spawn_blocking(f))
+        .await
+        .expect("access the filesystem timeout")
+        .unwrap()
+        .unwrap()
+}
+
+/// enter the new user and mount namespaces, map the current user to root in them. It runs in
+/// the forked child, so only the async-signal-safe functions are called.
+fn enter_namespaces(uid_map: &[u8], gid_map: &[u8]) -> IoResult<()> {
+    fn write_file(path: &CStr, content: &[u8]) -> IoResult<()> {
+        // Safety: path is a valid c string, content is a valid buffer
+        unsafe {
+            let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+            if fd < 0 {
+                return Err(IoError::last_os_error());
+            }
+
+            let n = libc::write(fd, content.as_ptr().cast(), content.len());
+            libc::close(fd);
+
+            if n < 0 {
+                return Err(IoError::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+
+    // Safety: unshare only changes the namespaces of the single threaded child
+    if unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS) } != 0 {
+        return Err(IoError::last_os_error());
+    }
+
+    write_file(c"/proc/self/setgroups", b"deny")?;
+    write_file(c"/proc/self/uid_map", uid_map)?;
+    write_file(c"/proc/self/gid_map", gid_map)?;
+
+    Ok(())
+}
+
+/// run the test `name` in a re-executed test process, which calls `in_namespace` in the new
+/// user and mount namespaces.
+fn run_in_namespace(name: &str, in_namespace: fn()) {
+    if env::var_os(IN_NAMESPACE_ENV).is_some() {
+        in_namespace();
+
+        return;
+    }
+
+    if let Err(err) = OpenOptions::new().read(true).write(true).open("/dev/fuse") {
+        eprintln!("skip, open /dev/fuse failed: {err}");
+
+        return;
+    }
+
+    // Safety: getuid and getgid are always successful
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let uid_map = format!("0 {uid} 1").into_bytes();
+    let gid_map = format!("0 {gid} 1").into_bytes();
+
+    let mut command = Command::new(env::current_exe().unwrap());
+    command
+        .args([name, "--exact", "--nocapture"])
+        .env(IN_NAMESPACE_ENV, "1");
+
+    // Safety: enter_namespaces only calls the async-signal-safe functions
+    unsafe {
+        command.pre_exec(move || enter_namespaces(&uid_map, &gid_map));
+    }
+
+    let output = match command.output() {
+        Err(err) => {
+            eprintln!("skip, enter the user namespace failed: {err}");
+
+            return;
+        }
+
+        Ok(output) => output,
+    };
+
+    if output.status.code() == Some(SKIP_EXIT_CODE) {
+        eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+
+        return;
+    }
+
+    assert!(
+        output.status.success(),
+        "mount test failed, stdout:\n{}\nstderr:\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn mount_in_user_namespace() {
+    run_in_namespace("mount_in_user_namespace", || {
+        with_mount(MemFs::default(), |mount_path, _| async move {
+            blocking(move || access_filesystem(&mount_path)).await;
+        })
+    });
+}
+
+#[test]
+fn notify_delete_entry() {
+    run_in_namespace("notify_delete_entry", || {
+        let fs = MemFs {
+            entry_ttl: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let lookups = fs.lookups.clone();
+
+        with_mount(fs, |mount_path, notify| async move {
+            let path = mount_path.join("hello");
+            let inode = blocking({
+                let path = path.clone();
+
+                move || {
+                    fs::write(&path, "hello")?;
+
+                    Ok(fs::metadata(&path)?.ino())
+                }
+            })
+            .await;
+
+            // the entry is cached, stat doesn't look it up
+            let cached_lookups = lookups.load(Ordering::Relaxed);
+            let stat_path = path.clone();
+            blocking(move || fs::metadata(stat_path)).await;
+            assert_eq!(lookups.load(Ordering::Relaxed), cached_lookups);
+
+            notify
+                .delete(ROOT_INODE, inode, OsString::from("hello"))
+                .await
+                .unwrap();
+
+            // the notify is written before the reply of this getattr, so kernel has handled it
+            // when the stat returns
+            blocking(move || fs::metadata(mount_path)).await;
+
+            // the entry is deleted from the kernel cache, but the file still exists
+            let metadata = blocking(move || fs::metadata(path)).await;
+            assert_eq!(metadata.ino(), inode);
+            assert_eq!(lookups.load(Ordering::Relaxed), cached_lookups + 1);
+        })
+    });
+}
+
+#[test]
+fn lseek_data_and_hole() {
+    run_in_namespace("lseek_data_and_hole", || {
+        let fs = MemFs::default();
+        let whences = fs.whences.clone();
+
+        with_mount(fs, |mount_path, _| async move {
+            let offsets = blocking(move || {
+                let file = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(mount_path.join("sparse"))?;
+
+                // data, hole, data
+                file.write_all_at(&[1; BLOCK_SIZE as usize], 0)?;
+                file.write_all_at(&[2; BLOCK_SIZE as usize], 2 * BLOCK_SIZE)?;
+
+                let seeks = [
+                    (0, libc::SEEK_DATA),
+                    (0, libc::SEEK_HOLE),
+                    (BLOCK_SIZE, libc::SEEK_DATA),
+                    (2 * BLOCK_SIZE, libc::SEEK_HOLE),
+                    (3 * BLOCK_SIZE, libc::SEEK_DATA),
+                ];
+
+                Ok(seeks
+                    .into_iter()
+                    .map(|(offset, whence)| {
+                        // Safety: the fd is valid until the file is dropped
+                        match unsafe { libc::lseek(file.as_raw_fd(), offset as _, whence) } {
+                            -1 => Err(IoError::last_os_error().raw_os_error().unwrap()),
+                            offset => Ok(offset as u64),
+                        }
+                    })
+                    .collect::<Vec<_>>())
+            })
+            .await;
+
+            assert_eq!(
+                offsets,
+                [
+                    Ok(0),
+                    Ok(BLOCK_SIZE),
+                    Ok(2 * BLOCK_SIZE),
+                    Ok(3 * BLOCK_SIZE),
+                    Err(libc::ENXIO)
+                ]
+            );
+
+            // the whence is passed to the filesystem unchanged
+            let seek_data = libc::SEEK_DATA as u32;
+            let seek_hole = libc::SEEK_HOLE as u32;
+            assert_eq!(
+                *whences.lock().unwrap(),
+                [seek_data, seek_hole, seek_data, seek_hole, seek_data]
+            );
+        })
+    });
+}
+
+#[test]
+fn read_buf_from_file() {
+    run_in_namespace("read_buf_from_file", || {
+        // larger than a read request, and not aligned to the page
+        let data = (0..300 * 1024 + 100)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let backing_path = env::temp_dir().join(format!("fuse3-read-buf-{}", process::id()));
+        fs::write(&backing_path, &data).unwrap();
+
+        // read by pread(2), then by splice(2) if kernel supports
+        for use_splice in [false, true] {
+            let fs = MemFs {
+                read_from: Some(Arc::new(File::open(&backing_path).unwrap())),
+                use_splice,
+                ..Default::default()
+            };
+
+            // the in-memory data only gives the file size
+            {
+                let mut inner = fs.inner.lock().unwrap();
+                inner.names.insert(OsString::from("hello"), 2);
+                inner.files.insert(2, vec![0; data.len()]);
+            }
+
+            let data = data.clone();
+            with_mount(fs, |mount_path, _| async move {
+                let read = blocking(move || fs::read(mount_path.join("hello"))).await;
+
+                assert!(read == data, "use_splice {use_splice}");
+            });
+        }
+
+        fs::remove_file(&backing_path).unwrap();
+    });
+}
+
+#[test]
+fn drop_mount_handle() {
+    run_in_namespace("drop_mount_handle", || {
+        let mount_path = create_mount_path();
+        let runtime = new_runtime();
+
+        // dropped in the runtime, the unmount task is spawned
+        runtime.block_on(async {
+            let mount_handle = mount(MemFs::default(), &mount_path).await;
+            assert!(is_mounted(&mount_path));
+
+            drop(mount_handle);
+
+            tokio::time::timeout(TIMEOUT, async {
+                while is_mounted(&mount_path) {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("unmount the dropped mount handle timeout");
+        });
+
+        // dropped out of the runtime, it is unmounted synchronously
+        let mount_handle = runtime.block_on(mount(MemFs::default(), &mount_path));
+        assert!(is_mounted(&mount_path));
+
+        drop(mount_handle);
+        assert!(!is_mounted(&mount_path));
+
+        // dropped after the runtime is shut down
+        let mount_handle = runtime.block_on(mount(MemFs::default(), &mount_path));
+        assert!(is_mounted(&mount_path));
+
+        drop(runtime);
+        drop(mount_handle);
+        assert!(!is_mounted(&mount_path));
+
+        fs::remove_dir(&mount_path).unwrap();
+    });
+}
+
+#[test]
+fn unmount_busy_mount_point() {
+    run_in_namespace("unmount_busy_mount_point", || {
+        let mount_path = create_mount_path();
+
+        new_runtime().block_on(async {
+            let mount_handle = mount(MemFs::default(), &mount_path).await;
+
+            // the opened file keeps the mount point busy
+            let file_path = mount_path.join("busy");
+            let file = blocking(move || fs::File::create(file_path)).await;
+
+            let err = mount_handle.unmount().await.unwrap_err();
+            let busy = err
+                .get_ref()
+                .and_then(|err| err.downcast_ref::<MountBusyError>())
+                .expect("unmount the busy mount point should fail with MountBusyError");
+            assert_eq!(busy.mount_path(), mount_path);
+            assert!(is_mounted(&mount_path));
+
+            drop(file);
+        });
+
+        let c_mount_path = CString::new(mount_path.as_os_str().as_bytes()).unwrap();
+        // Safety: the path is a valid c string
+        assert_eq!(
+            unsafe { libc::umount2(c_mount_path.as_ptr(), libc::MNT_DETACH) },
+            0
+        );
+        assert!(!is_mounted(&mount_path));
+
+        fs::remove_dir(&mount_path).unwrap();
+    });
+}
+
+#[test]
+fn connection_id_of_escaped_mount_point() {
+    run_in_namespace("connection_id_of_escaped_mount_point", || {
+        // the space is escaped as `\040` in mountinfo
+        let mount_path = env::temp_dir().join(format!("fuse3 mount test {}", process::id()));
+        fs::create_dir_all(&mount_path).unwrap();
+
+        new_runtime().block_on(async {
+            let mount_handle = mount(MemFs::default(), &mount_path).await;
+
+            let stat_path = mount_path.clone();
+            let dev = blocking(move || fs::metadata(stat_path)).await.dev();
+            // the kernel dev_t is major << 20 | minor
+            // Safety: major and minor only decode the number
+            let id = unsafe { (libc::major(dev) << 20) | libc::minor(dev) };
+
+            assert_eq!(mount_handle.connection_id().unwrap(), id);
+
+            mount_handle.unmount().await.unwrap();
+        });
+
+        fs::remove_dir(&mount_path).unwrap();
+    });
+}